@@ -0,0 +1,131 @@
+
+// Single scattering sky, rendered as a panorama around a viewer standing on the ground.
+
+struct VertexOutput {
+    @builtin(position) position: vec4f,
+    @location(0) tex_coords: vec2f,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var result: VertexOutput;
+
+    let x = i32(vertex_index) / 2;
+    let y = i32(vertex_index) & 1;
+
+    result.tex_coords = vec2f(
+        f32(x) * 2.0,
+        f32(y) * 2.0
+    );
+    result.position = vec4f(
+        result.tex_coords.x * 2.0 - 1.0,
+        1.0 - result.tex_coords.y * 2.0,
+        0.0, 1.0
+    );
+
+    return result;
+}
+
+const PI: f32 = 3.14159265;
+
+const EARTH_RADIUS: f32 = 6360e3;
+const ATMOSPHERE_RADIUS: f32 = 6420e3;
+
+const RAYLEIGH_SCALE_HEIGHT: f32 = 8e3;
+const MIE_SCALE_HEIGHT: f32 = 1.2e3;
+const RAYLEIGH_COEFFICIENTS: vec3f = vec3f(5.8e-6, 13.5e-6, 33.1e-6);
+const MIE_COEFFICIENT: f32 = 21e-6;
+const MIE_G: f32 = 0.76;
+
+const SUN_INTENSITY: f32 = 20.0;
+const SUN_ELEVATION: f32 = 0.1;
+
+const VIEW_SAMPLES: i32 = 16;
+const LIGHT_SAMPLES: i32 = 8;
+
+// Distance to where the ray leaves the sphere, the origin has to be inside
+fn ray_sphere_exit(origin: vec3f, dir: vec3f, radius: f32) -> f32 {
+    let b = dot(origin, dir);
+    let c = dot(origin, origin) - radius * radius;
+    return -b + sqrt(max(b * b - c, 0.0));
+}
+
+// Distance to where the ray enters the sphere, negative when it misses
+fn ray_sphere_enter(origin: vec3f, dir: vec3f, radius: f32) -> f32 {
+    let b = dot(origin, dir);
+    let c = dot(origin, origin) - radius * radius;
+    let d = b * b - c;
+    if d < 0.0 {
+        return -1.0;
+    }
+    return -b - sqrt(d);
+}
+
+fn density(p: vec3f) -> vec2f {
+    let height = length(p) - EARTH_RADIUS;
+    return exp(-height / vec2f(RAYLEIGH_SCALE_HEIGHT, MIE_SCALE_HEIGHT));
+}
+
+// Rayleigh and Mie optical depth from `p` towards the sun
+fn light_optical_depth(p: vec3f, sun_dir: vec3f) -> vec2f {
+    if ray_sphere_enter(p, sun_dir, EARTH_RADIUS) > 0.0 {
+        return vec2f(1e9);
+    }
+
+    let step = ray_sphere_exit(p, sun_dir, ATMOSPHERE_RADIUS) / f32(LIGHT_SAMPLES);
+    var depth = vec2f(0.0);
+    for (var i = 0; i < LIGHT_SAMPLES; i++) {
+        depth += density(p + sun_dir * (f32(i) + 0.5) * step) * step;
+    }
+    return depth;
+}
+
+fn sky(dir: vec3f, sun_dir: vec3f) -> vec3f {
+    let origin = vec3f(0.0, EARTH_RADIUS + 1.0, 0.0);
+
+    var ray_length = ray_sphere_exit(origin, dir, ATMOSPHERE_RADIUS);
+    let ground = ray_sphere_enter(origin, dir, EARTH_RADIUS);
+    if ground > 0.0 {
+        ray_length = ground;
+    }
+
+    let step = ray_length / f32(VIEW_SAMPLES);
+    var view_depth = vec2f(0.0);
+    var rayleigh = vec3f(0.0);
+    var mie = vec3f(0.0);
+    for (var i = 0; i < VIEW_SAMPLES; i++) {
+        let p = origin + dir * (f32(i) + 0.5) * step;
+        let sample_density = density(p) * step;
+        view_depth += sample_density;
+
+        let depth = view_depth + light_optical_depth(p, sun_dir);
+        let transmittance = exp(-(RAYLEIGH_COEFFICIENTS * depth.x + MIE_COEFFICIENT * 1.1 * depth.y));
+        rayleigh += sample_density.x * transmittance;
+        mie += sample_density.y * transmittance;
+    }
+
+    let mu = dot(dir, sun_dir);
+    let rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    let g2 = MIE_G * MIE_G;
+    let mie_phase = 3.0 / (8.0 * PI) * ((1.0 - g2) * (1.0 + mu * mu))
+        / ((2.0 + g2) * pow(1.0 + g2 - 2.0 * MIE_G * mu, 1.5));
+
+    return SUN_INTENSITY * (rayleigh * RAYLEIGH_COEFFICIENTS * rayleigh_phase + mie * MIE_COEFFICIENT * mie_phase);
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4f {
+    // Horizontally all around the viewer, vertically from straight down to straight up
+    let azimuth = (vertex.tex_coords.x - 0.5) * 2.0 * PI;
+    let elevation = (0.5 - vertex.tex_coords.y) * PI;
+    let dir = vec3f(
+        cos(elevation) * sin(azimuth),
+        sin(elevation),
+        cos(elevation) * cos(azimuth)
+    );
+    let sun_dir = normalize(vec3f(0.0, SUN_ELEVATION, 1.0));
+
+    // Simple exposure curve to bring the radiance into displayable range
+    let color = 1.0 - exp(-sky(dir, sun_dir));
+    return vec4f(color, 1.0);
+}
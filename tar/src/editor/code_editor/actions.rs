@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+/// Editor commands that can be bound to a key combination.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EditorAction {
+    UpperCase,
+    LowerCase,
+    ToggleCase,
+    JoinLines,
+    SortLines,
+    SortLinesDescending,
+    SortLinesUnique,
+    ExpandParagraphSelection,
+    ShrinkParagraphSelection,
+    MoveLinesUp,
+    MoveLinesDown,
+    DuplicateLinesUp,
+    DuplicateLinesDown,
+    ToggleComment,
+}
+
+impl EditorAction {
+    /// Whether the action edits the document, these are blocked while read-only.
+    pub fn is_mutating(self) -> bool {
+        !matches!(
+            self,
+            Self::ExpandParagraphSelection | Self::ShrinkParagraphSelection
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub key: egui::Key,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyBinding {
+    pub const fn new(key: egui::Key, ctrl: bool, shift: bool, alt: bool) -> Self {
+        Self {
+            key,
+            ctrl,
+            shift,
+            alt,
+        }
+    }
+}
+
+/// Maps key combinations to editor actions, can be rebound at runtime.
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    bindings: HashMap<EditorAction, KeyBinding>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use egui::Key;
+
+        Self {
+            bindings: HashMap::from([
+                (
+                    EditorAction::UpperCase,
+                    KeyBinding::new(Key::U, true, true, false),
+                ),
+                (
+                    EditorAction::LowerCase,
+                    KeyBinding::new(Key::L, true, true, false),
+                ),
+                (
+                    EditorAction::ToggleCase,
+                    KeyBinding::new(Key::T, true, true, false),
+                ),
+                (
+                    EditorAction::JoinLines,
+                    KeyBinding::new(Key::J, true, false, false),
+                ),
+                (
+                    EditorAction::SortLines,
+                    KeyBinding::new(Key::F9, false, false, false),
+                ),
+                (
+                    EditorAction::SortLinesDescending,
+                    KeyBinding::new(Key::F9, false, true, false),
+                ),
+                (
+                    EditorAction::SortLinesUnique,
+                    KeyBinding::new(Key::F9, false, false, true),
+                ),
+                (
+                    EditorAction::ExpandParagraphSelection,
+                    KeyBinding::new(Key::ArrowRight, false, true, true),
+                ),
+                (
+                    EditorAction::ShrinkParagraphSelection,
+                    KeyBinding::new(Key::ArrowLeft, false, true, true),
+                ),
+                (
+                    EditorAction::MoveLinesUp,
+                    KeyBinding::new(Key::ArrowUp, false, false, true),
+                ),
+                (
+                    EditorAction::MoveLinesDown,
+                    KeyBinding::new(Key::ArrowDown, false, false, true),
+                ),
+                (
+                    EditorAction::DuplicateLinesUp,
+                    KeyBinding::new(Key::ArrowUp, false, true, true),
+                ),
+                (
+                    EditorAction::DuplicateLinesDown,
+                    KeyBinding::new(Key::ArrowDown, false, true, true),
+                ),
+                (
+                    EditorAction::ToggleComment,
+                    KeyBinding::new(Key::Slash, true, false, false),
+                ),
+            ]),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn bind(&mut self, action: EditorAction, binding: KeyBinding) {
+        self.bindings.insert(action, binding);
+    }
+
+    pub fn unbind(&mut self, action: EditorAction) {
+        self.bindings.remove(&action);
+    }
+
+    pub fn binding(&self, action: EditorAction) -> Option<&KeyBinding> {
+        self.bindings.get(&action)
+    }
+
+    /// The action bound to the pressed key combination, if any.
+    pub fn action(&self, pressed: KeyBinding) -> Option<EditorAction> {
+        self.bindings
+            .iter()
+            .find(|(_, binding)| **binding == pressed)
+            .map(|(action, _)| *action)
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeSet;
+
+use ropey::Rope;
+
+use super::syntax::Syntax;
+
+/// Maximum number of entries shown in the completion popup.
+const MAX_ITEMS: usize = 8;
+
+/// Keywords after which the next identifier is a declared symbol.
+const DECLARATION_KEYWORDS: [&str; 7] =
+    ["fn", "struct", "const", "let", "var", "alias", "override"];
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Prefix based completion state, alive while the popup is shown.
+pub struct Completion {
+    /// Char index where the word being completed starts.
+    pub prefix_start: usize,
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+impl Completion {
+    /// Build the completion for the word ending at `cursor`, returns `None` when
+    /// there is nothing worth suggesting.
+    pub fn new(doc: &Rope, cursor: usize, syntax: &Syntax) -> Option<Self> {
+        let prefix_start = word_start(doc, cursor);
+        if prefix_start == cursor {
+            return None;
+        }
+
+        let prefix = doc.slice(prefix_start..cursor).to_string();
+        if prefix.starts_with(|c: char| c.is_numeric()) {
+            return None;
+        }
+
+        let symbols = declared_symbols(doc);
+        let candidates: BTreeSet<&str> = syntax
+            .keywords
+            .iter()
+            .chain(syntax.types.iter())
+            .chain(syntax.special.iter())
+            .copied()
+            .chain(symbols.iter().map(String::as_str))
+            .filter(|word| word.starts_with(prefix.as_str()) && *word != prefix)
+            .collect();
+
+        let mut items: Vec<String> = candidates.into_iter().map(str::to_string).collect();
+        items.sort_by_key(|item| item.len());
+        items.truncate(MAX_ITEMS);
+
+        if items.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            prefix_start,
+            items,
+            selected: 0,
+        })
+    }
+
+    pub fn selected_item(&self) -> &str {
+        &self.items[self.selected]
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.items.len();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+    }
+}
+
+/// Char index of the start of the identifier (or attribute) ending at `cursor`.
+fn word_start(doc: &Rope, cursor: usize) -> usize {
+    let mut start = cursor;
+    while start > 0 && is_identifier_char(doc.char(start - 1)) {
+        start -= 1;
+    }
+    if start > 0 && doc.char(start - 1) == '@' {
+        start -= 1;
+    }
+    start
+}
+
+/// Names of all `fn`/`struct`/`const`/`let`/... declarations in the document.
+fn declared_symbols(doc: &Rope) -> BTreeSet<String> {
+    let mut symbols = BTreeSet::new();
+    let mut previous_word = String::new();
+    let mut word = String::new();
+
+    for c in doc.chars().chain(std::iter::once(' ')) {
+        if is_identifier_char(c) {
+            word.push(c);
+        } else if !word.is_empty() {
+            if DECLARATION_KEYWORDS.contains(&previous_word.as_str()) {
+                symbols.insert(word.clone());
+            }
+            previous_word = std::mem::take(&mut word);
+        }
+    }
+
+    symbols
+}
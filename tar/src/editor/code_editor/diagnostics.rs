@@ -0,0 +1,25 @@
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            Self::Error => egui::Color32::from_rgb(220, 50, 50),
+            Self::Warning => egui::Color32::from_rgb(230, 180, 30),
+        }
+    }
+}
+
+/// A compiler message attached to a range of the document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Char range in the document.
+    pub range: Range<usize>,
+    pub message: String,
+    pub severity: Severity,
+}
@@ -38,6 +38,10 @@ impl Token {
             c if syntax.is_special(c.to_string().as_str()) => TokenType::Special,
             c if syntax.comment == c.to_string().as_str() => TokenType::Comment(false),
             c if syntax.comment_multiline[0] == c.to_string().as_str() => TokenType::Comment(true),
+            // Keywords like `#version` start with punctuation
+            c if c.is_ascii_punctuation() && syntax.keywords.iter().any(|k| k.starts_with(c)) => {
+                TokenType::Literal
+            }
             _ => TokenType::from(c),
         };
         token
@@ -67,16 +71,17 @@ impl Token {
         token
     }
 
-    /// Syntax highlighting
-    pub fn highlight(&mut self, editor: &CodeEditor, text: &str) -> LayoutJob {
-        *self = Token::default();
+    /// Syntax highlighting, `in_block_comment` when `text` starts inside a block comment.
+    pub fn highlight(
+        &mut self,
+        editor: &CodeEditor,
+        text: &str,
+        in_block_comment: bool,
+    ) -> LayoutJob {
         let mut job = LayoutJob::default();
-        for c in text.chars() {
-            for token in self.automata(c, editor.syntax()) {
-                editor.append(&mut job, &token);
-            }
+        for token in tokenize_from(editor.syntax(), text, in_block_comment) {
+            editor.append(&mut job, &token);
         }
-        editor.append(&mut job, self);
         job
     }
 
@@ -217,16 +222,129 @@ impl Token {
     }
 }
 
+/// Split `text` into the tokens [`highlight`] colors, concatenating their buffers gives back
+/// `text`. Empty tokens are left out.
+pub fn tokenize(syntax: &Syntax, text: &str) -> Vec<Token> {
+    tokenize_from(syntax, text, false)
+}
+
+/// Like [`tokenize`], for text that continues a block comment when `in_block_comment`.
+pub fn tokenize_from(syntax: &Syntax, text: &str, in_block_comment: bool) -> Vec<Token> {
+    let mut lexer = Token::default();
+    if in_block_comment {
+        lexer.ty = TokenType::Comment(true);
+    }
+    let mut tokens = Vec::new();
+    for c in text.chars() {
+        tokens.extend(lexer.automata(c, syntax));
+    }
+    tokens.push(lexer);
+    tokens.retain(|token| !token.buffer().is_empty());
+    tokens
+}
+
+/// For every line of `text`, whether it starts inside a block comment. Lines are only
+/// tokenized one window at a time, this carries the comment state over from the lines above.
+pub fn block_comment_starts(syntax: &Syntax, text: impl Iterator<Item = char>) -> Vec<bool> {
+    let mut lexer = Token::default();
+    let mut starts = vec![false];
+    for c in text {
+        lexer.automata(c, syntax);
+        if c == '\n' {
+            starts.push(lexer.ty == TokenType::Comment(true));
+        }
+    }
+    starts
+}
+
 use egui::text::LayoutJob;
 
-impl egui::util::cache::ComputerMut<(&CodeEditor, &str), LayoutJob> for Token {
-    fn compute(&mut self, (cache, text): (&CodeEditor, &str)) -> LayoutJob {
-        self.highlight(cache, text)
+impl egui::util::cache::ComputerMut<(&CodeEditor, &str, bool), LayoutJob> for Token {
+    fn compute(&mut self, (cache, text, in_block_comment): (&CodeEditor, &str, bool)) -> LayoutJob {
+        self.highlight(cache, text, in_block_comment)
     }
 }
 
 pub type HighlightCache = egui::util::cache::FrameCache<LayoutJob, Token>;
 
-pub fn highlight(ctx: &egui::Context, cache: &CodeEditor, text: &str) -> LayoutJob {
-    ctx.memory_mut(|mem| mem.caches.cache::<HighlightCache>().get((cache, text)))
+pub fn highlight(
+    ctx: &egui::Context,
+    cache: &CodeEditor,
+    text: &str,
+    in_block_comment: bool,
+) -> LayoutJob {
+    ctx.memory_mut(|mem| {
+        mem.caches
+            .cache::<HighlightCache>()
+            .get((cache, text, in_block_comment))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_wgsl() {
+        let text = "@fragment\nfn main() -> vec4<f32> { // done\n    return vec4(1.5); }";
+        let tokens = tokenize(&Syntax::wgsl(), text);
+
+        let text_back: String = tokens.iter().map(Token::buffer).collect();
+        assert_eq!(text_back, text);
+
+        let ty_of = |buffer: &str| {
+            tokens
+                .iter()
+                .find(|token| token.buffer() == buffer)
+                .map(Token::ty)
+        };
+        assert_eq!(ty_of("fn"), Some(TokenType::Keyword));
+        assert_eq!(ty_of("return"), Some(TokenType::Keyword));
+        assert_eq!(ty_of("main"), Some(TokenType::Function));
+        assert_eq!(ty_of("1.5"), Some(TokenType::Numeric(true)));
+        assert_eq!(ty_of("// done"), Some(TokenType::Comment(false)));
+    }
+
+    #[test]
+    fn test_tokenize_glsl() {
+        let text =
+            "#version 450\nlayout(location = 0) out vec4 color; /* a\nb */\nuniform sampler2D tex;";
+        let tokens = tokenize(&Syntax::glsl(), text);
+
+        let text_back: String = tokens.iter().map(Token::buffer).collect();
+        assert_eq!(text_back, text);
+
+        let ty_of = |buffer: &str| {
+            tokens
+                .iter()
+                .find(|token| token.buffer() == buffer)
+                .map(Token::ty)
+        };
+        assert_eq!(ty_of("#version"), Some(TokenType::Keyword));
+        assert_eq!(ty_of("450"), Some(TokenType::Numeric(false)));
+        assert_eq!(ty_of("out"), Some(TokenType::Keyword));
+        assert_eq!(ty_of("uniform"), Some(TokenType::Keyword));
+        assert_eq!(ty_of("vec4"), Some(TokenType::Type));
+        assert_eq!(ty_of("sampler2D"), Some(TokenType::Type));
+        assert_eq!(ty_of("/* a\nb */"), Some(TokenType::Comment(true)));
+    }
+
+    #[test]
+    fn test_block_comment_across_lines() {
+        let syntax = Syntax::wgsl();
+        let text = "let a = 1; /* start\nstill comment\nend */ let b;\n// /* not a block\nlet c;";
+        assert_eq!(
+            block_comment_starts(&syntax, text.chars()),
+            vec![false, true, true, false, false]
+        );
+
+        // Tokenizing only the window starting on the second line
+        let window = "still comment\nend */ let b;";
+        let tokens = tokenize_from(&syntax, window, true);
+        assert_eq!(tokens[0].buffer(), "still comment\nend */");
+        assert_eq!(tokens[0].ty(), TokenType::Comment(true));
+        assert!(tokens
+            .iter()
+            .any(|token| token.buffer() == "let" && token.ty() == TokenType::Keyword));
+    }
 }
@@ -0,0 +1,68 @@
+/// Line ending style of a file on disk, the editor itself always works with `\n`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::EnumIter)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// Style used by the majority of the lines in `text`, `Lf` when there are none.
+    pub fn detect(text: &str) -> Self {
+        let line_count = text.matches('\n').count();
+        let crlf_count = text.matches("\r\n").count();
+
+        if crlf_count > 0 && crlf_count * 2 >= line_count {
+            Self::CrLf
+        } else {
+            Self::Lf
+        }
+    }
+
+    /// Convert all line endings in `text` to `\n`.
+    pub fn normalize(text: &str) -> String {
+        text.replace("\r\n", "\n")
+    }
+
+    /// Convert normalized `text` back to this line ending style.
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Self::Lf => text.to_string(),
+            Self::CrLf => text.replace('\n', "\r\n"),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Lf => "Line feed (Unix)",
+            Self::CrLf => "Carriage return + line feed (Windows)",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_crlf_round_trip() {
+        let source = "fn main() {\r\n    return;\r\n}\r\n";
+
+        let line_ending = LineEnding::detect(source);
+        assert_eq!(line_ending, LineEnding::CrLf);
+
+        let normalized = LineEnding::normalize(source);
+        assert_eq!(normalized, "fn main() {\n    return;\n}\n");
+        assert_eq!(line_ending.apply(&normalized), source);
+
+        assert_eq!(LineEnding::detect(&normalized), LineEnding::Lf);
+        assert_eq!(LineEnding::detect(""), LineEnding::Lf);
+    }
+}
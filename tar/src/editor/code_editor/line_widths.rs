@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use ropey::RopeSlice;
+
+/// Widths of lines keyed by their content, so a relayout only measures the lines that
+/// changed since the last one.
+#[derive(Debug, Default)]
+pub struct LineWidthCache {
+    font_size: f32,
+    tab_width: usize,
+    widths: HashMap<u64, f32>,
+}
+
+impl LineWidthCache {
+    /// Width of the widest of `lines`, `measure` is only called for lines that aren't cached.
+    /// Entries for lines that no longer exist are dropped, all of them when the font size or
+    /// tab width changed.
+    pub fn max_width<'a>(
+        &mut self,
+        font_size: f32,
+        tab_width: usize,
+        lines: impl Iterator<Item = RopeSlice<'a>>,
+        mut measure: impl FnMut(&str) -> f32,
+    ) -> f32 {
+        if font_size != self.font_size || tab_width != self.tab_width {
+            self.font_size = font_size;
+            self.tab_width = tab_width;
+            self.widths.clear();
+        }
+
+        let mut widths = HashMap::with_capacity(self.widths.len());
+        let mut max_width: f32 = 0.0;
+        for line in lines {
+            let mut hasher = DefaultHasher::new();
+            line.hash(&mut hasher);
+            let key = hasher.finish();
+
+            let width = match self.widths.get(&key) {
+                Some(width) => *width,
+                None => measure(&line.to_string()),
+            };
+            widths.insert(key, width);
+            max_width = max_width.max(width);
+        }
+        self.widths = widths;
+
+        max_width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use super::*;
+
+    #[test]
+    fn test_only_changed_lines_are_measured() {
+        let text: String = (0..10_000)
+            .map(|i| format!("let line_{} = {};\n", i, i))
+            .collect();
+        let mut doc = Rope::from_str(&text);
+        let mut cache = LineWidthCache::default();
+
+        let mut measured = 0;
+        let mut measure = |line: &str| {
+            measured += 1;
+            line.chars().count() as f32
+        };
+        let width = cache.max_width(14.0, 4, doc.lines(), &mut measure);
+        assert_eq!(width, "let line_9999 = 9999;\n".len() as f32);
+        assert_eq!(measured, 10_001);
+
+        let line_start = doc.line_to_char(5000);
+        doc.insert(line_start, "// a much longer line than all of the others\n");
+
+        let mut measured = 0;
+        let mut measure = |line: &str| {
+            measured += 1;
+            line.chars().count() as f32
+        };
+        let width = cache.max_width(14.0, 4, doc.lines(), &mut measure);
+        assert_eq!(width, 45.0);
+        assert_eq!(measured, 1);
+
+        // A different font size measures everything again
+        let mut measured = 0;
+        cache.max_width(16.0, 4, doc.lines(), |_| {
+            measured += 1;
+            0.0
+        });
+        assert_eq!(measured, 10_002);
+
+        // So does a different tab width
+        let mut measured = 0;
+        cache.max_width(16.0, 8, doc.lines(), |_| {
+            measured += 1;
+            0.0
+        });
+        assert_eq!(measured, 10_002);
+    }
+}
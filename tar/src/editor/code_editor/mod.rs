@@ -1,7 +1,6 @@
 // Modified version built on-top of Roman Chumak's egui_code_editor
 // (https://github.com/p4ymak/egui_code_editor/)
 
-use egui::epaint::text::PlacedRow;
 use egui::Color32;
 use ropey::Rope;
 use std::hash::{DefaultHasher, Hash, Hasher};
@@ -9,13 +8,27 @@ use std::ops::Range;
 
 use crate::editor::code_editor::highlighting::highlight;
 use crate::editor::code_editor::{
-    highlighting::Token,
+    actions::{EditorAction, KeyBinding, KeyBindings},
+    completion::Completion,
+    diagnostics::Diagnostic,
+    highlighting::{block_comment_starts, tokenize, Token},
+    line_ending::LineEnding,
+    line_widths::LineWidthCache,
+    search::{find_matches, SearchState},
+    settings::{EditorSettings, LineNumbers},
     syntax::{Syntax, TokenType},
     themes::ColorTheme,
 };
 use crate::egui_util::KeyModifiers;
 
+pub mod actions;
+pub mod completion;
+pub mod diagnostics;
 pub mod highlighting;
+pub mod line_ending;
+pub mod line_widths;
+pub mod search;
+pub mod settings;
 pub mod syntax;
 pub mod themes;
 
@@ -29,15 +42,32 @@ const GUTTER_PADDING: f32 = 8.0;
 const TEXT_PADDING: f32 = 6.0;
 const CURSOR_REVEAL_V_MARGIN_LINES: f32 = 6.0;
 const CURSOR_REVEAL_H_MARGIN: f32 = 40.0;
+/// Documents with more lines than this skip features that scale with the document size.
+const LARGE_FILE_LINES: usize = 5000;
+const AUTO_CLOSE_PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+const BRACKET_PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+/// Give up looking for a matching bracket after this many chars.
+const BRACKET_MATCH_MAX_SCAN: usize = 100_000;
 
 // Cursor blink
 const BLINK_SPEED: f64 = 0.530 * 2.0;
+/// Seconds the highlight of [`CodeEditor::reveal_range`] takes to fade out.
+const REVEAL_FLASH_DURATION: f64 = 1.0;
+/// Width of the strip along the scrollbar marking the search matches.
+const SEARCH_RULER_WIDTH: f32 = 6.0;
+/// Matches closer together on the ruler than this share one tick.
+const SEARCH_RULER_TICK_HEIGHT: f32 = 2.0;
+/// Horizontal distance between the peaks and troughs of a diagnostic underline.
+const SQUIGGLE_STEP: f32 = 2.0;
+const SQUIGGLE_AMPLITUDE: f32 = 1.0;
 
 // Touch scrolling
 const TOUCH_SCROLL_SENSITIVITY: f32 = 4.5;
 const TOUCH_SCROLL_SMOOTHING: f32 = 20.0;
 const TOUCH_SCROLL_DAMPING: f32 = 5.0;
 const AXIS_LOCK_THRESHOLD: f32 = 6.0;
+/// Momentum below this speed in points per second stops scrolling.
+const MIN_MOMENTUM_SPEED: f32 = 30.0;
 
 // ============================================================================
 // Helper Functions
@@ -49,11 +79,62 @@ fn char_to_line_col(doc: &Rope, char_idx: usize) -> (usize, usize) {
     (line, char_idx - line_start)
 }
 
+/// Zigzag from `x_start` to `x_end` around `y`, used to underline diagnostics.
+fn squiggle_points(x_start: f32, x_end: f32, y: f32) -> Vec<egui::Pos2> {
+    let steps = ((x_end - x_start) / SQUIGGLE_STEP).ceil().max(1.0) as usize;
+    (0..=steps)
+        .map(|i| {
+            let x = (x_start + i as f32 * SQUIGGLE_STEP).min(x_end);
+            let offset = if i % 2 == 0 {
+                -SQUIGGLE_AMPLITUDE
+            } else {
+                SQUIGGLE_AMPLITUDE
+            };
+            egui::pos2(x, y + offset)
+        })
+        .collect()
+}
+
+/// The number shown in the gutter for `line`, `None` when the gutter is hidden.
+fn displayed_line_number(mode: LineNumbers, line: usize, cursor_line: usize) -> Option<usize> {
+    match mode {
+        LineNumbers::Absolute => Some(line + 1),
+        LineNumbers::Relative if line == cursor_line => Some(line + 1),
+        LineNumbers::Relative => Some(line.abs_diff(cursor_line)),
+        LineNumbers::Hidden => None,
+    }
+}
+
+/// The number the gutter is sized for in a document of `total_lines`. No number
+/// [`displayed_line_number`] produces is wider, relative numbers are sized the same so the
+/// gutter doesn't change width while the cursor moves.
+fn widest_line_number(mode: LineNumbers, total_lines: usize) -> Option<usize> {
+    match mode {
+        LineNumbers::Absolute | LineNumbers::Relative => Some(total_lines.max(1)),
+        LineNumbers::Hidden => None,
+    }
+}
+
 fn leading_whitespace(s: &str) -> &str {
     let count = s.chars().take_while(|c| *c == ' ' || *c == '\t').count();
     &s[..count]
 }
 
+/// Per line char ranges of the block between two `(line, column)` corners, columns past
+/// the end of a line are clamped to it.
+fn block_ranges(doc: &Rope, anchor: (usize, usize), head: (usize, usize)) -> Vec<Range<usize>> {
+    let lines = anchor.0.min(head.0)..anchor.0.max(head.0) + 1;
+    let (col_start, col_end) = (anchor.1.min(head.1), anchor.1.max(head.1));
+
+    lines
+        .map(|line| {
+            let line_start = doc.line_to_char(line);
+            let line_len = line_len_without_newline(doc.line(line));
+            line_start + col_start.min(line_len)..line_start + col_end.min(line_len)
+        })
+        .collect()
+}
+
 fn line_len_without_newline(line: ropey::RopeSlice) -> usize {
     let len = line.len_chars();
     if len > 0 && line.char(len - 1) == '\n' {
@@ -63,6 +144,212 @@ fn line_len_without_newline(line: ropey::RopeSlice) -> usize {
     }
 }
 
+/// Shift the lines of `text` so its indentation is relative to `indent`, the
+/// first line is assumed to be inserted after existing indentation.
+fn reindent(text: &str, indent: &str) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+
+    let common_indent = lines
+        .iter()
+        .enumerate()
+        .filter(|(i, line)| {
+            !line.trim().is_empty() && (*i > 0 || !leading_whitespace(line).is_empty())
+        })
+        .map(|(_, line)| leading_whitespace(line).len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                line.trim_start().to_string()
+            } else if line.trim().is_empty() {
+                String::new()
+            } else {
+                let strip = leading_whitespace(line).len().min(common_indent);
+                format!("{}{}", indent, &line[strip..])
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Word,
+    Whitespace,
+    Newline,
+    Punctuation,
+}
+
+fn char_class(c: char) -> CharClass {
+    match c {
+        '\n' => CharClass::Newline,
+        c if c.is_alphanumeric() || c == '_' => CharClass::Word,
+        c if c.is_whitespace() => CharClass::Whitespace,
+        _ => CharClass::Punctuation,
+    }
+}
+
+/// Where a word-wise step from `char_idx` ends, forwards or backwards. Skips whitespace
+/// on the same line and then one run of word or punctuation characters, a line break is
+/// a step of its own.
+fn next_word_boundary(doc: &Rope, char_idx: usize, forward: bool) -> usize {
+    let len = doc.len_chars();
+    let peek = |i: usize| {
+        if forward {
+            (i < len).then(|| char_class(doc.char(i)))
+        } else {
+            (i > 0).then(|| char_class(doc.char(i - 1)))
+        }
+    };
+    let step = |i: usize| if forward { i + 1 } else { i - 1 };
+
+    let mut i = char_idx.min(len);
+    if peek(i) == Some(CharClass::Newline) {
+        return step(i);
+    }
+
+    while peek(i) == Some(CharClass::Whitespace) {
+        i = step(i);
+    }
+    if let Some(class) = peek(i).filter(|class| *class != CharClass::Newline) {
+        while peek(i) == Some(class) {
+            i = step(i);
+        }
+    }
+    i
+}
+
+/// Positions of the bracket right after or else right before `cursor` and its partner,
+/// `None` when there is no bracket there or it is unbalanced.
+fn matching_bracket(doc: &Rope, cursor: usize) -> Option<(usize, usize)> {
+    let len = doc.len_chars();
+    let candidates = [cursor, cursor.wrapping_sub(1)];
+    let (pos, c) = candidates
+        .into_iter()
+        .filter(|pos| *pos < len)
+        .map(|pos| (pos, doc.char(pos)))
+        .find(|(_, c)| BRACKET_PAIRS.iter().any(|(o, cl)| c == o || c == cl))?;
+
+    let (&(open, close), forward) = BRACKET_PAIRS.iter().find_map(|pair| {
+        (pair.0 == c)
+            .then_some((pair, true))
+            .or((pair.1 == c).then_some((pair, false)))
+    })?;
+
+    let mut depth = 0usize;
+    if forward {
+        for (i, ch) in doc
+            .chars_at(pos + 1)
+            .enumerate()
+            .take(BRACKET_MATCH_MAX_SCAN)
+        {
+            if ch == open {
+                depth += 1;
+            } else if ch == close {
+                if depth == 0 {
+                    return Some((pos, pos + 1 + i));
+                }
+                depth -= 1;
+            }
+        }
+    } else {
+        let mut chars = doc.chars_at(pos);
+        for i in 0..pos.min(BRACKET_MATCH_MAX_SCAN) {
+            let ch = chars.prev()?;
+            if ch == close {
+                depth += 1;
+            } else if ch == open {
+                if depth == 0 {
+                    return Some((pos, pos - 1 - i));
+                }
+                depth -= 1;
+            }
+        }
+    }
+    None
+}
+
+fn is_blank_line(line: ropey::RopeSlice) -> bool {
+    line.chars().all(char::is_whitespace)
+}
+
+fn toggle_case(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_uppercase() {
+            result.extend(c.to_lowercase());
+        } else {
+            result.extend(c.to_uppercase());
+        }
+    }
+    result
+}
+
+/// Column on screen after `chars`, counting up to the next tab stop for every `\t`.
+fn visual_column(chars: impl IntoIterator<Item = char>, tab_width: usize) -> usize {
+    chars.into_iter().fold(0, |column, c| {
+        if c == '\t' {
+            (column / tab_width + 1) * tab_width
+        } else {
+            column + 1
+        }
+    })
+}
+
+/// Make every `\t` in `job` end at the next multiple of `tab_width` columns instead of
+/// egui's fixed width, by shifting the text after it. Assumes a monospace font with
+/// `space_width` wide columns and `job` starting at the beginning of a line.
+fn align_tab_stops(job: &mut egui::text::LayoutJob, tab_width: usize, space_width: f32) {
+    if !job.text.contains('\t') {
+        return;
+    }
+
+    let mut sections = Vec::with_capacity(job.sections.len());
+    let mut column = 0;
+    let mut pending_space = 0.0;
+    for section in std::mem::take(&mut job.sections) {
+        let mut start = section.byte_range.start;
+        let mut leading_space = section.leading_space + std::mem::take(&mut pending_space);
+
+        for (offset, c) in job.text[section.byte_range.clone()].char_indices() {
+            match c {
+                '\n' => column = 0,
+                '\t' => {
+                    let next_stop = (column / tab_width + 1) * tab_width;
+                    let shift = next_stop as f32 - (column + egui::epaint::text::TAB_SIZE) as f32;
+                    column = next_stop;
+
+                    // Split right after the tab, the rest starts that much further along
+                    let end = section.byte_range.start + offset + 1;
+                    sections.push(egui::text::LayoutSection {
+                        leading_space,
+                        byte_range: start..end,
+                        format: section.format.clone(),
+                    });
+                    start = end;
+                    leading_space = shift * space_width;
+                }
+                _ => column += 1,
+            }
+        }
+
+        if start < section.byte_range.end {
+            sections.push(egui::text::LayoutSection {
+                leading_space,
+                byte_range: start..section.byte_range.end,
+                format: section.format,
+            });
+        } else {
+            pending_space = leading_space;
+        }
+    }
+    job.sections = sections;
+}
+
 fn lerp_vec2(a: egui::Vec2, b: egui::Vec2, t: f32) -> egui::Vec2 {
     a + (b - a) * t
 }
@@ -82,10 +369,28 @@ pub struct Edit {
     pub selection_after: Option<Range<usize>>,
 }
 
+/// An undoable change, either to the text or to how it's saved.
+#[derive(Clone, Debug)]
+pub enum EditStep {
+    Text(Edit),
+    /// The document itself always uses `\n`, only the line endings it's saved with change.
+    LineEnding {
+        before: LineEnding,
+        after: LineEnding,
+    },
+    /// A text edit and a line ending change undone together, see
+    /// [`CodeEditor::set_text_and_line_ending`].
+    TextAndLineEnding {
+        edit: Edit,
+        before: LineEnding,
+        after: LineEnding,
+    },
+}
+
 #[derive(Default)]
 pub struct EditStack {
-    undo: Vec<Edit>,
-    redo: Vec<Edit>,
+    undo: Vec<EditStep>,
+    redo: Vec<EditStep>,
 }
 
 // ============================================================================
@@ -97,29 +402,143 @@ enum TouchScrollAxis {
     Horizontal,
 }
 
+/// Tuning of touch scrolling and the momentum that follows it.
+/// Smoothing and damping are rates per second, so they feel the same at any frame rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TouchScrollConfig {
+    /// Scroll by dragging a single finger instead of two, a long press starts a selection.
+    pub single_finger: bool,
+    /// Multiplier applied to the finger movement.
+    pub sensitivity: f32,
+    /// How quickly the momentum velocity follows the fingers, higher reacts faster to changes.
+    pub smoothing: f32,
+    /// How quickly the momentum dies down after releasing, higher stops sooner.
+    pub damping: f32,
+    /// Distance in points the fingers move before scrolling locks to the dominant axis.
+    pub axis_lock_threshold: f32,
+}
+
+impl Default for TouchScrollConfig {
+    fn default() -> Self {
+        Self {
+            single_finger: false,
+            sensitivity: TOUCH_SCROLL_SENSITIVITY,
+            smoothing: TOUCH_SCROLL_SMOOTHING,
+            damping: TOUCH_SCROLL_DAMPING,
+            axis_lock_threshold: AXIS_LOCK_THRESHOLD,
+        }
+    }
+}
+
+/// Highlight behind a revealed range, fading out over [`REVEAL_FLASH_DURATION`].
+struct RevealFlash {
+    range: Range<usize>,
+    /// Time the flash was first drawn, `None` until then.
+    start_time: Option<f64>,
+}
+
+/// Where the user was in a document, to return there after reopening it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EditorViewState {
+    pub cursor: usize,
+    pub selection: Option<Range<usize>>,
+    pub scroll_offset: f32,
+}
+
 // ============================================================================
 // CodeEditor
 // ============================================================================
 
+type OnChange = Box<dyn FnMut(&Rope)>;
+
 pub struct CodeEditor {
     pub doc: Rope,
     doc_hash: u64,
+    generation: u64,
+    /// Called with the document after every edit that actually changed its contents.
+    on_change: Option<OnChange>,
 
     edit_stack: EditStack,
     max_line_width: Option<f32>,
+    line_widths: LineWidthCache,
     text_layout_job: Option<egui::text::LayoutJob>,
     prev_scroll_offset: f32,
+    /// Scroll to this offset on the next frame, set when restoring a view state.
+    restore_scroll_offset: Option<f32>,
 
     pub cursor: usize,
     cursor_blink_offset: f64,
     cursor_request_focus: bool,
+    /// Take keyboard focus on the next frame.
+    focus_requested: bool,
     desired_column: Option<usize>,
     pub selection: Option<Range<usize>>,
     selection_anchor: Option<usize>,
-
+    /// Column selection made with Alt+drag, one range per line from top to bottom.
+    /// Coexists with `selection`, which is cleared while a block is selected.
+    pub block_selection: Option<Vec<Range<usize>>>,
+    /// Line and column the block selection drag started at.
+    block_anchor: Option<(usize, usize)>,
+    /// Selection being dragged somewhere else and the char the drag started at.
+    moving_selection: Option<(Range<usize>, usize)>,
+    /// Where the moved selection gets dropped, drawn as a ghost caret.
+    move_target: Option<usize>,
+    /// Selections and cursors from before each paragraph expansion, with the
+    /// selection the expansion produced so stale entries can be detected.
+    paragraph_selections: Vec<(Option<Range<usize>>, usize, Range<usize>)>,
+    completion: Option<Completion>,
+    diagnostics: Vec<Diagnostic>,
+    /// Sorted ranges of the current search, marked on the ruler along the scrollbar.
+    search_matches: Vec<Range<usize>>,
+    /// [`Self::generation`] the search matches were found for.
+    search_matches_generation: u64,
+    /// Generation, top line and result of the last [`Self::enclosing_header_line`] lookup.
+    sticky_header: Option<(u64, usize, Option<usize>)>,
+    /// The find/replace panel, `None` while closed.
+    search: Option<SearchState>,
+    /// Typed line number of the go to line input, `None` while closed.
+    goto_line_input: Option<String>,
+    goto_line_focus: bool,
+    reveal_flash: Option<RevealFlash>,
+    /// Block all edits, navigating, selecting and copying still work.
+    readonly: bool,
+    /// Line endings the document is saved with.
+    line_ending: LineEnding,
+
+    touch_scroll: TouchScrollConfig,
+    /// Momentum in points per second.
     touch_scroll_velocity: egui::Vec2,
     touch_scroll_axis_lock: Option<TouchScrollAxis>,
     touch_scroll_timestamp: f64,
+    /// Whether the last pointer press came from a touch screen.
+    touch_pointer: bool,
+    /// Long pressed during the current single finger touch, dragging selects instead of scrolls.
+    touch_selecting: bool,
+
+    /// Show the header of the function or struct enclosing the top visible line.
+    pub sticky_scroll: bool,
+    pub key_bindings: KeyBindings,
+    /// Re-indent pasted multi-line text to the indentation of the cursor line.
+    pub paste_reindent: bool,
+    /// Automatically insert the closing bracket or quote when typing an opening one.
+    pub auto_close: bool,
+    /// Typed text replaces the characters under the cursor, toggled with Insert.
+    pub overwrite: bool,
+    /// Columns between tab stops, both for inserted spaces and for drawing `\t`.
+    tab_width: usize,
+    /// Insert spaces up to the next tab stop on Tab instead of a `\t`.
+    use_spaces: bool,
+    /// Draw dots for spaces and arrows for tabs.
+    pub show_whitespace: bool,
+    /// Wrap long lines at the width of the editor instead of scrolling horizontally.
+    pub wrap: bool,
+    /// Width lines are wrapped at by the current layout, infinite while not wrapping.
+    wrap_width: f32,
+    /// First display row of every line while wrapping, followed by the total row count.
+    line_rows: Option<Vec<usize>>,
+    /// Generation the block comment state of every line was computed for.
+    block_comment_starts: Option<(u64, Vec<bool>)>,
+    pub line_numbers: LineNumbers,
 
     theme: ColorTheme,
     syntax: Syntax,
@@ -127,23 +546,62 @@ pub struct CodeEditor {
 }
 
 impl CodeEditor {
+    /// The document is normalized to `\n`, the line endings of `text` are kept in
+    /// [`Self::line_ending`].
     pub fn new(text: &str, theme: ColorTheme, syntax: Syntax) -> Self {
         let mut code_editor = Self {
-            doc: Rope::from_str(text),
+            doc: Rope::from_str(&LineEnding::normalize(text)),
             doc_hash: 0,
+            generation: 0,
+            on_change: None,
             edit_stack: EditStack::default(),
             max_line_width: None,
+            line_widths: LineWidthCache::default(),
             text_layout_job: None,
             prev_scroll_offset: 0.0,
+            restore_scroll_offset: None,
             cursor: 0,
             cursor_blink_offset: 0.0,
             cursor_request_focus: false,
+            focus_requested: false,
             desired_column: None,
             selection: None,
             selection_anchor: None,
+            block_selection: None,
+            block_anchor: None,
+            moving_selection: None,
+            move_target: None,
+            paragraph_selections: Vec::new(),
+            completion: None,
+            diagnostics: Vec::new(),
+            search_matches: Vec::new(),
+            search_matches_generation: 0,
+            sticky_header: None,
+            search: None,
+            goto_line_input: None,
+            goto_line_focus: false,
+            reveal_flash: None,
+            readonly: false,
+            line_ending: LineEnding::detect(text),
+            touch_scroll: TouchScrollConfig::default(),
             touch_scroll_velocity: egui::Vec2::ZERO,
             touch_scroll_axis_lock: None,
             touch_scroll_timestamp: 0.0,
+            touch_pointer: false,
+            touch_selecting: false,
+            sticky_scroll: false,
+            key_bindings: KeyBindings::default(),
+            paste_reindent: false,
+            auto_close: true,
+            overwrite: false,
+            tab_width: TAB_WIDTH,
+            use_spaces: true,
+            show_whitespace: false,
+            wrap: false,
+            wrap_width: f32::INFINITY,
+            line_rows: None,
+            block_comment_starts: None,
+            line_numbers: LineNumbers::default(),
             theme,
             syntax,
             fontsize: 14.0,
@@ -159,18 +617,273 @@ impl CodeEditor {
     // ========================================================================
 
     pub fn ui(&mut self, ui: &mut egui::Ui, key_modifiers: &KeyModifiers) -> bool {
-        egui::ScrollArea::both()
+        let goto_line_focused = self.goto_line_ui(ui);
+        let search_focused = self.search_ui(ui);
+
+        let scroll_area = if self.wrapping() {
+            egui::ScrollArea::vertical()
+        } else {
+            egui::ScrollArea::both()
+        };
+        let scroll_area = match self.restore_scroll_offset.take() {
+            Some(offset) => scroll_area.vertical_scroll_offset(offset),
+            None => scroll_area,
+        };
+        let editor_focused = scroll_area
             .auto_shrink([false, false])
             .show_viewport(ui, |ui, viewport| {
                 self.draw_editor(ui, viewport, key_modifiers)
             })
-            .inner
+            .inner;
+
+        editor_focused || search_focused || goto_line_focused
+    }
+
+    pub fn request_focus(&mut self) {
+        self.focus_requested = true;
+    }
+
+    pub fn apply_settings(&mut self, settings: &EditorSettings) {
+        self.set_tab_width(settings.tab_width);
+        self.set_use_spaces(settings.use_spaces);
+        self.fontsize = settings.font_size;
+        self.theme = settings.color_theme();
+        self.show_whitespace = settings.show_whitespace;
+        self.wrap = settings.word_wrap;
+        self.line_numbers = settings.line_numbers;
+        self.auto_close = settings.auto_close;
+        self.paste_reindent = settings.paste_reindent;
+        self.sticky_scroll = settings.sticky_scroll;
+        self.touch_scroll.single_finger = settings.single_finger_scroll;
+        self.invalidate_layout();
+    }
+
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Clamped to at least one column.
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+        self.invalidate_layout();
+    }
+
+    pub fn use_spaces(&self) -> bool {
+        self.use_spaces
+    }
+
+    pub fn set_use_spaces(&mut self, use_spaces: bool) {
+        self.use_spaces = use_spaces;
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.readonly
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Change the line endings the document is saved with, as an undoable edit.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.readonly || line_ending == self.line_ending {
+            return;
+        }
+
+        self.edit_stack.undo.push(EditStep::LineEnding {
+            before: self.line_ending,
+            after: line_ending,
+        });
+        self.edit_stack.redo.clear();
+        self.line_ending = line_ending;
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn set_readonly(&mut self, readonly: bool) {
+        self.readonly = readonly;
+        if readonly {
+            self.completion = None;
+        }
+    }
+
+    pub fn touch_scroll(&self) -> &TouchScrollConfig {
+        &self.touch_scroll
+    }
+
+    /// Negative values are clamped to zero.
+    pub fn set_touch_scroll(&mut self, touch_scroll: TouchScrollConfig) {
+        self.touch_scroll = TouchScrollConfig {
+            single_finger: touch_scroll.single_finger,
+            sensitivity: touch_scroll.sensitivity.max(0.0),
+            smoothing: touch_scroll.smoothing.max(0.0),
+            damping: touch_scroll.damping.max(0.0),
+            axis_lock_threshold: touch_scroll.axis_lock_threshold.max(0.0),
+        };
+    }
+
+    /// Vertical scroll offset of the last drawn frame.
+    pub fn scroll_offset(&self) -> f32 {
+        self.prev_scroll_offset
+    }
+
+    pub fn view_state(&self) -> EditorViewState {
+        EditorViewState {
+            cursor: self.cursor,
+            selection: self.selection.clone(),
+            scroll_offset: self.prev_scroll_offset,
+        }
+    }
+
+    /// Put the cursor, selection and scroll position back, clamped to the current document.
+    /// The scroll offset is applied the next time the editor is drawn.
+    pub fn restore_view_state(&mut self, view_state: EditorViewState) {
+        let len = self.doc.len_chars();
+        self.cursor = view_state.cursor.min(len);
+        self.selection = view_state
+            .selection
+            .map(|sel| sel.start.min(len)..sel.end.min(len))
+            .filter(|sel| !sel.is_empty());
+        self.selection_anchor = self.selection.as_ref().map(|sel| sel.start);
+        self.desired_column = None;
+        self.completion = None;
+        self.restore_scroll_offset = Some(view_state.scroll_offset.max(0.0));
     }
 
     pub fn doc_hash(&self) -> u64 {
         self.doc_hash
     }
 
+    /// Call `on_change` with the document whenever an edit, undo, redo or format changes
+    /// its contents, so the host doesn't have to compare [`Self::doc_hash`] every frame.
+    pub fn set_on_change(&mut self, on_change: impl FnMut(&Rope) + 'static) {
+        self.on_change = Some(Box::new(on_change));
+    }
+
+    /// Incremented on every change to the document, cheap to compare against a
+    /// previously seen value to find out if anything was edited.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Whether expensive features are disabled because the document is too large.
+    pub fn is_large_file(&self) -> bool {
+        self.doc.len_lines() > LARGE_FILE_LINES
+    }
+
+    /// Whether long lines are wrapped, never for large files as every line has to be laid
+    /// out to know how many rows it takes.
+    pub fn wrapping(&self) -> bool {
+        self.wrap && !self.is_large_file()
+    }
+
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics = diagnostics;
+    }
+
+    /// Mark the matches of a search on the ruler along the scrollbar, empty to hide the ruler.
+    /// They are dropped on the next edit, as they can't be found again without the query.
+    pub fn set_search_matches(&mut self, mut search_matches: Vec<Range<usize>>) {
+        search_matches.sort_by_key(|range| (range.start, range.end));
+        self.search_matches = search_matches;
+        self.search_matches_generation = self.generation;
+    }
+
+    pub fn search_matches(&self) -> &[Range<usize>] {
+        &self.search_matches
+    }
+
+    /// Replace the whole document as a single undoable edit, blocked while read-only like any
+    /// other edit. The cursor stays at the same offset where the new text is long enough.
+    pub fn set_text(&mut self, text: &str) {
+        let removed = self.doc.to_string();
+        if removed == text {
+            return;
+        }
+
+        let edit = Edit {
+            range: 0..self.doc.len_chars(),
+            removed,
+            inserted: text.to_string(),
+            cursor_before: self.cursor,
+            cursor_after: self.cursor.min(text.chars().count()),
+            selection_before: self.selection.clone(),
+            selection_after: None,
+        };
+        self.completion = None;
+        self.selection_anchor = None;
+        self.apply_edit(edit);
+    }
+
+    /// Replace the whole document like [`Self::set_text`] and change the line endings it's
+    /// saved with, as a single undoable edit.
+    pub fn set_text_and_line_ending(&mut self, text: &str, line_ending: LineEnding) {
+        if self.readonly {
+            return;
+        }
+
+        let undo_len = self.edit_stack.undo.len();
+        self.set_text(text);
+        if line_ending == self.line_ending {
+            return;
+        }
+
+        let before = std::mem::replace(&mut self.line_ending, line_ending);
+        self.generation = self.generation.wrapping_add(1);
+        // Fold the line ending into the edit `set_text` pushed when the text changed
+        let text_changed = self.edit_stack.undo.len() > undo_len;
+        let step = match text_changed.then(|| self.edit_stack.undo.pop()).flatten() {
+            Some(EditStep::Text(edit)) => EditStep::TextAndLineEnding {
+                edit,
+                before,
+                after: line_ending,
+            },
+            _ => EditStep::LineEnding {
+                before,
+                after: line_ending,
+            },
+        };
+        self.edit_stack.undo.push(step);
+        self.edit_stack.redo.clear();
+    }
+
+    /// The whole document split into the tokens used for highlighting.
+    pub fn tokens(&self) -> Vec<Token> {
+        tokenize(&self.syntax, &self.doc.to_string())
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Select `range`, scroll it into view and briefly flash a highlight behind it.
+    /// Shared by everything that navigates to a location, like find and diagnostics.
+    pub fn reveal_range(&mut self, range: Range<usize>) {
+        let len = self.doc.len_chars();
+        let range = range.start.min(len)..range.end.min(len);
+
+        self.selection_anchor = Some(range.start);
+        self.selection = (!range.is_empty()).then(|| range.clone());
+        self.desired_column = None;
+        self.update_cursor(range.end);
+        self.reveal_flash = Some(RevealFlash {
+            range,
+            start_time: None,
+        });
+    }
+
+    /// Move the cursor to the first non-whitespace character of the zero based `line`,
+    /// clamped to the last line, and scroll it into view.
+    pub fn goto_line(&mut self, line: usize) {
+        let line = line.min(self.doc.len_lines() - 1);
+        let indent = leading_whitespace(&self.doc.line(line).to_string()).len();
+
+        let pos = self.doc.line_to_char(line) + indent;
+
+        self.completion = None;
+        self.reveal_range(pos..pos);
+        self.selection_anchor = None;
+    }
+
     // ========================================================================
     // Main Draw Method
     // ========================================================================
@@ -186,19 +899,24 @@ impl CodeEditor {
 
         let font_id = egui::FontId::monospace(self.fontsize);
         let line_height = self.line_height(ui, &font_id);
+        let gutter_width = self.calculate_gutter_width(ui, &font_id);
+        self.update_wrap_width(viewport.width() - gutter_width - TEXT_PADDING * 2.0);
+        self.ensure_line_rows(ui, &font_id);
 
         let (start_line, end_line) = self.calculate_visible_lines(scroll_offset, line_height, ui);
         let visible_text = self.extract_visible_text(start_line, end_line);
 
-        self.ensure_layout_job(ui, &visible_text);
+        self.ensure_block_comment_starts();
+        // Edits made since the search panel was drawn move the matches
+        self.update_search();
+        self.ensure_layout_job(ui, &visible_text, self.starts_in_block_comment(start_line));
         let visible_galley = ui.fonts_mut(|f| f.layout_job(self.text_layout_job.clone().unwrap()));
 
-        let visible_offset_y = start_line as f32 * line_height;
+        let visible_offset_y = self.line_row(start_line) as f32 * line_height;
         let (rect, response, visible_rect) =
             self.allocate_editor_rect(ui, &font_id, line_height, visible_offset_y);
 
         let painter = ui.painter_at(visible_rect);
-        let gutter_width = self.calculate_gutter_width(ui, &font_id);
         let text_x = visible_rect.min.x + gutter_width + TEXT_PADDING;
 
         // Render
@@ -208,12 +926,49 @@ impl CodeEditor {
             ui,
             &font_id,
             visible_rect,
-            &visible_galley,
-            start_line,
+            start_line..end_line,
+            line_height,
             gutter_width,
         );
+        self.render_search_matches(
+            &painter,
+            ui,
+            &font_id,
+            rect,
+            text_x,
+            line_height,
+            start_line..end_line,
+        );
         self.render_selection(&painter, ui, &font_id, rect, text_x, line_height);
+        self.render_reveal_flash(&painter, ui, &font_id, rect, text_x, line_height);
         self.render_text(&painter, text_x, visible_rect, &visible_galley);
+        self.render_diagnostics(
+            &painter,
+            ui,
+            &font_id,
+            rect,
+            text_x,
+            line_height,
+            start_line..end_line,
+        );
+        let large_file = self.is_large_file();
+        let readonly_indicator = self
+            .readonly
+            .then(|| self.render_readonly_indicator(&painter, large_file));
+        if large_file {
+            self.render_large_file_indicator(&painter, visible_rect);
+        } else if self.sticky_scroll {
+            self.render_sticky_header(
+                &painter,
+                ui,
+                &font_id,
+                rect,
+                visible_rect,
+                scroll_offset,
+                line_height,
+                gutter_width,
+            );
+        }
 
         // Input handling
         let time = ui.input(|i| i.time);
@@ -221,11 +976,37 @@ impl CodeEditor {
 
         self.setup_event_filter(ui, response.id);
         let response = response.on_hover_cursor(egui::CursorIcon::Text);
+        let response = self.diagnostic_tooltip(
+            ui,
+            response,
+            &font_id,
+            rect,
+            text_x,
+            line_height,
+            start_line..end_line,
+        );
+        let response = match readonly_indicator {
+            Some(indicator)
+                if response
+                    .hover_pos()
+                    .is_some_and(|pos| indicator.contains(pos)) =>
+            {
+                response.on_hover_text_at_pointer(
+                    "Edits are blocked, selecting and copying still works",
+                )
+            }
+            _ => response,
+        };
+
+        if std::mem::take(&mut self.focus_requested) {
+            response.request_focus();
+        }
 
         if response.has_focus() {
-            self.handle_touch_scroll(ui, time, delta_time);
+            self.handle_touch_scroll(ui, &response, time, delta_time);
         } else {
             self.touch_scroll_velocity = egui::Vec2::ZERO;
+            self.completion = None;
         }
 
         self.handle_mouse_input(
@@ -241,10 +1022,17 @@ impl CodeEditor {
         self.apply_scroll_velocity(ui, delta_time);
 
         if response.has_focus() {
+            self.render_bracket_match(&painter, ui, &font_id, rect, text_x, line_height);
             self.render_cursor(&painter, ui, &font_id, rect, text_x, line_height, time);
+            self.render_completion(ui, &font_id, rect, text_x, line_height);
             self.handle_keyboard_input(ui, key_modifiers, time);
-            self.handle_cursor_scroll(ui, rect, line_height);
+            self.request_blink_repaint(ui, time);
         }
+        self.update_search();
+        self.search_ruler_ui(ui);
+
+        // Also while unfocused, so a range revealed from elsewhere scrolls into view
+        self.handle_cursor_scroll(ui, &font_id, rect, text_x, line_height);
 
         response.has_focus()
     }
@@ -269,11 +1057,11 @@ impl CodeEditor {
         let visible_start_y = scroll_offset.max(0.0);
         let visible_end_y = visible_start_y + ui.available_height();
 
-        let start_line = ((visible_start_y / line_height) as usize)
-            .saturating_sub(BUFFER_LINES)
-            .min(self.doc.len_lines() - 1);
-        let end_line =
-            ((visible_end_y / line_height) as usize + BUFFER_LINES + 1).min(self.doc.len_lines());
+        let start_line = self
+            .row_line((visible_start_y / line_height) as usize)
+            .saturating_sub(BUFFER_LINES);
+        let end_line = (self.row_line((visible_end_y / line_height) as usize) + BUFFER_LINES + 1)
+            .min(self.doc.len_lines());
 
         (start_line, end_line)
     }
@@ -287,12 +1075,86 @@ impl CodeEditor {
             .collect()
     }
 
-    fn ensure_layout_job(&mut self, ui: &egui::Ui, visible_text: &str) {
+    fn ensure_layout_job(&mut self, ui: &egui::Ui, visible_text: &str, in_block_comment: bool) {
         if self.text_layout_job.is_none() {
-            self.text_layout_job = Some(highlight(ui.ctx(), self, visible_text));
+            let mut job = highlight(ui.ctx(), self, visible_text, in_block_comment);
+            self.align_tab_stops(ui, &mut job);
+            job.wrap.max_width = self.wrap_width;
+            self.text_layout_job = Some(job);
+        }
+    }
+
+    /// Wrap at `width` from now on when wrapping, relayout when that changes the layout.
+    fn update_wrap_width(&mut self, width: f32) {
+        let wrap_width = if self.wrapping() {
+            width.max(1.0)
+        } else {
+            f32::INFINITY
+        };
+        if wrap_width != self.wrap_width {
+            self.wrap_width = wrap_width;
+            self.invalidate_layout();
+        }
+    }
+
+    fn ensure_block_comment_starts(&mut self) {
+        if self
+            .block_comment_starts
+            .as_ref()
+            .is_some_and(|(generation, _)| *generation == self.generation)
+        {
+            return;
+        }
+
+        let starts = block_comment_starts(&self.syntax, self.doc.chars());
+        self.block_comment_starts = Some((self.generation, starts));
+    }
+
+    fn starts_in_block_comment(&self, line: usize) -> bool {
+        self.block_comment_starts
+            .as_ref()
+            .and_then(|(_, starts)| starts.get(line))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    fn ensure_line_rows(&mut self, ui: &mut egui::Ui, font_id: &egui::FontId) {
+        if !self.wrapping() {
+            self.line_rows = None;
+            return;
+        }
+        if self.line_rows.is_some() {
+            return;
+        }
+
+        let mut line_rows = Vec::with_capacity(self.doc.len_lines() + 1);
+        let mut rows = 0;
+        for line in 0..self.doc.len_lines() {
+            line_rows.push(rows);
+            rows += self.layout_line(ui, font_id, line).rows.len().max(1);
+        }
+        line_rows.push(rows);
+        self.line_rows = Some(line_rows);
+    }
+
+    /// First display row of `line`, `line` itself while not wrapping. One past the last
+    /// line gives the total number of rows.
+    fn line_row(&self, line: usize) -> usize {
+        match &self.line_rows {
+            Some(line_rows) => line_rows[line.min(line_rows.len() - 1)],
+            None => line.min(self.doc.len_lines()),
         }
     }
 
+    /// Line shown on display `row`, clamped to the last line.
+    fn row_line(&self, row: usize) -> usize {
+        let line = match &self.line_rows {
+            Some(line_rows) => line_rows.partition_point(|&r| r <= row).saturating_sub(1),
+            None => row,
+        };
+        line.min(self.doc.len_lines() - 1)
+    }
+
     fn allocate_editor_rect(
         &mut self,
         ui: &mut egui::Ui,
@@ -300,10 +1162,14 @@ impl CodeEditor {
         line_height: f32,
         visible_offset_y: f32,
     ) -> (egui::Rect, egui::Response, egui::Rect) {
-        let width = self.calculate_editor_width(ui, font_id);
+        let width = if self.wrapping() {
+            ui.available_width()
+        } else {
+            self.calculate_editor_width(ui, font_id)
+        };
         let height = ui
             .available_height()
-            .max(line_height * self.doc.len_lines() as f32);
+            .max(line_height * self.line_row(self.doc.len_lines()) as f32);
         let desired_size = egui::vec2(width, height);
 
         let (rect, mut response) =
@@ -318,38 +1184,145 @@ impl CodeEditor {
 
     fn calculate_editor_width(&mut self, ui: &mut egui::Ui, font_id: &egui::FontId) -> f32 {
         if self.max_line_width.is_none() {
-            self.max_line_width = Some(
-                self.doc
+            let max_width = if self.is_large_file() {
+                // Counting columns is much cheaper than laying out every line
+                let column_width = self.measure_text_width(ui, font_id, "0");
+                let max_columns = self
+                    .doc
                     .lines()
-                    .map(|l| self.measure_text_width(ui, font_id, &l.to_string()))
-                    .fold(0.0, f32::max),
-            );
+                    .map(|line| {
+                        let chars = line.chars().filter(|c| !matches!(c, '\n' | '\r'));
+                        visual_column(chars, self.tab_width)
+                    })
+                    .max()
+                    .unwrap_or(0);
+                max_columns as f32 * column_width
+            } else {
+                // Taken out so the lines can be measured through `self` while it is updated
+                let mut line_widths = std::mem::take(&mut self.line_widths);
+                let max_width =
+                    line_widths.max_width(font_id.size, self.tab_width, self.doc.lines(), |line| {
+                        self.measure_text_width(ui, font_id, line.trim_end_matches(['\n', '\r']))
+                    });
+                self.line_widths = line_widths;
+                max_width
+            };
+            self.max_line_width = Some(max_width);
         }
         ui.available_width()
             .max(self.max_line_width.unwrap() + 200.0)
     }
 
     fn calculate_gutter_width(&self, ui: &mut egui::Ui, font_id: &egui::FontId) -> f32 {
-        let total_lines = self.doc.len_lines().max(1);
-        let digits = total_lines.ilog10() + 1;
+        let Some(widest) = widest_line_number(self.line_numbers, self.doc.len_lines()) else {
+            return 0.0;
+        };
+        let digits = widest.max(1).ilog10() + 1;
         let digit_width = self.measure_text_width(ui, font_id, "0");
         digit_width * digits as f32 + GUTTER_PADDING * 2.0
     }
 
+    /// Width of `text` laid out like the document, with tabs up to the next tab stop.
     fn measure_text_width(&self, ui: &mut egui::Ui, font_id: &egui::FontId, text: &str) -> f32 {
-        ui.fonts_mut(|f| {
-            f.layout_no_wrap(text.to_string(), font_id.clone(), Color32::WHITE)
-                .size()
-                .x
-        })
+        let mut job = egui::text::LayoutJob::simple_singleline(
+            text.to_string(),
+            font_id.clone(),
+            Color32::WHITE,
+        );
+        self.align_tab_stops(ui, &mut job);
+        ui.fonts_mut(|f| f.layout_job(job)).size().x
     }
 
-    fn line_height(&self, ui: &mut egui::Ui, font_id: &egui::FontId) -> f32 {
-        let mut test_job = egui::text::LayoutJob::default();
-        test_job.append("Xg", 0.0, self.format_token(TokenType::Literal));
-        let test_galley = ui.fonts_mut(|f| f.layout_job(test_job));
-
-        if !test_galley.rows.is_empty() {
+    /// Lay out `line` without its newline, glyph positions match the rendered text.
+    fn layout_line(
+        &self,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        line: usize,
+    ) -> std::sync::Arc<egui::Galley> {
+        let line_slice = self.doc.line(line);
+        let text = line_slice
+            .slice(..line_len_without_newline(line_slice))
+            .to_string();
+        let mut job =
+            egui::text::LayoutJob::simple_singleline(text, font_id.clone(), Color32::WHITE);
+        self.align_tab_stops(ui, &mut job);
+        job.wrap.max_width = self.wrap_width;
+        ui.fonts_mut(|f| f.layout_job(job))
+    }
+
+    fn align_tab_stops(&self, ui: &egui::Ui, job: &mut egui::text::LayoutJob) {
+        let font_id = egui::FontId::monospace(self.fontsize);
+        let space_width = ui.fonts_mut(|f| f.glyph_width(&font_id, ' '));
+        align_tab_stops(job, self.tab_width, space_width);
+    }
+
+    /// Horizontal offset and display row of char column `col` in `line`. Taken from the glyph
+    /// positions rather than summed char widths, so wide and multi-byte characters don't make
+    /// the cursor drift away from the text. A column where a line wraps is placed at the start
+    /// of the next row.
+    fn char_pos(
+        &self,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        line: usize,
+        col: usize,
+    ) -> (f32, usize) {
+        let galley = self.layout_line(ui, font_id, line);
+        let first_row = self.line_row(line);
+
+        let mut col = col;
+        for (i, row) in galley.rows.iter().enumerate() {
+            let row_chars = row.char_count_excluding_newline();
+            if col < row_chars || i + 1 == galley.rows.len() {
+                return (row.x_offset(col), first_row + i);
+            }
+            col -= row_chars;
+        }
+        (0.0, first_row)
+    }
+
+    /// The parts of columns `cols` of `line` on each display row as `(row, x_start, x_end)`.
+    /// An empty range gives a single zero width segment.
+    fn line_segments(
+        &self,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        line: usize,
+        cols: Range<usize>,
+    ) -> Vec<(usize, f32, f32)> {
+        if cols.is_empty() {
+            let (x, row) = self.char_pos(ui, font_id, line, cols.start);
+            return vec![(row, x, x)];
+        }
+
+        let galley = self.layout_line(ui, font_id, line);
+        let first_row = self.line_row(line);
+
+        let mut segments = Vec::new();
+        let mut row_start = 0;
+        for (i, row) in galley.rows.iter().enumerate() {
+            let row_end = row_start + row.char_count_excluding_newline();
+            let start = cols.start.max(row_start);
+            let end = cols.end.min(row_end);
+            if start < end {
+                segments.push((
+                    first_row + i,
+                    row.x_offset(start - row_start),
+                    row.x_offset(end - row_start),
+                ));
+            }
+            row_start = row_end;
+        }
+        segments
+    }
+
+    fn line_height(&self, ui: &mut egui::Ui, font_id: &egui::FontId) -> f32 {
+        let mut test_job = egui::text::LayoutJob::default();
+        test_job.append("Xg", 0.0, self.format_token(TokenType::Literal));
+        let test_galley = ui.fonts_mut(|f| f.layout_job(test_job));
+
+        if !test_galley.rows.is_empty() {
             test_galley.rows[0].height()
         } else {
             ui.fonts_mut(|f| f.row_height(font_id))
@@ -371,21 +1344,24 @@ impl CodeEditor {
         ui: &mut egui::Ui,
         font_id: &egui::FontId,
         visible_rect: egui::Rect,
-        galley: &egui::Galley,
-        start_line: usize,
+        visible_lines: Range<usize>,
+        line_height: f32,
         gutter_width: f32,
     ) {
         let (cursor_line, _) = char_to_line_col(&self.doc, self.cursor);
 
-        for (row_idx, row) in galley.rows.iter().enumerate() {
-            let line_num = row_idx + start_line;
-            let is_current = line_num == cursor_line;
+        // Placed by line rather than by galley row, the row of an empty document has no height
+        let first_row = self.line_row(visible_lines.start);
+        for line_num in visible_lines.clone() {
+            let row = self.line_row(line_num);
+            let top_y = visible_rect.min.y + (row - first_row) as f32 * line_height;
 
-            // Current line highlight
-            if is_current {
+            // Current line highlight, over all rows of a wrapped line
+            if line_num == cursor_line {
+                let rows = self.line_row(line_num + 1) - row;
                 let highlight_rect = egui::Rect::from_min_max(
-                    egui::pos2(visible_rect.min.x, visible_rect.min.y + row.min_y()),
-                    egui::pos2(visible_rect.max.x, visible_rect.min.y + row.max_y()),
+                    egui::pos2(visible_rect.min.x, top_y),
+                    egui::pos2(visible_rect.max.x, top_y + rows.max(1) as f32 * line_height),
                 );
                 painter.rect_filled(highlight_rect, 0.0, egui::Color32::from_rgb(35, 35, 35));
             }
@@ -396,9 +1372,9 @@ impl CodeEditor {
                 ui,
                 font_id,
                 visible_rect,
-                row,
+                top_y,
                 line_num,
-                is_current,
+                cursor_line,
                 gutter_width,
             );
         }
@@ -411,13 +1387,17 @@ impl CodeEditor {
         ui: &mut egui::Ui,
         font_id: &egui::FontId,
         visible_rect: egui::Rect,
-        row: &PlacedRow,
+        top_y: f32,
         line_num: usize,
-        is_current: bool,
+        cursor_line: usize,
         gutter_width: f32,
     ) {
-        let line_number = (line_num + 1).to_string();
-        let color = if is_current {
+        let Some(line_number) = displayed_line_number(self.line_numbers, line_num, cursor_line)
+        else {
+            return;
+        };
+        let line_number = line_number.to_string();
+        let color = if line_num == cursor_line {
             Color32::WHITE
         } else {
             Color32::from_gray(140)
@@ -426,7 +1406,7 @@ impl CodeEditor {
         let x = visible_rect.min.x + gutter_width - GUTTER_PADDING - text_width;
 
         painter.text(
-            egui::pos2(x, visible_rect.min.y + row.min_y()),
+            egui::pos2(x, top_y),
             egui::Align2::LEFT_TOP,
             line_number,
             font_id.clone(),
@@ -443,12 +1423,169 @@ impl CodeEditor {
         text_x: f32,
         line_height: f32,
     ) {
+        for range in self.block_selection.iter().flatten() {
+            if range.is_empty() {
+                // Rows of the block that are too short still get a caret
+                let (line, col) = char_to_line_col(&self.doc, range.start);
+                let (x, row) = self.char_pos(ui, font_id, line, col);
+                let x = text_x + x;
+                let y = rect.min.y + row as f32 * line_height;
+                painter.line_segment(
+                    [egui::pos2(x, y), egui::pos2(x, y + line_height)],
+                    egui::Stroke::new(1.0, self.theme.cursor().gamma_multiply(0.6)),
+                );
+            } else {
+                self.render_range(
+                    painter,
+                    ui,
+                    font_id,
+                    rect,
+                    text_x,
+                    line_height,
+                    range.clone(),
+                    self.theme.selection(),
+                );
+            }
+        }
+
+        // Ghost caret where a dragged selection would be dropped
+        if let Some(target) = self.move_target {
+            let (line, col) = char_to_line_col(&self.doc, target.min(self.doc.len_chars()));
+            let (x, row) = self.char_pos(ui, font_id, line, col);
+            let x = text_x + x;
+            let y = rect.min.y + row as f32 * line_height;
+            painter.line_segment(
+                [egui::pos2(x, y), egui::pos2(x, y + line_height)],
+                egui::Stroke::new(2.0, self.theme.cursor().gamma_multiply(0.5)),
+            );
+        }
+
         let Some(selection) = &self.selection else {
             return;
         };
 
-        let (start_line, start_col) = char_to_line_col(&self.doc, selection.start);
-        let (end_line, end_col) = char_to_line_col(&self.doc, selection.end);
+        self.render_range(
+            painter,
+            ui,
+            font_id,
+            rect,
+            text_x,
+            line_height,
+            selection.clone(),
+            self.theme.selection(),
+        );
+    }
+
+    /// Highlight the visible search matches, the current one stronger than the rest.
+    #[allow(clippy::too_many_arguments)]
+    fn render_search_matches(
+        &self,
+        painter: &egui::Painter,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+        visible_lines: Range<usize>,
+    ) {
+        let len_lines = self.doc.len_lines();
+        let visible_start = self
+            .doc
+            .line_to_char(visible_lines.start.min(len_lines - 1));
+        let visible_end = if visible_lines.end >= len_lines {
+            self.doc.len_chars()
+        } else {
+            self.doc.line_to_char(visible_lines.end)
+        };
+
+        let current = self.search.as_ref().and_then(|search| search.current);
+        let first = self
+            .search_matches
+            .partition_point(|range| range.end < visible_start);
+        for (i, range) in self.search_matches.iter().enumerate().skip(first) {
+            if range.start > visible_end {
+                break;
+            }
+
+            let color = if current == Some(i) {
+                Color32::from_rgba_unmultiplied(230, 180, 60, 140)
+            } else {
+                Color32::from_rgba_unmultiplied(230, 180, 60, 60)
+            };
+            self.render_range(
+                painter,
+                ui,
+                font_id,
+                rect,
+                text_x,
+                line_height,
+                range.clone(),
+                color,
+            );
+        }
+    }
+
+    fn render_reveal_flash(
+        &mut self,
+        painter: &egui::Painter,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+    ) {
+        let Some(flash) = &mut self.reveal_flash else {
+            return;
+        };
+
+        let time = ui.input(|i| i.time);
+        let elapsed = time - *flash.start_time.get_or_insert(time);
+        if elapsed >= REVEAL_FLASH_DURATION {
+            self.reveal_flash = None;
+            return;
+        }
+
+        // An edit since revealing can leave the range past the end of the document
+        let len = self.doc.len_chars();
+        let mut range = flash.range.start.min(len)..flash.range.end.min(len);
+        if range.is_empty() {
+            // Flash the whole line for a bare position
+            let line = self.doc.char_to_line(range.start);
+            let line_start = self.doc.line_to_char(line);
+            range = line_start..line_start + line_len_without_newline(self.doc.line(line));
+        }
+
+        let fade = 1.0 - (elapsed / REVEAL_FLASH_DURATION) as f32;
+        let color = self.theme.cursor().gamma_multiply(0.4 * fade);
+        self.render_range(
+            painter,
+            ui,
+            font_id,
+            rect,
+            text_x,
+            line_height,
+            range,
+            color,
+        );
+
+        ui.ctx().request_repaint();
+    }
+
+    /// Fill the background behind the chars in `range`, line by line.
+    #[allow(clippy::too_many_arguments)]
+    fn render_range(
+        &self,
+        painter: &egui::Painter,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+        range: Range<usize>,
+        color: Color32,
+    ) {
+        let (start_line, start_col) = char_to_line_col(&self.doc, range.start);
+        let (end_line, end_col) = char_to_line_col(&self.doc, range.end);
 
         for line in start_line..=end_line {
             let line_start_char = self.doc.line_to_char(line);
@@ -465,25 +1602,16 @@ impl CodeEditor {
                 continue;
             }
 
-            let x_start = text_x
-                + self.measure_text_width(
-                    ui,
-                    font_id,
-                    &self.doc.line(line).slice(..sel_start_col).to_string(),
-                );
-            let x_end = text_x
-                + self.measure_text_width(
-                    ui,
-                    font_id,
-                    &self.doc.line(line).slice(..sel_end_col).to_string(),
+            for (row, x_start, x_end) in
+                self.line_segments(ui, font_id, line, sel_start_col..sel_end_col)
+            {
+                let y = rect.min.y + row as f32 * line_height;
+                let selection_rect = egui::Rect::from_min_max(
+                    egui::pos2(text_x + x_start, y),
+                    egui::pos2(text_x + x_end, y + line_height),
                 );
-            let y = rect.min.y + line as f32 * line_height;
-
-            let selection_rect = egui::Rect::from_min_max(
-                egui::pos2(x_start, y),
-                egui::pos2(x_end, y + line_height),
-            );
-            painter.rect_filled(selection_rect, 0.0, self.theme.selection());
+                painter.rect_filled(selection_rect, 0.0, color);
+            }
         }
     }
 
@@ -494,15 +1622,47 @@ impl CodeEditor {
         visible_rect: egui::Rect,
         galley: &std::sync::Arc<egui::Galley>,
     ) {
-        painter.galley(
-            egui::pos2(text_x, visible_rect.min.y),
-            galley.clone(),
-            Color32::WHITE,
-        );
+        let origin = egui::pos2(text_x, visible_rect.min.y);
+        painter.galley(origin, galley.clone(), Color32::WHITE);
+
+        if self.show_whitespace {
+            let color = self
+                .theme
+                .type_color(TokenType::Comment(false))
+                .gamma_multiply(0.5);
+            let font_id = egui::FontId::monospace(self.fontsize);
+
+            // The galley includes buffer lines above and below the view, skip those
+            let clip_rect = painter.clip_rect();
+            let rows = galley
+                .rows
+                .iter()
+                .filter(|row| clip_rect.intersects(row.rect().translate(origin.to_vec2())));
+
+            for row in rows {
+                for glyph in &row.glyphs {
+                    let symbol = match glyph.chr {
+                        ' ' => "·",
+                        '\t' => "→",
+                        _ => continue,
+                    };
+
+                    let rect = glyph.logical_rect().translate(row.pos.to_vec2());
+                    painter.text(
+                        origin + rect.center().to_vec2(),
+                        egui::Align2::CENTER_CENTER,
+                        symbol,
+                        font_id.clone(),
+                        color,
+                    );
+                }
+            }
+        }
     }
 
+    /// Underline the visible parts of every diagnostic in its severity color.
     #[allow(clippy::too_many_arguments)]
-    fn render_cursor(
+    fn render_diagnostics(
         &self,
         painter: &egui::Painter,
         ui: &mut egui::Ui,
@@ -510,131 +1670,662 @@ impl CodeEditor {
         rect: egui::Rect,
         text_x: f32,
         line_height: f32,
-        time: f64,
+        visible_lines: Range<usize>,
     ) {
-        let cursor_visible =
-            ((time - self.cursor_blink_offset) % BLINK_SPEED) < (BLINK_SPEED * 0.5);
-        if !cursor_visible {
-            return;
+        for diagnostic in &self.diagnostics {
+            let stroke = egui::Stroke::new(1.0, diagnostic.severity.color());
+
+            for (row, x_start, x_end) in
+                self.diagnostic_segments(ui, font_id, diagnostic, visible_lines.clone())
+            {
+                let y = rect.min.y + (row + 1) as f32 * line_height - 2.0;
+                let points = squiggle_points(text_x + x_start, text_x + x_end, y);
+                painter.add(egui::Shape::line(points, stroke));
+            }
         }
+    }
 
-        let (cursor_line, cursor_col) = char_to_line_col(&self.doc, self.cursor);
-        let cursor_x = text_x
-            + self.measure_text_width(
-                ui,
-                font_id,
-                &self.doc.line(cursor_line).slice(..cursor_col).to_string(),
+    /// Rows and x ranges relative to the text start covered by `diagnostic` within `lines`.
+    fn diagnostic_segments(
+        &self,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        diagnostic: &Diagnostic,
+        lines: Range<usize>,
+    ) -> Vec<(usize, f32, f32)> {
+        let len_chars = self.doc.len_chars();
+        let start = diagnostic.range.start.min(len_chars);
+        let end = diagnostic.range.end.clamp(start, len_chars);
+        let (start_line, start_col) = char_to_line_col(&self.doc, start);
+        let (end_line, end_col) = char_to_line_col(&self.doc, end);
+
+        let mut segments = Vec::new();
+        for line in start_line.max(lines.start)..(end_line + 1).min(lines.end) {
+            let line_len = line_len_without_newline(self.doc.line(line));
+
+            let col_start = if line == start_line { start_col } else { 0 };
+            let col_end = if line == end_line { end_col } else { line_len };
+            let col_end = col_end.min(line_len).max(col_start);
+
+            segments.extend(
+                self.line_segments(ui, font_id, line, col_start..col_end)
+                    .into_iter()
+                    // Keep diagnostics on empty ranges visible
+                    .map(|(row, x_start, x_end)| (row, x_start, x_end.max(x_start + 6.0))),
             );
-        let cursor_y = rect.min.y + cursor_line as f32 * line_height;
-
-        painter.line_segment(
-            [
-                egui::pos2(cursor_x, cursor_y),
-                egui::pos2(cursor_x, cursor_y + line_height),
-            ],
-            egui::Stroke::new(1.0, self.theme.cursor()),
-        );
+        }
+        segments
     }
 
-    // ========================================================================
-    // Input Handling
-    // ========================================================================
-
-    fn setup_event_filter(&self, ui: &mut egui::Ui, id: egui::Id) {
-        let event_filter = egui::EventFilter {
-            tab: true,
-            vertical_arrows: true,
-            horizontal_arrows: true,
-            escape: false,
+    /// Show the messages of the diagnostics under the pointer.
+    #[allow(clippy::too_many_arguments)]
+    fn diagnostic_tooltip(
+        &self,
+        ui: &mut egui::Ui,
+        response: egui::Response,
+        font_id: &egui::FontId,
+        rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+        visible_lines: Range<usize>,
+    ) -> egui::Response {
+        let Some(pos) = response.hover_pos() else {
+            return response;
         };
-        ui.memory_mut(|mem| mem.set_focus_lock_filter(id, event_filter));
-    }
 
-    fn handle_touch_scroll(&mut self, ui: &mut egui::Ui, time: f64, delta_time: f32) {
-        let double_touch = ui.input(|i| i.multi_touch().is_some_and(|mt| mt.num_touches == 2));
+        let hovered: Vec<&Diagnostic> = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                self.diagnostic_segments(ui, font_id, diagnostic, visible_lines.clone())
+                    .into_iter()
+                    .any(|(row, x_start, x_end)| {
+                        let y = rect.min.y + row as f32 * line_height;
+                        (y..y + line_height).contains(&pos.y)
+                            && (text_x + x_start..text_x + x_end).contains(&pos.x)
+                    })
+            })
+            .collect();
+        if hovered.is_empty() {
+            return response;
+        }
+
+        response.on_hover_ui_at_pointer(|ui| {
+            for diagnostic in hovered {
+                ui.colored_label(diagnostic.severity.color(), &diagnostic.message);
+            }
+        })
+    }
 
-        if !double_touch {
+    /// Tick marks along the scrollbar for every search match in the whole document,
+    /// clicking one reveals that match.
+    fn search_ruler_ui(&mut self, ui: &mut egui::Ui) {
+        if self.search_matches.is_empty() {
             return;
         }
 
-        if let Some(multi_touch) = ui.input(|i| i.multi_touch()) {
-            let raw = multi_touch.translation_delta * TOUCH_SCROLL_SENSITIVITY;
+        // The clip rect is the part of the scroll area on screen
+        let clip_rect = ui.clip_rect();
+        let ruler_rect = egui::Rect::from_min_max(
+            egui::pos2(clip_rect.max.x - SEARCH_RULER_WIDTH, clip_rect.min.y),
+            clip_rect.max,
+        );
 
-            // Axis lock logic
-            if self.touch_scroll_axis_lock.is_none() && raw.length() > AXIS_LOCK_THRESHOLD {
-                self.touch_scroll_axis_lock = if raw.y.abs() > raw.x.abs() * 1.3 {
-                    Some(TouchScrollAxis::Vertical)
-                } else {
-                    Some(TouchScrollAxis::Horizontal)
-                };
+        let len_chars = self.doc.len_chars();
+        let total_lines = self.doc.len_lines().max(1) as f32;
+        let mut ticks: Vec<(f32, usize)> = Vec::new();
+        for (i, range) in self.search_matches.iter().enumerate() {
+            let line = self.doc.char_to_line(range.start.min(len_chars));
+            let y = egui::lerp(ruler_rect.y_range(), (line as f32 + 0.5) / total_lines);
+            if ticks
+                .last()
+                .is_some_and(|(last_y, _)| y - last_y < SEARCH_RULER_TICK_HEIGHT)
+            {
+                continue;
             }
+            ticks.push((y, i));
+        }
 
-            let filtered = match &self.touch_scroll_axis_lock {
-                Some(TouchScrollAxis::Vertical) => egui::vec2(0.0, raw.y),
-                Some(TouchScrollAxis::Horizontal) => egui::vec2(raw.x, 0.0),
-                None => raw,
-            };
+        let painter = ui.painter();
+        for (y, _) in &ticks {
+            painter.rect_filled(
+                egui::Rect::from_center_size(
+                    egui::pos2(ruler_rect.center().x, *y),
+                    egui::vec2(SEARCH_RULER_WIDTH, SEARCH_RULER_TICK_HEIGHT),
+                ),
+                0.0,
+                Color32::from_rgb(230, 180, 60),
+            );
+        }
+
+        let response = ui.interact(
+            ruler_rect,
+            ui.id().with("search_ruler"),
+            egui::Sense::click(),
+        );
+        let nearest_tick = response.hover_pos().and_then(|pos| {
+            ticks
+                .iter()
+                .map(|(y, i)| ((y - pos.y).abs(), *i))
+                .filter(|(distance, _)| *distance <= SEARCH_RULER_TICK_HEIGHT * 2.0)
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, i)| i)
+        });
+        let Some(i) = nearest_tick else {
+            return;
+        };
+
+        let response = response.on_hover_cursor(egui::CursorIcon::PointingHand);
+        if response.clicked() {
+            self.reveal_range(self.search_matches[i].clone());
+            self.focus_requested = true;
+        }
+    }
 
-            // Apply scroll IMMEDIATELY during touch - no smoothing
-            ui.scroll_with_delta(filtered);
+    /// Line of the `fn`/`struct` header enclosing `top_line`, if that header itself
+    /// has been scrolled out of view.
+    fn enclosing_header_line(&self, top_line: usize) -> Option<usize> {
+        let mut depth = 0usize;
+        let mut header = None;
+        let mut entered_body = false;
+
+        for line in 0..top_line.min(self.doc.len_lines()) {
+            let line_text = self.doc.line(line);
+
+            if depth == 0 {
+                // Skip attributes like `@fragment` in front of the declaration
+                let line_string = line_text.to_string();
+                let first_word = line_string
+                    .split_whitespace()
+                    .find(|word| !word.starts_with('@'));
+                if matches!(first_word, Some("fn" | "struct")) {
+                    header = Some(line);
+                    entered_body = false;
+                }
+            }
 
-            // Track velocity for momentum after release (light smoothing to reduce jitter)
-            let t = 1.0 - (-TOUCH_SCROLL_SMOOTHING * delta_time).exp();
-            self.touch_scroll_velocity = lerp_vec2(self.touch_scroll_velocity, filtered, t);
+            for c in line_text.chars() {
+                match c {
+                    '{' => {
+                        depth += 1;
+                        entered_body = true;
+                    }
+                    '}' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
 
-            self.touch_scroll_timestamp = time;
+            if depth == 0 && entered_body {
+                header = None;
+                entered_body = false;
+            }
         }
+
+        header
     }
 
     #[allow(clippy::too_many_arguments)]
-    fn handle_mouse_input(
+    fn render_sticky_header(
         &mut self,
+        painter: &egui::Painter,
         ui: &mut egui::Ui,
-        response: &egui::Response,
         font_id: &egui::FontId,
+        rect: egui::Rect,
         visible_rect: egui::Rect,
-        text_x: f32,
+        scroll_offset: f32,
         line_height: f32,
-        start_line: usize,
-        time: f64,
+        gutter_width: f32,
     ) {
-        let double_touch = ui.input(|i| i.multi_touch().is_some_and(|mt| mt.num_touches == 2));
-        if double_touch {
+        let top_line = self.row_line((scroll_offset.max(0.0) / line_height) as usize);
+        // Scanning for the header goes over every line above the view, only redo it after a change
+        let header_line = match self.sticky_header {
+            Some((generation, line, header_line))
+                if generation == self.generation && line == top_line =>
+            {
+                header_line
+            }
+            _ => {
+                let header_line = self.enclosing_header_line(top_line);
+                self.sticky_header = Some((self.generation, top_line, header_line));
+                header_line
+            }
+        };
+        let Some(header_line) = header_line else {
             return;
+        };
+
+        let top_y = rect.min.y + scroll_offset.max(0.0);
+        let header_rect = egui::Rect::from_min_size(
+            egui::pos2(visible_rect.min.x, top_y),
+            egui::vec2(visible_rect.width(), line_height),
+        );
+        painter.rect_filled(header_rect, 0.0, self.theme.bg());
+        painter.hline(
+            header_rect.x_range(),
+            header_rect.max.y,
+            egui::Stroke::new(1.0, Color32::from_gray(60)),
+        );
+
+        let (cursor_line, _) = char_to_line_col(&self.doc, self.cursor);
+        if let Some(line_number) =
+            displayed_line_number(self.line_numbers, header_line, cursor_line)
+        {
+            let line_number = line_number.to_string();
+            let number_width = self.measure_text_width(ui, font_id, &line_number);
+            painter.text(
+                egui::pos2(
+                    visible_rect.min.x + gutter_width - GUTTER_PADDING - number_width,
+                    top_y,
+                ),
+                egui::Align2::LEFT_TOP,
+                line_number,
+                font_id.clone(),
+                Color32::from_gray(140),
+            );
         }
 
-        // Click to position cursor
-        if ui.input(|i| i.pointer.any_pressed()) {
-            if let Some(pos) = ui.input(|i| i.pointer.interact_pos()) {
-                if response.rect.contains(pos) {
-                    let (char_idx, col) = self.pos_to_char_index(
-                        ui,
-                        font_id,
-                        pos,
-                        visible_rect,
-                        text_x,
-                        line_height,
-                        start_line,
-                    );
+        let header_line_slice = self.doc.line(header_line);
+        let header_text = header_line_slice
+            .slice(..line_len_without_newline(header_line_slice))
+            .to_string();
+        let mut header_job = highlight(
+            ui.ctx(),
+            self,
+            &header_text,
+            self.starts_in_block_comment(header_line),
+        );
+        self.align_tab_stops(ui, &mut header_job);
+        let header_galley = ui.fonts_mut(|f| f.layout_job(header_job));
+        painter.galley(
+            egui::pos2(visible_rect.min.x + gutter_width + TEXT_PADDING, top_y),
+            header_galley,
+            Color32::WHITE,
+        );
+    }
 
-                    self.cursor = char_idx;
-                    self.desired_column = Some(col);
-                    self.selection = None;
-                    self.selection_anchor = None;
-                    self.cursor_blink_offset = time;
+    /// Wake the UI up exactly when the cursor blink toggles, so an idle editor doesn't redraw.
+    fn request_blink_repaint(&self, ui: &egui::Ui, time: f64) {
+        let phase = (time - self.cursor_blink_offset).rem_euclid(BLINK_SPEED);
+        let half = BLINK_SPEED * 0.5;
+        let until_toggle = if phase < half {
+            half - phase
+        } else {
+            BLINK_SPEED - phase
+        };
 
-                    ui.memory_mut(|m| m.request_focus(response.id));
-                }
-            }
-        }
+        ui.ctx()
+            .request_repaint_after(std::time::Duration::from_secs_f64(until_toggle));
+    }
 
-        // Drag to select
-        if response.dragged() && (self.touch_scroll_timestamp + 0.5 < time) {
-            if let Some(pos) = ui.input(|i| i.pointer.interact_pos()) {
-                let (char_idx, col) = self.pos_to_char_index(
-                    ui,
-                    font_id,
-                    pos,
+    /// A hint in the top right corner of the view, below the large file indicator if shown.
+    /// Returns its rect for the hover tooltip.
+    fn render_readonly_indicator(&self, painter: &egui::Painter, large_file: bool) -> egui::Rect {
+        let offset_y = if large_file { 16.0 } else { 0.0 };
+        painter.text(
+            painter.clip_rect().right_top()
+                + egui::vec2(-GUTTER_PADDING, GUTTER_PADDING + offset_y),
+            egui::Align2::RIGHT_TOP,
+            format!("{} Read-only", egui_phosphor::regular::LOCK),
+            egui::FontId::proportional(11.0),
+            Color32::from_gray(110),
+        )
+    }
+
+    fn render_large_file_indicator(&self, painter: &egui::Painter, visible_rect: egui::Rect) {
+        painter.text(
+            visible_rect.right_top() + egui::vec2(-GUTTER_PADDING, GUTTER_PADDING),
+            egui::Align2::RIGHT_TOP,
+            "Large file mode",
+            egui::FontId::proportional(11.0),
+            Color32::from_gray(110),
+        );
+    }
+
+    /// Outline the bracket next to the cursor and its partner.
+    fn render_bracket_match(
+        &self,
+        painter: &egui::Painter,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+    ) {
+        let Some((bracket, partner)) = matching_bracket(&self.doc, self.cursor) else {
+            return;
+        };
+
+        let stroke = egui::Stroke::new(1.0, self.theme.cursor().gamma_multiply(0.6));
+        for pos in [bracket, partner] {
+            let (line, col) = char_to_line_col(&self.doc, pos);
+            for (row, x_start, x_end) in self.line_segments(ui, font_id, line, col..col + 1) {
+                let y = rect.min.y + row as f32 * line_height;
+                painter.rect_stroke(
+                    egui::Rect::from_min_max(
+                        egui::pos2(text_x + x_start, y),
+                        egui::pos2(text_x + x_end, y + line_height),
+                    ),
+                    1.0,
+                    stroke,
+                    egui::StrokeKind::Inside,
+                );
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_cursor(
+        &self,
+        painter: &egui::Painter,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+        time: f64,
+    ) {
+        let cursor_visible =
+            ((time - self.cursor_blink_offset) % BLINK_SPEED) < (BLINK_SPEED * 0.5);
+        if !cursor_visible {
+            return;
+        }
+
+        let (cursor_line, cursor_col) = char_to_line_col(&self.doc, self.cursor);
+        let (cursor_x, cursor_row) = self.char_pos(ui, font_id, cursor_line, cursor_col);
+        let cursor_x = text_x + cursor_x;
+        let cursor_y = rect.min.y + cursor_row as f32 * line_height;
+
+        // A block over the char that gets overwritten, a space wide at the end of the line
+        if self.overwrite {
+            let line_len = line_len_without_newline(self.doc.line(cursor_line));
+            let next = (cursor_col < line_len)
+                .then(|| self.char_pos(ui, font_id, cursor_line, cursor_col + 1))
+                .filter(|(_, row)| *row == cursor_row);
+            let width = match next {
+                Some((next_x, _)) => text_x + next_x - cursor_x,
+                None => ui.fonts_mut(|f| f.glyph_width(font_id, ' ')),
+            };
+
+            let block = egui::Rect::from_min_size(
+                egui::pos2(cursor_x, cursor_y),
+                egui::vec2(width, line_height),
+            );
+            painter.rect_filled(block, 0.0, self.theme.cursor().gamma_multiply(0.5));
+            return;
+        }
+
+        painter.line_segment(
+            [
+                egui::pos2(cursor_x, cursor_y),
+                egui::pos2(cursor_x, cursor_y + line_height),
+            ],
+            egui::Stroke::new(1.0, self.theme.cursor()),
+        );
+    }
+
+    fn render_completion(
+        &self,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+    ) {
+        let Some(completion) = &self.completion else {
+            return;
+        };
+
+        let (line, col) = char_to_line_col(&self.doc, completion.prefix_start);
+        let (x, row) = self.char_pos(ui, font_id, line, col);
+        let x = text_x + x;
+        let y = rect.min.y + (row + 1) as f32 * line_height;
+
+        egui::Area::new(ui.id().with("completion"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(egui::pos2(x, y))
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    for (i, item) in completion.items.iter().enumerate() {
+                        let mut text = egui::RichText::new(item).font(font_id.clone());
+                        if i == completion.selected {
+                            text = text
+                                .background_color(self.theme.selection())
+                                .color(Color32::WHITE);
+                        }
+                        ui.label(text);
+                    }
+                });
+            });
+    }
+
+    // ========================================================================
+    // Input Handling
+    // ========================================================================
+
+    fn setup_event_filter(&self, ui: &mut egui::Ui, id: egui::Id) {
+        let event_filter = egui::EventFilter {
+            tab: true,
+            vertical_arrows: true,
+            horizontal_arrows: true,
+            // Escape dismisses the completion popup instead of surrendering focus
+            escape: self.completion.is_some(),
+        };
+        ui.memory_mut(|mem| mem.set_focus_lock_filter(id, event_filter));
+    }
+
+    /// Whether single finger drags of the current touch scroll instead of select.
+    fn single_finger_scrolling(&self) -> bool {
+        self.touch_scroll.single_finger && self.touch_pointer && !self.touch_selecting
+    }
+
+    fn handle_touch_scroll(
+        &mut self,
+        ui: &mut egui::Ui,
+        response: &egui::Response,
+        time: f64,
+        delta_time: f32,
+    ) {
+        let multi_touch = ui.input(|i| i.multi_touch().filter(|mt| mt.num_touches == 2));
+        let translation = match multi_touch {
+            Some(multi_touch) => multi_touch.translation_delta,
+            None if response.dragged() && self.single_finger_scrolling() => {
+                ui.input(|i| i.pointer.delta())
+            }
+            None => return,
+        };
+
+        let raw = translation * self.touch_scroll.sensitivity;
+
+        // Axis lock logic
+        if self.touch_scroll_axis_lock.is_none()
+            && raw.length() > self.touch_scroll.axis_lock_threshold
+        {
+            self.touch_scroll_axis_lock = if raw.y.abs() > raw.x.abs() * 1.3 {
+                Some(TouchScrollAxis::Vertical)
+            } else {
+                Some(TouchScrollAxis::Horizontal)
+            };
+        }
+
+        let filtered = match &self.touch_scroll_axis_lock {
+            Some(TouchScrollAxis::Vertical) => egui::vec2(0.0, raw.y),
+            Some(TouchScrollAxis::Horizontal) => egui::vec2(raw.x, 0.0),
+            None => raw,
+        };
+
+        // Apply scroll IMMEDIATELY during touch - no smoothing
+        ui.scroll_with_delta(filtered);
+
+        // Track velocity for momentum after release (light smoothing to reduce jitter)
+        if delta_time > 0.0 {
+            let t = 1.0 - (-self.touch_scroll.smoothing * delta_time).exp();
+            self.touch_scroll_velocity =
+                lerp_vec2(self.touch_scroll_velocity, filtered / delta_time, t);
+        }
+
+        self.touch_scroll_timestamp = time;
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn handle_mouse_input(
+        &mut self,
+        ui: &mut egui::Ui,
+        response: &egui::Response,
+        font_id: &egui::FontId,
+        visible_rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+        start_line: usize,
+        time: f64,
+    ) {
+        let double_touch = ui.input(|i| i.multi_touch().is_some_and(|mt| mt.num_touches == 2));
+        if double_touch {
+            return;
+        }
+
+        let pressed = ui.input(|i| i.pointer.any_pressed());
+        if pressed {
+            self.touch_pointer = ui.input(|i| i.any_touches());
+            self.touch_selecting = false;
+        }
+
+        // With single finger scrolling a touch only moves the cursor on a tap or long press,
+        // so starting to scroll leaves it in place
+        let place_cursor = if self.touch_scroll.single_finger && self.touch_pointer {
+            if pressed && response.contains_pointer() {
+                ui.memory_mut(|m| m.request_focus(response.id));
+            }
+            if response.long_touched() {
+                self.touch_selecting = true;
+            }
+            response.clicked() || response.long_touched()
+        } else {
+            pressed
+        };
+
+        // Click to position cursor
+        if place_cursor {
+            if let Some(pos) = ui.input(|i| i.pointer.interact_pos()) {
+                if response.rect.contains(pos) {
+                    let (char_idx, col) = self.pos_to_char_index(
+                        ui,
+                        font_id,
+                        pos,
+                        visible_rect,
+                        text_x,
+                        line_height,
+                        start_line,
+                    );
+
+                    // Pressing inside the selection starts moving it instead of a new one
+                    let alt = ui.input(|i| i.modifiers.alt);
+                    let in_selection = self.selection.as_ref().is_some_and(|sel| {
+                        !sel.is_empty() && sel.start <= char_idx && char_idx <= sel.end
+                    });
+                    if in_selection && !alt && !self.touch_pointer {
+                        self.moving_selection = self.selection.clone().map(|sel| (sel, char_idx));
+                        self.move_target = None;
+                        ui.memory_mut(|m| m.request_focus(response.id));
+                        return;
+                    }
+
+                    self.cursor = char_idx;
+                    self.desired_column = Some(col);
+                    self.selection = None;
+                    self.selection_anchor = None;
+                    self.completion = None;
+                    self.cursor_blink_offset = time;
+
+                    // Alt starts a column selection, rows don't line up with lines while wrapping
+                    self.block_selection = None;
+                    self.block_anchor = (alt && !self.wrapping()).then(|| {
+                        self.block_point(
+                            ui,
+                            font_id,
+                            pos,
+                            visible_rect,
+                            text_x,
+                            line_height,
+                            start_line,
+                        )
+                    });
+
+                    ui.memory_mut(|m| m.request_focus(response.id));
+                }
+            }
+        }
+
+        if let Some((source, pressed_at)) = self.moving_selection.clone() {
+            if ui.input(|i| i.pointer.any_released()) {
+                self.moving_selection = None;
+                match self.move_target.take() {
+                    Some(target) => self.move_text(source, target, time),
+                    // Only clicked, place the cursor like any other click
+                    None => {
+                        self.update_cursor(pressed_at);
+                        self.selection = None;
+                        self.desired_column = None;
+                        self.cursor_blink_offset = time;
+                    }
+                }
+            } else if response.dragged() {
+                if let Some(pos) = ui.input(|i| i.pointer.interact_pos()) {
+                    let (char_idx, _) = self.pos_to_char_index(
+                        ui,
+                        font_id,
+                        pos,
+                        visible_rect,
+                        text_x,
+                        line_height,
+                        start_line,
+                    );
+                    self.move_target = Some(char_idx);
+                    ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+                }
+            }
+            return;
+        }
+
+        // Drag to select
+        if response.dragged()
+            && (self.touch_scroll_timestamp + 0.5 < time)
+            && !self.single_finger_scrolling()
+        {
+            if let Some(pos) = ui.input(|i| i.pointer.interact_pos()) {
+                if let Some(anchor) = self.block_anchor {
+                    let head = self.block_point(
+                        ui,
+                        font_id,
+                        pos,
+                        visible_rect,
+                        text_x,
+                        line_height,
+                        start_line,
+                    );
+                    let ranges = block_ranges(&self.doc, anchor, head);
+                    let head_range = if head.0 < anchor.0 {
+                        &ranges[0]
+                    } else {
+                        &ranges[ranges.len() - 1]
+                    };
+                    self.update_cursor(if head.1 < anchor.1 {
+                        head_range.start
+                    } else {
+                        head_range.end
+                    });
+                    self.selection = None;
+                    self.block_selection = Some(ranges);
+                    self.desired_column = None;
+                    self.cursor_blink_offset = time;
+                    self.touch_scroll_velocity = egui::Vec2::ZERO;
+                    return;
+                }
+
+                let (char_idx, col) = self.pos_to_char_index(
+                    ui,
+                    font_id,
+                    pos,
                     visible_rect,
                     text_x,
                     line_height,
@@ -657,8 +2348,33 @@ impl CodeEditor {
         }
     }
 
+    /// Move the text in `source` to `target` as a single edit and select it there, dropping
+    /// it inside `source` leaves everything as is.
+    fn move_text(&mut self, source: Range<usize>, target: usize, time: f64) {
+        if (source.start..=source.end).contains(&target) {
+            return;
+        }
+
+        let text = self.doc.slice(source.clone()).to_string();
+        let len = source.len();
+        let (parts, inserted_part) = if target < source.start {
+            (vec![(target..target, text), (source, String::new())], 0)
+        } else {
+            (vec![(source, String::new()), (target..target, text)], 1)
+        };
+
+        self.apply_compound_edit(&parts, |ends| {
+            let end = ends[inserted_part];
+            (end, Some(end - len..end))
+        });
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Line and column under `pos` for block selections. Unlike [`Self::pos_to_char_index`]
+    /// the column isn't clamped to the line, so a block can span past short lines.
     #[allow(clippy::too_many_arguments)]
-    fn pos_to_char_index(
+    fn block_point(
         &self,
         ui: &mut egui::Ui,
         font_id: &egui::FontId,
@@ -668,27 +2384,45 @@ impl CodeEditor {
         line_height: f32,
         start_line: usize,
     ) -> (usize, usize) {
-        let y = pos.y - visible_rect.min.y;
-        let line =
-            ((y / line_height) as usize + start_line).min(self.doc.len_lines().saturating_sub(1));
-
-        let line_text = self.doc.line(line);
-        let max_col = line_len_without_newline(line_text);
+        let y = (pos.y - visible_rect.min.y).max(0.0);
+        let row = (y / line_height) as usize + self.line_row(start_line);
+        let line = self.row_line(row).min(self.doc.len_lines() - 1);
 
-        let mut x = 0.0;
-        let mut col = 0;
+        let space_width = ui.fonts_mut(|f| f.glyph_width(font_id, ' '));
+        let col = ((pos.x - text_x) / space_width).round().max(0.0) as usize;
 
-        for (i, c) in line_text.chars().take(max_col).enumerate() {
-            let char_width = self.measure_text_width(ui, font_id, &c.to_string());
-            if text_x + x + char_width / 2.0 >= pos.x {
-                col = i;
-                break;
-            }
-            x += char_width;
-            col = i + 1;
-        }
+        (line, col)
+    }
 
-        let col = col.min(max_col);
+    #[allow(clippy::too_many_arguments)]
+    fn pos_to_char_index(
+        &self,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        pos: egui::Pos2,
+        visible_rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+        start_line: usize,
+    ) -> (usize, usize) {
+        let y = pos.y - visible_rect.min.y;
+        let row = (y / line_height) as usize + self.line_row(start_line);
+        let line = self.row_line(row);
+
+        // Columns on the wrapped rows of the line above the clicked one
+        let galley = self.layout_line(ui, font_id, line);
+        let row_in_line = (row - self.line_row(line)).min(galley.rows.len().saturating_sub(1));
+        let row_start: usize = galley.rows[..row_in_line]
+            .iter()
+            .map(|row| row.char_count_excluding_newline())
+            .sum();
+
+        let max_col = line_len_without_newline(self.doc.line(line));
+        let col = galley
+            .rows
+            .get(row_in_line)
+            .map_or(0, |row| row_start + row.char_at(pos.x - text_x))
+            .min(max_col);
         let char_idx = self.doc.line_to_char(line) + col;
 
         (char_idx, col)
@@ -703,34 +2437,63 @@ impl CodeEditor {
         self.touch_scroll_axis_lock = None;
 
         // Apply momentum
-        if self.touch_scroll_velocity.length() > 0.5 {
-            ui.scroll_with_delta(self.touch_scroll_velocity);
+        if self.touch_scroll_velocity.length() > MIN_MOMENTUM_SPEED {
+            ui.scroll_with_delta(self.touch_scroll_velocity * delta_time);
 
-            let decay = (-TOUCH_SCROLL_DAMPING * delta_time).exp();
+            let decay = (-self.touch_scroll.damping * delta_time).exp();
             self.touch_scroll_velocity *= decay;
+
+            // Keep animating until the momentum has died out
+            ui.ctx().request_repaint();
         } else {
             self.touch_scroll_velocity = egui::Vec2::ZERO;
         }
     }
 
-    fn handle_cursor_scroll(&mut self, ui: &mut egui::Ui, rect: egui::Rect, line_height: f32) {
+    fn handle_cursor_scroll(
+        &mut self,
+        ui: &mut egui::Ui,
+        font_id: &egui::FontId,
+        rect: egui::Rect,
+        text_x: f32,
+        line_height: f32,
+    ) {
         if !self.cursor_request_focus {
             return;
         }
 
+        // The editor rect was allocated for the document as it was before this frame's
+        // edits, an undo or redo can move the cursor past it. Reveal on the next frame
+        // once the rect matches the document again.
+        if self.text_layout_job.is_none() {
+            ui.ctx().request_repaint();
+            return;
+        }
+
         self.cursor_request_focus = false;
 
-        let (cursor_line, _cursor_col) = char_to_line_col(&self.doc, self.cursor);
-        let cursor_x = rect.min.x; // Simplified - actual x calculation would need font_id
-        let cursor_y = rect.min.y + cursor_line as f32 * line_height;
+        let (cursor_line, cursor_col) = char_to_line_col(&self.doc, self.cursor);
+        let (cursor_x, cursor_row) = self.char_pos(ui, font_id, cursor_line, cursor_col);
+        let cursor_x = text_x + cursor_x;
+        let cursor_y = rect.min.y + cursor_row as f32 * line_height;
 
+        // Keep a restored selection in view as a whole when it fits
         let v_margin = line_height * CURSOR_REVEAL_V_MARGIN_LINES;
+        let (mut min_y, mut max_y) = (cursor_y, cursor_y + line_height);
+        if let Some(selection) = &self.selection {
+            let first_line = self.doc.char_to_line(selection.start);
+            let last_line = self.doc.char_to_line(selection.end);
+            let selection_min_y = rect.min.y + self.line_row(first_line) as f32 * line_height;
+            let selection_max_y = rect.min.y + self.line_row(last_line + 1) as f32 * line_height;
+            if selection_max_y - selection_min_y + v_margin * 2.0 <= ui.clip_rect().height() {
+                min_y = min_y.min(selection_min_y);
+                max_y = max_y.max(selection_max_y);
+            }
+        }
+
         let reveal_rect = egui::Rect::from_min_max(
-            egui::pos2(cursor_x - CURSOR_REVEAL_H_MARGIN, cursor_y - v_margin),
-            egui::pos2(
-                cursor_x + CURSOR_REVEAL_H_MARGIN,
-                cursor_y + line_height + v_margin,
-            ),
+            egui::pos2(cursor_x - CURSOR_REVEAL_H_MARGIN, min_y - v_margin),
+            egui::pos2(cursor_x + CURSOR_REVEAL_H_MARGIN, max_y + v_margin),
         );
 
         ui.scroll_to_rect(reveal_rect, None);
@@ -746,12 +2509,18 @@ impl CodeEditor {
             tab: true,
             vertical_arrows: true,
             horizontal_arrows: true,
-            escape: false,
+            escape: self.completion.is_some(),
         };
 
         let events = ui.input(|i| i.filtered_events(&event_filter));
 
         for event in events {
+            if self.block_selection.is_some()
+                && self.handle_block_event(ui, key_modifiers, &event, time)
+            {
+                continue;
+            }
+
             match event {
                 egui::Event::Text(text) => self.handle_text_input(ui, key_modifiers, &text, time),
                 egui::Event::Key {
@@ -764,97 +2533,832 @@ impl CodeEditor {
                 }
                 egui::Event::Copy => self.copy(ui),
                 egui::Event::Cut => self.cut(ui),
-                egui::Event::Paste(text) => self.paste(ui, text),
+                egui::Event::Paste(text) if !self.readonly => self.paste(ui, text),
                 _ => {}
             }
         }
     }
 
-    fn handle_text_input(
-        &mut self,
-        #[allow(unused_variables)] ui: &mut egui::Ui,
-        key_modifiers: &KeyModifiers,
-        text: &str,
-        time: f64,
-    ) {
-        if text.is_empty() {
+    /// Edit every row of the block selection at once, returns whether `event` was handled.
+    /// Navigating drops the block and lets the event through.
+    fn handle_block_event(
+        &mut self,
+        ui: &mut egui::Ui,
+        key_modifiers: &KeyModifiers,
+        event: &egui::Event,
+        time: f64,
+    ) -> bool {
+        match event {
+            egui::Event::Text(text) if !key_modifiers.ctrl => {
+                let ranges = self.block_selection.clone().unwrap_or_default();
+                self.replace_block(
+                    ranges
+                        .into_iter()
+                        .map(|range| (range, text.clone()))
+                        .collect(),
+                    time,
+                );
+            }
+            egui::Event::Copy => self.copy(ui),
+            egui::Event::Cut => {
+                self.copy(ui);
+                self.delete_block(true, time);
+            }
+            egui::Event::Paste(text) => self.paste_block(text, time),
+            egui::Event::Key {
+                key: key @ (egui::Key::Backspace | egui::Key::Delete),
+                pressed: true,
+                ..
+            } => self.delete_block(*key == egui::Key::Backspace, time),
+            egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => {
+                // Keys that type something are followed by a text event for the block
+                use egui::Key;
+                let navigating = modifiers.ctrl
+                    || modifiers.command
+                    || key_modifiers.ctrl
+                    || matches!(
+                        key,
+                        Key::ArrowLeft
+                            | Key::ArrowRight
+                            | Key::ArrowUp
+                            | Key::ArrowDown
+                            | Key::Home
+                            | Key::End
+                            | Key::PageUp
+                            | Key::PageDown
+                            | Key::Enter
+                            | Key::Tab
+                    );
+                if navigating {
+                    self.block_selection = None;
+                }
+                return false;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Replace ranges of the rows of the block selection as a single edit, `rows` are in
+    /// document order. Afterwards the block is a caret behind every replacement.
+    fn replace_block(&mut self, rows: Vec<(Range<usize>, String)>, time: f64) {
+        // The cursor stays on the row it was on
+        let cursor_line = self.doc.char_to_line(self.cursor);
+        let cursor_row = rows
+            .iter()
+            .position(|(range, _)| self.doc.char_to_line(range.start) == cursor_line)
+            .unwrap_or(0);
+
+        let ends = self.apply_compound_edit(&rows, |ends| (ends[cursor_row], None));
+        if ends.is_empty() {
+            return;
+        }
+
+        self.block_selection = Some(ends.into_iter().map(|end| end..end).collect());
+        self.completion = None;
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Delete the selected part of every row, or a char next to every caret when the
+    /// block is empty.
+    fn delete_block(&mut self, backward: bool, time: f64) {
+        let ranges = self.block_selection.clone().unwrap_or_default();
+        let has_text = ranges.iter().any(|range| !range.is_empty());
+
+        let rows = ranges
+            .into_iter()
+            .map(|range| {
+                if has_text {
+                    return (range, String::new());
+                }
+
+                let (line, col) = char_to_line_col(&self.doc, range.start);
+                let line_len = line_len_without_newline(self.doc.line(line));
+                let range = match backward {
+                    true if col > 0 => range.start - 1..range.start,
+                    false if col < line_len => range.start..range.start + 1,
+                    _ => range,
+                };
+                (range, String::new())
+            })
+            .collect();
+
+        self.replace_block(rows, time);
+    }
+
+    /// Paste one line per row when the line count matches the rows, otherwise the whole
+    /// text into every row.
+    fn paste_block(&mut self, text: &str, time: f64) {
+        let text = LineEnding::normalize(text);
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        let lines: Vec<&str> = text.split('\n').collect();
+
+        let ranges = self.block_selection.clone().unwrap_or_default();
+        let rows = if lines.len() == ranges.len() {
+            ranges
+                .into_iter()
+                .zip(lines)
+                .map(|(range, line)| (range, line.to_string()))
+                .collect()
+        } else {
+            ranges
+                .into_iter()
+                .map(|range| (range, text.to_string()))
+                .collect()
+        };
+
+        self.replace_block(rows, time);
+    }
+
+    fn handle_text_input(
+        &mut self,
+        #[allow(unused_variables)] ui: &mut egui::Ui,
+        key_modifiers: &KeyModifiers,
+        text: &str,
+        time: f64,
+    ) {
+        if text.is_empty() {
+            return;
+        }
+
+        if key_modifiers.ctrl {
+            #[cfg(target_os = "android")]
+            match text {
+                "c" | "C" => self.copy(ui),
+                "v" | "V" if !self.readonly => {
+                    if let Ok(clipboard_text) = android_clipboard::get_text() {
+                        self.paste(ui, clipboard_text);
+                    }
+                }
+                "x" | "X" => self.cut(ui),
+                _ => {}
+            }
+            return;
+        }
+
+        if self.readonly {
+            return;
+        }
+
+        if self.overwrite {
+            self.overwrite_text(text, time);
+            self.completion = None;
+            return;
+        }
+
+        if self.auto_close && self.handle_auto_close(text, time) {
+            self.completion = None;
+            return;
+        }
+
+        self.insert_text(text, time);
+        self.completion = self.completion_at_cursor();
+    }
+
+    /// Replace as many chars after the cursor as `text` has, without running past the end
+    /// of the line. A selection is replaced like when inserting.
+    fn overwrite_text(&mut self, text: &str, time: f64) {
+        if self
+            .selection
+            .as_ref()
+            .is_some_and(|sel| sel.start != sel.end)
+        {
+            return self.insert_text(text, time);
+        }
+
+        let (line, col) = char_to_line_col(&self.doc, self.cursor);
+        let line_len = line_len_without_newline(self.doc.line(line));
+        let overwritten = text.chars().count().min(line_len - col);
+        let range = self.cursor..self.cursor + overwritten;
+
+        let edit = Edit {
+            removed: self.doc.slice(range.clone()).to_string(),
+            range,
+            inserted: text.to_string(),
+            cursor_before: self.cursor,
+            cursor_after: self.cursor + text.chars().count(),
+            selection_before: self.selection.clone(),
+            selection_after: None,
+        };
+
+        self.apply_edit(edit);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Insert or step over bracket and quote pairs, returns whether `text` was handled.
+    fn handle_auto_close(&mut self, text: &str, time: f64) -> bool {
+        let mut chars = text.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return false;
+        };
+        if self
+            .selection
+            .as_ref()
+            .is_some_and(|sel| sel.start != sel.end)
+        {
+            return false;
+        }
+
+        let next_char = (self.cursor < self.doc.len_chars()).then(|| self.doc.char(self.cursor));
+
+        // Typing a closer right in front of the same closer just steps over it
+        if next_char == Some(c) && AUTO_CLOSE_PAIRS.iter().any(|(_, close)| *close == c) {
+            self.update_cursor(self.cursor + 1);
+            self.selection = None;
+            self.desired_column = None;
+            self.cursor_blink_offset = time;
+            return true;
+        }
+
+        let Some((open, close)) = AUTO_CLOSE_PAIRS.iter().find(|(open, _)| *open == c) else {
+            return false;
+        };
+
+        // Only pair up when not typing directly in front of a word
+        if next_char.is_some_and(|next| next.is_alphanumeric() || next == '_') {
+            return false;
+        }
+
+        let edit = Edit {
+            range: self.cursor..self.cursor,
+            removed: String::new(),
+            inserted: format!("{}{}", open, close),
+            cursor_before: self.cursor,
+            cursor_after: self.cursor + 1,
+            selection_before: self.selection.clone(),
+            selection_after: None,
+        };
+
+        self.apply_edit(edit);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+        true
+    }
+
+    fn handle_key_input(
+        &mut self,
+        ui: &mut egui::Ui,
+        key_modifiers: &KeyModifiers,
+        key: egui::Key,
+        modifiers: egui::Modifiers,
+        time: f64,
+    ) {
+        let is_ctrl = modifiers.ctrl || modifiers.command || key_modifiers.ctrl;
+
+        if let Some(completion) = &mut self.completion {
+            match key {
+                egui::Key::Tab | egui::Key::Enter => return self.accept_completion(time),
+                egui::Key::ArrowDown => return completion.select_next(),
+                egui::Key::ArrowUp => return completion.select_previous(),
+                egui::Key::Escape => {
+                    self.completion = None;
+                    return;
+                }
+                egui::Key::Backspace => {
+                    self.handle_backspace(false, time);
+                    self.completion = self.completion_at_cursor();
+                    return;
+                }
+                _ => self.completion = None,
+            }
+        }
+
+        let pressed = KeyBinding::new(
+            key,
+            is_ctrl,
+            modifiers.shift || key_modifiers.shift,
+            modifiers.alt || key_modifiers.alt,
+        );
+        if let Some(action) = self.key_bindings.action(pressed) {
+            if !self.readonly || !action.is_mutating() {
+                self.run_action(action, time);
+            }
+            return;
+        }
+
+        if key == egui::Key::F && is_ctrl {
+            return self.open_search();
+        }
+        if key == egui::Key::G && is_ctrl {
+            self.goto_line_input = Some(String::new());
+            self.goto_line_focus = true;
+            return;
+        }
+
+        let shift = modifiers.shift || key_modifiers.shift;
+
+        if self.readonly {
+            match key {
+                egui::Key::Home => self.handle_home(shift, time),
+                egui::Key::End => self.handle_end(shift, time),
+                egui::Key::ArrowLeft => self.handle_arrow_left(time),
+                egui::Key::ArrowRight => self.handle_arrow_right(time),
+                egui::Key::ArrowUp => self.handle_arrow_up(time),
+                egui::Key::ArrowDown => self.handle_arrow_down(time),
+                egui::Key::A if is_ctrl => self.select_all(time),
+                _ => {}
+            }
+            return;
+        }
+
+        match key {
+            egui::Key::Enter => self.handle_enter(time),
+            egui::Key::Backspace => self.handle_backspace(is_ctrl, time),
+            egui::Key::Delete => self.handle_delete(is_ctrl, time),
+            egui::Key::Tab => self.handle_tab(shift, time),
+            egui::Key::Insert => self.overwrite = !self.overwrite,
+            egui::Key::Home => self.handle_home(shift, time),
+            egui::Key::End => self.handle_end(shift, time),
+            egui::Key::ArrowLeft => self.handle_arrow_left(time),
+            egui::Key::ArrowRight => self.handle_arrow_right(time),
+            egui::Key::ArrowUp => self.handle_arrow_up(time),
+            egui::Key::ArrowDown => self.handle_arrow_down(time),
+            egui::Key::Z if is_ctrl => self.undo(ui),
+            egui::Key::Y if is_ctrl => self.redo(ui),
+            egui::Key::A if is_ctrl => self.select_all(time),
+            egui::Key::S if is_ctrl => self.format(),
+            _ => {}
+        }
+    }
+
+    fn run_action(&mut self, action: EditorAction, time: f64) {
+        match action {
+            EditorAction::UpperCase => self.transform_selection(str::to_uppercase, time),
+            EditorAction::LowerCase => self.transform_selection(str::to_lowercase, time),
+            EditorAction::ToggleCase => self.transform_selection(toggle_case, time),
+            EditorAction::JoinLines => self.join_lines(time),
+            EditorAction::SortLines => self.sort_lines(false, false, time),
+            EditorAction::SortLinesDescending => self.sort_lines(true, false, time),
+            EditorAction::SortLinesUnique => self.sort_lines(false, true, time),
+            EditorAction::ExpandParagraphSelection => self.expand_paragraph_selection(time),
+            EditorAction::ShrinkParagraphSelection => self.shrink_paragraph_selection(time),
+            EditorAction::MoveLinesUp => self.move_lines(true, time),
+            EditorAction::MoveLinesDown => self.move_lines(false, time),
+            EditorAction::DuplicateLinesUp => self.duplicate_lines(true, time),
+            EditorAction::DuplicateLinesDown => self.duplicate_lines(false, time),
+            EditorAction::ToggleComment => self.toggle_comment(time),
+        }
+    }
+
+    // ========================================================================
+    // Edit Operations
+    // ========================================================================
+
+    /// Replace the selected text with `f(selected text)`, keeping it selected.
+    fn transform_selection(&mut self, f: impl Fn(&str) -> String, time: f64) {
+        let Some(selection) = self.selection.clone() else {
+            return;
+        };
+        if selection.start == selection.end {
+            return;
+        }
+
+        let removed = self.doc.slice(selection.clone()).to_string();
+        let inserted = f(&removed);
+        if inserted == removed {
+            return;
+        }
+
+        let selection_after = selection.start..selection.start + inserted.chars().count();
+        let edit = Edit {
+            range: selection.clone(),
+            removed,
+            inserted,
+            cursor_before: self.cursor,
+            cursor_after: selection_after.end,
+            selection_before: Some(selection),
+            selection_after: Some(selection_after),
+        };
+
+        self.apply_edit(edit);
+        self.cursor_blink_offset = time;
+    }
+
+    /// Returns the range to edit and the text being removed (if any)
+    fn get_edit_range(&self) -> (Range<usize>, String) {
+        if let Some(sel) = &self.selection {
+            if sel.start != sel.end {
+                let removed = self.doc.slice(sel.clone()).to_string();
+                return (sel.clone(), removed);
+            }
+        }
+        (self.cursor..self.cursor, String::new())
+    }
+
+    fn insert_text(&mut self, text: &str, time: f64) {
+        let selection_before = self.selection.clone();
+        let cursor_before = self.cursor;
+        let (range, removed) = self.get_edit_range();
+        let cursor_after = range.start + text.chars().count();
+
+        let edit = Edit {
+            range,
+            removed,
+            inserted: text.to_string(),
+            cursor_before,
+            cursor_after,
+            selection_before,
+            selection_after: None,
+        };
+
+        self.apply_edit(edit);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Completion for the word at the cursor, it scans the whole document so it's
+    /// left out for large files.
+    fn completion_at_cursor(&self) -> Option<Completion> {
+        if self.is_large_file() {
+            return None;
+        }
+
+        Completion::new(&self.doc, self.cursor, &self.syntax)
+    }
+
+    fn accept_completion(&mut self, time: f64) {
+        let Some(completion) = self.completion.take() else {
+            return;
+        };
+
+        let range = completion.prefix_start..self.cursor;
+        let inserted = completion.selected_item().to_string();
+        let cursor_after = range.start + inserted.chars().count();
+
+        let edit = Edit {
+            removed: self.doc.slice(range.clone()).to_string(),
+            range,
+            inserted,
+            cursor_before: self.cursor,
+            cursor_after,
+            selection_before: self.selection.clone(),
+            selection_after: None,
+        };
+
+        self.apply_edit(edit);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Join the current line with the next one, or all lines spanned by the selection.
+    fn join_lines(&mut self, time: f64) {
+        let (first_line, last_line) = match &self.selection {
+            Some(sel) if sel.start != sel.end => (
+                self.doc.char_to_line(sel.start),
+                self.doc.char_to_line(sel.end),
+            ),
+            _ => {
+                let line = self.doc.char_to_line(self.cursor);
+                (line, line)
+            }
+        };
+        let last_line = last_line
+            .max(first_line + 1)
+            .min(self.doc.len_lines().saturating_sub(1));
+        if last_line <= first_line {
+            return;
+        }
+
+        let mut joined = String::new();
+        let mut join_point = 0;
+        for line in first_line..=last_line {
+            let line_slice = self.doc.line(line);
+            let text = line_slice
+                .slice(..line_len_without_newline(line_slice))
+                .to_string();
+
+            if line == first_line {
+                joined.push_str(text.trim_end());
+                join_point = joined.chars().count();
+                continue;
+            }
+
+            joined.truncate(joined.trim_end().len());
+            join_point = joined.chars().count();
+            let text = text.trim();
+            if !text.is_empty() {
+                if !joined.is_empty() {
+                    joined.push(' ');
+                }
+                joined.push_str(text);
+            }
+        }
+
+        let line_start = self.doc.line_to_char(first_line);
+        let last_line_slice = self.doc.line(last_line);
+        let range = line_start
+            ..self.doc.line_to_char(last_line) + line_len_without_newline(last_line_slice);
+
+        let edit = Edit {
+            removed: self.doc.slice(range.clone()).to_string(),
+            range,
+            inserted: joined,
+            cursor_before: self.cursor,
+            cursor_after: line_start + join_point,
+            selection_before: self.selection.clone(),
+            selection_after: None,
+        };
+
+        self.apply_edit(edit);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Select the run of non-blank lines around the selection, or when that's
+    /// already selected, grow it to the end of the next paragraph.
+    fn expand_paragraph_selection(&mut self, time: f64) {
+        let last_line = self.doc.len_lines() - 1;
+        let (first, last) = match &self.selection {
+            Some(sel) if sel.start != sel.end => (
+                self.doc.char_to_line(sel.start),
+                // A selection ending at a line start doesn't include that line
+                self.doc
+                    .char_to_line(sel.end.saturating_sub(1).max(sel.start)),
+            ),
+            _ => {
+                let line = self.doc.char_to_line(self.cursor);
+                (line, line)
+            }
+        };
+
+        let line_range = |first: usize, last: usize| {
+            self.doc.line_to_char(first)..self.doc.line_to_char((last + 1).min(last_line + 1))
+        };
+
+        let mut start = first;
+        while start > 0 && !is_blank_line(self.doc.line(start - 1)) {
+            start -= 1;
+        }
+        let mut end = last;
+        while end < last_line && !is_blank_line(self.doc.line(end + 1)) {
+            end += 1;
+        }
+
+        if self.selection.as_ref() == Some(&line_range(start, end)) {
+            // Skip the blank lines after the paragraph and take the next one
+            while end < last_line && is_blank_line(self.doc.line(end + 1)) {
+                end += 1;
+            }
+            while end < last_line && !is_blank_line(self.doc.line(end + 1)) {
+                end += 1;
+            }
+        }
+
+        let range = line_range(start, end);
+        if self.selection.as_ref() == Some(&range) {
+            return;
+        }
+
+        self.paragraph_selections
+            .push((self.selection.clone(), self.cursor, range.clone()));
+        self.selection_anchor = Some(range.start);
+        self.cursor = range.end;
+        self.selection = Some(range);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Undo the last [`Self::expand_paragraph_selection`], as long as the selection
+    /// hasn't been changed since.
+    fn shrink_paragraph_selection(&mut self, time: f64) {
+        let Some((selection, cursor, expanded)) = self.paragraph_selections.pop() else {
+            return;
+        };
+
+        if self.selection.as_ref() != Some(&expanded) {
+            self.paragraph_selections.clear();
+            return;
+        }
+
+        self.selection_anchor = selection.as_ref().map(|sel| sel.start);
+        self.selection = selection;
+        self.cursor = cursor;
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// First and last line spanned by the selection, or the cursor line without one.
+    fn selected_lines(&self) -> (usize, usize) {
+        match &self.selection {
+            Some(sel) if sel.start != sel.end => {
+                let first_line = self.doc.char_to_line(sel.start);
+                let (last_line, last_col) = char_to_line_col(&self.doc, sel.end);
+                // A selection ending at the start of a line doesn't include that line
+                if last_col == 0 && last_line > first_line {
+                    (first_line, last_line - 1)
+                } else {
+                    (first_line, last_line)
+                }
+            }
+            _ => {
+                let line = self.doc.char_to_line(self.cursor);
+                (line, line)
+            }
+        }
+    }
+
+    /// Text of `first_line..=last_line` without the newline of the last line.
+    fn line_block(&self, first_line: usize, last_line: usize) -> (Range<usize>, String) {
+        let last_line_slice = self.doc.line(last_line);
+        let range = self.doc.line_to_char(first_line)
+            ..self.doc.line_to_char(last_line) + line_len_without_newline(last_line_slice);
+        (range.clone(), self.doc.slice(range).to_string())
+    }
+
+    /// Swap the current line, or all lines spanned by the selection, with the line above or below.
+    fn move_lines(&mut self, up: bool, time: f64) {
+        let (first_line, last_line) = self.selected_lines();
+        let neighbour = if up {
+            let Some(line) = first_line.checked_sub(1) else {
+                return;
+            };
+            line
+        } else {
+            if last_line + 1 >= self.doc.len_lines() {
+                return;
+            }
+            last_line + 1
+        };
+
+        let (_, block) = self.line_block(first_line, last_line);
+        let (_, neighbour_text) = self.line_block(neighbour, neighbour);
+        let (range, removed) = self.line_block(first_line.min(neighbour), last_line.max(neighbour));
+        let inserted = if up {
+            format!("{}\n{}", block, neighbour_text)
+        } else {
+            format!("{}\n{}", neighbour_text, block)
+        };
+
+        // The moved lines shift by the neighbouring line and its newline
+        let offset = neighbour_text.chars().count() + 1;
+        let shift = |pos: usize| if up { pos - offset } else { pos + offset };
+
+        let edit = Edit {
+            range,
+            removed,
+            inserted,
+            cursor_before: self.cursor,
+            cursor_after: shift(self.cursor),
+            selection_before: self.selection.clone(),
+            selection_after: self
+                .selection
+                .clone()
+                .map(|sel| shift(sel.start)..shift(sel.end)),
+        };
+
+        self.apply_edit(edit);
+        self.selection_anchor = self.selection_anchor.map(shift);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Copy the current line, or all lines spanned by the selection, keeping the cursor on
+    /// the upper copy when going up and on the lower copy when going down.
+    fn duplicate_lines(&mut self, up: bool, time: f64) {
+        let (first_line, last_line) = self.selected_lines();
+        let (range, block) = self.line_block(first_line, last_line);
+
+        // The copy always goes below, so only moving to the lower copy shifts the cursor
+        let offset = if up { 0 } else { block.chars().count() + 1 };
+        let shift = |pos: usize| pos + offset;
+
+        let edit = Edit {
+            range: range.end..range.end,
+            removed: String::new(),
+            inserted: format!("\n{}", block),
+            cursor_before: self.cursor,
+            cursor_after: shift(self.cursor),
+            selection_before: self.selection.clone(),
+            selection_after: self
+                .selection
+                .clone()
+                .map(|sel| shift(sel.start)..shift(sel.end)),
+        };
+
+        self.apply_edit(edit);
+        self.selection_anchor = self.selection_anchor.map(shift);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Comment out the current line or all lines spanned by the selection with the line comment
+    /// of the syntax, or uncomment them when they all already are. Blank lines are left alone.
+    fn toggle_comment(&mut self, time: f64) {
+        let comment = self.syntax.comment();
+        if comment.is_empty() {
             return;
         }
 
-        if key_modifiers.ctrl {
-            #[cfg(target_os = "android")]
-            match text {
-                "c" | "C" => self.copy(ui),
-                "v" | "V" => {
-                    if let Ok(clipboard_text) = android_clipboard::get_text() {
-                        self.paste(ui, clipboard_text);
-                    }
+        let (first_line, last_line) = self.selected_lines();
+        let (range, removed) = self.line_block(first_line, last_line);
+        let lines: Vec<&str> = removed.split('\n').collect();
+        let code_lines = || lines.iter().filter(|line| !line.trim().is_empty());
+
+        let uncomment = code_lines().all(|line| line.trim_start().starts_with(comment));
+        // Comment markers line up at the indentation of the least indented line
+        let column = code_lines()
+            .map(|line| leading_whitespace(line).len())
+            .min()
+            .unwrap_or(0);
+
+        let inserted = lines
+            .iter()
+            .map(|line| {
+                if line.trim().is_empty() {
+                    line.to_string()
+                } else if uncomment {
+                    let indent = leading_whitespace(line);
+                    let rest = &line[indent.len() + comment.len()..];
+                    format!("{}{}", indent, rest.strip_prefix(' ').unwrap_or(rest))
+                } else {
+                    format!("{}{} {}", &line[..column], comment, &line[column..])
                 }
-                "x" | "X" => self.cut(ui),
-                _ => {}
-            }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if inserted == removed {
             return;
         }
 
-        self.insert_text(text, time);
+        let new_end = range.start + inserted.chars().count();
+        let (cursor_after, selection_after) = if self.selection.is_some() {
+            (new_end, Some(range.start..new_end))
+        } else {
+            let cursor = self.cursor + inserted.chars().count();
+            let cursor = cursor.saturating_sub(removed.chars().count());
+            (cursor.clamp(range.start, new_end), None)
+        };
+
+        let edit = Edit {
+            range: range.clone(),
+            removed,
+            inserted,
+            cursor_before: self.cursor,
+            cursor_after,
+            selection_before: self.selection.clone(),
+            selection_after,
+        };
+
+        self.apply_edit(edit);
+        self.selection_anchor = self.selection.as_ref().map(|sel| sel.start);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
     }
 
-    fn handle_key_input(
-        &mut self,
-        ui: &mut egui::Ui,
-        key_modifiers: &KeyModifiers,
-        key: egui::Key,
-        modifiers: egui::Modifiers,
-        time: f64,
-    ) {
-        let is_ctrl = modifiers.ctrl || modifiers.command || key_modifiers.ctrl;
+    /// Sort the lines spanned by the selection, optionally dropping duplicates.
+    fn sort_lines(&mut self, descending: bool, unique: bool, time: f64) {
+        let Some(selection) = self.selection.clone() else {
+            return;
+        };
 
-        match key {
-            egui::Key::Enter => self.handle_enter(time),
-            egui::Key::Backspace => self.handle_backspace(time),
-            egui::Key::Delete => self.handle_delete(time),
-            egui::Key::Tab => self.handle_tab(time),
-            egui::Key::ArrowLeft => self.handle_arrow_left(time),
-            egui::Key::ArrowRight => self.handle_arrow_right(time),
-            egui::Key::ArrowUp => self.handle_arrow_up(time),
-            egui::Key::ArrowDown => self.handle_arrow_down(time),
-            egui::Key::Z if is_ctrl => self.undo(ui),
-            egui::Key::Y if is_ctrl => self.redo(ui),
-            egui::Key::A if is_ctrl => self.select_all(time),
-            egui::Key::S if is_ctrl => self.format(),
-            _ => {}
+        let first_line = self.doc.char_to_line(selection.start);
+        let (mut last_line, last_col) = char_to_line_col(&self.doc, selection.end);
+        // A selection ending at the start of a line doesn't include that line
+        if last_col == 0 && last_line > first_line {
+            last_line -= 1;
+        }
+        if last_line <= first_line {
+            return;
         }
-    }
 
-    // ========================================================================
-    // Edit Operations
-    // ========================================================================
+        let mut lines: Vec<String> = (first_line..=last_line)
+            .map(|line| {
+                let line_slice = self.doc.line(line);
+                line_slice
+                    .slice(..line_len_without_newline(line_slice))
+                    .to_string()
+            })
+            .collect();
 
-    /// Returns the range to edit and the text being removed (if any)
-    fn get_edit_range(&self) -> (Range<usize>, String) {
-        if let Some(sel) = &self.selection {
-            if sel.start != sel.end {
-                let removed = self.doc.slice(sel.clone()).to_string();
-                return (sel.clone(), removed);
-            }
+        lines.sort();
+        if unique {
+            lines.dedup();
+        }
+        if descending {
+            lines.reverse();
         }
-        (self.cursor..self.cursor, String::new())
-    }
 
-    fn insert_text(&mut self, text: &str, time: f64) {
-        let selection_before = self.selection.clone();
-        let cursor_before = self.cursor;
-        let (range, removed) = self.get_edit_range();
-        let cursor_after = range.start + text.chars().count();
+        let last_line_slice = self.doc.line(last_line);
+        let range = self.doc.line_to_char(first_line)
+            ..self.doc.line_to_char(last_line) + line_len_without_newline(last_line_slice);
+        let inserted = lines.join("\n");
+        let selection_after = range.start..range.start + inserted.chars().count();
 
         let edit = Edit {
+            removed: self.doc.slice(range.clone()).to_string(),
             range,
-            removed,
-            inserted: text.to_string(),
-            cursor_before,
-            cursor_after,
-            selection_before,
-            selection_after: None,
+            inserted,
+            cursor_before: self.cursor,
+            cursor_after: selection_after.end,
+            selection_before: Some(selection),
+            selection_after: Some(selection_after),
         };
 
         self.apply_edit(edit);
@@ -888,26 +3392,50 @@ impl CodeEditor {
         self.cursor_blink_offset = time;
     }
 
-    fn handle_backspace(&mut self, time: f64) {
+    /// Delete the selection or the character before the cursor, the whole word before
+    /// it when `word`.
+    fn handle_backspace(&mut self, word: bool, time: f64) {
         let selection_before = self.selection.clone();
         let cursor_before = self.cursor;
+        let start = if word {
+            next_word_boundary(&self.doc, self.cursor, false)
+        } else {
+            self.cursor.saturating_sub(1)
+        };
 
         let (range, removed) = if let Some(sel) = &self.selection {
             if sel.start != sel.end {
                 (sel.clone(), self.doc.slice(sel.clone()).to_string())
             } else if self.cursor > 0 {
-                let r = (self.cursor - 1)..self.cursor;
+                let r = start..self.cursor;
                 (r.clone(), self.doc.slice(r).to_string())
             } else {
                 return;
             }
         } else if self.cursor > 0 {
-            let r = (self.cursor - 1)..self.cursor;
+            let r = start..self.cursor;
             (r.clone(), self.doc.slice(r).to_string())
         } else {
             return;
         };
 
+        // Remove both halves of an empty auto-closed pair
+        let has_selection = self
+            .selection
+            .as_ref()
+            .is_some_and(|sel| sel.start != sel.end);
+        let (range, removed) = if self.auto_close
+            && !word
+            && !has_selection
+            && range.end < self.doc.len_chars()
+            && AUTO_CLOSE_PAIRS.contains(&(self.doc.char(range.start), self.doc.char(range.end)))
+        {
+            let r = range.start..(range.end + 1);
+            (r.clone(), self.doc.slice(r).to_string())
+        } else {
+            (range, removed)
+        };
+
         let edit = Edit {
             range: range.clone(),
             removed,
@@ -923,21 +3451,28 @@ impl CodeEditor {
         self.cursor_blink_offset = time;
     }
 
-    fn handle_delete(&mut self, time: f64) {
+    /// Delete the selection or the character after the cursor, the whole word after it
+    /// when `word`.
+    fn handle_delete(&mut self, word: bool, time: f64) {
         let selection_before = self.selection.clone();
         let cursor_before = self.cursor;
+        let end = if word {
+            next_word_boundary(&self.doc, self.cursor, true)
+        } else {
+            self.cursor + 1
+        };
 
         let (range, removed) = if let Some(sel) = &self.selection {
             if sel.start != sel.end {
                 (sel.clone(), self.doc.slice(sel.clone()).to_string())
             } else if self.cursor < self.doc.len_chars() {
-                let r = self.cursor..(self.cursor + 1);
+                let r = self.cursor..end;
                 (r.clone(), self.doc.slice(r).to_string())
             } else {
                 return;
             }
         } else if self.cursor < self.doc.len_chars() {
-            let r = self.cursor..(self.cursor + 1);
+            let r = self.cursor..end;
             (r.clone(), self.doc.slice(r).to_string())
         } else {
             return;
@@ -958,73 +3493,484 @@ impl CodeEditor {
         self.cursor_blink_offset = time;
     }
 
-    fn handle_tab(&mut self, time: f64) {
-        let line = self.doc.char_to_line(self.cursor);
-        let line_start = self.doc.line_to_char(line);
-        let column = self.cursor - line_start;
-        let spaces = TAB_WIDTH - (column % TAB_WIDTH);
-        let text: String = " ".repeat(spaces);
+    /// Insert indentation at the cursor, or indent every line of a multi-line selection.
+    /// With `dedent` the touched lines are unindented instead.
+    fn handle_tab(&mut self, dedent: bool, time: f64) {
+        let (first_line, last_line) = self.selected_lines();
+        if dedent || first_line != last_line {
+            return self.indent_lines(dedent, time);
+        }
+
+        let (line, col) = char_to_line_col(&self.doc, self.cursor);
+        let column = visual_column(self.doc.line(line).chars().take(col), self.tab_width);
+        let text = if self.use_spaces {
+            " ".repeat(self.tab_width - (column % self.tab_width))
+        } else {
+            "\t".to_string()
+        };
+
+        self.insert_text(&text, time);
+    }
+
+    /// Indent or dedent all lines touched by the selection by one level as a single edit,
+    /// selecting the lines afterwards.
+    fn indent_lines(&mut self, dedent: bool, time: f64) {
+        let (first_line, last_line) = self.selected_lines();
+        let (range, removed) = self.line_block(first_line, last_line);
+        let indent = if self.use_spaces {
+            " ".repeat(self.tab_width)
+        } else {
+            "\t".to_string()
+        };
+
+        let inserted = removed
+            .split('\n')
+            .map(|line| {
+                if dedent {
+                    let spaces = line
+                        .chars()
+                        .take(self.tab_width)
+                        .take_while(|c| *c == ' ')
+                        .count();
+                    let strip = if spaces == 0 && line.starts_with('\t') {
+                        1
+                    } else {
+                        spaces
+                    };
+                    line[strip..].to_string()
+                } else if line.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}{}", indent, line)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if inserted == removed {
+            return;
+        }
+
+        let selection_after = range.start..range.start + inserted.chars().count();
+        let edit = Edit {
+            range,
+            removed,
+            inserted,
+            cursor_before: self.cursor,
+            cursor_after: selection_after.end,
+            selection_before: self.selection.clone(),
+            selection_after: Some(selection_after.clone()),
+        };
+
+        self.apply_edit(edit);
+        self.selection_anchor = Some(selection_after.start);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    fn handle_arrow_left(&mut self, time: f64) {
+        self.update_cursor(self.cursor.saturating_sub(1));
+        self.selection = None;
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    fn handle_arrow_right(&mut self, time: f64) {
+        self.update_cursor((self.cursor + 1).min(self.doc.len_chars()));
+        self.selection = None;
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Jump to the first non-whitespace character of the line, or to column zero when
+    /// already there.
+    fn handle_home(&mut self, extend: bool, time: f64) {
+        let (line, col) = char_to_line_col(&self.doc, self.cursor);
+        let indent = leading_whitespace(&self.doc.line(line).to_string()).len();
+        let target_col = if col == indent { 0 } else { indent };
+
+        self.move_cursor(self.doc.line_to_char(line) + target_col, extend);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    fn handle_end(&mut self, extend: bool, time: f64) {
+        let line = self.doc.char_to_line(self.cursor);
+        let line_len = line_len_without_newline(self.doc.line(line));
+
+        self.move_cursor(self.doc.line_to_char(line) + line_len, extend);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Move the cursor to `cursor`, when `extend` the selection grows or shrinks from its
+    /// anchor instead of being cleared.
+    fn move_cursor(&mut self, cursor: usize, extend: bool) {
+        if extend {
+            // Only keep the anchor when it is still one end of the current selection
+            let anchor = self
+                .selection_anchor
+                .filter(|anchor| {
+                    self.selection
+                        .as_ref()
+                        .is_some_and(|sel| sel.start == *anchor || sel.end == *anchor)
+                })
+                .unwrap_or(self.cursor);
+
+            self.selection_anchor = Some(anchor);
+            self.selection = (anchor != cursor).then(|| anchor.min(cursor)..anchor.max(cursor));
+        } else {
+            self.selection = None;
+            self.selection_anchor = None;
+        }
+        self.update_cursor(cursor);
+    }
+
+    fn handle_arrow_up(&mut self, time: f64) {
+        let (line, col) = char_to_line_col(&self.doc, self.cursor);
+
+        if line > 0 {
+            let target_col = self.desired_column.unwrap_or(col);
+            let prev_line = self.doc.line(line - 1);
+            let prev_line_start = self.doc.line_to_char(line - 1);
+            let prev_line_len = line_len_without_newline(prev_line);
+
+            let new_col = target_col.min(prev_line_len);
+            self.update_cursor(prev_line_start + new_col);
+            self.desired_column = Some(target_col);
+        }
+
+        self.selection = None;
+        self.cursor_blink_offset = time;
+    }
+
+    fn handle_arrow_down(&mut self, time: f64) {
+        let (line, col) = char_to_line_col(&self.doc, self.cursor);
+
+        if line + 1 < self.doc.len_lines() {
+            let target_col = self.desired_column.unwrap_or(col);
+            let next_line = self.doc.line(line + 1);
+            let next_line_start = self.doc.line_to_char(line + 1);
+            let next_line_len = line_len_without_newline(next_line);
+
+            let new_col = target_col.min(next_line_len);
+            self.update_cursor(next_line_start + new_col);
+            self.desired_column = Some(target_col);
+        }
+
+        self.selection = None;
+        self.cursor_blink_offset = time;
+    }
+
+    fn select_all(&mut self, time: f64) {
+        let len = self.doc.len_chars();
+        self.selection = Some(0..len);
+        self.cursor = len;
+        self.selection_anchor = Some(0);
+        self.desired_column = None;
+        self.cursor_blink_offset = time;
+    }
+
+    /// Draw the go to line input above the editor when it is open, returns whether it has
+    /// keyboard focus.
+    fn goto_line_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let Some(input) = &mut self.goto_line_input else {
+            return false;
+        };
+
+        let (enter, escape) = ui.input(|i| {
+            (
+                i.key_pressed(egui::Key::Enter),
+                i.key_pressed(egui::Key::Escape),
+            )
+        });
+
+        let response = ui
+            .horizontal(|ui| {
+                ui.label("Go to Line");
+                let response = ui.add(
+                    egui::TextEdit::singleline(input)
+                        .hint_text(format!("1 - {}", self.doc.len_lines()))
+                        .desired_width(80.0),
+                );
+                input.retain(|c| c.is_ascii_digit());
+                response
+            })
+            .inner;
+
+        if std::mem::take(&mut self.goto_line_focus) {
+            response.request_focus();
+        }
+        if !response.lost_focus() {
+            return response.has_focus();
+        }
+
+        // Losing focus any other way than with Enter cancels
+        if enter && !escape {
+            if let Ok(line) = input.parse::<usize>() {
+                self.goto_line(line.saturating_sub(1));
+            }
+        }
+        self.goto_line_input = None;
+        self.focus_requested = true;
+        false
+    }
+
+    // ========================================================================
+    // Find & Replace
+    // ========================================================================
+
+    /// Open the find/replace panel, searching for the selection when it is on a single line.
+    fn open_search(&mut self) {
+        let selected = self
+            .selected_text()
+            .filter(|text| !text.is_empty() && !text.contains('\n'));
+
+        match &mut self.search {
+            Some(search) => {
+                if let Some(selected) = selected {
+                    search.query = selected;
+                }
+                search.focus_query = true;
+            }
+            None => self.search = Some(SearchState::new(selected.unwrap_or_default())),
+        }
+        self.update_search();
+    }
+
+    fn close_search(&mut self) {
+        self.search = None;
+        self.search_matches.clear();
+        self.focus_requested = true;
+    }
+
+    /// Find the matches again when the query or the document changed since the last search.
+    fn update_search(&mut self) {
+        let Some(search) = &mut self.search else {
+            if self.search_matches_generation != self.generation {
+                self.search_matches.clear();
+            }
+            return;
+        };
+        if !search.is_outdated(self.generation) {
+            return;
+        }
+
+        self.search_matches = find_matches(&self.doc, &search.query, search.case_sensitive);
+        self.search_matches_generation = self.generation;
+        search.current = self
+            .search_matches
+            .iter()
+            .position(|range| Some(range) == self.selection.as_ref());
+        search.mark_searched(self.generation);
+    }
+
+    /// Select the next or previous match relative to the current one, or to the cursor when
+    /// no match is selected. Wraps around at either end of the document.
+    fn select_search_match(&mut self, forward: bool) {
+        self.update_search();
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let count = self.search_matches.len();
+        if count == 0 {
+            return;
+        }
+
+        let current = search
+            .current
+            .filter(|&i| Some(&self.search_matches[i]) == self.selection.as_ref());
+        let next = match (current, forward) {
+            (Some(i), true) => (i + 1) % count,
+            (Some(i), false) => (i + count - 1) % count,
+            (None, true) => self
+                .search_matches
+                .iter()
+                .position(|range| range.start >= self.cursor)
+                .unwrap_or(0),
+            (None, false) => self
+                .search_matches
+                .iter()
+                .rposition(|range| range.end <= self.cursor)
+                .unwrap_or(count - 1),
+        };
+
+        search.current = Some(next);
+        self.reveal_range(self.search_matches[next].clone());
+    }
+
+    /// Replace the selected match and move on to the next one. When no match is
+    /// selected yet this only selects the next match, so it can be seen before replacing.
+    fn replace_search_match(&mut self, time: f64) {
+        self.update_search();
+        let Some(search) = &self.search else {
+            return;
+        };
+        let selected = search
+            .current
+            .map(|i| self.search_matches[i].clone())
+            .filter(|range| Some(range) == self.selection.as_ref());
+
+        if let Some(range) = selected {
+            let inserted = search.replace.clone();
+            let edit = Edit {
+                range: range.clone(),
+                removed: self.doc.slice(range.clone()).to_string(),
+                cursor_after: range.start + inserted.chars().count(),
+                inserted,
+                cursor_before: self.cursor,
+                selection_before: self.selection.clone(),
+                selection_after: None,
+            };
+            self.apply_edit(edit);
+            self.desired_column = None;
+            self.cursor_blink_offset = time;
+        }
 
-        self.insert_text(&text, time);
+        self.select_search_match(true);
     }
 
-    fn handle_arrow_left(&mut self, time: f64) {
-        self.update_cursor(self.cursor.saturating_sub(1));
-        self.selection = None;
-        self.desired_column = None;
-        self.cursor_blink_offset = time;
-    }
+    /// Replace every match as a single edit, so one undo restores all of them.
+    fn replace_all_search_matches(&mut self, time: f64) {
+        self.update_search();
+        let Some(search) = &self.search else {
+            return;
+        };
+        let (Some(first), Some(last)) = (self.search_matches.first(), self.search_matches.last())
+        else {
+            return;
+        };
+        let range = first.start..last.end;
+
+        let mut inserted = String::new();
+        let mut prev_end = range.start;
+        for m in &self.search_matches {
+            inserted.extend(self.doc.slice(prev_end..m.start).chars());
+            inserted.push_str(&search.replace);
+            prev_end = m.end;
+        }
 
-    fn handle_arrow_right(&mut self, time: f64) {
-        self.update_cursor((self.cursor + 1).min(self.doc.len_chars()));
-        self.selection = None;
+        let edit = Edit {
+            removed: self.doc.slice(range.clone()).to_string(),
+            cursor_after: range.start + inserted.chars().count(),
+            range,
+            inserted,
+            cursor_before: self.cursor,
+            selection_before: self.selection.clone(),
+            selection_after: None,
+        };
+        self.apply_edit(edit);
         self.desired_column = None;
         self.cursor_blink_offset = time;
+        self.update_search();
     }
 
-    fn handle_arrow_up(&mut self, time: f64) {
-        let (line, col) = char_to_line_col(&self.doc, self.cursor);
+    /// Draw the find/replace panel above the editor when it is open, returns whether one of
+    /// its fields has keyboard focus.
+    fn search_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        self.update_search();
+        let Some(search) = &mut self.search else {
+            return false;
+        };
 
-        if line > 0 {
-            let target_col = self.desired_column.unwrap_or(col);
-            let prev_line = self.doc.line(line - 1);
-            let prev_line_start = self.doc.line_to_char(line - 1);
-            let prev_line_len = line_len_without_newline(prev_line);
+        let mut navigate = None;
+        let mut replace = false;
+        let mut replace_all = false;
+        let mut close = false;
+        let mut has_focus = false;
+
+        let (enter, shift, escape) = ui.input(|i| {
+            (
+                i.key_pressed(egui::Key::Enter),
+                i.modifiers.shift,
+                i.key_pressed(egui::Key::Escape),
+            )
+        });
+
+        ui.horizontal(|ui| {
+            let query = ui.add(
+                egui::TextEdit::singleline(&mut search.query)
+                    .hint_text("Find")
+                    .desired_width(200.0),
+            );
+            if std::mem::take(&mut search.focus_query) {
+                query.request_focus();
+            }
+            if query.lost_focus() && enter {
+                navigate = Some(!shift);
+                search.focus_query = true;
+            }
+            close |= query.lost_focus() && escape;
+            has_focus |= query.has_focus();
 
-            let new_col = target_col.min(prev_line_len);
-            self.update_cursor(prev_line_start + new_col);
-            self.desired_column = Some(target_col);
-        }
+            ui.toggle_value(&mut search.case_sensitive, "Aa")
+                .on_hover_text("Match Case");
 
-        self.selection = None;
-        self.cursor_blink_offset = time;
-    }
+            let count = self.search_matches.len();
+            match search.current {
+                _ if search.query.is_empty() => {}
+                _ if count == 0 => {
+                    ui.weak("No results");
+                }
+                Some(current) => {
+                    ui.weak(format!("{} of {}", current + 1, count));
+                }
+                None => {
+                    ui.weak(format!("{} results", count));
+                }
+            }
 
-    fn handle_arrow_down(&mut self, time: f64) {
-        let (line, col) = char_to_line_col(&self.doc, self.cursor);
+            if ui
+                .button(egui_phosphor::regular::ARROW_UP)
+                .on_hover_text("Previous Match (Shift+Enter)")
+                .clicked()
+            {
+                navigate = Some(false);
+            }
+            if ui
+                .button(egui_phosphor::regular::ARROW_DOWN)
+                .on_hover_text("Next Match (Enter)")
+                .clicked()
+            {
+                navigate = Some(true);
+            }
+            close |= ui.button(egui_phosphor::regular::X).clicked();
+        });
+
+        ui.add_enabled_ui(!self.readonly, |ui| {
+            ui.horizontal(|ui| {
+                let replace_field = ui.add(
+                    egui::TextEdit::singleline(&mut search.replace)
+                        .hint_text("Replace")
+                        .desired_width(200.0),
+                );
+                if replace_field.lost_focus() && enter {
+                    replace = true;
+                    replace_field.request_focus();
+                }
+                close |= replace_field.lost_focus() && escape;
+                has_focus |= replace_field.has_focus();
 
-        if line + 1 < self.doc.len_lines() {
-            let target_col = self.desired_column.unwrap_or(col);
-            let next_line = self.doc.line(line + 1);
-            let next_line_start = self.doc.line_to_char(line + 1);
-            let next_line_len = line_len_without_newline(next_line);
+                replace |= ui.button("Replace").clicked();
+                replace_all |= ui.button("Replace All").clicked();
+            });
+        });
 
-            let new_col = target_col.min(next_line_len);
-            self.update_cursor(next_line_start + new_col);
-            self.desired_column = Some(target_col);
+        let time = ui.input(|i| i.time);
+        if close {
+            self.close_search();
+            return false;
+        }
+        if let Some(forward) = navigate {
+            self.select_search_match(forward);
+        }
+        if replace {
+            self.replace_search_match(time);
+        }
+        if replace_all {
+            self.replace_all_search_matches(time);
         }
 
-        self.selection = None;
-        self.cursor_blink_offset = time;
-    }
-
-    fn select_all(&mut self, time: f64) {
-        let len = self.doc.len_chars();
-        self.selection = Some(0..len);
-        self.cursor = len;
-        self.selection_anchor = Some(0);
-        self.desired_column = None;
-        self.cursor_blink_offset = time;
+        has_focus
     }
 
     // ========================================================================
@@ -1034,10 +3980,68 @@ impl CodeEditor {
     fn update_doc_hash(&mut self) {
         let mut hasher = DefaultHasher::new();
         self.doc.hash(&mut hasher);
-        self.doc_hash = hasher.finish();
+        let doc_hash = hasher.finish();
+        let changed = doc_hash != self.doc_hash;
+        self.doc_hash = doc_hash;
+        self.generation = self.generation.wrapping_add(1);
+
+        if changed {
+            if let Some(on_change) = &mut self.on_change {
+                on_change(&self.doc);
+            }
+        }
+    }
+
+    /// Apply non-overlapping replacements, in document order, as one edit spanning all of
+    /// them so they undo together. `place` gets where every replacement ends afterwards and
+    /// returns the cursor and selection, the same ends are returned.
+    fn apply_compound_edit(
+        &mut self,
+        parts: &[(Range<usize>, String)],
+        place: impl FnOnce(&[usize]) -> (usize, Option<Range<usize>>),
+    ) -> Vec<usize> {
+        if self.readonly {
+            return Vec::new();
+        }
+        let (Some((first, _)), Some((last, _))) = (parts.first(), parts.last()) else {
+            return Vec::new();
+        };
+
+        let range = first.start..last.end;
+        let mut inserted = String::new();
+        let mut inserted_len = 0;
+        let mut ends = Vec::with_capacity(parts.len());
+        let mut prev_end = range.start;
+        for (part_range, text) in parts {
+            let between = self.doc.slice(prev_end..part_range.start);
+            inserted.extend(between.chars());
+            inserted.push_str(text);
+            inserted_len += between.len_chars() + text.chars().count();
+
+            ends.push(range.start + inserted_len);
+            prev_end = part_range.end;
+        }
+
+        let (cursor_after, selection_after) = place(&ends);
+        let edit = Edit {
+            removed: self.doc.slice(range.clone()).to_string(),
+            range,
+            inserted,
+            cursor_before: self.cursor,
+            cursor_after,
+            selection_before: self.selection.clone(),
+            selection_after,
+        };
+
+        self.apply_edit(edit);
+        ends
     }
 
     fn apply_edit(&mut self, edit: Edit) {
+        if self.readonly {
+            return;
+        }
+
         self.doc.remove(edit.range.clone());
         self.doc.insert(edit.range.start, &edit.inserted);
         self.update_doc_hash();
@@ -1046,7 +4050,7 @@ impl CodeEditor {
         self.selection = edit.selection_after.clone();
         self.invalidate_layout();
 
-        self.edit_stack.undo.push(edit);
+        self.edit_stack.undo.push(EditStep::Text(edit));
         self.edit_stack.redo.clear();
     }
 
@@ -1058,6 +4062,7 @@ impl CodeEditor {
     fn invalidate_layout(&mut self) {
         self.max_line_width = None;
         self.text_layout_job = None;
+        self.line_rows = None;
     }
 
     fn copy(&self, ui: &mut egui::Ui) {
@@ -1074,7 +4079,12 @@ impl CodeEditor {
         }
     }
 
+    /// Only copies while read-only.
     fn cut(&mut self, ui: &mut egui::Ui) {
+        if self.readonly {
+            return self.copy(ui);
+        }
+
         let Some(selection) = self.selection.clone() else {
             return;
         };
@@ -1117,9 +4127,22 @@ impl CodeEditor {
             return;
         }
 
+        // Clipboard contents from other applications may use CRLF
+        let text = LineEnding::normalize(&text);
+
         let selection_before = self.selection.clone();
         let cursor_before = self.cursor;
         let (range, removed) = self.get_edit_range();
+
+        let text = if self.paste_reindent && text.contains('\n') {
+            let line = self
+                .doc
+                .line(self.doc.char_to_line(range.start))
+                .to_string();
+            reindent(&text, leading_whitespace(&line))
+        } else {
+            text
+        };
         let cursor_after = range.start + text.chars().count();
 
         let edit = Edit {
@@ -1138,10 +4161,51 @@ impl CodeEditor {
     }
 
     fn undo(&mut self, ui: &mut egui::Ui) {
-        let Some(edit) = self.edit_stack.undo.pop() else {
+        if self.readonly {
+            return;
+        }
+        let Some(step) = self.edit_stack.undo.pop() else {
+            return;
+        };
+        match &step {
+            EditStep::Text(edit) => self.undo_text_edit(edit),
+            EditStep::LineEnding { before, .. } => self.restore_line_ending(*before),
+            EditStep::TextAndLineEnding { edit, before, .. } => {
+                self.undo_text_edit(edit);
+                self.restore_line_ending(*before);
+            }
+        }
+
+        self.edit_stack.redo.push(step);
+        self.cursor_blink_offset = ui.input(|i| i.time);
+    }
+
+    fn redo(&mut self, ui: &mut egui::Ui) {
+        if self.readonly {
+            return;
+        }
+        let Some(step) = self.edit_stack.redo.pop() else {
             return;
         };
+        match &step {
+            EditStep::Text(edit) => self.redo_text_edit(edit),
+            EditStep::LineEnding { after, .. } => self.restore_line_ending(*after),
+            EditStep::TextAndLineEnding { edit, after, .. } => {
+                self.redo_text_edit(edit);
+                self.restore_line_ending(*after);
+            }
+        }
+
+        self.edit_stack.undo.push(step);
+        self.cursor_blink_offset = ui.input(|i| i.time);
+    }
+
+    fn restore_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+        self.generation = self.generation.wrapping_add(1);
+    }
 
+    fn undo_text_edit(&mut self, edit: &Edit) {
         let revert_range = edit.range.start..(edit.range.start + edit.inserted.chars().count());
 
         self.doc.remove(revert_range.clone());
@@ -1150,50 +4214,64 @@ impl CodeEditor {
 
         self.update_cursor(edit.cursor_before);
         self.selection = edit.selection_before.clone();
+        self.block_selection = None;
         self.invalidate_layout();
-
-        self.edit_stack.redo.push(edit);
-        self.cursor_blink_offset = ui.input(|i| i.time);
     }
 
-    fn redo(&mut self, ui: &mut egui::Ui) {
-        let Some(edit) = self.edit_stack.redo.pop() else {
-            return;
-        };
-
+    fn redo_text_edit(&mut self, edit: &Edit) {
         self.doc.remove(edit.range.clone());
         self.doc.insert(edit.range.start, &edit.inserted);
         self.update_doc_hash();
 
         self.update_cursor(edit.cursor_after);
         self.selection = edit.selection_after.clone();
+        self.block_selection = None;
         self.invalidate_layout();
-
-        self.edit_stack.undo.push(edit);
-        self.cursor_blink_offset = ui.input(|i| i.time);
     }
 
+    /// Format the whole document as a single undoable edit.
     fn format(&mut self) {
-        let cursor_line = self.doc.char_to_line(self.cursor);
-        let line_start = self.doc.line_to_char(cursor_line);
-        let cursor_col = self.cursor - line_start;
+        if self.readonly {
+            return;
+        }
 
-        let source = self.doc.to_string();
-        let formatted = self.syntax.formatter.format(source);
+        let (cursor_line, cursor_col) = char_to_line_col(&self.doc, self.cursor);
 
-        self.doc = Rope::from_str(&formatted);
-        self.update_doc_hash();
+        let source = self.doc.to_string();
+        let formatted = self.syntax.formatter.format(source.clone());
+        if formatted == source {
+            return;
+        }
 
         // Restore cursor position as best we can
-        let new_line = cursor_line.min(self.doc.len_lines() - 1);
-        let line_len = self.doc.line(new_line).len_chars();
+        let formatted_doc = Rope::from_str(&formatted);
+        let new_line = cursor_line.min(formatted_doc.len_lines() - 1);
+        let line_len = line_len_without_newline(formatted_doc.line(new_line));
         let new_col = cursor_col.min(line_len);
 
-        self.update_cursor(self.doc.line_to_char(new_line) + new_col);
-        self.selection = None;
+        let edit = Edit {
+            range: 0..self.doc.len_chars(),
+            removed: source,
+            inserted: formatted,
+            cursor_before: self.cursor,
+            cursor_after: formatted_doc.line_to_char(new_line) + new_col,
+            selection_before: self.selection.clone(),
+            selection_after: None,
+        };
+        self.completion = None;
+        self.selection_anchor = None;
+        self.apply_edit(edit);
     }
 
     fn selected_text(&self) -> Option<String> {
+        if let Some(ranges) = &self.block_selection {
+            let rows: Vec<String> = ranges
+                .iter()
+                .map(|range| self.doc.slice(range.clone()).to_string())
+                .collect();
+            return Some(rows.join("\n"));
+        }
+
         self.selection
             .as_ref()
             .map(|range| self.doc.slice(range.clone()).to_string())
@@ -1205,11 +4283,27 @@ impl CodeEditor {
 
     fn format_token(&self, ty: TokenType) -> egui::text::TextFormat {
         let font_id = egui::FontId::monospace(self.fontsize);
-        let color = self.theme.type_color(ty);
+        let style = self.theme.type_style(ty);
+        let mut color = self.theme.type_color(ty);
+        if style.strong {
+            let contrast = if self.theme.is_dark() {
+                Color32::WHITE
+            } else {
+                Color32::BLACK
+            };
+            color = color.lerp_to_gamma(contrast, 0.35);
+        }
+
         egui::text::TextFormat {
             font_id,
             color,
             line_height: Some(self.fontsize * 1.25),
+            italics: style.italic,
+            underline: if style.underline {
+                egui::Stroke::new(1.0, color)
+            } else {
+                egui::Stroke::NONE
+            },
             ..Default::default()
         }
     }
@@ -1232,3 +4326,607 @@ impl Hash for CodeEditor {
         self.syntax.hash(state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMENT: &str = "// 你好 🎉 wörld\nfn main() {}\n";
+
+    fn editor(text: &str) -> CodeEditor {
+        CodeEditor::new(text, ColorTheme::GITHUB_DARK, Syntax::wgsl())
+    }
+
+    /// Run `f` with a `Ui` that has fonts loaded, outside of any window.
+    fn with_ui(f: impl FnOnce(&mut egui::Ui)) {
+        let mut f = Some(f);
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if let Some(f) = f.take() {
+                    f(ui);
+                }
+            });
+        });
+        assert!(f.is_none(), "the ui closure never ran");
+    }
+
+    #[test]
+    pub fn test_type_into_and_delete_to_empty_document() {
+        let mut editor = editor("");
+        assert_eq!(editor.doc.len_lines(), 1);
+
+        with_ui(|ui| {
+            editor.ui(ui, &KeyModifiers::default());
+
+            let font_id = egui::FontId::monospace(editor.fontsize);
+            let line_height = editor.line_height(ui, &font_id);
+            let rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(200.0, 200.0));
+            let click = egui::pos2(150.0, 100.0);
+            assert_eq!(
+                editor.pos_to_char_index(ui, &font_id, click, rect, 0.0, line_height, 0),
+                (0, 0)
+            );
+        });
+
+        editor.insert_text("a", 0.0);
+        editor.handle_enter(0.0);
+        assert_eq!(editor.doc.to_string(), "a\n");
+        assert_eq!(char_to_line_col(&editor.doc, editor.cursor), (1, 0));
+
+        editor.handle_backspace(false, 0.0);
+        editor.handle_backspace(false, 0.0);
+        assert_eq!(editor.doc.to_string(), "");
+        assert_eq!(editor.cursor, 0);
+
+        // Nothing left to delete
+        editor.handle_backspace(false, 0.0);
+        editor.handle_delete(false, 0.0);
+        assert_eq!(editor.doc.to_string(), "");
+        assert_eq!(editor.cursor, 0);
+
+        with_ui(|ui| {
+            editor.ui(ui, &KeyModifiers::default());
+        });
+    }
+
+    #[test]
+    pub fn test_relative_line_numbers() {
+        assert_eq!(displayed_line_number(LineNumbers::Relative, 7, 7), Some(8));
+        assert_eq!(displayed_line_number(LineNumbers::Relative, 4, 7), Some(3));
+        assert_eq!(displayed_line_number(LineNumbers::Relative, 10, 7), Some(3));
+        assert_eq!(displayed_line_number(LineNumbers::Hidden, 4, 7), None);
+
+        // Sized for the line count wherever the cursor is
+        assert_eq!(widest_line_number(LineNumbers::Relative, 200), Some(200));
+        assert_eq!(widest_line_number(LineNumbers::Absolute, 200), Some(200));
+        assert_eq!(widest_line_number(LineNumbers::Relative, 0), Some(1));
+        assert_eq!(widest_line_number(LineNumbers::Hidden, 200), None);
+    }
+
+    #[test]
+    pub fn test_move_and_duplicate_lines() {
+        let mut editor = editor("a\nbb\nc");
+        editor.cursor = 4;
+
+        editor.move_lines(true, 0.0);
+        assert_eq!(editor.doc.to_string(), "bb\na\nc");
+        assert_eq!(editor.cursor, 2);
+
+        // Already on the first line
+        editor.move_lines(true, 0.0);
+        assert_eq!(editor.doc.to_string(), "bb\na\nc");
+
+        editor.move_lines(false, 0.0);
+        editor.move_lines(false, 0.0);
+        assert_eq!(editor.doc.to_string(), "a\nc\nbb");
+        assert_eq!(editor.cursor, 6);
+
+        editor.duplicate_lines(true, 0.0);
+        assert_eq!(editor.doc.to_string(), "a\nc\nbb\nbb");
+        assert_eq!(editor.cursor, 6);
+
+        editor.duplicate_lines(false, 0.0);
+        assert_eq!(editor.doc.to_string(), "a\nc\nbb\nbb\nbb");
+        assert_eq!(editor.cursor, 9);
+    }
+
+    #[test]
+    pub fn test_delete_words() {
+        let doc = Rope::from_str("let value = foo(a);\n  next");
+        assert_eq!(next_word_boundary(&doc, 9, false), 4);
+        assert_eq!(next_word_boundary(&doc, 4, true), 9);
+        assert_eq!(next_word_boundary(&doc, 9, true), 11);
+        assert_eq!(next_word_boundary(&doc, 15, true), 16);
+        assert_eq!(next_word_boundary(&doc, 19, true), 20);
+        assert_eq!(next_word_boundary(&doc, 22, false), 20);
+        assert_eq!(next_word_boundary(&doc, 20, false), 19);
+
+        let mut editor = editor("let value = foo(a);");
+        editor.cursor = 9;
+        editor.handle_backspace(true, 0.0);
+        assert_eq!(editor.doc.to_string(), "let  = foo(a);");
+        assert_eq!(editor.cursor, 4);
+
+        editor.handle_delete(true, 0.0);
+        assert_eq!(editor.doc.to_string(), "let  foo(a);");
+
+        with_ui(|ui| editor.undo(ui));
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.doc.to_string(), "let value = foo(a);");
+        assert_eq!(editor.cursor, 9);
+    }
+
+    #[test]
+    pub fn test_indent_lines() {
+        let mut editor = editor("a\n\n  b\nc");
+        editor.selection = Some(0..5);
+        editor.cursor = 5;
+
+        editor.handle_tab(false, 0.0);
+        assert_eq!(editor.doc.to_string(), "    a\n\n      b\nc");
+        assert_eq!(editor.selection, Some(0..14));
+
+        editor.handle_tab(true, 0.0);
+        editor.handle_tab(true, 0.0);
+        assert_eq!(editor.doc.to_string(), "a\n\nb\nc");
+        assert_eq!(editor.selection, Some(0..4));
+
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.doc.to_string(), "a\n\n  b\nc");
+    }
+
+    #[test]
+    pub fn test_toggle_comment() {
+        let mut editor = editor("fn a() {\n    let b = 1;\n\n  // c\n}");
+        editor.selection = Some(9..27);
+        editor.cursor = 27;
+
+        editor.toggle_comment(0.0);
+        assert_eq!(
+            editor.doc.to_string(),
+            "fn a() {\n  //   let b = 1;\n\n  // // c\n}"
+        );
+        assert_eq!(editor.selection, Some(9..37));
+
+        editor.toggle_comment(0.0);
+        assert_eq!(
+            editor.doc.to_string(),
+            "fn a() {\n    let b = 1;\n\n  // c\n}"
+        );
+
+        // Only the cursor line, the cursor stays on the same character
+        editor.selection = None;
+        editor.cursor = 2;
+        editor.toggle_comment(0.0);
+        assert_eq!(editor.doc.line(0).to_string(), "// fn a() {\n");
+        assert_eq!(editor.cursor, 5);
+    }
+
+    #[test]
+    pub fn test_matching_bracket() {
+        let doc = Rope::from_str("fn a(b: vec2<f32>) { c[(1)]; }");
+        assert_eq!(matching_bracket(&doc, 4), Some((4, 17)));
+        assert_eq!(matching_bracket(&doc, 18), Some((17, 4)));
+        assert_eq!(matching_bracket(&doc, 22), Some((22, 26)));
+        assert_eq!(matching_bracket(&doc, 24), Some((23, 25)));
+        assert_eq!(matching_bracket(&doc, 1), None);
+
+        // Unbalanced
+        let doc = Rope::from_str("{ ( }");
+        assert_eq!(matching_bracket(&doc, 2), None);
+        assert_eq!(matching_bracket(&doc, 0), Some((0, 4)));
+    }
+
+    #[test]
+    pub fn test_undo_format() {
+        let source = "fn main() {\nlet a = 1;\n}\n";
+        let mut editor = editor(source);
+        editor.cursor = 14;
+
+        editor.format();
+        let formatted = editor.doc.to_string();
+        assert_ne!(formatted, source);
+
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.doc.to_string(), source);
+        assert_eq!(editor.cursor, 14);
+
+        with_ui(|ui| editor.redo(ui));
+        assert_eq!(editor.doc.to_string(), formatted);
+    }
+
+    #[test]
+    pub fn test_undo_line_ending() {
+        let mut editor = editor("a\r\nb\r\n");
+        assert_eq!(editor.doc.to_string(), "a\nb\n");
+        assert_eq!(editor.line_ending(), LineEnding::CrLf);
+
+        editor.set_line_ending(LineEnding::Lf);
+        assert_eq!(editor.line_ending(), LineEnding::Lf);
+
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.line_ending(), LineEnding::CrLf);
+
+        with_ui(|ui| editor.redo(ui));
+        assert_eq!(editor.line_ending(), LineEnding::Lf);
+        assert_eq!(editor.doc.to_string(), "a\nb\n");
+
+        // Replacing the text and line endings together is undone in one step
+        editor.set_text_and_line_ending("c\n", LineEnding::CrLf);
+        assert_eq!(editor.doc.to_string(), "c\n");
+        assert_eq!(editor.line_ending(), LineEnding::CrLf);
+
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.doc.to_string(), "a\nb\n");
+        assert_eq!(editor.line_ending(), LineEnding::Lf);
+
+        with_ui(|ui| editor.redo(ui));
+        assert_eq!(editor.doc.to_string(), "c\n");
+        assert_eq!(editor.line_ending(), LineEnding::CrLf);
+    }
+
+    #[test]
+    pub fn test_overwrite_mode() {
+        let mut editor = editor("abc\nd");
+        editor.overwrite = true;
+        editor.cursor = 1;
+
+        editor.overwrite_text("xy", 0.0);
+        assert_eq!(editor.doc.to_string(), "axy\nd");
+        assert_eq!(editor.cursor, 3);
+
+        // Past the end of the line it inserts instead of eating the newline
+        editor.overwrite_text("zw", 0.0);
+        assert_eq!(editor.doc.to_string(), "axyzw\nd");
+
+        with_ui(|ui| editor.undo(ui));
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.doc.to_string(), "abc\nd");
+        assert_eq!(editor.cursor, 1);
+    }
+
+    #[test]
+    pub fn test_block_selection() {
+        let mut editor = editor("abc\nde\nfghi");
+        let ranges = block_ranges(&editor.doc, (0, 1), (2, 3));
+        assert_eq!(ranges, vec![1..3, 5..6, 8..10]);
+        assert_eq!(block_ranges(&editor.doc, (2, 3), (0, 1)), ranges);
+
+        editor.block_selection = Some(ranges);
+        assert_eq!(editor.selected_text().as_deref(), Some("bc\ne\ngh"));
+
+        let ranges = editor.block_selection.clone().unwrap();
+        editor.replace_block(
+            ranges
+                .into_iter()
+                .map(|range| (range, "X".into()))
+                .collect(),
+            0.0,
+        );
+        assert_eq!(editor.doc.to_string(), "aX\ndX\nfXi");
+        assert_eq!(editor.block_selection, Some(vec![2..2, 5..5, 8..8]));
+
+        editor.paste_block("1\n2\n3\n", 0.0);
+        assert_eq!(editor.doc.to_string(), "aX1\ndX2\nfX3i");
+
+        editor.delete_block(true, 0.0);
+        editor.delete_block(true, 0.0);
+        assert_eq!(editor.doc.to_string(), "a\nd\nfi");
+
+        // The short first row is already at the end
+        editor.delete_block(false, 0.0);
+        assert_eq!(editor.doc.to_string(), "a\nd\nf");
+
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.doc.to_string(), "a\nd\nfi");
+        assert_eq!(editor.block_selection, None);
+    }
+
+    #[test]
+    pub fn test_move_text() {
+        let mut editor = editor("hello big world");
+        editor.selection = Some(0..6);
+
+        // Dropping inside the selection does nothing
+        editor.move_text(0..6, 3, 0.0);
+        assert_eq!(editor.doc.to_string(), "hello big world");
+
+        editor.move_text(0..6, 10, 0.0);
+        assert_eq!(editor.doc.to_string(), "big hello world");
+        assert_eq!(editor.selection, Some(4..10));
+        assert_eq!(editor.cursor, 10);
+
+        editor.move_text(10..15, 0, 0.0);
+        assert_eq!(editor.doc.to_string(), "worldbig hello ");
+        assert_eq!(editor.selection, Some(0..5));
+
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.doc.to_string(), "big hello world");
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.doc.to_string(), "hello big world");
+        assert_eq!(editor.selection, Some(0..6));
+    }
+
+    #[test]
+    pub fn test_on_change() {
+        let changes = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut editor = editor("ab");
+        let changes_clone = changes.clone();
+        editor.set_on_change(move |doc| changes_clone.borrow_mut().push(doc.to_string()));
+
+        editor.cursor = 2;
+        editor.insert_text("c", 0.0);
+        with_ui(|ui| editor.undo(ui));
+        with_ui(|ui| editor.redo(ui));
+
+        // Replacing text with the same text isn't a change
+        editor.selection = Some(0..1);
+        editor.insert_text("a", 0.0);
+
+        assert_eq!(*changes.borrow(), ["abc", "ab", "abc"]);
+    }
+
+    #[test]
+    pub fn test_tab_stops() {
+        assert_eq!(visual_column("a\tb".chars(), 4), 5);
+        assert_eq!(visual_column("abcd\t".chars(), 4), 8);
+        assert_eq!(visual_column("\t\t".chars(), 2), 4);
+
+        let mut job = egui::text::LayoutJob::default();
+        job.append("ab\tc", 0.0, Default::default());
+        job.append("\t", 0.0, Default::default());
+        job.append("d", 0.0, Default::default());
+        align_tab_stops(&mut job, 4, 10.0);
+        let sections: Vec<_> = job
+            .sections
+            .iter()
+            .map(|section| (&job.text[section.byte_range.clone()], section.leading_space))
+            .collect();
+        // Egui draws a tab four spaces wide, the tab after "ab" only spans two columns
+        assert_eq!(
+            sections,
+            vec![("ab\t", 0.0), ("c", -20.0), ("\t", 0.0), ("d", -10.0)]
+        );
+
+        let mut editor = editor("\tab");
+        editor.set_use_spaces(true);
+        editor.cursor = 2;
+        editor.handle_tab(false, 0.0);
+        assert_eq!(editor.doc.to_string(), "\ta   b");
+    }
+
+    #[test]
+    pub fn test_home_end() {
+        let mut editor = editor("fn main() {\n    let a = 1;\n}");
+        editor.cursor = 20;
+
+        editor.handle_home(false, 0.0);
+        assert_eq!(editor.cursor, 16);
+        editor.handle_home(false, 0.0);
+        assert_eq!(editor.cursor, 12);
+        editor.handle_home(false, 0.0);
+        assert_eq!(editor.cursor, 16);
+
+        editor.handle_end(true, 0.0);
+        assert_eq!(editor.cursor, 26);
+        assert_eq!(editor.selection, Some(16..26));
+        editor.handle_home(true, 0.0);
+        assert_eq!(editor.selection, None);
+        editor.handle_home(true, 0.0);
+        assert_eq!(editor.selection, Some(12..16));
+
+        editor.handle_end(false, 0.0);
+        assert_eq!(editor.cursor, 26);
+        assert_eq!(editor.selection, None);
+    }
+
+    #[test]
+    pub fn test_restore_view_state() {
+        let mut editor = editor("fn main() {}\n");
+        editor.restore_view_state(EditorViewState {
+            cursor: 100,
+            selection: Some(3..100),
+            scroll_offset: 40.0,
+        });
+        assert_eq!(editor.cursor, 13);
+        assert_eq!(editor.selection, Some(3..13));
+
+        with_ui(|ui| {
+            editor.ui(ui, &KeyModifiers::default());
+        });
+        assert_eq!(editor.restore_scroll_offset, None);
+        assert_eq!(editor.view_state().cursor, 13);
+    }
+
+    #[test]
+    pub fn test_goto_line() {
+        let mut editor = editor("fn main() {\n    \tlet a = 1;\n}");
+        editor.selection = Some(0..2);
+
+        editor.goto_line(1);
+        assert_eq!(editor.cursor, 17);
+        assert_eq!(editor.selection, None);
+
+        editor.goto_line(100);
+        assert_eq!(char_to_line_col(&editor.doc, editor.cursor), (2, 0));
+    }
+
+    #[test]
+    pub fn test_find_and_replace() {
+        let mut editor = editor("let a = A;\nlet b = a + aa;");
+        assert_eq!(
+            find_matches(&editor.doc, "a", true),
+            vec![4..5, 19..20, 23..24, 24..25]
+        );
+        assert_eq!(find_matches(&editor.doc, "AA", false), vec![23..25]);
+        assert!(find_matches(&editor.doc, "", false).is_empty());
+
+        editor.search = Some(SearchState::new("a".to_string()));
+        let search = editor.search.as_mut().unwrap();
+        search.replace = "x".to_string();
+        search.case_sensitive = true;
+
+        // The first replace only selects the match after the cursor
+        editor.replace_search_match(0.0);
+        assert_eq!(editor.selection, Some(4..5));
+        editor.replace_search_match(0.0);
+        assert_eq!(editor.doc.to_string(), "let x = A;\nlet b = a + aa;");
+        assert_eq!(editor.selection, Some(19..20));
+
+        editor.replace_all_search_matches(0.0);
+        assert_eq!(editor.doc.to_string(), "let x = A;\nlet b = x + xx;");
+        assert!(editor.search_matches().is_empty());
+
+        with_ui(|ui| editor.undo(ui));
+        assert_eq!(editor.doc.to_string(), "let x = A;\nlet b = a + aa;");
+        assert_eq!(editor.search_matches().len(), 0);
+        editor.update_search();
+        assert_eq!(editor.search_matches().len(), 3);
+    }
+
+    #[test]
+    pub fn test_search_matches_follow_edits() {
+        let mut editor = editor("let a = 1;\nlet b = a;");
+        editor.search = Some(SearchState::new("a".to_string()));
+        editor.update_search();
+        assert_eq!(editor.search_matches(), &[4..5, 19..20]);
+
+        editor.insert_text("// ", 0.0);
+        editor.update_search();
+        assert_eq!(editor.search_matches(), &[7..8, 22..23]);
+
+        // Matches set from outside can't be found again, they're dropped instead
+        editor.search = None;
+        editor.set_search_matches(vec![22..23, 7..8]);
+        editor.update_search();
+        assert_eq!(editor.search_matches(), &[7..8, 22..23]);
+        editor.insert_text(" ", 0.0);
+        editor.update_search();
+        assert!(editor.search_matches().is_empty());
+    }
+
+    #[test]
+    pub fn test_line_col_with_multi_byte_chars() {
+        let doc = Rope::from_str(COMMENT);
+
+        assert_eq!(line_len_without_newline(doc.line(0)), 13);
+        assert_eq!(char_to_line_col(&doc, 7), (0, 7));
+        assert_eq!(char_to_line_col(&doc, 14), (1, 0));
+        assert_eq!(doc.line(0).slice(..6).to_string(), "// 你好 ");
+    }
+
+    #[test]
+    pub fn test_insert_navigate_delete_multi_byte_chars() {
+        let mut editor = editor("// \nfn\n");
+        editor.cursor = 3;
+
+        editor.insert_text("你好🎉", 0.0);
+        assert_eq!(editor.doc.to_string(), "// 你好🎉\nfn\n");
+        assert_eq!(editor.cursor, 6);
+
+        editor.handle_arrow_left(0.0);
+        editor.handle_backspace(false, 0.0);
+        assert_eq!(editor.doc.to_string(), "// 你🎉\nfn\n");
+        assert_eq!(editor.cursor, 4);
+
+        editor.handle_delete(false, 0.0);
+        assert_eq!(editor.doc.to_string(), "// 你\nfn\n");
+
+        // Stepping past the end of the line lands on the start of the next one
+        editor.handle_arrow_right(0.0);
+        assert_eq!(char_to_line_col(&editor.doc, editor.cursor), (1, 0));
+
+        editor.handle_arrow_up(0.0);
+        assert_eq!(char_to_line_col(&editor.doc, editor.cursor), (0, 0));
+    }
+
+    #[test]
+    pub fn test_column_positions_with_multi_byte_chars() {
+        let editor = editor(COMMENT);
+
+        with_ui(|ui| {
+            let font_id = egui::FontId::monospace(editor.fontsize);
+            let line_height = 20.0;
+            let visible_rect =
+                egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(1000.0, 1000.0));
+            let max_col = line_len_without_newline(editor.doc.line(0));
+
+            let mut previous_x = -1.0;
+            for col in 0..=max_col {
+                let (x, _) = editor.char_pos(ui, &font_id, 0, col);
+                assert!(
+                    x > previous_x,
+                    "column {col} is not right of the previous one"
+                );
+                previous_x = x;
+
+                // Clicking just right of where a column starts puts the cursor on it
+                let pos = egui::pos2(x + 1.0, line_height * 0.5);
+                let (char_idx, clicked_col) =
+                    editor.pos_to_char_index(ui, &font_id, pos, visible_rect, 0.0, line_height, 0);
+                assert_eq!(clicked_col, col);
+                assert_eq!(char_idx, col);
+            }
+        });
+    }
+
+    #[test]
+    pub fn test_wrapped_rows() {
+        let mut editor = editor("short\nthis line is long enough to wrap a few times\nend");
+        editor.wrap = true;
+
+        with_ui(|ui| {
+            let font_id = egui::FontId::monospace(editor.fontsize);
+            let char_width = editor.measure_text_width(ui, &font_id, "0");
+            editor.update_wrap_width(char_width * 12.5);
+            editor.ensure_line_rows(ui, &font_id);
+
+            let wrapped_rows = editor.line_row(2) - editor.line_row(1);
+            assert!(wrapped_rows >= 3, "only wrapped into {wrapped_rows} rows");
+            assert_eq!(editor.line_row(3), editor.line_row(2) + 1);
+            assert_eq!(editor.row_line(editor.line_row(2) - 1), 1);
+            assert_eq!(editor.row_line(editor.line_row(2)), 2);
+
+            // Every column of the wrapped line can be clicked back into where it is drawn
+            let line_height = 20.0;
+            let visible_rect =
+                egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(1000.0, 1000.0));
+            let line_start = editor.doc.line_to_char(1);
+            let mut rows = Vec::new();
+            for col in 0..line_len_without_newline(editor.doc.line(1)) {
+                let (x, row) = editor.char_pos(ui, &font_id, 1, col);
+                rows.push(row);
+
+                let pos = egui::pos2(x + 1.0, (row as f32 + 0.5) * line_height);
+                let (char_idx, clicked_col) =
+                    editor.pos_to_char_index(ui, &font_id, pos, visible_rect, 0.0, line_height, 0);
+                assert_eq!(clicked_col, col);
+                assert_eq!(char_idx, line_start + col);
+            }
+            assert_eq!(rows.first(), Some(&1));
+            assert_eq!(rows.last(), Some(&(editor.line_row(2) - 1)));
+        });
+    }
+
+    #[test]
+    pub fn test_editor_width_of_large_file_follows_tab_stops() {
+        let text = format!("{}ab\tx\n", "x\n".repeat(LARGE_FILE_LINES));
+        let mut editor = editor(&text);
+        assert!(editor.is_large_file());
+
+        with_ui(|ui| {
+            let font_id = egui::FontId::monospace(editor.fontsize);
+            let column_width = editor.measure_text_width(ui, &font_id, "0");
+
+            // Estimated from the widest visual column instead of laying out every line
+            editor.set_tab_width(8);
+            editor.calculate_editor_width(ui, &font_id);
+            assert_eq!(editor.max_line_width, Some(9.0 * column_width));
+
+            editor.set_tab_width(2);
+            editor.calculate_editor_width(ui, &font_id);
+            assert_eq!(editor.max_line_width, Some(5.0 * column_width));
+        });
+    }
+}
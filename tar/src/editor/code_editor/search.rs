@@ -0,0 +1,73 @@
+use std::ops::Range;
+
+use ropey::Rope;
+
+/// State of the find/replace panel, only present while the panel is open.
+#[derive(Debug, Default)]
+pub struct SearchState {
+    pub query: String,
+    pub replace: String,
+    pub case_sensitive: bool,
+    /// Index of the match that is currently selected in the editor.
+    pub current: Option<usize>,
+    /// Query, case sensitivity and document generation the matches were last found for.
+    searched: Option<(String, bool, u64)>,
+    /// Focus the query field on the next frame.
+    pub focus_query: bool,
+}
+
+impl SearchState {
+    pub fn new(query: String) -> Self {
+        Self {
+            query,
+            focus_query: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the matches have to be searched again for the document at `generation`,
+    /// see [`CodeEditor::generation`](super::CodeEditor::generation).
+    pub fn is_outdated(&self, generation: u64) -> bool {
+        self.searched
+            .as_ref()
+            .is_none_or(|(query, case_sensitive, searched_generation)| {
+                *query != self.query
+                    || *case_sensitive != self.case_sensitive
+                    || *searched_generation != generation
+            })
+    }
+
+    pub fn mark_searched(&mut self, generation: u64) {
+        self.searched = Some((self.query.clone(), self.case_sensitive, generation));
+    }
+}
+
+/// Char ranges of all non-overlapping occurrences of `query` in `doc`, in document order.
+pub fn find_matches(doc: &Rope, query: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+    // Fold per char so indices into the folded text stay char indices into the document
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+
+    let query: Vec<char> = query.chars().map(fold).collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let text: Vec<char> = doc.chars().map(fold).collect();
+
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + query.len() <= text.len() {
+        if text[i..i + query.len()] == query[..] {
+            matches.push(i..i + query.len());
+            i += query.len();
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator as _;
+
+use super::themes::{ColorTheme, DEFAULT_THEMES};
+
+/// What the gutter shows next to each line.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+pub enum LineNumbers {
+    #[default]
+    Absolute,
+    /// Distance from the cursor line, the cursor line itself shows its absolute number.
+    Relative,
+    /// No gutter at all.
+    Hidden,
+}
+
+/// Code editor preferences, stored with the project and applied to every editor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorSettings {
+    pub tab_width: usize,
+    pub use_spaces: bool,
+    pub font_size: f32,
+    /// Name of one of the [`DEFAULT_THEMES`].
+    pub theme: String,
+    /// Path to a theme file, used instead of `theme` when not empty.
+    pub theme_file: String,
+    pub show_whitespace: bool,
+    pub word_wrap: bool,
+    pub line_numbers: LineNumbers,
+    pub auto_close: bool,
+    /// Re-indent pasted multi-line text to the cursor line, off to paste it as is.
+    pub paste_reindent: bool,
+    /// Keep the header of the enclosing function or struct at the top of the editor.
+    pub sticky_scroll: bool,
+    /// Scroll with one finger on touch screens, long press to select.
+    pub single_finger_scroll: bool,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            use_spaces: true,
+            font_size: 14.0,
+            theme: ColorTheme::GITHUB_DARK.name().to_string(),
+            theme_file: String::new(),
+            show_whitespace: false,
+            word_wrap: cfg!(target_os = "android"),
+            line_numbers: LineNumbers::default(),
+            auto_close: true,
+            paste_reindent: false,
+            sticky_scroll: false,
+            single_finger_scroll: cfg!(target_os = "android"),
+        }
+    }
+}
+
+impl EditorSettings {
+    /// The configured theme, falls back to Github Dark for unknown names.
+    pub fn color_theme(&self) -> ColorTheme {
+        if !self.theme_file.is_empty() {
+            match ColorTheme::load(Path::new(&self.theme_file)) {
+                Ok(theme) => return theme,
+                Err(e) => log::warn!("{:#}", e),
+            }
+        }
+
+        DEFAULT_THEMES
+            .into_iter()
+            .find(|theme| theme.name() == self.theme)
+            .unwrap_or(ColorTheme::GITHUB_DARK)
+    }
+
+    /// Draw widgets for all settings, returns whether any of them changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+
+        egui::Grid::new("editor_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Theme");
+                egui::ComboBox::from_id_salt("editor_settings_theme")
+                    .selected_text(&self.theme)
+                    .show_ui(ui, |ui| {
+                        for theme in DEFAULT_THEMES {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.theme,
+                                    theme.name().to_string(),
+                                    theme.name(),
+                                )
+                                .changed();
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Theme file")
+                    .on_hover_text("Path to a theme file, used instead of the theme above");
+                // Only reload once the path is done being typed
+                changed |= ui.text_edit_singleline(&mut self.theme_file).lost_focus();
+                ui.end_row();
+
+                ui.label("Font size");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut self.font_size).range(6.0..=48.0))
+                    .changed();
+                ui.end_row();
+
+                ui.label("Tab width");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut self.tab_width).range(1..=16))
+                    .changed();
+                ui.end_row();
+
+                ui.label("Indent with spaces");
+                changed |= ui.checkbox(&mut self.use_spaces, "").changed();
+                ui.end_row();
+
+                ui.label("Show whitespace");
+                changed |= ui.checkbox(&mut self.show_whitespace, "").changed();
+                ui.end_row();
+
+                ui.label("Word wrap");
+                changed |= ui.checkbox(&mut self.word_wrap, "").changed();
+                ui.end_row();
+
+                ui.label("Line numbers");
+                egui::ComboBox::from_id_salt("editor_settings_line_numbers")
+                    .selected_text(self.line_numbers.to_string())
+                    .show_ui(ui, |ui| {
+                        for line_numbers in LineNumbers::iter() {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.line_numbers,
+                                    line_numbers,
+                                    line_numbers.to_string(),
+                                )
+                                .changed();
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Auto close brackets");
+                changed |= ui.checkbox(&mut self.auto_close, "").changed();
+                ui.end_row();
+
+                ui.label("Re-indent on paste")
+                    .on_hover_text("Indent pasted lines like the line they are pasted into");
+                changed |= ui.checkbox(&mut self.paste_reindent, "").changed();
+                ui.end_row();
+
+                ui.label("Sticky scroll").on_hover_text(
+                    "Show the header of the function or struct scrolled past at the top",
+                );
+                changed |= ui.checkbox(&mut self.sticky_scroll, "").changed();
+                ui.end_row();
+
+                ui.label("Single finger scroll")
+                    .on_hover_text("Scroll with one finger on touch screens, long press to select");
+                changed |= ui.checkbox(&mut self.single_finger_scroll, "").changed();
+                ui.end_row();
+            });
+
+        changed
+    }
+}
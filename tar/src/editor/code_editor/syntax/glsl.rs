@@ -0,0 +1,128 @@
+use crate::editor::code_editor::syntax::SyntaxFormatter;
+
+use super::Syntax;
+use std::collections::BTreeSet;
+
+struct Formatter;
+impl SyntaxFormatter for Formatter {
+    fn format(&self, source: String) -> String {
+        source
+    }
+}
+
+impl Syntax {
+    pub fn glsl() -> Self {
+        Syntax {
+            language: "Glsl",
+            case_sensitive: true,
+            comment: "//",
+            comment_multiline: ["/*", "*/"],
+            hyperlinks: BTreeSet::from(["http"]),
+            keywords: BTreeSet::from([
+                // Preprocessor
+                "#define",
+                "#elif",
+                "#else",
+                "#endif",
+                "#error",
+                "#extension",
+                "#if",
+                "#ifdef",
+                "#ifndef",
+                "#include",
+                "#line",
+                "#pragma",
+                "#undef",
+                "#version",
+                // Qualifiers
+                "attribute",
+                "buffer",
+                "centroid",
+                "coherent",
+                "const",
+                "flat",
+                "highp",
+                "in",
+                "inout",
+                "invariant",
+                "layout",
+                "lowp",
+                "mediump",
+                "noperspective",
+                "out",
+                "precision",
+                "readonly",
+                "restrict",
+                "shared",
+                "smooth",
+                "uniform",
+                "varying",
+                "volatile",
+                "writeonly",
+                // Control flow
+                "break",
+                "case",
+                "continue",
+                "default",
+                "discard",
+                "do",
+                "else",
+                "for",
+                "if",
+                "return",
+                "struct",
+                "switch",
+                "while",
+            ]),
+            types: BTreeSet::from([
+                "void",
+                "bool",
+                "int",
+                "uint",
+                "float",
+                "double",
+                "vec2",
+                "vec3",
+                "vec4",
+                "ivec2",
+                "ivec3",
+                "ivec4",
+                "uvec2",
+                "uvec3",
+                "uvec4",
+                "bvec2",
+                "bvec3",
+                "bvec4",
+                "dvec2",
+                "dvec3",
+                "dvec4",
+                "mat2",
+                "mat3",
+                "mat4",
+                "mat2x2",
+                "mat2x3",
+                "mat2x4",
+                "mat3x2",
+                "mat3x3",
+                "mat3x4",
+                "mat4x2",
+                "mat4x3",
+                "mat4x4",
+                "sampler1D",
+                "sampler2D",
+                "sampler3D",
+                "samplerCube",
+                "sampler2DArray",
+                "sampler2DShadow",
+                "isampler2D",
+                "usampler2D",
+                "image2D",
+                "image3D",
+                "iimage2D",
+                "uimage2D",
+            ]),
+            special: BTreeSet::from(["true", "false"]),
+            formatter: Box::new(Formatter),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod glsl;
 pub mod rust;
 pub mod wgsl;
 
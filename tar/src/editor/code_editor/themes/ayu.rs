@@ -1,4 +1,4 @@
-use super::ColorTheme;
+use super::{ColorTheme, TokenStyles};
 
 impl ColorTheme {
     /// Author: André Sá <enkodr@outlook.com>
@@ -19,6 +19,7 @@ impl ColorTheme {
         strs: "#86b300",        // green
         types: "#399ee6",       // blue
         special: "#f07171",     // red
+        styles: TokenStyles::PLAIN,
     };
 
     pub const AYU_MIRAGE: ColorTheme = ColorTheme {
@@ -36,6 +37,7 @@ impl ColorTheme {
         strs: "#d5ff80",        // green
         types: "#73d0ff",       // blue
         special: "#f28779",     // red
+        styles: TokenStyles::PLAIN,
     };
 
     pub const AYU_DARK: ColorTheme = ColorTheme {
@@ -53,5 +55,6 @@ impl ColorTheme {
         strs: "#aad94c",        // green
         types: "#59c2ff",       // blue
         special: "#f28779",     // red
+        styles: TokenStyles::PLAIN,
     };
 }
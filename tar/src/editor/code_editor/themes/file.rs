@@ -0,0 +1,253 @@
+use std::{collections::BTreeSet, path::Path, sync::Mutex};
+
+use anyhow::{anyhow, bail, Context};
+
+use super::{ColorTheme, TokenStyle};
+
+/// Theme colors are `&'static str` so the built-in themes can be constants, strings read
+/// from a theme file are leaked once and reused when the file is loaded again.
+fn intern(s: &str) -> &'static str {
+    static INTERNED: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+    let mut interned = INTERNED.lock().unwrap();
+    if let Some(s) = interned.get(s) {
+        return s;
+    }
+    let s: &'static str = Box::leak(s.to_owned().into_boxed_str());
+    interned.insert(s);
+    s
+}
+
+fn is_hex_color(s: &str) -> bool {
+    s == "none"
+        || (s.len() == 7 && s.starts_with('#') && s[1..].chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn parse_style(value: &str) -> anyhow::Result<TokenStyle> {
+    let mut style = TokenStyle::PLAIN;
+    for hint in value.split_whitespace() {
+        match hint {
+            "italic" => style.italic = true,
+            "underline" => style.underline = true,
+            "strong" => style.strong = true,
+            _ => bail!(
+                "Unknown style `{}`, expected italic, underline or strong",
+                hint
+            ),
+        }
+    }
+    Ok(style)
+}
+
+fn style_to_str(style: TokenStyle) -> String {
+    [
+        (style.italic, "italic"),
+        (style.underline, "underline"),
+        (style.strong, "strong"),
+    ]
+    .into_iter()
+    .filter_map(|(set, hint)| set.then_some(hint))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+fn parse_str(value: &str) -> anyhow::Result<&str> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .filter(|value| !value.contains('"'))
+        .ok_or_else(|| anyhow!("Expected a quoted string, found `{}`", value))
+}
+
+/// Drop a `#` comment, hex colors are quoted so a `#` inside quotes is kept.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+impl ColorTheme {
+    fn colors(&self) -> [(&'static str, &'static str); 12] {
+        [
+            ("bg", self.bg),
+            ("cursor", self.cursor),
+            ("selection", self.selection),
+            ("comments", self.comments),
+            ("functions", self.functions),
+            ("keywords", self.keywords),
+            ("literals", self.literals),
+            ("numerics", self.numerics),
+            ("punctuation", self.punctuation),
+            ("strs", self.strs),
+            ("types", self.types),
+            ("special", self.special),
+        ]
+    }
+
+    fn color_mut(&mut self, key: &str) -> Option<&mut &'static str> {
+        Some(match key {
+            "bg" => &mut self.bg,
+            "cursor" => &mut self.cursor,
+            "selection" => &mut self.selection,
+            "comments" => &mut self.comments,
+            "functions" => &mut self.functions,
+            "keywords" => &mut self.keywords,
+            "literals" => &mut self.literals,
+            "numerics" => &mut self.numerics,
+            "punctuation" => &mut self.punctuation,
+            "strs" => &mut self.strs,
+            "types" => &mut self.types,
+            "special" => &mut self.special,
+            _ => return None,
+        })
+    }
+
+    fn styles(&self) -> [(&'static str, TokenStyle); 9] {
+        [
+            ("comments", self.styles.comments),
+            ("functions", self.styles.functions),
+            ("keywords", self.styles.keywords),
+            ("literals", self.styles.literals),
+            ("numerics", self.styles.numerics),
+            ("punctuation", self.styles.punctuation),
+            ("strs", self.styles.strs),
+            ("types", self.styles.types),
+            ("special", self.styles.special),
+        ]
+    }
+
+    fn style_mut(&mut self, token: &str) -> Option<&mut TokenStyle> {
+        Some(match token {
+            "comments" => &mut self.styles.comments,
+            "functions" => &mut self.styles.functions,
+            "keywords" => &mut self.styles.keywords,
+            "literals" => &mut self.styles.literals,
+            "numerics" => &mut self.styles.numerics,
+            "punctuation" => &mut self.styles.punctuation,
+            "strs" => &mut self.styles.strs,
+            "types" => &mut self.styles.types,
+            "special" => &mut self.styles.special,
+            _ => return None,
+        })
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key {
+            "name" => self.name = intern(parse_str(value)?),
+            "dark" => {
+                self.dark = value
+                    .parse()
+                    .map_err(|_| anyhow!("Expected true or false, found `{}`", value))?
+            }
+            _ if key.ends_with("_style") => {
+                let Some(style) = self.style_mut(key.trim_end_matches("_style")) else {
+                    bail!("Unknown key `{}`", key);
+                };
+                *style = parse_style(parse_str(value)?)?;
+            }
+            _ => {
+                let Some(color) = self.color_mut(key) else {
+                    bail!("Unknown key `{}`", key);
+                };
+                let hex = parse_str(value)?;
+                if !is_hex_color(hex) {
+                    bail!("`{}` is not a color like \"#rrggbb\"", hex);
+                }
+                *color = intern(hex);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse a theme from flat `key = "value"` lines with a `#rrggbb` color for the
+    /// background, cursor, selection and every token type. Colors missing from the file
+    /// are taken from [`ColorTheme::GITHUB_DARK`]. Token types can also get a style like
+    /// `comments_style = "italic"`, combining `italic`, `underline` and `strong`.
+    pub fn from_toml(src: &str) -> anyhow::Result<Self> {
+        let mut theme = ColorTheme {
+            name: "Custom",
+            ..ColorTheme::GITHUB_DARK
+        };
+
+        let mut seen = BTreeSet::new();
+        for (i, line) in src.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parse_line = || {
+                if line.starts_with('[') {
+                    bail!("Tables are not supported, keys go at the top level");
+                }
+                let (key, value) = line
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Expected `key = value`"))?;
+                let key = key.trim();
+                if !seen.insert(key.to_owned()) {
+                    bail!("`{}` is set twice", key);
+                }
+                theme.set(key, value.trim())
+            };
+            parse_line().with_context(|| format!("Invalid theme on line {}", i + 1))?;
+        }
+
+        Ok(theme)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut toml = format!("name = \"{}\"\ndark = {}\n\n", self.name, self.dark);
+        for (key, color) in self.colors() {
+            toml.push_str(&format!("{} = \"{}\"\n", key, color));
+        }
+        for (key, style) in self.styles() {
+            if style != TokenStyle::PLAIN {
+                toml.push_str(&format!("{}_style = \"{}\"\n", key, style_to_str(style)));
+            }
+        }
+        toml
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let src = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read theme {}", path.display()))?;
+        Self::from_toml(&src).with_context(|| format!("Failed to load theme {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_toml() {
+        let theme = ColorTheme::from_toml(&ColorTheme::GRUVBOX.to_toml()).unwrap();
+        assert_eq!(theme, ColorTheme::GRUVBOX);
+
+        let src = "# Dracula\nname = \"Dracula\"\ndark = true\nbg = \"#282a36\" # background\n";
+        let theme = ColorTheme::from_toml(src).unwrap();
+        assert_eq!(theme.name, "Dracula");
+        assert_eq!(theme.bg, "#282a36");
+        assert_eq!(theme.keywords, ColorTheme::GITHUB_DARK.keywords);
+
+        let error =
+            ColorTheme::from_toml("bg = \"#282a36\"\nforeground = \"#f8f8f2\"").unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "Invalid theme on line 2: Unknown key `foreground`"
+        );
+        assert!(ColorTheme::from_toml("bg = \"#28\"").is_err());
+
+        let theme = ColorTheme::from_toml("comments_style = \"italic strong\"").unwrap();
+        assert!(theme.styles.comments.italic && theme.styles.comments.strong);
+        assert!(!theme.styles.comments.underline);
+        assert_eq!(ColorTheme::from_toml(&theme.to_toml()).unwrap(), theme);
+        assert!(ColorTheme::from_toml("comments_style = \"bold\"").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use super::ColorTheme;
+use super::{ColorTheme, TokenStyles};
 
 impl ColorTheme {
     /// Author : Jakub Bartodziej <kubabartodziej@gmail.com>
@@ -18,6 +18,7 @@ impl ColorTheme {
         strs: "#8ec07c",        // aqua1
         types: "#fabd2f",       // yellow1
         special: "#83a598",     // blue1
+        styles: TokenStyles::PLAIN,
     };
 
     pub const GRUVBOX_DARK: ColorTheme = ColorTheme::GRUVBOX;
@@ -37,5 +38,6 @@ impl ColorTheme {
         strs: "#427b58",        // aqua1
         types: "#b57614",       // yellow1
         special: "#af3a03",     // orange1
+        styles: TokenStyles::PLAIN,
     };
 }
@@ -1,4 +1,5 @@
 pub mod ayu;
+pub mod file;
 pub mod github;
 pub mod gruvbox;
 pub mod sonokai;
@@ -32,6 +33,51 @@ fn color_from_hex(hex: &str) -> Option<Color32> {
     Some(color)
 }
 
+/// Font style hints drawn on top of the color of a token type.
+#[derive(Hash, Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TokenStyle {
+    pub italic: bool,
+    pub underline: bool,
+    /// There is no bold monospace font, strong tokens get a more contrasting color instead.
+    pub strong: bool,
+}
+
+impl TokenStyle {
+    pub const PLAIN: TokenStyle = TokenStyle {
+        italic: false,
+        underline: false,
+        strong: false,
+    };
+}
+
+/// Style hints per token type, next to the colors of the [`ColorTheme`].
+#[derive(Hash, Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TokenStyles {
+    pub comments: TokenStyle,
+    pub functions: TokenStyle,
+    pub keywords: TokenStyle,
+    pub literals: TokenStyle,
+    pub numerics: TokenStyle,
+    pub punctuation: TokenStyle,
+    pub strs: TokenStyle,
+    pub types: TokenStyle,
+    pub special: TokenStyle,
+}
+
+impl TokenStyles {
+    pub const PLAIN: TokenStyles = TokenStyles {
+        comments: TokenStyle::PLAIN,
+        functions: TokenStyle::PLAIN,
+        keywords: TokenStyle::PLAIN,
+        literals: TokenStyle::PLAIN,
+        numerics: TokenStyle::PLAIN,
+        punctuation: TokenStyle::PLAIN,
+        strs: TokenStyle::PLAIN,
+        types: TokenStyle::PLAIN,
+        special: TokenStyle::PLAIN,
+    };
+}
+
 #[derive(Hash, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 /// Colors in hexadecimal notation as used in HTML and CSS.
 pub struct ColorTheme {
@@ -49,6 +95,7 @@ pub struct ColorTheme {
     pub strs: &'static str,
     pub types: &'static str,
     pub special: &'static str,
+    pub styles: TokenStyles,
 }
 impl Default for ColorTheme {
     fn default() -> Self {
@@ -120,6 +167,22 @@ impl ColorTheme {
         .unwrap_or(ERROR_COLOR)
     }
 
+    pub fn type_style(&self, ty: TokenType) -> TokenStyle {
+        match ty {
+            TokenType::Comment(_) => self.styles.comments,
+            TokenType::Function => self.styles.functions,
+            TokenType::Keyword => self.styles.keywords,
+            TokenType::Literal => self.styles.literals,
+            TokenType::Hyperlink => self.styles.special,
+            TokenType::Numeric(_) => self.styles.numerics,
+            TokenType::Punctuation(_) => self.styles.punctuation,
+            TokenType::Special => self.styles.special,
+            TokenType::Str(_) => self.styles.strs,
+            TokenType::Type => self.styles.types,
+            TokenType::Whitespace(_) | TokenType::Unknown => TokenStyle::PLAIN,
+        }
+    }
+
     pub fn monocolor(
         dark: bool,
         bg: &'static str,
@@ -142,6 +205,7 @@ impl ColorTheme {
             strs: fg,
             comments: fg,
             special: fg,
+            styles: TokenStyles::PLAIN,
         }
     }
 }
@@ -1,4 +1,4 @@
-use super::ColorTheme;
+use super::{ColorTheme, TokenStyles};
 
 impl ColorTheme {
     ///  Original Author: sainnhe <https://github.com/sainnhe/sonokai>
@@ -18,5 +18,6 @@ impl ColorTheme {
         strs: "#e7c664",        // yellow
         types: "#399ee6",       // blue
         special: "#f39660",     // orange
+        styles: TokenStyles::PLAIN,
     };
 }
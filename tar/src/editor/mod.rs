@@ -5,14 +5,20 @@ use uuid::Uuid;
 
 use crate::{
     editor::{
-        popup::{create_project::CreateProject, open_project::OpenProject, Popup},
+        code_editor::{settings::EditorSettings, EditorViewState},
+        popup::{
+            create_project::CreateProject, export_file::ExportFile, import_graph::ImportGraph,
+            open_project::OpenProject, Popup,
+        },
         tabs::{
             code_editor::CodeEditorTab, console::ConsoleTab, file_explorer::FileExplorerTab,
             render_graph::RenderGraphTab, viewport::ViewportTab, Tab, TabViewer,
         },
     },
     egui_util::{EguiPass, KeyModifiers},
-    project::Project,
+    project::{self, Project},
+    time::Timer,
+    wgpu_util::{blit_pass::AspectMode, context_wrapper::ContextWrapper},
 };
 
 pub mod code_editor;
@@ -28,9 +34,22 @@ pub enum EditorDragPayload {
     Folder(PathBuf),
 }
 
+/// Toggles maximizing the focused pane over the whole editor area.
+const ZEN_MODE_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(
+    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+    egui::Key::Enter,
+);
+
+/// Seconds between two writes of the recovery file while the project has changes.
+const AUTOSAVE_INTERVAL: f32 = 30.0;
+
 struct Tabs {
     tree: Tree<Tab>,
     last_focussed_code_editor: Option<TileId>,
+    last_focussed_pane: Option<TileId>,
+    /// Tiles hidden while a single pane is maximized, shown again when leaving zen mode.
+    zen_hidden: Option<Vec<TileId>>,
+    code_editor_view_states: HashMap<Uuid, EditorViewState>,
 }
 
 impl Tabs {
@@ -45,8 +64,10 @@ impl Tabs {
         let first_code_file = project.code_files.files_iter().next();
 
         let main_tabs = if let Some(first_code_file) = first_code_file {
-            let code_editor_id =
-                tiles.insert_pane(Tab::CodeEditor(CodeEditorTab::new(first_code_file.1)));
+            let code_editor_id = tiles.insert_pane(Tab::CodeEditor(CodeEditorTab::new(
+                first_code_file.1,
+                &project.editor_settings,
+            )));
             egui_tiles::Tabs {
                 children: vec![code_editor_id, render_graph_id],
                 active: Some(code_editor_id),
@@ -97,9 +118,52 @@ impl Tabs {
         Self {
             tree,
             last_focussed_code_editor: None,
+            last_focussed_pane: None,
+            zen_hidden: None,
+            code_editor_view_states: HashMap::new(),
         }
     }
 
+    fn is_zen_mode(&self) -> bool {
+        self.zen_hidden.is_some()
+    }
+
+    /// Maximize the focused pane by hiding every tile that doesn't contain it, or restore
+    /// the layout when already maximized. Hiding leaves the layout itself untouched.
+    fn toggle_zen_mode(&mut self) {
+        if let Some(hidden) = self.zen_hidden.take() {
+            for tile_id in hidden {
+                self.tree.set_visible(tile_id, true);
+            }
+            return;
+        }
+
+        let Some(pane) = self
+            .last_focussed_pane
+            .filter(|&pane| self.tree.tiles.get(pane).is_some())
+        else {
+            return;
+        };
+
+        let mut keep = vec![pane];
+        while let Some(parent) = self.tree.tiles.parent_of(*keep.last().unwrap()) {
+            keep.push(parent);
+        }
+
+        // Tiles that were already hidden stay hidden when restoring
+        let hidden: Vec<TileId> = self
+            .tree
+            .tiles
+            .tile_ids()
+            .filter(|tile_id| !keep.contains(tile_id) && self.tree.is_visible(*tile_id))
+            .collect();
+        for &tile_id in &hidden {
+            self.tree.set_visible(tile_id, false);
+        }
+
+        self.zen_hidden = Some(hidden);
+    }
+
     pub fn get_container_and_tile_id(&self, target_tab: &Tab) -> Option<(TileId, TileId)> {
         for (tile_id, tile) in self.tree.tiles.iter() {
             if let egui_tiles::Tile::Container(egui_tiles::Container::Tabs(tabs)) = tile {
@@ -149,6 +213,118 @@ impl Tabs {
 
         None
     }
+
+    /// All panes in layout order, depth first from the root.
+    fn panes(&self) -> Vec<TileId> {
+        let mut panes = Vec::new();
+        let mut stack: Vec<TileId> = self.tree.root().into_iter().collect();
+
+        while let Some(tile_id) = stack.pop() {
+            match self.tree.tiles.get(tile_id) {
+                Some(egui_tiles::Tile::Pane(_)) => panes.push(tile_id),
+                Some(egui_tiles::Tile::Container(container)) => {
+                    stack.extend(container.children_vec().into_iter().rev());
+                }
+                None => {}
+            }
+        }
+
+        panes
+    }
+
+    /// Show `pane` and give it keyboard focus.
+    fn focus_pane(&mut self, pane: TileId) {
+        if !self.tree.is_visible(pane) && self.is_zen_mode() {
+            self.toggle_zen_mode();
+        }
+
+        self.tree.make_active(|tile_id, _| tile_id == pane);
+        self.last_focussed_pane = Some(pane);
+
+        if let Some(egui_tiles::Tile::Pane(Tab::CodeEditor(code_editor))) =
+            self.tree.tiles.get_mut(pane)
+        {
+            code_editor.request_focus();
+            self.last_focussed_code_editor = Some(pane);
+        }
+    }
+
+    /// Ctrl+Tab and Ctrl+Shift+Tab cycle through the panes, Ctrl+1..9 jump to one.
+    /// Ctrl+Shift+Enter toggles zen mode.
+    fn handle_focus_shortcuts(&mut self, ctx: &egui::Context) {
+        const DIGITS: [egui::Key; 9] = [
+            egui::Key::Num1,
+            egui::Key::Num2,
+            egui::Key::Num3,
+            egui::Key::Num4,
+            egui::Key::Num5,
+            egui::Key::Num6,
+            egui::Key::Num7,
+            egui::Key::Num8,
+            egui::Key::Num9,
+        ];
+
+        if ctx.input_mut(|i| i.consume_shortcut(&ZEN_MODE_SHORTCUT)) {
+            self.toggle_zen_mode();
+        }
+
+        let panes = self.panes();
+        if panes.is_empty() {
+            return;
+        }
+
+        let current = self
+            .last_focussed_pane
+            .and_then(|pane| panes.iter().position(|&id| id == pane));
+
+        let target = ctx.input_mut(|i| {
+            if i.consume_key(
+                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                egui::Key::Tab,
+            ) {
+                Some(current.map_or(panes.len() - 1, |c| (c + panes.len() - 1) % panes.len()))
+            } else if i.consume_key(egui::Modifiers::COMMAND, egui::Key::Tab) {
+                Some(current.map_or(0, |c| (c + 1) % panes.len()))
+            } else {
+                DIGITS
+                    .iter()
+                    .position(|&key| i.consume_key(egui::Modifiers::COMMAND, key))
+                    .filter(|&index| index < panes.len())
+            }
+        });
+
+        if let Some(target) = target {
+            self.focus_pane(panes[target]);
+        }
+    }
+
+    /// Sources of the code editors with unsaved changes, keyed by their code file.
+    fn unsaved_sources(&mut self) -> HashMap<Uuid, String> {
+        self.unsaved_code_editors()
+            .map(|code_editor| (code_editor.id(), code_editor.source()))
+            .collect()
+    }
+
+    /// Open code editors with changes that aren't saved to their file yet.
+    fn unsaved_code_editors(&mut self) -> impl Iterator<Item = &mut CodeEditorTab> {
+        self.tree.tiles.tiles_mut().filter_map(|tile| match tile {
+            egui_tiles::Tile::Pane(Tab::CodeEditor(code_editor))
+                if code_editor.source_code_changed() =>
+            {
+                Some(code_editor)
+            }
+            _ => None,
+        })
+    }
+
+    /// Apply the editor settings to every open code editor.
+    pub fn apply_editor_settings(&mut self, settings: &EditorSettings) {
+        for tile in self.tree.tiles.tiles_mut() {
+            if let egui_tiles::Tile::Pane(Tab::CodeEditor(code_editor)) = tile {
+                code_editor.apply_settings(settings);
+            }
+        }
+    }
 }
 
 pub struct Editor {
@@ -156,6 +332,16 @@ pub struct Editor {
     popups: HashMap<TypeId, Box<dyn Popup>>,
     drag_payload: Option<EditorDragPayload>,
     viewport_texture: Option<(wgpu::TextureView, [u32; 2])>,
+    /// Present modes supported by the window surface.
+    present_modes: Vec<wgpu::PresentMode>,
+    /// Present mode picked in the settings, `None` until the surface reported the one in use.
+    present_mode: Option<wgpu::PresentMode>,
+    /// Closing was requested with unsaved code files, ask what to do with them.
+    close_prompt: bool,
+    /// The unsaved changes were dealt with, the application can exit.
+    exit_confirmed: bool,
+    /// Time since the recovery file was last written.
+    autosave_timer: Timer,
 }
 
 impl Default for Editor {
@@ -166,14 +352,147 @@ impl Default for Editor {
 
 impl Editor {
     pub fn new() -> Self {
-        Self {
+        let mut editor = Self {
             tabs: None,
             popups: HashMap::new(),
             drag_payload: None,
             viewport_texture: None,
+            present_modes: Vec::new(),
+            present_mode: None,
+            close_prompt: false,
+            exit_confirmed: false,
+            autosave_timer: Timer::new(),
+        };
+
+        // Offer to restore the changes a crash left behind right away
+        if project::recent_projects()
+            .iter()
+            .any(|path| project::has_newer_recovery(path))
+        {
+            editor.open_popup(OpenProject::default());
+        }
+
+        editor
+    }
+
+    /// Write the project and the unsaved code editors to the recovery file when the project
+    /// changed and the last write was at least [`AUTOSAVE_INTERVAL`] ago.
+    fn autosave(&mut self, ctx: &egui::Context, project: &mut Option<Project>) {
+        let Some(project) = project.as_mut().filter(|project| project.is_dirty()) else {
+            return;
+        };
+
+        let remaining = AUTOSAVE_INTERVAL - self.autosave_timer.elapsed();
+        if remaining > 0.0 {
+            // Also write the changes when nothing else causes a repaint
+            ctx.request_repaint_after(std::time::Duration::from_secs_f32(remaining));
+            return;
+        }
+        self.autosave_timer.reset();
+
+        let unsaved_sources = self
+            .tabs
+            .as_mut()
+            .map(Tabs::unsaved_sources)
+            .unwrap_or_default();
+        Self::update_recovery(project, unsaved_sources);
+    }
+
+    /// Write the unsaved code editors to the recovery file. Without any the recovery file would
+    /// only hold what the project already has, so the project is saved instead, which also
+    /// drops the recovery file. Called after code editors are saved so the recovery file never
+    /// holds sources older than the saved ones.
+    fn update_recovery(project: &mut Project, unsaved_sources: HashMap<Uuid, String>) {
+        if unsaved_sources.is_empty() {
+            if let Err(e) = project.save() {
+                log::warn!("Failed to save project: {:#}", e);
+            }
+        } else if let Err(e) = project.write_recovery(unsaved_sources) {
+            log::warn!("Failed to write recovery file: {:#}", e);
+        }
+    }
+
+    /// Returns whether the application can close right away, otherwise asks about the unsaved
+    /// code files first and reports the answer through [`Self::should_exit`].
+    pub fn request_close(&mut self) -> bool {
+        let unsaved = self
+            .tabs
+            .as_mut()
+            .is_some_and(|tabs| tabs.unsaved_code_editors().next().is_some());
+        self.close_prompt = unsaved;
+        !unsaved
+    }
+
+    pub fn should_exit(&self) -> bool {
+        self.exit_confirmed
+    }
+
+    fn close_prompt_ui(&mut self, ctx: &egui::Context, project: &mut Option<Project>) {
+        if !self.close_prompt {
+            return;
+        }
+        let (Some(tabs), Some(project)) = (&mut self.tabs, project.as_mut()) else {
+            self.close_prompt = false;
+            self.exit_confirmed = true;
+            return;
+        };
+
+        let mut save = false;
+        let mut discard = false;
+        let mut cancelled = false;
+        let modal = egui::Modal::new(egui::Id::new("close_prompt")).show(ctx, |ui| {
+            ui.heading("Unsaved Changes");
+            ui.label("Save the changes to these files before closing?");
+            for code_editor in tabs.unsaved_code_editors() {
+                ui.label(format!(
+                    "{} {}",
+                    egui_phosphor::regular::FILE,
+                    code_editor.title()
+                ));
+            }
+
+            ui.horizontal(|ui| {
+                save = ui.button("Save All").clicked();
+                discard = ui.button("Discard").clicked();
+                cancelled = ui.button("Cancel").clicked();
+            });
+        });
+
+        if save {
+            for code_editor in tabs.unsaved_code_editors() {
+                code_editor.save_to_project(project);
+            }
+            Self::update_recovery(project, tabs.unsaved_sources());
+
+            // Files that failed to save stay listed and keep the application open
+            if tabs.unsaved_code_editors().next().is_none() {
+                self.close_prompt = false;
+                self.exit_confirmed = true;
+            }
+        } else if discard {
+            if let Err(e) = project.discard_recovery() {
+                log::warn!("Failed to discard recovery file: {:#}", e);
+            }
+            self.close_prompt = false;
+            self.exit_confirmed = true;
+        } else if cancelled || modal.should_close() {
+            self.close_prompt = false;
         }
     }
 
+    /// Track the present modes of the surface and return the one picked in the settings.
+    pub fn update_present_mode(
+        &mut self,
+        supported: &[wgpu::PresentMode],
+        current: wgpu::PresentMode,
+    ) -> wgpu::PresentMode {
+        if self.present_modes != supported {
+            self.present_modes = supported.to_vec();
+        }
+
+        *self.present_mode.get_or_insert(current)
+    }
+
     fn open_popup<T: Popup + 'static>(&mut self, popup: T) -> bool {
         let type_id = TypeId::of::<T>();
 
@@ -199,6 +518,7 @@ impl Editor {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn ui(
         &mut self,
         egui_ctx: &mut egui::Context,
@@ -206,7 +526,7 @@ impl Editor {
         project: &mut Option<Project>,
         key_modifiers: &KeyModifiers,
         render_graph_dirty: &mut bool,
-        device: &wgpu::Device,
+        context: &ContextWrapper,
     ) {
         egui::TopBottomPanel::top("top_bar").show(egui_ctx, |ui| {
             egui::MenuBar::new().ui(ui, |ui| {
@@ -298,7 +618,7 @@ impl Editor {
                                     ui.close();
 
                                     code_editor.save_to_project(project);
-                                    // TODO: save
+                                    Self::update_recovery(project, tabs.unsaved_sources());
                                 }
                             }
                         }
@@ -308,11 +628,120 @@ impl Editor {
                                     .on_hover_text("Save the currently focussed code file");
                             });
                         }
+
+                        let revert_hover_text =
+                            "Discard unsaved changes to the currently focussed code file";
+                        let code_editor = self
+                            .tabs
+                            .as_mut()
+                            .and_then(|tabs| tabs.get_focussed_code_editor())
+                            .filter(|code_editor| !code_editor.readonly());
+                        if let Some(code_editor) = code_editor {
+                            if ui
+                                .button("Revert File")
+                                .on_hover_text(revert_hover_text)
+                                .clicked()
+                            {
+                                ui.close();
+
+                                code_editor.request_revert();
+                            }
+                        } else {
+                            ui.add_enabled_ui(false, |ui| {
+                                ui.button("Revert File").on_hover_text(revert_hover_text);
+                            });
+                        }
+
+                        ui.separator();
+
+                        let export_hover_text =
+                            "Export the render graph as WGSL shaders, a JSON graph description and a Graphviz DOT file";
+                        if let Some(project) = project.as_ref() {
+                            if ui
+                                .button("Export Graph")
+                                .on_hover_text(export_hover_text)
+                                .clicked()
+                            {
+                                ui.close();
+
+                                match project.export_render_graph() {
+                                    Ok(path) => log::info!("Exported render graph to {:?}", path),
+                                    Err(e) => log::warn!("Failed to export render graph: {}", e),
+                                }
+                            }
+                        } else {
+                            ui.add_enabled_ui(false, |ui| {
+                                ui.button("Export Graph").on_hover_text(export_hover_text);
+                            });
+                        }
+
+                        let import_hover_text =
+                            "Replace the render graph with a previously exported graph";
+                        if project.is_some() {
+                            if ui
+                                .button("Import Graph")
+                                .on_hover_text(import_hover_text)
+                                .clicked()
+                            {
+                                ui.close();
+
+                                self.open_popup(ImportGraph::default());
+                            }
+                        } else {
+                            ui.add_enabled_ui(false, |ui| {
+                                ui.button("Import Graph").on_hover_text(import_hover_text);
+                            });
+                        }
+                    });
+
+                    ui.menu_button("View", |ui| {
+                        let zen_mode = self.tabs.as_ref().is_some_and(Tabs::is_zen_mode);
+                        let button = egui::Button::new("Zen Mode")
+                            .selected(zen_mode)
+                            .shortcut_text(ui.ctx().format_shortcut(&ZEN_MODE_SHORTCUT));
+                        if ui
+                            .add_enabled(self.tabs.is_some(), button)
+                            .on_hover_text("Maximize the focused tab, hiding all others")
+                            .clicked()
+                        {
+                            if let Some(tabs) = &mut self.tabs {
+                                tabs.toggle_zen_mode();
+                            }
+                        }
+                    });
+
+                    ui.menu_button("Settings", |ui| {
+                        if let Some(project) = project.as_mut() {
+                            ui.menu_button("Editor", |ui| {
+                                if project.editor_settings.ui(ui) {
+                                    if let Some(tabs) = &mut self.tabs {
+                                        tabs.apply_editor_settings(&project.editor_settings);
+                                    }
+                                }
+                            });
+                        } else {
+                            ui.add_enabled_ui(false, |ui| {
+                                ui.menu_button("Editor", |_| {});
+                            });
+                        }
+
+                        ui.menu_button("Present Mode", |ui| {
+                            let auto_modes =
+                                [wgpu::PresentMode::AutoVsync, wgpu::PresentMode::AutoNoVsync];
+                            for &mode in auto_modes.iter().chain(&self.present_modes) {
+                                let selected = self.present_mode == Some(mode);
+                                if ui.radio(selected, present_mode_label(mode)).clicked() {
+                                    self.present_mode = Some(mode);
+                                }
+                            }
+                        });
                     });
                 });
             });
         });
 
+        let mut file_to_export = None;
+
         egui::CentralPanel::default()
             .frame(
                 egui::Frame::central_panel(&egui_ctx.style())
@@ -324,6 +753,8 @@ impl Editor {
                     if let Some(tabs) = &mut self.tabs {
                         let mut file_to_open = None;
 
+                        tabs.handle_focus_shortcuts(ui.ctx());
+
                         tabs.tree.ui(
                             &mut TabViewer::new(
                                 egui_pass,
@@ -331,10 +762,13 @@ impl Editor {
                                 project,
                                 &mut self.drag_payload,
                                 &mut file_to_open,
+                                &mut file_to_export,
                                 &mut tabs.last_focussed_code_editor,
+                                &mut tabs.last_focussed_pane,
                                 render_graph_dirty,
+                                &mut tabs.code_editor_view_states,
                                 &mut self.viewport_texture,
-                                device,
+                                context,
                             ),
                             ui,
                         );
@@ -395,7 +829,14 @@ impl Editor {
                                     // Create a new tab and add it to the container
                                     let code_file =
                                         project.code_files.get_file(file_to_open).unwrap();
-                                    let new_tab = Tab::CodeEditor(CodeEditorTab::new(code_file));
+                                    let mut new_tab =
+                                        CodeEditorTab::new(code_file, &project.editor_settings);
+                                    if let Some(view_state) =
+                                        tabs.code_editor_view_states.remove(&file_to_open)
+                                    {
+                                        new_tab.restore_view_state(view_state);
+                                    }
+                                    let new_tab = Tab::CodeEditor(new_tab);
                                     let new_tile_id = tree.tiles.insert_pane(new_tab);
 
                                     if let Some(egui_tiles::Tile::Container(
@@ -411,12 +852,18 @@ impl Editor {
                             }
                         }
                     } else {
-                        self.tabs = Some(Tabs::new(project, device));
+                        self.tabs = Some(Tabs::new(project, &context.device));
                     }
                 }
             });
 
+        if let (Some(id), Some(project)) = (file_to_export, project.as_ref()) {
+            self.open_popup(ExportFile::new(id, project));
+        }
+
         self.popup_ui(egui_ctx, project);
+        self.close_prompt_ui(egui_ctx, project);
+        self.autosave(egui_ctx, project);
 
         if let (Some(ty), Some(project)) = (&self.drag_payload, &project) {
             let pointer_pos = egui_ctx.pointer_interact_pos();
@@ -472,4 +919,58 @@ impl Editor {
     pub fn viewport_texture(&self) -> &Option<(wgpu::TextureView, [u32; 2])> {
         &self.viewport_texture
     }
+
+    /// Whether a viewport tab is on screen, which isn't the case while another tab of its
+    /// container is selected.
+    pub fn viewport_visible(&self) -> bool {
+        self.tabs.as_ref().is_some_and(|tabs| {
+            tabs.tree.active_tiles().into_iter().any(|tile_id| {
+                matches!(
+                    tabs.tree.tiles.get(tile_id),
+                    Some(egui_tiles::Tile::Pane(Tab::Viewport(_)))
+                )
+            })
+        })
+    }
+
+    /// Aspect mode of the viewport the graph renders into, stretching without one.
+    pub fn viewport_aspect_mode(&self) -> AspectMode {
+        self.viewport_texture
+            .as_ref()
+            .and_then(|_| {
+                self.tabs.as_ref()?.tree.tiles.tiles().find_map(|tile| {
+                    if let egui_tiles::Tile::Pane(Tab::Viewport(viewport)) = tile {
+                        Some(viewport.aspect_mode)
+                    } else {
+                        None
+                    }
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    /// Tonemap the graph output that was rendered into the viewport texture for display.
+    pub fn present_viewport(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let Some(tabs) = &mut self.tabs else {
+            return;
+        };
+
+        for tile in tabs.tree.tiles.tiles_mut() {
+            if let egui_tiles::Tile::Pane(Tab::Viewport(viewport)) = tile {
+                viewport.present(device, queue);
+                return;
+            }
+        }
+    }
+}
+
+fn present_mode_label(present_mode: wgpu::PresentMode) -> &'static str {
+    match present_mode {
+        wgpu::PresentMode::AutoVsync => "Auto (vsync)",
+        wgpu::PresentMode::AutoNoVsync => "Auto (no vsync)",
+        wgpu::PresentMode::Fifo => "Fifo (vsync)",
+        wgpu::PresentMode::FifoRelaxed => "Fifo relaxed (adaptive vsync)",
+        wgpu::PresentMode::Immediate => "Immediate (no vsync)",
+        wgpu::PresentMode::Mailbox => "Mailbox (no tearing, uncapped)",
+    }
 }
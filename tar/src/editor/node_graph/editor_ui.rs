@@ -47,6 +47,9 @@ pub enum NodeResponse<UserResponse: UserResponseTrait, NodeData: NodeDataTrait>
     /// As a user of this library, prefer listening for `DeleteNodeFull` which
     /// will also contain the user data for the deleted node.
     DeleteNodeUi(NodeId),
+    /// Emitted by the close button instead of [`Self::DeleteNodeUi`] when
+    /// [`NodeDataTrait::user_handles_delete`] is true, the node is left untouched.
+    DeleteNodeRequest(NodeId),
     /// Emitted when a node is deleted. The node will no longer exist in the
     /// graph after this response is returned from the draw function, but its
     /// contents are passed along with the event.
@@ -104,6 +107,8 @@ pub struct GraphNodeWidget<'a, NodeData, DataType, ValueType> {
     pub pan: Vec2,
     /// Last known pointer position, used for touch release detection
     pub last_pointer_pos: Option<Pos2>,
+    /// Label every port with the name of its data type.
+    pub show_port_types: bool,
 }
 
 impl<NodeData, DataType, ValueType, NodeTemplate, UserResponse, UserState, CategoryType>
@@ -389,6 +394,7 @@ where
                 selected: self.selected_nodes.contains(&node_id),
                 pan: self.pan_zoom.pan + editor_rect.min.to_vec2(),
                 last_pointer_pos,
+                show_port_types: self.show_port_types,
             }
             .show(&self.pan_zoom, ui, user_state);
 
@@ -405,8 +411,26 @@ where
             if let Some(pos) = node_finder.position {
                 node_finder_area = node_finder_area.current_pos(pos);
             }
+            // Only offer templates that can take the dragged connection
+            let connect_from = node_finder
+                .connect_from
+                .filter(|output| self.graph.try_get_output(*output).is_some());
+            let kinds = match connect_from {
+                Some(output) => {
+                    let data_type = &self.graph[output].typ;
+                    FilteredKinds(
+                        all_kinds
+                            .all_kinds()
+                            .into_iter()
+                            .filter(|kind| has_compatible_input(kind, data_type, user_state))
+                            .collect(),
+                    )
+                }
+                None => FilteredKinds(all_kinds.all_kinds()),
+            };
+
             node_finder_area.show(ui.ctx(), |ui| {
-                if let Some(node_kind) = node_finder.show(ui, all_kinds, user_state) {
+                if let Some(node_kind) = node_finder.show(ui, kinds, user_state) {
                     let new_node = self.graph.add_node(
                         node_kind.node_graph_label(user_state),
                         node_kind.user_data(user_state),
@@ -422,6 +446,20 @@ where
 
                     should_close_node_finder = true;
                     delayed_responses.push(NodeResponse::CreatedNode(new_node));
+
+                    if let Some(output) = connect_from {
+                        let data_type = &self.graph[output].typ;
+                        if let Some(input) = self.graph[new_node]
+                            .input_ids()
+                            .find(|input| accepts_connection(&self.graph, *input, data_type))
+                        {
+                            delayed_responses.push(NodeResponse::ConnectEventEnded {
+                                output,
+                                input,
+                                input_hook: 0,
+                            });
+                        }
+                    }
                 }
                 let finder_rect = ui.min_rect();
                 // If the cursor is not in the main editor, check if the cursor is in the finder
@@ -564,7 +602,7 @@ where
                     self.selected_nodes = Vec::from([*node_id]);
                 }
                 NodeResponse::DeleteNodeUi(node_id) => {
-                    let (node, disc_events) = self.graph.remove_node(*node_id);
+                    let (node, disc_events) = self.delete_node(*node_id);
 
                     // Pass the disconnection responses first so user code can perform cleanup
                     // before node removal response.
@@ -579,10 +617,9 @@ where
                         node_id: *node_id,
                         node,
                     });
-                    self.node_positions.remove(*node_id);
-                    // Make sure to not leave references to old nodes hanging
-                    self.selected_nodes.retain(|id| *id != *node_id);
-                    self.node_order.retain(|id| *id != *node_id);
+                }
+                NodeResponse::DeleteNodeRequest(_) => {
+                    // Deleting is up to the user code.
                 }
                 NodeResponse::DisconnectEvent { input, output } => {
                     let other_node = self.graph.get_output(*output).node;
@@ -619,6 +656,8 @@ where
             }
         }
 
+        let cursor_over_node = node_rects.values().any(|rect| rect.contains(cursor_pos));
+
         // Handle box selection
         if let Some(box_start) = self.ongoing_box_selection {
             let selection_rect = Rect::from_two_pos(cursor_pos, box_start);
@@ -679,8 +718,17 @@ where
             )
         });
 
-        // Release connection in progress when pointer is released (mouse or touch)
-        if any_released && self.connection_in_progress.is_some() {
+        // Release connection in progress when pointer is released (mouse or touch).
+        // Dropping an output on empty canvas opens the finder to create a node for it.
+        if any_released {
+            if let Some((_, AnyParameterId::Output(output))) = self.connection_in_progress {
+                let connected = delayed_responses
+                    .iter()
+                    .any(|response| matches!(response, NodeResponse::ConnectEventEnded { .. }));
+                if !connected && !cursor_over_node && cursor_in_editor && !cursor_in_finder {
+                    self.node_finder = Some(NodeFinder::new_connected_at(cursor_pos, output));
+                }
+            }
             self.connection_in_progress = None;
         }
 
@@ -865,8 +913,12 @@ where
 
         child_ui.vertical(|ui| {
             ui.horizontal(|ui| {
+                let label = self.graph[self.node_id]
+                    .user_data
+                    .titlebar_label(self.node_id, self.graph, user_state)
+                    .unwrap_or_else(|| self.graph[self.node_id].label.clone());
                 ui.add(Label::new(
-                    RichText::new(&self.graph[self.node_id].label)
+                    RichText::new(label)
                         .text_style(TextStyle::Button)
                         .color(text_color),
                 ));
@@ -1032,6 +1084,7 @@ where
             connections: usize,
             max_connections: usize,
             last_pointer_pos: Option<Pos2>,
+            show_type_label: bool,
         ) where
             DataType: DataTypeTrait<UserState>,
             UserResponse: UserResponseTrait,
@@ -1073,7 +1126,9 @@ where
                 Sense::click_and_drag()
             };
 
-            let resp = ui.allocate_rect(port_rect, sense);
+            let resp = ui
+                .allocate_rect(port_rect, sense)
+                .on_hover_text(port_type.name());
 
             // Check if the distance between the port and the pointer is the distance to connect
             // Use interact_pos for touch support, fall back to hover_pos for mouse,
@@ -1153,6 +1208,49 @@ where
                 }
             }
 
+            let label_font = egui::FontId::proportional(10.0 * pan_zoom.zoom);
+            if show_type_label {
+                // Outside of the node, left of inputs and right of outputs
+                let (anchor, align) = match param_id {
+                    AnyParameterId::Input(_) => (
+                        port_rect.left_center() - vec2(2.0, 0.0),
+                        egui::Align2::RIGHT_CENTER,
+                    ),
+                    AnyParameterId::Output(_) => (
+                        port_rect.right_center() + vec2(2.0, 0.0),
+                        egui::Align2::LEFT_CENTER,
+                    ),
+                };
+                ui.painter().text(
+                    anchor,
+                    align,
+                    port_type.name(),
+                    label_font.clone(),
+                    port_type.data_type_color(user_state).gamma_multiply(0.8),
+                );
+            }
+
+            // Tooltips are hidden while dragging, so explain a refused connection next to the port
+            if let Some((origin_node, origin_param)) = ongoing_drag {
+                let origin_type = graph.any_param_type(origin_param).unwrap();
+                if origin_node != node_id && close_enough && origin_type != port_type {
+                    let galley = ui.painter().layout_no_wrap(
+                        format!("Expects {}, not {}", port_type.name(), origin_type.name()),
+                        label_font,
+                        ui.visuals().error_fg_color,
+                    );
+                    let text_pos =
+                        port_rect.center_top() - vec2(galley.size().x / 2.0, galley.size().y + 4.0);
+                    ui.painter().rect_filled(
+                        Rect::from_min_size(text_pos, galley.size()).expand(3.0),
+                        3.0,
+                        ui.visuals().extreme_bg_color,
+                    );
+                    ui.painter()
+                        .galley(text_pos, galley, ui.visuals().error_fg_color);
+                }
+            }
+
             if connections > 0 {
                 if let AnyParameterId::Input(input) = param_id {
                     for (k, dst_pos) in port_locations[&AnyParameterId::Input(input)]
@@ -1275,6 +1373,7 @@ where
                     self.graph.connections(*param).len(),
                     max_connections,
                     self.last_pointer_pos,
+                    self.show_port_types,
                 );
 
                 // Consumer inputs also draw their passthrough output port on the right at the same row.
@@ -1295,6 +1394,7 @@ where
                         0,
                         1,
                         self.last_pointer_pos,
+                        self.show_port_types,
                     );
                 }
             }
@@ -1324,6 +1424,7 @@ where
                 0,
                 1,
                 self.last_pointer_pos,
+                self.show_port_types,
             );
         }
 
@@ -1399,7 +1500,14 @@ where
         );
 
         if can_delete && Self::close_button(pan_zoom, ui, outer_rect).clicked() {
-            responses.push(NodeResponse::DeleteNodeUi(self.node_id));
+            let user_handles_delete = self.graph.nodes[self.node_id]
+                .user_data
+                .user_handles_delete(self.node_id, self.graph, user_state);
+            responses.push(if user_handles_delete {
+                NodeResponse::DeleteNodeRequest(self.node_id)
+            } else {
+                NodeResponse::DeleteNodeUi(self.node_id)
+            });
         };
 
         // Movement
@@ -1469,3 +1577,48 @@ where
         resp
     }
 }
+
+/// Node templates shown by the node finder.
+struct FilteredKinds<NodeTemplate>(Vec<NodeTemplate>);
+
+impl<NodeTemplate: Clone> NodeTemplateIter for FilteredKinds<NodeTemplate> {
+    type Item = NodeTemplate;
+
+    fn all_kinds(&self) -> Vec<Self::Item> {
+        self.0.clone()
+    }
+}
+
+/// Whether `input` can be connected to an output of `data_type`.
+fn accepts_connection<NodeData, DataType: PartialEq, ValueType>(
+    graph: &Graph<NodeData, DataType, ValueType>,
+    input: InputId,
+    data_type: &DataType,
+) -> bool {
+    let input = &graph[input];
+    input.typ == *data_type && !matches!(input.kind, InputParamKind::ConstantOnly)
+}
+
+/// Whether a node built from `kind` has an input that accepts `data_type`.
+/// The node is built into a scratch graph so templates don't have to describe
+/// their inputs separately.
+fn has_compatible_input<NodeTemplate: NodeTemplateTrait>(
+    kind: &NodeTemplate,
+    data_type: &NodeTemplate::DataType,
+    user_state: &mut NodeTemplate::UserState,
+) -> bool
+where
+    NodeTemplate::DataType: PartialEq,
+{
+    let mut graph = Graph::new();
+    let node_id = graph.add_node(
+        kind.node_graph_label(user_state),
+        kind.user_data(user_state),
+        |graph, node_id| kind.build_node(graph, user_state, node_id),
+    );
+
+    let compatible = graph[node_id]
+        .input_ids()
+        .any(|input| accepts_connection(&graph, input, data_type));
+    compatible
+}
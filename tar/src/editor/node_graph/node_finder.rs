@@ -1,7 +1,7 @@
 use std::{collections::BTreeMap, marker::PhantomData};
 
 use crate::editor::node_graph::{
-    color_hex_utils::*, CategoryTrait, NodeTemplateIter, NodeTemplateTrait,
+    color_hex_utils::*, CategoryTrait, NodeTemplateIter, NodeTemplateTrait, OutputId,
 };
 
 use egui::*;
@@ -12,6 +12,9 @@ pub struct NodeFinder<NodeTemplate> {
     /// Reset every frame. When set, the node finder will be moved at that position
     pub position: Option<Pos2>,
     pub just_spawned: bool,
+    /// Output a connection was dropped from, the created node gets connected to it.
+    #[serde(skip)]
+    pub connect_from: Option<OutputId>,
     _phantom: PhantomData<NodeTemplate>,
 }
 
@@ -26,10 +29,19 @@ where
             query: "".into(),
             position: Some(pos),
             just_spawned: true,
+            connect_from: None,
             _phantom: Default::default(),
         }
     }
 
+    /// Open the finder for a connection dropped from `output` on empty canvas.
+    pub fn new_connected_at(pos: Pos2, output: OutputId) -> Self {
+        NodeFinder {
+            connect_from: Some(output),
+            ..Self::new_at(pos)
+        }
+    }
+
     /// Shows the node selector panel with a search bar. Returns whether a node
     /// archetype was selected and, in that case, the finder should be hidden on
     /// the next frame.
@@ -63,7 +75,10 @@ where
                 let resp = ui
                     .scope(|ui| {
                         ui.visuals_mut().selection.stroke = Stroke::NONE;
-                        ui.text_edit_singleline(&mut self.query)
+                        ui.add(TextEdit::singleline(&mut self.query).hint_text(format!(
+                            "{} Search nodes",
+                            egui_phosphor::regular::MAGNIFYING_GLASS
+                        )))
                     })
                     .inner;
                 if self.just_spawned {
@@ -77,6 +92,9 @@ where
                 let max_height = ui.input(|i| i.content_rect().height() * 0.5);
                 let scroll_area_width = resp.rect.width();
 
+                let query = self.query.to_lowercase();
+                let matches_query = |kind_name: &str| kind_name.to_lowercase().contains(&query);
+
                 let all_kinds = all_kinds.all_kinds();
                 let mut categories: BTreeMap<String, Vec<&NodeTemplate>> = Default::default();
                 let mut orphan_kinds = Vec::new();
@@ -101,6 +119,8 @@ where
                             .min_scrolled_height(500.0)
                             .show(ui, |ui| {
                                 ui.set_width(scroll_area_width);
+                                let mut any_match = false;
+
                                 for (category, kinds) in categories {
                                     let filtered_kinds: Vec<_> = kinds
                                         .into_iter()
@@ -109,14 +129,11 @@ where
                                                 kind.node_finder_label(user_state).to_string();
                                             (kind, kind_name)
                                         })
-                                        .filter(|(_kind, kind_name)| {
-                                            kind_name
-                                                .to_lowercase()
-                                                .contains(self.query.to_lowercase().as_str())
-                                        })
+                                        .filter(|(_kind, kind_name)| matches_query(kind_name))
                                         .collect();
 
                                     if !filtered_kinds.is_empty() {
+                                        any_match = true;
                                         let default_open = !self.query.is_empty();
 
                                         CollapsingHeader::new(&category)
@@ -140,6 +157,10 @@ where
 
                                 for kind in orphan_kinds {
                                     let kind_name = kind.node_finder_label(user_state).to_string();
+                                    if !matches_query(&kind_name) {
+                                        continue;
+                                    }
+                                    any_match = true;
 
                                     if ui.selectable_label(false, kind_name).clicked() {
                                         submitted_archetype = Some(kind.clone());
@@ -148,6 +169,10 @@ where
                                         query_submit = false;
                                     }
                                 }
+
+                                if !any_match {
+                                    ui.weak("No matching nodes");
+                                }
                             });
                     });
             });
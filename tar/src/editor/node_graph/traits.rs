@@ -150,6 +150,17 @@ where
         Default::default()
     }
 
+    /// Text shown in the titlebar.
+    /// If the return value is None, the node's label is shown.
+    fn titlebar_label(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType>,
+        _user_state: &mut Self::UserState,
+    ) -> Option<String> {
+        None
+    }
+
     /// Set background color on titlebar
     /// If the return value is None, the default color is set.
     fn titlebar_color(
@@ -188,6 +199,17 @@ where
     ) -> bool {
         true
     }
+
+    /// Leave deleting this node to user code, for example to ask for confirmation first.
+    /// The close button then emits [`NodeResponse::DeleteNodeRequest`] instead of deleting.
+    fn user_handles_delete(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType>,
+        _user_state: &mut Self::UserState,
+    ) -> bool {
+        false
+    }
 }
 
 /// This trait can be implemented by any user type. The trait tells the library
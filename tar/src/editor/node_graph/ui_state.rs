@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 const MIN_ZOOM: f32 = 0.2;
 const MAX_ZOOM: f32 = 2.0;
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState> {
     pub graph: Graph<NodeData, DataType, ValueType>,
     /// Nodes are drawn in this order. Draw order is important because nodes
@@ -30,6 +30,9 @@ pub struct GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserSta
     pub node_finder: Option<NodeFinder<NodeTemplate>>,
     /// The panning of the graph viewport.
     pub pan_zoom: PanZoom,
+    /// Label every port with the name of its data type.
+    #[serde(default)]
+    pub show_port_types: bool,
     pub _user_state: PhantomData<fn() -> UserState>,
 }
 
@@ -42,7 +45,38 @@ impl<NodeData, DataType, ValueType, NodeKind, UserState>
             ..Default::default()
         }
     }
+
+    /// Remove a node along with its editor state, returning the node and the
+    /// connections that were removed with it.
+    pub fn delete_node(&mut self, node_id: NodeId) -> (Node<NodeData>, Vec<(InputId, OutputId)>) {
+        let removed = self.graph.remove_node(node_id);
+        self.node_positions.remove(node_id);
+        // Make sure to not leave references to old nodes hanging
+        self.selected_nodes.retain(|id| *id != node_id);
+        self.node_order.retain(|id| *id != node_id);
+        removed
+    }
+}
+// Not derived, that would require the user state to be `Clone` because of the `PhantomData`
+impl<NodeData: Clone, DataType: Clone, ValueType: Clone, NodeKind: Clone, UserState> Clone
+    for GraphEditorState<NodeData, DataType, ValueType, NodeKind, UserState>
+{
+    fn clone(&self) -> Self {
+        Self {
+            graph: self.graph.clone(),
+            node_order: self.node_order.clone(),
+            connection_in_progress: self.connection_in_progress,
+            selected_nodes: self.selected_nodes.clone(),
+            ongoing_box_selection: self.ongoing_box_selection,
+            node_positions: self.node_positions.clone(),
+            node_finder: self.node_finder.clone(),
+            pan_zoom: self.pan_zoom.clone(),
+            show_port_types: self.show_port_types,
+            _user_state: PhantomData,
+        }
+    }
 }
+
 impl<NodeData, DataType, ValueType, NodeKind, UserState> Default
     for GraphEditorState<NodeData, DataType, ValueType, NodeKind, UserState>
 {
@@ -56,6 +90,7 @@ impl<NodeData, DataType, ValueType, NodeKind, UserState> Default
             node_positions: Default::default(),
             node_finder: Default::default(),
             pan_zoom: Default::default(),
+            show_port_types: false,
             _user_state: Default::default(),
         }
     }
@@ -1,14 +1,16 @@
 use std::path::PathBuf;
 
 use egui_file_dialog::FileDialog;
+use strum::IntoEnumIterator;
 
-use crate::project::{self, Project};
+use crate::project::{self, Project, ProjectTemplate};
 
 use super::Popup;
 
 pub struct CreateProject {
     pub project_name: String,
     pub project_path: PathBuf,
+    pub template: ProjectTemplate,
     is_first_frame: bool,
 
     file_dialog: Option<FileDialog>,
@@ -22,6 +24,7 @@ impl Default for CreateProject {
         Self {
             project_name: "new-project".to_owned(),
             project_path,
+            template: ProjectTemplate::default(),
             is_first_frame: true,
             file_dialog: None,
         }
@@ -65,6 +68,20 @@ impl Popup for CreateProject {
                     }
                 });
 
+                ui.horizontal(|ui| {
+                    ui.label("Template:");
+
+                    egui::ComboBox::from_id_salt("project_template")
+                        .selected_text(self.template.label())
+                        .show_ui(ui, |ui| {
+                            for template in ProjectTemplate::iter() {
+                                ui.selectable_value(&mut self.template, template, template.label())
+                                    .on_hover_text(template.description());
+                            }
+                        });
+                });
+                ui.weak(self.template.description());
+
                 ui.add_space(10.0);
 
                 let project_name = self.project_name.replace(" ", "_");
@@ -77,15 +94,26 @@ impl Popup for CreateProject {
 
                 ui.add_enabled_ui(!exists, |ui| {
                     if ui.button("Create").clicked() {
-                        // Create new project struct from the input fields
-                        let new_project = Project::new(&project_file_path);
-
-                        // Create all necessary directories
-                        if std::fs::create_dir_all(&project_path).is_ok() {
+                        // Create all necessary directories and the project from the input fields
+                        let new_project = std::fs::create_dir_all(&project_path)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|_| {
+                                Project::from_template(&project_file_path, self.template)
+                            });
+
+                        match new_project {
                             // Save the project to a file
-                            if new_project.save().is_ok() {
-                                *project = Some(new_project);
+                            Ok(mut new_project) => {
+                                if new_project.save().is_ok() {
+                                    if let Err(e) = project::add_recent_project(&project_file_path)
+                                    {
+                                        log::warn!("Failed to remember recent project: {}", e);
+                                    }
+
+                                    *project = Some(new_project);
+                                }
                             }
+                            Err(e) => log::warn!("Failed to create project: {}", e),
                         }
 
                         should_close = true;
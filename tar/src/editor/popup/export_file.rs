@@ -0,0 +1,63 @@
+use uuid::Uuid;
+
+use egui_file_dialog::FileDialog;
+
+use crate::project::{self, Project};
+
+use super::Popup;
+
+/// Save a copy of a single code file anywhere on disk, outside of the project.
+pub struct ExportFile {
+    id: Uuid,
+    file_dialog: FileDialog,
+}
+
+impl ExportFile {
+    pub fn new(id: Uuid, project: &Project) -> Self {
+        // Keep the code file extension visible in front of the shader language one
+        let file_name = project
+            .code_files
+            .get_file(id)
+            .and_then(|file| file.relative_path().file_name())
+            .map(|name| format!("{}.wgsl", name.to_string_lossy()))
+            .unwrap_or_default();
+
+        let mut file_dialog = FileDialog::new().default_file_name(&file_name);
+        if let Some(default_project_path) = project::default_project_path() {
+            file_dialog = file_dialog.initial_directory(default_project_path);
+        }
+
+        file_dialog.save_file();
+
+        Self { id, file_dialog }
+    }
+}
+
+impl Popup for ExportFile {
+    fn ui(&mut self, ctx: &egui::Context, project: &mut Option<Project>) -> bool {
+        self.file_dialog.update(ctx);
+        if let Some(picked) = self.file_dialog.take_picked() {
+            if let Some(project) = project {
+                let result = project
+                    .code_files
+                    .get_source(self.id)
+                    .and_then(|source| Ok(std::fs::write(&picked, source)?));
+
+                match result {
+                    Ok(()) => {
+                        log::info!("Exported code file to {:?}", picked);
+
+                        if let Err(e) = project::reveal_in_file_manager(&picked) {
+                            log::warn!("Failed to reveal {:?}: {}", picked, e);
+                        }
+                    }
+                    Err(e) => log::warn!("Failed to export code file: {}", e),
+                }
+            }
+
+            false
+        } else {
+            *self.file_dialog.state() == egui_file_dialog::DialogState::Open
+        }
+    }
+}
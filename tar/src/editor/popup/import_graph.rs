@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use egui_file_dialog::FileDialog;
+
+use crate::{
+    project::{default_project_path, Project},
+    render_graph::bundle::BUNDLE_GRAPH_FILE,
+};
+
+use super::Popup;
+
+pub struct ImportGraph {
+    file_dialog: FileDialog,
+}
+
+impl Default for ImportGraph {
+    fn default() -> Self {
+        let mut file_dialog = FileDialog::new()
+            .add_file_filter(
+                "Graph Bundle",
+                Arc::new(|path| {
+                    path.file_name()
+                        .is_some_and(|file_name| file_name == BUNDLE_GRAPH_FILE)
+                }),
+            )
+            .default_file_filter("Graph Bundle");
+
+        if let Some(default_project_path) = default_project_path() {
+            file_dialog = file_dialog.initial_directory(default_project_path);
+        }
+
+        file_dialog.pick_file();
+
+        Self { file_dialog }
+    }
+}
+
+impl Popup for ImportGraph {
+    fn ui(&mut self, ctx: &egui::Context, project: &mut Option<Project>) -> bool {
+        self.file_dialog.update(ctx);
+        if let Some(picked) = self.file_dialog.take_picked() {
+            if let (Some(project), Some(dir)) = (project.as_mut(), picked.parent()) {
+                match project.import_render_graph(dir) {
+                    Ok(problems) => {
+                        for problem in problems {
+                            log::warn!("Graph import: {}", problem);
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to import graph: {}", e);
+                    }
+                }
+            }
+
+            false
+        } else {
+            true
+        }
+    }
+}
@@ -3,6 +3,8 @@ use std::any::TypeId;
 use crate::project::Project;
 
 pub mod create_project;
+pub mod export_file;
+pub mod import_graph;
 pub mod open_project;
 
 pub trait Popup: 'static {
@@ -1,17 +1,48 @@
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use egui_file_dialog::FileDialog;
 
-use crate::project::{default_project_path, Project};
+use crate::project::{self, default_project_path, Project};
 
 use super::Popup;
 
 pub struct OpenProject {
-    file_dialog: FileDialog,
+    recent_projects: Vec<PathBuf>,
+    /// Why the last project failed to open, shown until another one is picked.
+    error: Option<String>,
+    /// Project with autosaved changes newer than the saved project, waiting for the user to
+    /// restore or discard them.
+    recovery: Option<PathBuf>,
+
+    file_dialog: Option<FileDialog>,
 }
 
 impl Default for OpenProject {
     fn default() -> Self {
+        let recent_projects = project::recent_projects();
+
+        // Without any recent projects there is nothing to choose from, go straight to browsing
+        let file_dialog = recent_projects.is_empty().then(Self::browse);
+
+        let recovery = recent_projects
+            .iter()
+            .find(|path| project::has_newer_recovery(path))
+            .cloned();
+
+        Self {
+            recent_projects,
+            error: None,
+            recovery,
+            file_dialog,
+        }
+    }
+}
+
+impl OpenProject {
+    fn browse() -> FileDialog {
         let mut file_dialog = FileDialog::new()
             .add_file_filter(
                 "Tar Project",
@@ -24,27 +55,174 @@ impl Default for OpenProject {
         }
 
         file_dialog.pick_file();
+        file_dialog
+    }
 
-        Self { file_dialog }
+    /// Returns whether the project was opened, on failure the error is shown in the popup.
+    /// A project with newer autosaved changes is only opened once the user picked which to keep.
+    fn open(&mut self, path: &Path, project: &mut Option<Project>) -> bool {
+        if project::has_newer_recovery(path) {
+            self.error = None;
+            self.recovery = Some(path.to_path_buf());
+            return false;
+        }
+
+        self.load(path, project, Project::load)
+    }
+
+    fn load(
+        &mut self,
+        path: &Path,
+        project: &mut Option<Project>,
+        load: fn(&Path) -> anyhow::Result<Project>,
+    ) -> bool {
+        match load(path) {
+            Ok(new_project) => {
+                *project = Some(new_project);
+
+                if let Err(e) = project::add_recent_project(path) {
+                    log::warn!("Failed to remember recent project: {}", e);
+                }
+
+                true
+            }
+            Err(e) => {
+                self.error = Some(format!("Failed to open {}: {:#}", path.display(), e));
+
+                false
+            }
+        }
     }
 }
 
 impl Popup for OpenProject {
     fn ui(&mut self, ctx: &egui::Context, project: &mut Option<Project>) -> bool {
-        self.file_dialog.update(ctx);
-        if let Some(picked) = self.file_dialog.take_picked() {
-            match Project::load(&picked) {
-                Ok(new_project) => {
-                    *project = Some(new_project);
+        if let Some(file_dialog) = &mut self.file_dialog {
+            file_dialog.update(ctx);
+            if let Some(picked) = file_dialog.take_picked() {
+                self.file_dialog = None;
+                return !self.open(&picked, project);
+            }
+
+            let still_open = *file_dialog.state() == egui_file_dialog::DialogState::Open;
+            if still_open {
+                return true;
+            }
+
+            // Cancelled, go back to the list or close when there is none
+            self.file_dialog = None;
+            if self.recent_projects.is_empty() && self.error.is_none() {
+                return false;
+            }
+        }
+
+        let mut open = true;
+        let mut picked = None;
+        let mut restore = false;
+        let mut discard = false;
+
+        let window_rect =
+            egui::Rect::from_center_size(ctx.content_rect().center(), egui::vec2(400.0, 300.0));
+
+        egui::Window::new("Open Project")
+            .default_rect(window_rect)
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if let Some(path) = &self.recovery {
+                    let name = path
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    ui.label(format!(
+                        "{} {} has autosaved changes that are newer than the saved project.",
+                        egui_phosphor::regular::CLOCK_COUNTER_CLOCKWISE,
+                        name
+                    ));
+
+                    ui.horizontal(|ui| {
+                        restore = ui
+                            .button("Restore")
+                            .on_hover_text("Open the autosaved changes and save them")
+                            .clicked();
+                        discard = ui
+                            .button("Discard")
+                            .on_hover_text(
+                                "Open the saved project and delete the autosaved changes",
+                            )
+                            .clicked();
+                    });
+
+                    ui.separator();
+                }
+
+                ui.label("Recent Projects");
+
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        if self.recent_projects.is_empty() {
+                            ui.weak("No recent projects");
+                        }
+
+                        for path in &self.recent_projects {
+                            let name = path
+                                .file_stem()
+                                .map(|s| s.to_string_lossy().to_string())
+                                .unwrap_or_default();
+                            let exists = path.is_file();
+
+                            let response = ui
+                                .add_enabled(exists, egui::Button::new(name).frame(false))
+                                .on_hover_text(path.display().to_string())
+                                .on_disabled_hover_text(format!(
+                                    "{} no longer exists",
+                                    path.display()
+                                ));
+                            if response.clicked() {
+                                picked = Some(path.clone());
+                            }
+                        }
+                    });
+
+                ui.separator();
+
+                if ui
+                    .button(format!("{} Browse...", egui_phosphor::regular::FOLDER_OPEN))
+                    .clicked()
+                {
+                    self.error = None;
+                    self.file_dialog = Some(Self::browse());
                 }
-                Err(e) => {
-                    log::warn!("Failed to open project: {}", e);
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
                 }
+            });
+
+        if let Some(path) = self.recovery.clone().filter(|_| restore || discard) {
+            self.recovery = None;
+
+            let load = if restore {
+                Project::restore_recovery
+            } else {
+                if let Err(e) = project::discard_recovery(&path) {
+                    log::warn!("Failed to discard recovery file: {:#}", e);
+                }
+                Project::load
+            };
+            if self.load(&path, project, load) {
+                return false;
             }
+        }
 
-            false
-        } else {
-            true
+        if let Some(path) = picked {
+            if self.open(&path, project) {
+                return false;
+            }
         }
+
+        open
     }
 }
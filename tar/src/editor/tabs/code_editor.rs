@@ -1,17 +1,41 @@
+use strum::IntoEnumIterator;
 use uuid::Uuid;
 
 use crate::{
-    editor::code_editor::{syntax::Syntax, themes::ColorTheme, CodeEditor},
+    editor::code_editor::{
+        diagnostics::{Diagnostic, Severity},
+        line_ending::LineEnding,
+        settings::EditorSettings,
+        syntax::Syntax,
+        themes::ColorTheme,
+        CodeEditor, EditorViewState,
+    },
     egui_util::KeyModifiers,
-    project::{CodeFile, Project},
+    project::{CodeFile, CodeFileType, Project},
+    render_graph::shader::MessageLocation,
+    time::Timer,
 };
 
+/// Idle time after the last edit before the source is sent off for recompilation.
+const LIVE_RECOMPILE_DELAY: f32 = 0.4;
+
 pub struct CodeEditorTab {
     id: Uuid,
     title: String,
     code_editor: CodeEditor,
     has_focus: bool,
     saved_source_code_hash: u64,
+    /// Hash of the source last handed to the project for compiling, saved or live.
+    synced_source_code_hash: u64,
+    seen_generation: u64,
+    /// Line endings of the file on disk.
+    saved_line_ending: LineEnding,
+
+    /// Recompile shaders from the unsaved source shortly after typing stops.
+    pub live_recompile: bool,
+    live_recompile_timer: Option<Timer>,
+    /// Revert to the saved source on the next frame, asking first when there are unsaved changes.
+    revert_requested: bool,
 }
 
 impl PartialEq for CodeEditorTab {
@@ -21,9 +45,13 @@ impl PartialEq for CodeEditorTab {
 }
 
 impl CodeEditorTab {
-    pub fn new(code_file: &CodeFile) -> Self {
-        let code_editor =
-            CodeEditor::new(&code_file.source, ColorTheme::GITHUB_DARK, Syntax::wgsl());
+    pub fn new(code_file: &CodeFile, settings: &EditorSettings) -> Self {
+        let syntax = match code_file.ty() {
+            CodeFileType::Glsl => Syntax::glsl(),
+            _ => Syntax::wgsl(),
+        };
+        let mut code_editor = CodeEditor::new(&code_file.source, ColorTheme::GITHUB_DARK, syntax);
+        code_editor.apply_settings(settings);
 
         let title = code_file
             .relative_path()
@@ -32,6 +60,8 @@ impl CodeEditorTab {
             .unwrap_or_default();
 
         let saved_source_code_hash = code_editor.doc_hash();
+        let seen_generation = code_editor.generation();
+        let saved_line_ending = code_editor.line_ending();
 
         Self {
             id: code_file.id(),
@@ -39,6 +69,12 @@ impl CodeEditorTab {
             code_editor,
             has_focus: false,
             saved_source_code_hash,
+            synced_source_code_hash: saved_source_code_hash,
+            seen_generation,
+            saved_line_ending,
+            live_recompile: true,
+            live_recompile_timer: None,
+            revert_requested: false,
         }
     }
 
@@ -54,11 +90,33 @@ impl CodeEditorTab {
         }
     }
 
+    pub fn apply_settings(&mut self, settings: &EditorSettings) {
+        self.code_editor.apply_settings(settings);
+    }
+
+    pub fn view_state(&self) -> EditorViewState {
+        self.code_editor.view_state()
+    }
+
+    pub fn restore_view_state(&mut self, view_state: EditorViewState) {
+        self.code_editor.restore_view_state(view_state);
+    }
+
     pub fn has_focus(&self) -> bool {
         self.has_focus
     }
 
-    pub fn ui(&mut self, ui: &mut egui::Ui, project: &mut Project, key_modifiers: &KeyModifiers) {
+    pub fn request_focus(&mut self) {
+        self.code_editor.request_focus();
+    }
+
+    /// Returns whether the document was edited this frame.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        project: &mut Project,
+        key_modifiers: &KeyModifiers,
+    ) -> bool {
         if let Some(code_file) = project.code_files.get_file(self.id) {
             self.title = code_file
                 .relative_path()
@@ -66,28 +124,238 @@ impl CodeEditorTab {
                 .map(|s| s.to_string_lossy().to_string())
                 .unwrap_or_default();
 
+            if self.revert_requested {
+                self.revert_ui(ui.ctx(), project);
+            }
+
+            self.update_diagnostics(project);
+
+            egui::TopBottomPanel::bottom(ui.id().with("status_bar"))
+                .show_inside(ui, |ui| self.status_bar_ui(ui));
             self.has_focus = self.code_editor.ui(ui, key_modifiers);
         } else {
             ui.centered_and_justified(|ui| {
                 ui.label("Unable to locate file, it was probably removed.");
             });
         }
+
+        let generation = self.code_editor.generation();
+        let changed = generation != self.seen_generation;
+        self.seen_generation = generation;
+
+        if self.live_recompile {
+            self.update_live_recompile(ui.ctx(), project, changed);
+        }
+
+        changed
+    }
+
+    fn status_bar_ui(&mut self, ui: &mut egui::Ui) {
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label("UTF-8")
+                .on_hover_text("Files are always read and written as UTF-8");
+
+            let mut readonly = self.code_editor.readonly();
+            let icon = if readonly {
+                egui_phosphor::regular::LOCK
+            } else {
+                egui_phosphor::regular::LOCK_OPEN
+            };
+            if ui
+                .toggle_value(&mut readonly, icon)
+                .on_hover_text(
+                    "Read-only, prevents accidental edits while selecting and copying still works",
+                )
+                .changed()
+            {
+                self.code_editor.set_readonly(readonly);
+            }
+
+            let mut line_ending = self.code_editor.line_ending();
+            ui.add_enabled_ui(!self.code_editor.readonly(), |ui| {
+                egui::ComboBox::from_id_salt(ui.id().with("line_ending"))
+                    .selected_text(line_ending.as_str())
+                    .show_ui(ui, |ui| {
+                        for option in LineEnding::iter() {
+                            ui.selectable_value(&mut line_ending, option, option.as_str())
+                                .on_hover_text(option.description());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Line endings used when saving this file");
+            });
+            // Applied on the next save, undoable like any other edit
+            self.code_editor.set_line_ending(line_ending);
+        });
+    }
+
+    /// Mirror the compile errors and warnings of this file's shader into the editor.
+    fn update_diagnostics(&mut self, project: &Project) {
+        let Some(shader) = project.render_graph().shader(self.id) else {
+            self.code_editor.set_diagnostics(Vec::new());
+            return;
+        };
+
+        let doc = &self.code_editor.doc;
+        let char_range = |location: Option<MessageLocation>| {
+            // Naga line numbers start at 1, messages without one go on the first line
+            let line = location.map_or(1, |location| location.line).max(1) as usize - 1;
+            let line = line.min(doc.len_lines() - 1);
+            let line_start = doc.line_to_char(line);
+            let line_end = line_start + doc.line(line).len_chars();
+
+            // Without a column the whole line is marked
+            match location {
+                Some(location) => {
+                    let start = (line_start + location.column as usize).min(line_end);
+                    let end = (start + location.length as usize).min(doc.len_chars());
+                    start..end
+                }
+                None => line_start..line_end,
+            }
+        };
+
+        let errors = shader
+            .get_errors()
+            .iter()
+            .map(|error| (error, Severity::Error));
+        let warnings = shader
+            .get_warnings()
+            .iter()
+            .map(|warning| (warning, Severity::Warning));
+        let diagnostics = errors
+            .chain(warnings)
+            .map(|((message, location), severity)| Diagnostic {
+                range: char_range(*location),
+                message: message.clone(),
+                severity,
+            })
+            .collect();
+
+        self.code_editor.set_diagnostics(diagnostics);
+    }
+
+    fn update_live_recompile(&mut self, ctx: &egui::Context, project: &mut Project, changed: bool) {
+        if changed {
+            // Restart the debounce on every keystroke
+            self.live_recompile_timer = Some(Timer::new());
+        }
+
+        if self
+            .live_recompile_timer
+            .as_ref()
+            .is_some_and(|timer| timer.elapsed() >= LIVE_RECOMPILE_DELAY)
+        {
+            self.live_recompile_timer = None;
+
+            // Edits that end up with the same text, like undoing, don't need a recompile
+            let doc_hash = self.code_editor.doc_hash();
+            if doc_hash != self.synced_source_code_hash {
+                if let Err(e) = project
+                    .code_files
+                    .set_live_source(self.id, self.code_editor.doc.to_string())
+                {
+                    log::warn!("Failed to update live source: {e}");
+                } else {
+                    self.synced_source_code_hash = doc_hash;
+                }
+            }
+        }
+
+        if let Some(timer) = &self.live_recompile_timer {
+            let remaining = (LIVE_RECOMPILE_DELAY - timer.elapsed()).max(0.0);
+            ctx.request_repaint_after(std::time::Duration::from_secs_f32(remaining));
+        }
+    }
+
+    /// Discard the buffer and go back to the saved source of the file.
+    pub fn request_revert(&mut self) {
+        self.revert_requested = true;
+    }
+
+    fn revert_ui(&mut self, ctx: &egui::Context, project: &mut Project) {
+        if !self.source_code_changed() {
+            self.revert_requested = false;
+            self.revert(project);
+            return;
+        }
+
+        let mut confirmed = false;
+        let mut cancelled = false;
+        let modal =
+            egui::Modal::new(egui::Id::new("revert_confirmation").with(self.id)).show(ctx, |ui| {
+                ui.heading("Revert File");
+                ui.label(format!(
+                    "Discard the unsaved changes to {}? This can be undone with Ctrl+Z.",
+                    self.title
+                ));
+
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("Revert").clicked()
+                        || ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+
+        if confirmed {
+            self.revert_requested = false;
+            self.revert(project);
+        } else if cancelled || modal.should_close() {
+            self.revert_requested = false;
+        }
+    }
+
+    fn revert(&mut self, project: &mut Project) {
+        if self.code_editor.readonly() {
+            log::warn!("Unable to revert {} while it is read-only", self.title);
+            return;
+        }
+
+        let source = match project.code_files.get_source(self.id) {
+            Ok(source) => source,
+            Err(e) => {
+                log::warn!("Failed to revert file: {e}");
+                return;
+            }
+        };
+
+        self.code_editor
+            .set_text_and_line_ending(&LineEnding::normalize(&source), LineEnding::detect(&source));
+        self.saved_line_ending = self.code_editor.line_ending();
+        self.saved_source_code_hash = self.code_editor.doc_hash();
+        self.synced_source_code_hash = self.saved_source_code_hash;
+        self.live_recompile_timer = None;
+
+        if let Err(e) = project.code_files.clear_live_source(self.id) {
+            log::warn!("Failed to clear live source: {e}");
+        }
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.code_editor.readonly()
     }
 
     pub fn source_code_changed(&self) -> bool {
         self.saved_source_code_hash != self.code_editor.doc_hash()
+            || self.saved_line_ending != self.code_editor.line_ending()
+    }
+
+    /// The document with the line endings it is saved with.
+    pub fn source(&self) -> String {
+        self.code_editor
+            .line_ending()
+            .apply(&self.code_editor.doc.to_string())
     }
 
     pub fn save_to_project(&mut self, project: &mut Project) {
-        if let Err(e) = project
-            .code_files
-            .set_source(self.id, self.code_editor.doc.to_string())
-        {
+        if let Err(e) = project.code_files.set_source(self.id, self.source()) {
             log::warn!("Failed to save file internally: {e}");
         } else if let Err(e) = project.code_files.save_file(self.id) {
             log::warn!("Failed to save file: {e}");
         } else {
             self.saved_source_code_hash = self.code_editor.doc_hash();
+            self.synced_source_code_hash = self.saved_source_code_hash;
+            self.saved_line_ending = self.code_editor.line_ending();
         }
     }
 }
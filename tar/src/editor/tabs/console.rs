@@ -7,12 +7,14 @@ use crate::project::Project;
 enum Severity {
     Error,
     Warning,
+    /// Always shown, like the values sampled by readback nodes.
+    Info,
 }
 
 struct ConsoleMessage {
     severity: Severity,
     text: String,
-    file: Uuid,
+    file: Option<Uuid>,
     line: Option<u32>,
 }
 
@@ -40,6 +42,7 @@ impl ConsoleTab {
 
     const ERROR_COLOR: egui::Color32 = egui::Color32::from_rgb(220, 50, 50);
     const WARNING_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 180, 30);
+    const INFO_COLOR: egui::Color32 = egui::Color32::from_rgb(90, 150, 230);
 
     pub fn id(&self) -> Uuid {
         self.id
@@ -57,6 +60,7 @@ impl ConsoleTab {
             .filter(|m| match m.severity {
                 Severity::Error => self.show_errors,
                 Severity::Warning => self.show_warnings,
+                Severity::Info => true,
             })
             .collect();
 
@@ -74,24 +78,66 @@ impl ConsoleTab {
         let mut messages = Vec::new();
 
         for (id, shader) in rg.shaders_iter() {
-            for (err, line) in shader.get_errors() {
+            for (err, location) in shader.get_errors() {
                 messages.push(ConsoleMessage {
                     severity: Severity::Error,
                     text: err.clone(),
-                    file: *id,
-                    line: *line,
+                    file: Some(*id),
+                    line: location.map(|location| location.line),
                 });
             }
-            for (warn, line) in shader.get_warnings() {
+            for (warn, location) in shader.get_warnings() {
                 messages.push(ConsoleMessage {
                     severity: Severity::Warning,
                     text: warn.clone(),
-                    file: *id,
-                    line: *line,
+                    file: Some(*id),
+                    line: location.map(|location| location.line),
                 });
             }
         }
 
+        for (_, error) in rg.pass_errors() {
+            messages.push(ConsoleMessage {
+                severity: Severity::Error,
+                text: error.clone(),
+                file: None,
+                line: None,
+            });
+        }
+
+        for (label, value) in rg.texel_values() {
+            let [x, y] = value.pixel;
+            let (severity, text) = if value.is_finite() {
+                (
+                    Severity::Info,
+                    format!(
+                        "Readback '{}' ({}, {}): {}",
+                        label,
+                        x,
+                        y,
+                        value.channels_text()
+                    ),
+                )
+            } else {
+                (
+                    Severity::Warning,
+                    format!(
+                        "Readback '{}' ({}, {}) is not finite: {}",
+                        label,
+                        x,
+                        y,
+                        value.channels_text()
+                    ),
+                )
+            };
+            messages.push(ConsoleMessage {
+                severity,
+                text,
+                file: None,
+                line: None,
+            });
+        }
+
         messages
     }
 
@@ -154,6 +200,7 @@ impl ConsoleTab {
         let (icon, icon_color) = match message.severity {
             Severity::Error => (icons::X_CIRCLE, Self::ERROR_COLOR),
             Severity::Warning => (icons::WARNING, Self::WARNING_COLOR),
+            Severity::Info => (icons::INFO, Self::INFO_COLOR),
         };
 
         // Draw icon
@@ -181,9 +228,9 @@ impl ConsoleTab {
         );
 
         // Draw file name (bottom-left, smaller and dimmer — Unity style)
-        let mut file_name = project
-            .code_files
-            .get_file(message.file)
+        let mut file_name = message
+            .file
+            .and_then(|file| project.code_files.get_file(file))
             .map(|file| file.relative_path().file_name().unwrap_or_default())
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or_default();
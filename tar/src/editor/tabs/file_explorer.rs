@@ -76,9 +76,10 @@ impl FileExplorerTab {
         project: &mut Project,
         drag_payload: &mut Option<EditorDragPayload>,
         file_to_open: &mut Option<Uuid>,
+        file_to_export: &mut Option<Uuid>,
     ) {
         ui.add_space(4.0);
-        self.draw_toolbar(ui, project);
+        self.draw_toolbar(ui, project, file_to_export);
         ui.separator();
 
         egui::ScrollArea::both()
@@ -121,7 +122,12 @@ impl FileExplorerTab {
         }
     }
 
-    fn draw_toolbar(&mut self, ui: &mut egui::Ui, project: &mut Project) {
+    fn draw_toolbar(
+        &mut self,
+        ui: &mut egui::Ui,
+        project: &mut Project,
+        file_to_export: &mut Option<Uuid>,
+    ) {
         ui.horizontal(|ui| {
             ui.add_space(8.0);
 
@@ -133,6 +139,7 @@ impl FileExplorerTab {
                     ui.add_space(8.0);
                     self.draw_delete_button(ui, project);
                     self.draw_rename_button(ui);
+                    self.draw_export_button(ui, project, file_to_export);
                 });
             }
         });
@@ -272,6 +279,29 @@ impl FileExplorerTab {
         }
     }
 
+    fn draw_export_button(
+        &self,
+        ui: &mut egui::Ui,
+        project: &Project,
+        file_to_export: &mut Option<Uuid>,
+    ) {
+        let Some(id) = self
+            .selected
+            .as_ref()
+            .and_then(|selected| self.file_id_for_path(project, selected))
+        else {
+            return;
+        };
+
+        if ui
+            .button(icons::EXPORT)
+            .on_hover_text("Export a copy of this file")
+            .clicked()
+        {
+            *file_to_export = Some(id);
+        }
+    }
+
     fn draw_explorer(
         &mut self,
         ui: &mut egui::Ui,
@@ -1,8 +1,11 @@
+use std::collections::HashMap;
+
 use egui_tiles::TileId;
 use uuid::Uuid;
 
 use crate::{
     editor::{
+        code_editor::EditorViewState,
         tabs::{
             code_editor::CodeEditorTab, console::ConsoleTab, file_explorer::FileExplorerTab,
             render_graph::RenderGraphTab, viewport::ViewportTab,
@@ -11,6 +14,7 @@ use crate::{
     },
     egui_util::{EguiPass, KeyModifiers},
     project::Project,
+    wgpu_util::context_wrapper::ContextWrapper,
 };
 
 pub mod code_editor;
@@ -67,11 +71,15 @@ pub struct TabViewer<'a> {
     project: &'a mut Project,
     drag_payload: &'a mut Option<EditorDragPayload>,
     file_to_open: &'a mut Option<Uuid>,
+    file_to_export: &'a mut Option<Uuid>,
     last_focussed_code_editor: &'a mut Option<TileId>,
+    last_focussed_pane: &'a mut Option<TileId>,
     render_graph_dirty: &'a mut bool,
+    /// Where each closed code editor was left, restored when the file is opened again.
+    code_editor_view_states: &'a mut HashMap<Uuid, EditorViewState>,
 
     viewport_texture: &'a mut Option<(wgpu::TextureView, [u32; 2])>,
-    device: &'a wgpu::Device,
+    context: &'a ContextWrapper,
 }
 
 impl<'a> TabViewer<'a> {
@@ -81,10 +89,13 @@ impl<'a> TabViewer<'a> {
         project: &'a mut Project,
         drag_payload: &'a mut Option<EditorDragPayload>,
         file_to_open: &'a mut Option<Uuid>,
+        file_to_export: &'a mut Option<Uuid>,
         last_focussed_code_editor: &'a mut Option<TileId>,
+        last_focussed_pane: &'a mut Option<TileId>,
         render_graph_dirty: &'a mut bool,
+        code_editor_view_states: &'a mut HashMap<Uuid, EditorViewState>,
         viewport_texture: &'a mut Option<(wgpu::TextureView, [u32; 2])>,
-        device: &'a wgpu::Device,
+        context: &'a ContextWrapper,
     ) -> Self {
         Self {
             egui_pass,
@@ -92,10 +103,13 @@ impl<'a> TabViewer<'a> {
             project,
             drag_payload,
             file_to_open,
+            file_to_export,
             last_focussed_code_editor,
+            last_focussed_pane,
             render_graph_dirty,
+            code_editor_view_states,
             viewport_texture,
-            device,
+            context,
         }
     }
 }
@@ -111,26 +125,45 @@ impl<'a> egui_tiles::Behavior<Tab> for TabViewer<'a> {
         tile_id: egui_tiles::TileId,
         tab: &mut Tab,
     ) -> egui_tiles::UiResponse {
+        if ui.ui_contains_pointer() && ui.input(|i| i.pointer.any_pressed()) {
+            *self.last_focussed_pane = Some(tile_id);
+        }
+
         match tab {
             Tab::Viewport(tab) => {
-                tab.ui(ui, self.egui_pass, self.viewport_texture, self.device);
+                tab.ui(
+                    ui,
+                    self.egui_pass,
+                    self.viewport_texture,
+                    &self.context.device,
+                    &self.context.queue,
+                );
             }
             Tab::Console(tab) => {
                 tab.ui(ui, self.project);
             }
             Tab::FileExplorer(tab) => {
-                tab.ui(ui, self.project, self.drag_payload, self.file_to_open);
+                tab.ui(
+                    ui,
+                    self.project,
+                    self.drag_payload,
+                    self.file_to_open,
+                    self.file_to_export,
+                );
             }
             Tab::RenderGraph(tab) => {
-                if tab.ui(ui, self.project, self.drag_payload) {
+                if tab.ui(ui, self.project, self.drag_payload, self.context) {
                     *self.render_graph_dirty = true;
                 }
             }
             Tab::CodeEditor(tab) => {
-                tab.ui(ui, self.project, self.key_modifiers);
+                if tab.ui(ui, self.project, self.key_modifiers) {
+                    self.project.mark_dirty();
+                }
 
                 if tab.has_focus() {
                     *self.last_focussed_code_editor = Some(tile_id);
+                    *self.last_focussed_pane = Some(tile_id);
                 }
             }
         }
@@ -146,6 +179,14 @@ impl<'a> egui_tiles::Behavior<Tab> for TabViewer<'a> {
         }
     }
 
+    fn on_tab_close(&mut self, tiles: &mut egui_tiles::Tiles<Tab>, tile_id: TileId) -> bool {
+        if let Some(egui_tiles::Tile::Pane(Tab::CodeEditor(tab))) = tiles.get(tile_id) {
+            self.code_editor_view_states
+                .insert(tab.id(), tab.view_state());
+        }
+        true
+    }
+
     fn simplification_options(&self) -> egui_tiles::SimplificationOptions {
         egui_tiles::SimplificationOptions {
             all_panes_must_have_tabs: true,
@@ -8,10 +8,15 @@ use crate::{
     editor::{node_graph::*, EditorDragPayload},
     project::{CodeFileType, Project},
     render_graph::{
+        add_graphics_pass_depth_inputs, is_image_file, BufferInit, DepthCompare, DepthFormat,
         RgDataType, RgGraph, RgGraphState, RgNodeData, RgNodeTemplate, RgValueType,
-        ScreenTexResolution, Tex2D, Tex2DArray, Tex3D, TextureUsage,
+        SamplerAddressMode, SamplerFilter, ScreenTexResolution, Tex2D, Tex2DArray, Tex3D,
+        TextureUsage,
+    },
+    wgpu_util::{
+        blit_pass::BlendMode, context_wrapper::ContextWrapper, noise_pass::NoiseType,
+        BasicColorTextureFormat,
     },
-    wgpu_util::BasicColorTextureFormat,
 };
 
 // // ========= First, define your user data types =============
@@ -85,10 +90,14 @@ use crate::{
 /// node in the graph. Most side-effects (creating new nodes, deleting existing
 /// nodes, handling connections...) are already handled by the library, but this
 /// mechanism allows creating additional side effects from user code.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MyResponse {
     SetInspectNode(NodeId),
     ClearInspectNode,
+    SetBypass(NodeId, bool),
+    DuplicateNode(NodeId),
+    SetLabel(NodeId, Option<String>),
+    SetColor(NodeId, Option<egui::Color32>),
     ValueChanged,
 }
 
@@ -106,6 +115,13 @@ impl DataTypeTrait<RgGraphState> for RgDataType {
             Self::ScreenTexResolution => egui::Color32::from_rgb(238, 207, 109),
             Self::TextureFormat => egui::Color32::from_rgb(238, 207, 109),
             Self::TextureUsage => egui::Color32::from_rgb(238, 207, 109),
+            Self::BufferInit => egui::Color32::from_rgb(238, 207, 109),
+            Self::NoiseType => egui::Color32::from_rgb(238, 207, 109),
+            Self::BlendMode => egui::Color32::from_rgb(238, 207, 109),
+            Self::SamplerFilter => egui::Color32::from_rgb(238, 207, 109),
+            Self::SamplerAddressMode => egui::Color32::from_rgb(238, 207, 109),
+            Self::DepthFormat => egui::Color32::from_rgb(238, 207, 109),
+            Self::DepthCompare => egui::Color32::from_rgb(238, 207, 109),
             Self::Tex2D => egui::Color32::from_rgb(109, 238, 182),
             Self::HistoryTex2D => egui::Color32::from_rgb(238, 109, 182),
             Self::Tex2DArray => egui::Color32::from_rgb(109, 182, 238),
@@ -113,7 +129,10 @@ impl DataTypeTrait<RgGraphState> for RgDataType {
             Self::HistoryTex3D => egui::Color32::from_rgb(182, 38, 211),
             Self::Buffer => egui::Color32::from_rgb(38, 211, 182),
             Self::HistoryBuffer => egui::Color32::from_rgb(38, 211, 182),
+            Self::Sampler => egui::Color32::from_rgb(211, 109, 38),
+            Self::DepthTex2D => egui::Color32::from_rgb(150, 150, 160),
             Self::CodeFile => egui::Color32::from_rgb(38, 211, 182),
+            Self::ImageFile => egui::Color32::from_rgb(38, 211, 182),
         }
     }
 
@@ -127,6 +146,13 @@ impl DataTypeTrait<RgGraphState> for RgDataType {
             Self::ScreenTexResolution => Cow::Borrowed("screen texture resolution"),
             Self::TextureFormat => Cow::Borrowed("texture format"),
             Self::TextureUsage => Cow::Borrowed("texture usage"),
+            Self::BufferInit => Cow::Borrowed("buffer init"),
+            Self::NoiseType => Cow::Borrowed("noise type"),
+            Self::BlendMode => Cow::Borrowed("blend mode"),
+            Self::SamplerFilter => Cow::Borrowed("sampler filter"),
+            Self::SamplerAddressMode => Cow::Borrowed("sampler address mode"),
+            Self::DepthFormat => Cow::Borrowed("depth format"),
+            Self::DepthCompare => Cow::Borrowed("depth compare"),
             Self::Tex2D => Cow::Borrowed("2D texture"),
             Self::HistoryTex2D => Cow::Borrowed("history 2D texture"),
             Self::Tex2DArray => Cow::Borrowed("2D texture array"),
@@ -134,7 +160,10 @@ impl DataTypeTrait<RgGraphState> for RgDataType {
             Self::HistoryTex3D => Cow::Borrowed("history 3D texture"),
             Self::Buffer => Cow::Borrowed("buffer"),
             Self::HistoryBuffer => Cow::Borrowed("history buffer"),
+            Self::Sampler => Cow::Borrowed("sampler"),
+            Self::DepthTex2D => Cow::Borrowed("2D depth texture"),
             Self::CodeFile => Cow::Borrowed("code file"),
+            Self::ImageFile => Cow::Borrowed("image file"),
         }
     }
 }
@@ -157,11 +186,17 @@ impl NodeTemplateTrait for RgNodeTemplate {
             Self::Tex2DArray => "Tex 2D Array",
             Self::Tex3D => "Tex 3D",
             Self::HistoryTex3D => "History Tex 3D",
+            Self::ImageTexture => "Image Texture",
+            Self::Noise => "Noise",
             Self::Buffer => "Buffer",
             Self::HistoryBuffer => "History Buffer",
+            Self::Sampler => "Sampler",
+            Self::DepthTex => "Depth Tex",
 
             Self::GraphicsPass => "Graphics Pass",
 
+            Self::Readback => "Readback",
+
             Self::DisplayOut => "Display Out",
         })
     }
@@ -175,7 +210,10 @@ impl NodeTemplateTrait for RgNodeTemplate {
             | Self::HistoryTex2D
             | Self::Tex2DArray
             | Self::Tex3D
-            | Self::HistoryTex3D => {
+            | Self::HistoryTex3D
+            | Self::ImageTexture
+            | Self::Noise
+            | Self::DepthTex => {
                 vec![format!("{} Texture", egui_phosphor::regular::CHECKERBOARD)]
             }
 
@@ -183,8 +221,12 @@ impl NodeTemplateTrait for RgNodeTemplate {
                 vec![format!("{} Buffer", egui_phosphor::regular::BINARY)]
             }
 
+            Self::Sampler => vec![format!("{} Sampler", egui_phosphor::regular::FUNNEL)],
+
             Self::GraphicsPass => vec![format!("{} Render", egui_phosphor::regular::GRAPHICS_CARD)],
 
+            Self::Readback => vec![format!("{} Debug", egui_phosphor::regular::BUG)],
+
             Self::DisplayOut => vec![format!("{} Display", egui_phosphor::regular::MONITOR)],
         }
     }
@@ -196,7 +238,7 @@ impl NodeTemplateTrait for RgNodeTemplate {
     }
 
     fn user_data(&self, _user_state: &mut Self::UserState) -> Self::NodeData {
-        RgNodeData(*self)
+        RgNodeData::new(*self)
     }
 
     fn build_node(
@@ -297,6 +339,41 @@ impl NodeTemplateTrait for RgNodeTemplate {
             );
         };
 
+        let input_buffer_init = |graph: &mut RgGraph, name: &str| {
+            graph.add_input_param(
+                node_id,
+                name.to_string(),
+                RgDataType::BufferInit,
+                RgValueType::BufferInit(BufferInit::default()),
+                InputParamKind::ConstantOnly,
+                true,
+                true,
+            );
+        };
+
+        let input_noise_type = |graph: &mut RgGraph, name: &str| {
+            graph.add_input_param(
+                node_id,
+                name.to_string(),
+                RgDataType::NoiseType,
+                RgValueType::NoiseType(NoiseType::default()),
+                InputParamKind::ConstantOnly,
+                true,
+                true,
+            );
+        };
+        let input_blend_mode = |graph: &mut RgGraph, name: &str| {
+            graph.add_input_param(
+                node_id,
+                name.to_string(),
+                RgDataType::BlendMode,
+                RgValueType::BlendMode(BlendMode::default()),
+                InputParamKind::ConstantOnly,
+                true,
+                true,
+            );
+        };
+
         let input_tex_2d = |graph: &mut RgGraph, name: &str| {
             graph.add_input_param(
                 node_id,
@@ -342,6 +419,18 @@ impl NodeTemplateTrait for RgNodeTemplate {
             );
         };
 
+        let input_image_file = |graph: &mut RgGraph, name: &str| {
+            graph.add_input_param(
+                node_id,
+                name.to_string(),
+                RgDataType::ImageFile,
+                RgValueType::ImageFile(None),
+                InputParamKind::ConstantOnly,
+                true,
+                true,
+            );
+        };
+
         let output_tex_2d = |graph: &mut RgGraph, name: &str| {
             graph.add_output_param(node_id, name.to_string(), RgDataType::Tex2D);
         };
@@ -355,6 +444,30 @@ impl NodeTemplateTrait for RgNodeTemplate {
             graph.add_output_param(node_id, name.to_string(), RgDataType::Buffer);
         };
 
+        let input_sampler_filter = |graph: &mut RgGraph, name: &str| {
+            graph.add_input_param(
+                node_id,
+                name.to_string(),
+                RgDataType::SamplerFilter,
+                RgValueType::SamplerFilter(SamplerFilter::default()),
+                InputParamKind::ConstantOnly,
+                true,
+                true,
+            );
+        };
+
+        let input_sampler_address_mode = |graph: &mut RgGraph, name: &str| {
+            graph.add_input_param(
+                node_id,
+                name.to_string(),
+                RgDataType::SamplerAddressMode,
+                RgValueType::SamplerAddressMode(SamplerAddressMode::default()),
+                InputParamKind::ConstantOnly,
+                true,
+                true,
+            );
+        };
+
         match self {
             RgNodeTemplate::ScreenTex => {
                 input_screen_tex_resolution(graph, "resolution");
@@ -410,22 +523,97 @@ impl NodeTemplateTrait for RgNodeTemplate {
                 output_tex_3d(graph, "current tex");
                 output_tex_3d(graph, "previous tex");
             }
+            RgNodeTemplate::ImageTexture => {
+                input_image_file(graph, "image");
+                input_bool(graph, "srgb");
+                output_tex_2d(graph, "tex");
+            }
+            RgNodeTemplate::Noise => {
+                input_uint2(graph, "resolution");
+                input_tex_format(graph, "format");
+                input_noise_type(graph, "type");
+                input_uint(graph, "seed");
+                input_float(graph, "frequency");
+                output_tex_2d(graph, "tex");
+
+                // Start out with something visible instead of a single constant texel
+                let defaults = [
+                    ("resolution", RgValueType::UInt2([256; 2])),
+                    ("frequency", RgValueType::Float(8.0)),
+                ];
+                for (name, value) in defaults {
+                    if let Ok(input) = graph[node_id].get_input(name) {
+                        graph[input].value = value;
+                    }
+                }
+            }
             RgNodeTemplate::Buffer => {
                 input_uint(graph, "size");
                 input_bool(graph, "persistent");
+                input_buffer_init(graph, "init");
+                input_uint(graph, "fill");
                 output_buffer(graph, "buf");
             }
             RgNodeTemplate::HistoryBuffer => {
                 input_uint(graph, "size");
+                input_buffer_init(graph, "init");
+                input_uint(graph, "fill");
                 output_buffer(graph, "current buf");
                 output_buffer(graph, "previous buf");
             }
+            RgNodeTemplate::Sampler => {
+                input_sampler_filter(graph, "mag filter");
+                input_sampler_filter(graph, "min filter");
+                input_sampler_filter(graph, "mipmap filter");
+                input_sampler_address_mode(graph, "address mode u");
+                input_sampler_address_mode(graph, "address mode v");
+                input_sampler_address_mode(graph, "address mode w");
+                graph.add_output_param(node_id, "sampler".to_string(), RgDataType::Sampler);
+            }
+            RgNodeTemplate::DepthTex => {
+                input_screen_tex_resolution(graph, "resolution");
+                graph.add_input_param(
+                    node_id,
+                    "format".to_string(),
+                    RgDataType::DepthFormat,
+                    RgValueType::DepthFormat(DepthFormat::default()),
+                    InputParamKind::ConstantOnly,
+                    true,
+                    true,
+                );
+                graph.add_output_param(node_id, "depth tex".to_string(), RgDataType::DepthTex2D);
+            }
             RgNodeTemplate::GraphicsPass => {
                 input_code_file(graph, "code");
                 input_tex_2d(graph, "render target");
+                add_graphics_pass_depth_inputs(graph, node_id);
+            }
+            RgNodeTemplate::Readback => {
+                // Reads the texture without consuming it, so it can stay connected elsewhere
+                graph.add_input_param(
+                    node_id,
+                    "tex".to_string(),
+                    RgDataType::Tex2D,
+                    RgValueType::Tex2D(Tex2D::default()),
+                    InputParamKind::ConnectionOnly,
+                    false,
+                    true,
+                );
+                input_uint2(graph, "pixel");
             }
             RgNodeTemplate::DisplayOut => {
-                input_tex_2d(graph, "in");
+                // Every connection is a layer, composited in order on top of the first
+                graph.add_wide_input_param(
+                    node_id,
+                    "in".to_string(),
+                    RgDataType::Tex2D,
+                    RgValueType::Tex2D(Tex2D::default()),
+                    InputParamKind::ConnectionOnly,
+                    false,
+                    None,
+                    true,
+                );
+                input_blend_mode(graph, "blend");
             }
         }
     }
@@ -440,6 +628,49 @@ impl NodeTemplateIter for AllMyNodeTemplates {
     }
 }
 
+/// Speed for a `DragValue` scrubbed from the node graph. Shift for fine control is
+/// already handled by `egui::DragValue` itself, Ctrl makes the drag coarse.
+fn drag_speed(ui: &egui::Ui, base: f64) -> f64 {
+    if ui.input(|i| i.modifiers.ctrl) {
+        base * 10.0
+    } else {
+        base
+    }
+}
+
+// Upper bounds for uint parameters, matching the limits requested from the device
+const MAX_TEXTURE_DIMENSION_2D: u32 = 1024 * 8;
+const MAX_TEXTURE_DIMENSION_3D: u32 = 2048;
+const MAX_TEXTURE_ARRAY_LAYERS: u32 = 256;
+const MAX_MIP_LEVELS: u32 = MAX_TEXTURE_DIMENSION_2D.ilog2() + 1;
+const MAX_BUFFER_SIZE: u32 = 128 << 20;
+const MAX_MEMORY_BUDGET_MIB: u64 = 64 << 10;
+
+/// The largest sensible value for a uint parameter of a node.
+fn uint_max(template: RgNodeTemplate, param_name: &str) -> u32 {
+    match (template, param_name) {
+        (RgNodeTemplate::Tex3D | RgNodeTemplate::HistoryTex3D, "resolution") => {
+            MAX_TEXTURE_DIMENSION_3D
+        }
+        (_, "resolution") => MAX_TEXTURE_DIMENSION_2D,
+        (_, "count") => MAX_TEXTURE_ARRAY_LAYERS,
+        (_, "mips") => MAX_MIP_LEVELS,
+        (_, "size") => MAX_BUFFER_SIZE,
+        _ => u32::MAX,
+    }
+}
+
+fn drag_uint(ui: &mut egui::Ui, value: &mut u32, max: u32) -> bool {
+    let speed = drag_speed(ui, 1.0);
+    ui.add(egui::DragValue::new(value).speed(speed).range(0..=max))
+        .changed()
+}
+
+fn drag_float(ui: &mut egui::Ui, value: &mut f32) -> bool {
+    let speed = drag_speed(ui, 0.1);
+    ui.add(egui::DragValue::new(value).speed(speed)).changed()
+}
+
 impl WidgetValueTrait for RgValueType {
     type Response = MyResponse;
     type UserState = RgGraphState;
@@ -450,9 +681,10 @@ impl WidgetValueTrait for RgValueType {
         _node_id: NodeId,
         ui: &mut egui::Ui,
         user_state: &mut RgGraphState,
-        _node_data: &RgNodeData,
+        node_data: &RgNodeData,
     ) -> Vec<MyResponse> {
         let mut dirty = false;
+        let uint_max = uint_max(node_data.template, param_name);
 
         // This trait is used to tell the library which UI to display for the
         // inline parameter widgets.
@@ -460,33 +692,33 @@ impl WidgetValueTrait for RgValueType {
             Self::UInt(value) => {
                 ui.horizontal(|ui| {
                     ui.label(param_name);
-                    dirty = dirty || ui.add(egui::DragValue::new(value)).changed();
+                    dirty |= drag_uint(ui, value, uint_max);
                 });
             }
             Self::UInt2(value) => {
                 ui.label(param_name);
                 ui.horizontal(|ui| {
                     ui.label("x");
-                    dirty = dirty || ui.add(egui::DragValue::new(&mut value[0])).changed();
+                    dirty |= drag_uint(ui, &mut value[0], uint_max);
                     ui.label("y");
-                    dirty = dirty || ui.add(egui::DragValue::new(&mut value[1])).changed();
+                    dirty |= drag_uint(ui, &mut value[1], uint_max);
                 });
             }
             Self::UInt3(value) => {
                 ui.label(param_name);
                 ui.horizontal(|ui| {
                     ui.label("x");
-                    dirty = dirty || ui.add(egui::DragValue::new(&mut value[0])).changed();
+                    dirty |= drag_uint(ui, &mut value[0], uint_max);
                     ui.label("y");
-                    dirty = dirty || ui.add(egui::DragValue::new(&mut value[1])).changed();
+                    dirty |= drag_uint(ui, &mut value[1], uint_max);
                     ui.label("z");
-                    dirty = dirty || ui.add(egui::DragValue::new(&mut value[2])).changed();
+                    dirty |= drag_uint(ui, &mut value[2], uint_max);
                 });
             }
             Self::Float(value) => {
                 ui.horizontal(|ui| {
                     ui.label(param_name);
-                    dirty = dirty || ui.add(egui::DragValue::new(value)).changed();
+                    dirty |= drag_float(ui, value);
                 });
             }
             Self::Bool(value) => {
@@ -540,6 +772,111 @@ impl WidgetValueTrait for RgValueType {
                         });
                 });
             }
+            Self::BufferInit(value) => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    egui::ComboBox::from_id_salt(param_name)
+                        .selected_text(value.to_string())
+                        .show_ui(ui, |ui| {
+                            for variant in BufferInit::iter() {
+                                dirty = dirty
+                                    || ui
+                                        .selectable_value(value, variant, variant.to_string())
+                                        .changed();
+                            }
+                        });
+                });
+            }
+            Self::NoiseType(value) => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    egui::ComboBox::from_id_salt(param_name)
+                        .selected_text(value.to_string())
+                        .show_ui(ui, |ui| {
+                            for variant in NoiseType::iter() {
+                                dirty = dirty
+                                    || ui
+                                        .selectable_value(value, variant, variant.to_string())
+                                        .changed();
+                            }
+                        });
+                });
+            }
+            Self::BlendMode(value) => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    egui::ComboBox::from_id_salt(param_name)
+                        .selected_text(value.to_string())
+                        .show_ui(ui, |ui| {
+                            for variant in BlendMode::iter() {
+                                dirty = dirty
+                                    || ui
+                                        .selectable_value(value, variant, variant.to_string())
+                                        .changed();
+                            }
+                        });
+                });
+            }
+            Self::SamplerFilter(value) => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    egui::ComboBox::from_id_salt(param_name)
+                        .selected_text(value.to_string())
+                        .show_ui(ui, |ui| {
+                            for variant in SamplerFilter::iter() {
+                                dirty = dirty
+                                    || ui
+                                        .selectable_value(value, variant, variant.to_string())
+                                        .changed();
+                            }
+                        });
+                });
+            }
+            Self::SamplerAddressMode(value) => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    egui::ComboBox::from_id_salt(param_name)
+                        .selected_text(value.to_string())
+                        .show_ui(ui, |ui| {
+                            for variant in SamplerAddressMode::iter() {
+                                dirty = dirty
+                                    || ui
+                                        .selectable_value(value, variant, variant.to_string())
+                                        .changed();
+                            }
+                        });
+                });
+            }
+            Self::DepthFormat(value) => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    egui::ComboBox::from_id_salt(param_name)
+                        .selected_text(value.to_string())
+                        .show_ui(ui, |ui| {
+                            for variant in DepthFormat::iter() {
+                                dirty = dirty
+                                    || ui
+                                        .selectable_value(value, variant, variant.to_string())
+                                        .changed();
+                            }
+                        });
+                });
+            }
+            Self::DepthCompare(value) => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+                    egui::ComboBox::from_id_salt(param_name)
+                        .selected_text(value.to_string())
+                        .show_ui(ui, |ui| {
+                            for variant in DepthCompare::iter() {
+                                dirty = dirty
+                                    || ui
+                                        .selectable_value(value, variant, variant.to_string())
+                                        .changed();
+                            }
+                        });
+                });
+            }
             Self::CodeFile(value) => {
                 let editor = user_state.editor.as_mut().unwrap();
                 let code_file_names = &editor.code_file_names;
@@ -600,7 +937,50 @@ impl WidgetValueTrait for RgValueType {
                     }
                 });
             }
-            Self::Tex2D(_) | Self::Tex2DArray(_) | Self::Tex3D(_) | Self::Buffer(_) => {
+            Self::ImageFile(value) => {
+                ui.horizontal(|ui| {
+                    ui.label(param_name);
+
+                    let drop_target = ui.group(|ui| {
+                        let file_name = value
+                            .as_ref()
+                            .and_then(|path| path.file_name())
+                            .map(|s| s.to_string_lossy().to_string())
+                            .unwrap_or_else(|| "None".to_string());
+                        ui.label(file_name)
+                    });
+                    let drop_target = match value {
+                        Some(path) => drop_target
+                            .response
+                            .on_hover_text(path.display().to_string()),
+                        None => drop_target
+                            .response
+                            .on_hover_text("Drop an image file here to load it"),
+                    };
+
+                    // The first image file dropped from the OS onto the widget replaces the
+                    // current one, any other dropped files are left in the input
+                    if drop_target.contains_pointer() {
+                        let dropped_path = ui.input_mut(|i| {
+                            let files = &mut i.raw.dropped_files;
+                            let index = files
+                                .iter()
+                                .position(|file| file.path.as_deref().is_some_and(is_image_file))?;
+                            files.remove(index).path
+                        });
+                        if let Some(path) = dropped_path {
+                            *value = Some(path);
+                            dirty = true;
+                        }
+                    }
+                });
+            }
+            Self::Tex2D(_)
+            | Self::Tex2DArray(_)
+            | Self::Tex3D(_)
+            | Self::Buffer(_)
+            | Self::Sampler
+            | Self::DepthTex2D => {
                 ui.label(param_name);
             }
         }
@@ -650,29 +1030,168 @@ impl NodeDataTrait for RgNodeData {
         // or clear the active node. These responses do nothing by themselves,
         // the library only makes the responses available to you after the graph
         // has been drawn. See below at the update method for an example.
-        if !is_active {
+        ui.horizontal(|ui| {
+            if !is_active {
+                if ui
+                    .button(egui::RichText::new(format!(
+                        "{} Inspect",
+                        egui_phosphor::regular::EYE
+                    )))
+                    .clicked()
+                {
+                    responses.push(NodeResponse::User(MyResponse::SetInspectNode(node_id)));
+                }
+            } else {
+                let button = egui::Button::new(
+                    egui::RichText::new(format!("{} Inspect", egui_phosphor::regular::EYE_SLASH))
+                        .color(egui::Color32::BLACK),
+                )
+                .fill(egui::Color32::GOLD);
+                if ui.add(button).clicked() {
+                    responses.push(NodeResponse::User(MyResponse::ClearInspectNode));
+                }
+            }
+
+            if matches!(self.template, RgNodeTemplate::GraphicsPass) {
+                let button = if self.bypass {
+                    egui::Button::new(
+                        egui::RichText::new(format!("{} Bypass", egui_phosphor::regular::PROHIBIT))
+                            .color(egui::Color32::BLACK),
+                    )
+                    .fill(egui::Color32::GOLD)
+                } else {
+                    egui::Button::new(format!("{} Bypass", egui_phosphor::regular::PROHIBIT))
+                };
+
+                if ui
+                    .add(button)
+                    .on_hover_text("Skip this pass and pass its render target through unchanged")
+                    .clicked()
+                {
+                    responses.push(NodeResponse::User(MyResponse::SetBypass(
+                        node_id,
+                        !self.bypass,
+                    )));
+                }
+
+                if let Some(ms) = user_state.pass_timings.get(&node_id) {
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{} {:.2} ms",
+                            egui_phosphor::regular::TIMER,
+                            ms
+                        ))
+                        .weak(),
+                    )
+                    .on_hover_text("GPU time of this pass");
+                }
+            }
+
+            if let Some(value) = user_state.texel_values.get(&node_id) {
+                let text = egui::RichText::new(value.channels_text()).monospace();
+                let text = if value.is_finite() {
+                    text
+                } else {
+                    text.color(ui.visuals().warn_fg_color)
+                };
+                ui.label(text).on_hover_text(format!(
+                    "{:?} texel at ({}, {})",
+                    value.format, value.pixel[0], value.pixel[1]
+                ));
+            }
+
             if ui
-                .button(egui::RichText::new(format!(
-                    "{} Inspect",
-                    egui_phosphor::regular::EYE
-                )))
+                .button(egui_phosphor::regular::COPY)
+                .on_hover_text("Duplicate this node without its connections (Ctrl+D)")
                 .clicked()
             {
-                responses.push(NodeResponse::User(MyResponse::SetInspectNode(node_id)));
+                responses.push(NodeResponse::User(MyResponse::DuplicateNode(node_id)));
             }
-        } else {
-            let button = egui::Button::new(
-                egui::RichText::new(format!("{} Inspect", egui_phosphor::regular::EYE_SLASH))
-                    .color(egui::Color32::BLACK),
-            )
-            .fill(egui::Color32::GOLD);
-            if ui.add(button).clicked() {
-                responses.push(NodeResponse::User(MyResponse::ClearInspectNode));
-            }
-        }
+
+            ui.menu_button(egui_phosphor::regular::PENCIL_SIMPLE, |ui| {
+                egui::Grid::new(ui.id().with("node_appearance"))
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Label");
+                        let mut label = self.label.clone().unwrap_or_default();
+                        if ui
+                            .add(
+                                egui::TextEdit::singleline(&mut label)
+                                    .hint_text(self.template.node_graph_label(user_state)),
+                            )
+                            .changed()
+                        {
+                            responses.push(NodeResponse::User(MyResponse::SetLabel(
+                                node_id,
+                                (!label.is_empty()).then_some(label),
+                            )));
+                        }
+                        ui.end_row();
+
+                        ui.label("Color");
+                        ui.horizontal(|ui| {
+                            let mut color = self.color.unwrap_or(egui::Color32::GRAY);
+                            if egui::color_picker::color_edit_button_srgba(
+                                ui,
+                                &mut color,
+                                egui::color_picker::Alpha::Opaque,
+                            )
+                            .changed()
+                            {
+                                responses.push(NodeResponse::User(MyResponse::SetColor(
+                                    node_id,
+                                    Some(color),
+                                )));
+                            }
+
+                            if self.color.is_some()
+                                && ui
+                                    .button(egui_phosphor::regular::X)
+                                    .on_hover_text("Use the default color")
+                                    .clicked()
+                            {
+                                responses
+                                    .push(NodeResponse::User(MyResponse::SetColor(node_id, None)));
+                            }
+                        });
+                        ui.end_row();
+                    });
+            })
+            .response
+            .on_hover_text("Change the label and color of this node");
+        });
 
         responses
     }
+
+    fn titlebar_label(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<RgNodeData, RgDataType, RgValueType>,
+        _user_state: &mut Self::UserState,
+    ) -> Option<String> {
+        self.label.clone()
+    }
+
+    fn titlebar_color(
+        &self,
+        _ui: &egui::Ui,
+        _node_id: NodeId,
+        _graph: &Graph<RgNodeData, RgDataType, RgValueType>,
+        _user_state: &mut Self::UserState,
+    ) -> Option<egui::Color32> {
+        self.color
+    }
+
+    fn user_handles_delete(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<RgNodeData, RgDataType, RgValueType>,
+        _user_state: &mut Self::UserState,
+    ) -> bool {
+        // Deletes go through `RenderGraph` so they can be confirmed and undone
+        true
+    }
 }
 
 pub type RgEditorState =
@@ -698,7 +1217,9 @@ impl RenderGraphTab {
         ui: &mut egui::Ui,
         project: &mut Project,
         drag_payload: &mut Option<EditorDragPayload>,
+        context: &ContextWrapper,
     ) -> bool {
+        let timestamp_queries = context.supports(wgpu::Features::TIMESTAMP_QUERY);
         let code_file_names: std::collections::HashMap<Uuid, (CodeFileType, std::path::PathBuf)> =
             project
                 .code_files
@@ -706,11 +1227,64 @@ impl RenderGraphTab {
                 .map(|(id, file)| (*id, (file.ty(), file.relative_path().clone())))
                 .collect();
 
+        let toolbar = egui::TopBottomPanel::top(ui.id().with("toolbar")).show_inside(ui, |ui| {
+            let render_graph = project.render_graph_mut();
+            let mut show_port_types = render_graph.show_port_types();
+            if ui
+                .toggle_value(
+                    &mut show_port_types,
+                    format!("{} Port Types", egui_phosphor::regular::TAG),
+                )
+                .on_hover_text("Label every port with its data type")
+                .changed()
+            {
+                render_graph.set_show_port_types(show_port_types);
+            }
+
+            let profile_passes = render_graph.profile_passes();
+            if ui
+                .add_enabled(
+                    timestamp_queries,
+                    egui::Button::selectable(
+                        profile_passes,
+                        format!("{} Profile", egui_phosphor::regular::TIMER),
+                    ),
+                )
+                .on_hover_text("Time every pass on the GPU")
+                .on_disabled_hover_text("The graphics device doesn't support timestamp queries")
+                .clicked()
+            {
+                render_graph.set_profile_passes(!profile_passes);
+            }
+
+            ui.separator();
+
+            // Edited in MiB, whole bytes are too fine grained to drag
+            let mut budget_mib = render_graph.memory_budget() >> 20;
+            ui.label(format!("{} Memory Budget", egui_phosphor::regular::MEMORY))
+                .on_hover_text("Most memory the textures and buffers of the graph may take up");
+            let budget_changed = ui
+                .add(
+                    egui::DragValue::new(&mut budget_mib)
+                        .range(1..=MAX_MEMORY_BUDGET_MIB)
+                        .suffix(" MiB"),
+                )
+                .changed();
+            if budget_changed {
+                render_graph.set_memory_budget(budget_mib << 20);
+            }
+
+            budget_changed
+        });
+
         let dirty = project
             .render_graph_mut()
-            .ui(ui, code_file_names, drag_payload);
+            .ui(ui, code_file_names, drag_payload)
+            || toolbar.inner;
 
         if dirty {
+            // Stays dirty when saving fails, the autosave then keeps the change
+            project.mark_dirty();
             if let Err(e) = project.save() {
                 log::warn!("Failed to save project: {}", e);
             }
@@ -1,13 +1,204 @@
 use egui::epaint;
+use futures::channel::oneshot;
+use strum::IntoEnumIterator;
 use uuid::Uuid;
 
-use crate::egui_util::EguiPass;
+use crate::{
+    egui_util::EguiPass,
+    time::Timer,
+    wgpu_util::{
+        blit_pass::{self, AspectMode, Tonemap, TonemapBuffer, TonemapOperator},
+        f16_to_f32, BufferReadback,
+    },
+};
+
+const RESOLUTION_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.25..=2.0;
+
+/// Exposure range of the viewport in stops.
+const EXPOSURE_RANGE: std::ops::RangeInclusive<f32> = -8.0..=8.0;
+
+const VIEWPORT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Minimum time in seconds between two pixel readbacks.
+const PIXEL_READBACK_INTERVAL: f32 = 0.1;
+
+/// Reads back the viewport pixel under the cursor, at most once every
+/// [`PIXEL_READBACK_INTERVAL`].
+struct PixelInspector {
+    buffer_readback: BufferReadback,
+    pixel_buffer: wgpu::Buffer,
+    pending: Option<oneshot::Receiver<Vec<u16>>>,
+    timer: Timer,
+    pixel: Option<[u32; 2]>,
+    value: Option<[f32; 4]>,
+}
+
+impl PixelInspector {
+    fn new(device: &wgpu::Device) -> Self {
+        Self {
+            buffer_readback: BufferReadback::new(),
+            pixel_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("viewport pixel readback"),
+                size: VIEWPORT_FORMAT.block_copy_size(None).unwrap() as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            pending: None,
+            timer: Timer::new(),
+            pixel: None,
+            value: None,
+        }
+    }
+
+    fn update(
+        &mut self,
+        texture: &wgpu::Texture,
+        pixel: [u32; 2],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        if let Some(pending) = &mut self.pending {
+            match pending.try_recv() {
+                Ok(Some(data)) => {
+                    self.value = Some([0, 1, 2, 3].map(|i| f16_to_f32(data[i])));
+                    self.pending = None;
+                }
+                Ok(None) => return,
+                Err(_) => self.pending = None,
+            }
+        }
+
+        if self.timer.elapsed() < PIXEL_READBACK_INTERVAL {
+            return;
+        }
+        self.pixel = Some(pixel);
+        self.timer.reset();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("viewport pixel readback"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: pixel[0],
+                    y: pixel[1],
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.pixel_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        self.pending = Some(self.buffer_readback.readback(
+            &self.pixel_buffer,
+            self.pixel_buffer.size(),
+            device,
+            queue,
+        ));
+    }
+
+    fn ui(&self, ui: &mut egui::Ui) {
+        let Some(pixel) = self.pixel else {
+            return;
+        };
+
+        ui.label(format!("Pixel ({}, {})", pixel[0], pixel[1]));
+
+        if let Some(value) = self.value {
+            let [r, g, b, a] = value;
+            let [r8, g8, b8, a8] = value.map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u8);
+
+            ui.horizontal(|ui| {
+                let (swatch_rect, _) =
+                    ui.allocate_exact_size(egui::vec2(32.0, 32.0), egui::Sense::hover());
+                ui.painter().rect_filled(
+                    swatch_rect,
+                    2.0,
+                    egui::Color32::from_rgba_unmultiplied(r8, g8, b8, 255),
+                );
+
+                ui.vertical(|ui| {
+                    ui.monospace(format!("{r:.4} {g:.4} {b:.4} {a:.4}"));
+                    ui.monospace(format!("{r8:>6} {g8:>6} {b8:>6} {a8:>6}"));
+                });
+            });
+        }
+    }
+}
+
+/// How a captured reference frame is shown on top of the live output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumIter, strum::Display)]
+pub enum CompareMode {
+    Off,
+    Blend,
+    Split,
+}
+
+/// A frozen copy of the untonemapped output to compare the live output against.
+struct ReferenceFrame {
+    view: wgpu::TextureView,
+    display: DisplayTexture,
+}
+
+/// Tonemapped copy of a viewport texture that's shown in the ui.
+struct DisplayTexture {
+    view: wgpu::TextureView,
+    ui_id: epaint::TextureId,
+}
+
+impl DisplayTexture {
+    fn new(width: u32, height: u32, egui_pass: &mut EguiPass, device: &wgpu::Device) -> Self {
+        let view = ViewportTab::rebuild_texture(width, height, device)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            ui_id: egui_pass.register_native_texture(device, &view, wgpu::FilterMode::Linear),
+            view,
+        }
+    }
+}
 
-#[derive(Debug, Clone, PartialEq)]
 pub struct ViewportTab {
     id: Uuid,
+    /// The graph renders into this texture, it holds the untonemapped output.
     viewport_texture: wgpu::Texture,
-    viewport_texture_ui_id: epaint::TextureId,
+    display: Option<DisplayTexture>,
+    tonemap_buffer: TonemapBuffer,
+    /// Factor applied to the viewport size before it's used as the graph's screen size.
+    pub resolution_scale: f32,
+    /// Show the value of the pixel under the cursor while hovering the viewport.
+    pub pixel_inspector: bool,
+    inspector: PixelInspector,
+    reference: Option<ReferenceFrame>,
+    pub compare_mode: CompareMode,
+    /// Opacity of the reference frame in [`CompareMode::Blend`].
+    pub compare_blend: f32,
+    /// Horizontal position of the divider in [`CompareMode::Split`], the reference is shown left of it.
+    pub compare_split: f32,
+    /// How the HDR output is mapped to the display range.
+    pub tonemap: Tonemap,
+    /// How outputs with a different aspect ratio than the viewport are shown.
+    pub aspect_mode: AspectMode,
+}
+
+impl PartialEq for ViewportTab {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
 }
 
 impl ViewportTab {
@@ -15,7 +206,17 @@ impl ViewportTab {
         Self {
             id: Uuid::new_v4(),
             viewport_texture: Self::rebuild_texture(32, 32, device),
-            viewport_texture_ui_id: epaint::TextureId::default(),
+            display: None,
+            tonemap_buffer: TonemapBuffer::new(device),
+            resolution_scale: 1.0,
+            pixel_inspector: true,
+            inspector: PixelInspector::new(device),
+            reference: None,
+            compare_mode: CompareMode::Split,
+            compare_blend: 0.5,
+            compare_split: 0.5,
+            tonemap: Tonemap::default(),
+            aspect_mode: AspectMode::default(),
         }
     }
 
@@ -32,24 +233,244 @@ impl ViewportTab {
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
-            format: wgpu::TextureFormat::Rgba16Float,
+            format: VIEWPORT_FORMAT,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             view_formats: &[],
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
         })
     }
 
+    /// Tonemap the graph output and its reference frame into the textures shown in the ui.
+    pub fn present(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let Some(display) = &self.display else {
+            return;
+        };
+        self.tonemap_buffer.write(self.tonemap, queue);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("viewport present"),
+        });
+        let viewport_view = self
+            .viewport_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let reference = self
+            .reference
+            .as_ref()
+            .map(|reference| (&reference.view, &reference.display));
+        for (src_view, display) in std::iter::once((&viewport_view, display)).chain(reference) {
+            blit_pass::encode_blit(
+                &blit_pass::BlitPassParameters {
+                    src_view,
+                    dst_view: &display.view,
+                    target_format: VIEWPORT_FORMAT,
+                    blending: None,
+                    tonemap: Some(&self.tonemap_buffer),
+                    viewport: None,
+                },
+                device,
+                &mut encoder,
+            );
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// Copy the current graph output into a new reference frame.
+    fn capture_reference(
+        &mut self,
+        egui_pass: &mut EguiPass,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let texture = Self::rebuild_texture(
+            self.viewport_texture.width(),
+            self.viewport_texture.height(),
+            device,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("viewport reference capture"),
+        });
+        blit_pass::encode_blit(
+            &blit_pass::BlitPassParameters {
+                src_view: &self
+                    .viewport_texture
+                    .create_view(&wgpu::TextureViewDescriptor::default()),
+                dst_view: &view,
+                target_format: VIEWPORT_FORMAT,
+                blending: None,
+                tonemap: None,
+                viewport: None,
+            },
+            device,
+            &mut encoder,
+        );
+        queue.submit(Some(encoder.finish()));
+
+        if let Some(reference) = self.reference.take() {
+            egui_pass.free_texture(&reference.display.ui_id);
+        }
+        self.reference = Some(ReferenceFrame {
+            view,
+            display: DisplayTexture::new(texture.width(), texture.height(), egui_pass, device),
+        });
+    }
+
+    fn draw_reference(&mut self, ui: &mut egui::Ui, image_rect: egui::Rect) {
+        let Some(reference) = &self.reference else {
+            return;
+        };
+
+        match self.compare_mode {
+            CompareMode::Off => {}
+            CompareMode::Blend => {
+                ui.painter().image(
+                    reference.display.ui_id,
+                    image_rect,
+                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    egui::Color32::from_white_alpha((self.compare_blend * 255.0) as u8),
+                );
+            }
+            CompareMode::Split => {
+                let split_x = egui::lerp(image_rect.x_range(), self.compare_split);
+                ui.painter().image(
+                    reference.display.ui_id,
+                    egui::Rect::from_min_max(image_rect.min, egui::pos2(split_x, image_rect.max.y)),
+                    egui::Rect::from_min_max(
+                        egui::pos2(0.0, 0.0),
+                        egui::pos2(self.compare_split, 1.0),
+                    ),
+                    egui::Color32::WHITE,
+                );
+
+                let divider_rect = egui::Rect::from_x_y_ranges(
+                    split_x - 4.0..=split_x + 4.0,
+                    image_rect.y_range(),
+                );
+                let divider = ui
+                    .interact(
+                        divider_rect,
+                        ui.id().with("compare_split"),
+                        egui::Sense::drag(),
+                    )
+                    .on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+                if let Some(pointer) = divider.interact_pointer_pos() {
+                    self.compare_split =
+                        ((pointer.x - image_rect.min.x) / image_rect.width()).clamp(0.0, 1.0);
+                }
+
+                ui.painter().vline(
+                    split_x,
+                    image_rect.y_range(),
+                    egui::Stroke::new(2.0, egui::Color32::WHITE),
+                );
+            }
+        }
+    }
+
+    fn toolbar_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        egui_pass: &mut EguiPass,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        ui.add(
+            egui::Slider::new(&mut self.resolution_scale, RESOLUTION_SCALE_RANGE)
+                .custom_formatter(|scale, _| format!("{:.0}%", scale * 100.0))
+                .custom_parser(|text| {
+                    text.trim_end_matches('%')
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .map(|percent| percent / 100.0)
+                })
+                .text("Resolution"),
+        )
+        .on_hover_text("Render the graph at a fraction of the viewport resolution");
+
+        egui::ComboBox::from_id_salt(ui.id().with("tonemap_operator"))
+            .selected_text(self.tonemap.operator.to_string())
+            .show_ui(ui, |ui| {
+                for operator in TonemapOperator::iter() {
+                    ui.selectable_value(&mut self.tonemap.operator, operator, operator.to_string());
+                }
+            })
+            .response
+            .on_hover_text("Tonemapping applied to the displayed output");
+
+        egui::ComboBox::from_id_salt(ui.id().with("aspect_mode"))
+            .selected_text(self.aspect_mode.to_string())
+            .show_ui(ui, |ui| {
+                for mode in AspectMode::iter() {
+                    ui.selectable_value(&mut self.aspect_mode, mode, mode.to_string());
+                }
+            })
+            .response
+            .on_hover_text("Stretch outputs of a different aspect ratio or fit them with bars");
+
+        ui.add(
+            egui::Slider::new(&mut self.tonemap.exposure, EXPOSURE_RANGE)
+                .step_by(0.1)
+                .suffix(" EV")
+                .text("Exposure"),
+        )
+        .on_hover_text("Scale the output by 2^exposure before tonemapping");
+
+        if ui
+            .button(format!(
+                "{} Capture Reference",
+                egui_phosphor::regular::CAMERA
+            ))
+            .on_hover_text("Freeze the current output to compare against")
+            .clicked()
+        {
+            self.capture_reference(egui_pass, device, queue);
+        }
+
+        if self.reference.is_some() {
+            egui::ComboBox::from_id_salt(ui.id().with("compare_mode"))
+                .selected_text(self.compare_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in CompareMode::iter() {
+                        ui.selectable_value(&mut self.compare_mode, mode, mode.to_string());
+                    }
+                });
+
+            if self.compare_mode == CompareMode::Blend {
+                ui.add(egui::Slider::new(&mut self.compare_blend, 0.0..=1.0).text("Blend"));
+            }
+
+            if ui
+                .button(egui_phosphor::regular::X)
+                .on_hover_text("Discard the reference frame")
+                .clicked()
+            {
+                if let Some(reference) = self.reference.take() {
+                    egui_pass.free_texture(&reference.display.ui_id);
+                }
+            }
+        }
+    }
+
     pub fn ui(
         &mut self,
         ui: &mut egui::Ui,
         egui_pass: &mut EguiPass,
         viewport_texture: &mut Option<(wgpu::TextureView, [u32; 2])>,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
     ) {
         let size = ui.available_size();
-        let width = (size.x.ceil() as u32).max(1);
-        let height = (size.y.ceil() as u32).max(1);
+
+        // Render at the scaled size, the image is stretched back to the full viewport
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        let scaled_size = size * self.resolution_scale;
+        let width = (scaled_size.x.ceil() as u32).clamp(1, max_dimension);
+        let height = (scaled_size.y.ceil() as u32).clamp(1, max_dimension);
 
         let rebuild =
             self.viewport_texture.width() != width || self.viewport_texture.height() != height;
@@ -62,15 +483,49 @@ impl ViewportTab {
                 .viewport_texture
                 .create_view(&wgpu::TextureViewDescriptor::default());
 
-            if self.viewport_texture_ui_id != epaint::TextureId::default() {
-                egui_pass.free_texture(&self.viewport_texture_ui_id);
+            if let Some(display) = self.display.take() {
+                egui_pass.free_texture(&display.ui_id);
             }
-            self.viewport_texture_ui_id =
-                egui_pass.register_native_texture(device, &texture_view, wgpu::FilterMode::Linear);
+            self.display = Some(DisplayTexture::new(width, height, egui_pass, device));
 
             *viewport_texture = Some((texture_view, [width, height]));
         }
 
-        ui.image((self.viewport_texture_ui_id, size));
+        let display_ui_id = self
+            .display
+            .as_ref()
+            .map_or(epaint::TextureId::default(), |display| display.ui_id);
+        let image_response = ui.image((display_ui_id, size));
+        let image_rect = image_response.rect;
+
+        // The pixel inspector reads the live output, also where the reference is shown
+        if self.pixel_inspector {
+            if let Some(hover_pos) = image_response.hover_pos() {
+                let uv = (hover_pos - image_rect.min) / image_rect.size();
+                let pixel = [
+                    ((uv.x * width as f32) as u32).min(width - 1),
+                    ((uv.y * height as f32) as u32).min(height - 1),
+                ];
+
+                self.inspector
+                    .update(&self.viewport_texture, pixel, device, queue);
+                // Keep polling while hovered, the readback only resolves on later frames
+                ui.ctx()
+                    .request_repaint_after(std::time::Duration::from_secs_f32(
+                        PIXEL_READBACK_INTERVAL,
+                    ));
+                image_response.on_hover_ui_at_pointer(|ui| self.inspector.ui(ui));
+            }
+        }
+
+        self.draw_reference(ui, image_rect);
+
+        let toolbar_rect = egui::Rect::from_min_size(
+            image_rect.min + egui::vec2(8.0, 8.0),
+            egui::vec2(image_rect.width() - 16.0, ui.spacing().interact_size.y),
+        );
+        ui.scope_builder(egui::UiBuilder::new().max_rect(toolbar_rect), |ui| {
+            ui.horizontal(|ui| self.toolbar_ui(ui, egui_pass, device, queue));
+        });
     }
 }
@@ -21,6 +21,7 @@ pub struct EguiPass {
     context: Option<egui::Context>,
     state: State,
     renderer: Renderer,
+    repaint_delay: std::time::Duration,
 }
 
 impl EguiPass {
@@ -50,11 +51,19 @@ impl EguiPass {
             context: Some(egui_context),
             state: egui_state,
             renderer: egui_renderer,
+            repaint_delay: std::time::Duration::ZERO,
         }
     }
 
-    pub fn handle_window_event(&mut self, window: &Window, event: &WindowEvent) {
-        let _ = self.state.on_window_event(window, event);
+    /// Returns whether egui wants to be repainted in response to the event.
+    pub fn handle_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).repaint
+    }
+
+    /// Time until egui asked to be repainted during the last encoded frame,
+    /// [`std::time::Duration::MAX`] when it can stay idle until the next event.
+    pub fn repaint_delay(&self) -> std::time::Duration {
+        self.repaint_delay
     }
 
     pub fn begin_frame(&mut self, window: &Window) -> egui::Context {
@@ -83,6 +92,11 @@ impl EguiPass {
 
         let full_output = context.end_pass();
 
+        self.repaint_delay = full_output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .map_or(std::time::Duration::MAX, |viewport| viewport.repaint_delay);
+
         self.state
             .handle_platform_output(window, full_output.platform_output);
 
@@ -3,10 +3,11 @@ use std::sync::Arc;
 use crate::{
     editor::Editor,
     egui_util::{EguiPass, KeyModifiers},
-    project::{CodeFileType, Project},
+    project::Project,
     render_graph::compiled_render_graph::CompiledRenderGraph,
     runtime::{Runtime, Static},
     time::FpsCounter,
+    wgpu_util::{context_wrapper::ContextWrapper, BufferReadback},
 };
 
 pub mod editor;
@@ -36,9 +37,15 @@ impl App {
 pub struct RenderPipeline {
     surface_config: wgpu::SurfaceConfiguration,
     compiled_rg: Option<CompiledRenderGraph>,
+    buffer_readback: BufferReadback,
 }
 
 impl runtime::RenderPipeline<App> for RenderPipeline {
+    fn optional_features() -> wgpu::Features {
+        // Used to time the render graph passes when available
+        wgpu::Features::TIMESTAMP_QUERY
+    }
+
     fn required_limits() -> wgpu::Limits {
         wgpu::Limits {
             max_texture_dimension_2d: 1024 * 8,
@@ -56,6 +63,7 @@ impl runtime::RenderPipeline<App> for RenderPipeline {
         Self {
             surface_config,
             compiled_rg: None,
+            buffer_readback: BufferReadback::new(),
         }
     }
 
@@ -68,17 +76,47 @@ impl runtime::RenderPipeline<App> for RenderPipeline {
         self.surface_config = surface_config;
     }
 
+    fn close_requested(&mut self, app: &mut App) -> bool {
+        app.editor.request_close()
+    }
+
+    fn should_exit(&mut self, app: &mut App) -> bool {
+        app.editor.should_exit()
+    }
+
+    fn present_mode(
+        &mut self,
+        supported: &[wgpu::PresentMode],
+        current: wgpu::PresentMode,
+        app: &mut App,
+    ) -> wgpu::PresentMode {
+        app.editor.update_present_mode(supported, current)
+    }
+
+    fn animating(&self, app: &App) -> bool {
+        // Only history resources change the output without any input, and that's only worth
+        // redrawing while it's on screen. Without a viewport the graph renders into the window.
+        let on_screen = app.editor.viewport_visible() || app.editor.viewport_texture().is_none();
+        self.compiled_rg.is_some()
+            && on_screen
+            && app
+                .project
+                .as_ref()
+                .is_some_and(|project| project.render_graph().uses_history())
+    }
+
     fn render(
         &mut self,
         target_view: &wgpu::TextureView,
         target_format: wgpu::TextureFormat,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
+        context: &ContextWrapper,
         egui_ctx: &mut egui::Context,
         egui_pass: &mut EguiPass,
         key_modifiers: &KeyModifiers,
         app: &mut App,
     ) {
+        let (device, queue) = (&context.device, &context.queue);
+
         if app.fps_counter.update() {
             log::info!(
                 "FPS {} (ms {:.2})",
@@ -94,22 +132,14 @@ impl runtime::RenderPipeline<App> for RenderPipeline {
             &mut app.project,
             key_modifiers,
             &mut render_graph_dirty,
-            device,
+            context,
         );
 
         if let Some(project) = &mut app.project {
-            // TODO: cloning all sources here is slow
-            let code_sources: Vec<(uuid::Uuid, String)> = project
-                .code_files
-                .files_iter()
-                .filter(|(_, f)| f.ty() == CodeFileType::Fragment)
-                .map(|(id, f)| (*id, f.source.clone()))
-                .collect();
+            // Update the shaders when the code changed and retrieve if there are any dirty shaders
+            let shaders_dirty = project.sync_shaders(device);
 
             let rg = project.render_graph_mut();
-
-            // Update all shaders and retrieve if there are any dirty shaders
-            let shaders_dirty = rg.sync_graphics_shaders(&code_sources, device);
             if shaders_dirty {
                 // If there were dirty shaders we need to update the dynamic node inputs
                 rg.sync_dynamic_node_inputs();
@@ -143,7 +173,17 @@ impl runtime::RenderPipeline<App> for RenderPipeline {
             };
 
             // If any of the shaders are dirty, the graph itself or the target resolution, we recompile
-            if shaders_dirty || render_graph_dirty || viewport_resolution_dirty {
+            // Shader edits only rebuild the affected passes when nothing else about the graph changed
+            let passes_updated = shaders_dirty
+                && !render_graph_dirty
+                && !viewport_resolution_dirty
+                && self
+                    .compiled_rg
+                    .as_mut()
+                    .is_some_and(|compiled_rg| rg.update_compiled(compiled_rg, device));
+
+            if !passes_updated && (shaders_dirty || render_graph_dirty || viewport_resolution_dirty)
+            {
                 log::info!(
                     "RECOMPILE RG shaders={} rg={} resolution={}!",
                     shaders_dirty,
@@ -151,7 +191,7 @@ impl runtime::RenderPipeline<App> for RenderPipeline {
                     viewport_resolution_dirty
                 );
 
-                match rg.compile(rg_target_resolution, device) {
+                match rg.compile(rg_target_resolution, context) {
                     Ok(compiled_rg) => {
                         // Cache the compiled graph when succesful
                         self.compiled_rg = Some(compiled_rg);
@@ -164,11 +204,25 @@ impl runtime::RenderPipeline<App> for RenderPipeline {
             }
 
             // Execute the render graph
-            if let Some(compiled_rg) = &self.compiled_rg {
-                let encoder =
-                    compiled_rg.record_command_encoder(device, rg_target_view, rg_target_format);
+            if let Some(compiled_rg) = &mut self.compiled_rg {
+                rg.poll_validation(compiled_rg);
+                compiled_rg.set_profiling(rg.profile_passes());
+                let encoder = compiled_rg.record_command_encoder(
+                    device,
+                    queue,
+                    rg_target_view,
+                    rg_target_format,
+                    rg.inspect_node(),
+                    app.editor.viewport_aspect_mode(),
+                );
 
                 queue.submit(Some(encoder.finish()));
+                app.editor.present_viewport(device, queue);
+
+                compiled_rg.read_timestamps(&self.buffer_readback, device, queue);
+                rg.set_pass_timings(compiled_rg.last_pass_timings());
+                compiled_rg.read_texels(&self.buffer_readback, device, queue);
+                rg.set_texel_values(compiled_rg.texel_values());
             }
         }
     }
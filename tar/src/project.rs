@@ -4,17 +4,26 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use strum::EnumIter;
+use strum::{EnumIter, IntoEnumIterator};
 use uuid::Uuid;
 
-use crate::render_graph::RenderGraph;
+use crate::{
+    editor::code_editor::settings::EditorSettings,
+    render_graph::{
+        bundle::{GraphBundle, BUNDLE_GRAPH_FILE, BUNDLE_VERSION},
+        RenderGraph,
+    },
+};
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, EnumIter)]
 pub enum CodeFileType {
     Fragment,
     Compute,
     Shared,
+    /// Edited with GLSL highlighting, not compiled by the render graph.
+    Glsl,
 }
 
 impl CodeFileType {
@@ -23,6 +32,7 @@ impl CodeFileType {
             Self::Fragment => egui_phosphor::regular::IMAGE,
             Self::Compute => egui_phosphor::regular::CPU,
             Self::Shared => egui_phosphor::regular::CODE_SIMPLE,
+            Self::Glsl => egui_phosphor::regular::FILE_CODE,
         }
     }
 
@@ -31,6 +41,7 @@ impl CodeFileType {
             Self::Fragment => format!("{} Fragment", self.icon()),
             Self::Compute => format!("{} Compute", self.icon()),
             Self::Shared => format!("{} Shared", self.icon()),
+            Self::Glsl => format!("{} GLSL", self.icon()),
         }
     }
 
@@ -39,6 +50,7 @@ impl CodeFileType {
             Self::Fragment => "frag",
             Self::Compute => "comp",
             Self::Shared => "shared",
+            Self::Glsl => "glsl",
         }
     }
 }
@@ -97,6 +109,18 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 fn my_func(a: u32, b: u32) -> u32 {
     return a + b;
 }
+"#,
+            ),
+            Self::Glsl => String::from(
+                r#"
+#version 450
+
+layout(location = 0) in vec2 tex_coords;
+layout(location = 0) out vec4 color;
+
+void main() {
+    color = vec4(tex_coords, 0.0, 1.0);
+}
 "#,
             ),
         }
@@ -109,6 +133,9 @@ pub struct CodeFile {
     relative_path: PathBuf,
     ty: CodeFileType,
     pub source: String,
+    /// Unsaved source from an open editor that shaders are compiled from instead of `source`.
+    #[serde(skip)]
+    live_source: Option<String>,
 }
 
 impl CodeFile {
@@ -120,6 +147,7 @@ impl CodeFile {
             relative_path: relative_path.into(),
             ty,
             source,
+            live_source: None,
         }
     }
 
@@ -135,6 +163,11 @@ impl CodeFile {
         &self.relative_path
     }
 
+    /// The source shaders should be compiled from, including unsaved live edits.
+    pub fn compile_source(&self) -> &str {
+        self.live_source.as_deref().unwrap_or(&self.source)
+    }
+
     pub fn path(&self, code_path: &Path) -> PathBuf {
         code_path.join(&self.relative_path)
     }
@@ -147,18 +180,14 @@ pub struct CodeFiles {
     /// Tracks explicitly created empty folders (not derived from file paths)
     #[serde(default)]
     extra_dirs: HashSet<PathBuf>,
+    /// Bumped whenever a source changes or a file is added or removed.
+    #[serde(skip)]
+    revision: u64,
 }
 
 impl CodeFiles {
     pub fn new<P: Into<PathBuf>>(project_path: P) -> Self {
-        let project_path = project_path.into();
-        let code_path = project_path.join("code");
-
-        let mut code_files = Self {
-            code_path,
-            files: HashMap::new(),
-            extra_dirs: HashSet::new(),
-        };
+        let mut code_files = Self::empty(project_path);
 
         let _ = code_files.create_file("main", CodeFileType::Fragment);
         // let _ = code_files.create_file("bake_noise", CodeFileType::Compute);
@@ -179,6 +208,17 @@ impl CodeFiles {
         code_files
     }
 
+    pub fn empty<P: Into<PathBuf>>(project_path: P) -> Self {
+        let project_path = project_path.into();
+
+        Self {
+            code_path: project_path.join("code"),
+            files: HashMap::new(),
+            extra_dirs: HashSet::new(),
+            revision: 0,
+        }
+    }
+
     pub fn contains_file<P: AsRef<Path>>(&self, relative_path: P) -> bool {
         let relative_path = relative_path.as_ref();
         self.files
@@ -210,6 +250,7 @@ impl CodeFiles {
         let file = CodeFile::new(relative_path, ty);
         let id = file.id;
         self.files.insert(id, file);
+        self.revision += 1;
 
         self.save_file(id)?;
 
@@ -280,6 +321,31 @@ impl CodeFiles {
     pub fn set_source<S: Into<String>>(&mut self, id: Uuid, source: S) -> anyhow::Result<()> {
         if let Some(code_file) = self.files.get_mut(&id) {
             code_file.source = source.into();
+            code_file.live_source = None;
+            self.revision += 1;
+            Ok(())
+        } else {
+            anyhow::bail!("No code file found with id {}", id);
+        }
+    }
+
+    /// Set unsaved source to compile from without touching the saved source.
+    pub fn set_live_source<S: Into<String>>(&mut self, id: Uuid, source: S) -> anyhow::Result<()> {
+        if let Some(code_file) = self.files.get_mut(&id) {
+            code_file.live_source = Some(source.into());
+            self.revision += 1;
+            Ok(())
+        } else {
+            anyhow::bail!("No code file found with id {}", id);
+        }
+    }
+
+    /// Compile from the saved source again, dropping any unsaved source.
+    pub fn clear_live_source(&mut self, id: Uuid) -> anyhow::Result<()> {
+        if let Some(code_file) = self.files.get_mut(&id) {
+            if code_file.live_source.take().is_some() {
+                self.revision += 1;
+            }
             Ok(())
         } else {
             anyhow::bail!("No code file found with id {}", id);
@@ -318,6 +384,7 @@ impl CodeFiles {
             let mut loaded_src = String::new();
             file.read_to_string(&mut loaded_src)?;
             code_file.source = loaded_src;
+            self.revision += 1;
 
             Ok(())
         } else {
@@ -471,6 +538,7 @@ impl CodeFiles {
 
     pub fn delete_file(&mut self, id: Uuid) -> anyhow::Result<()> {
         if let Some(file) = self.files.remove(&id) {
+            self.revision += 1;
             std::fs::remove_file(file.path(&self.code_path))?;
         } else {
             anyhow::bail!("No code file found with id {}", id);
@@ -507,6 +575,7 @@ impl CodeFiles {
 
         for id in files_to_delete {
             self.files.remove(&id);
+            self.revision += 1;
         }
 
         // Delete the folder from disk
@@ -518,6 +587,11 @@ impl CodeFiles {
         Ok(())
     }
 
+    /// Changes whenever shaders may need to be recompiled, cheap to compare against a previous value.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
     pub fn files_iter(&self) -> impl Iterator<Item = (&Uuid, &CodeFile)> {
         self.files.iter()
     }
@@ -527,6 +601,63 @@ impl CodeFiles {
     }
 }
 
+/// Starting point for a new project.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter)]
+pub enum ProjectTemplate {
+    Empty,
+    #[default]
+    FullscreenFragment,
+    Compute,
+    Atmosphere,
+}
+
+impl ProjectTemplate {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Empty => "Empty",
+            Self::FullscreenFragment => "Fullscreen Fragment",
+            Self::Compute => "Compute",
+            Self::Atmosphere => "Atmosphere Example",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Empty => "No code files, only the display output",
+            Self::FullscreenFragment => "A fragment shader drawn over the whole screen",
+            Self::Compute => "A compute shader next to the fullscreen fragment shader",
+            Self::Atmosphere => "A physically based sky rendered by a fullscreen pass",
+        }
+    }
+
+    /// Code files the template starts with, paths are relative to the code directory.
+    fn code_files(&self) -> Vec<(&'static str, CodeFileType, String)> {
+        let fullscreen = (
+            "main",
+            CodeFileType::Fragment,
+            include_str!("../assets/templates/fullscreen.frag.wgsl").to_owned(),
+        );
+
+        match self {
+            Self::Empty => Vec::new(),
+            Self::FullscreenFragment => vec![fullscreen],
+            Self::Compute => vec![
+                fullscreen,
+                (
+                    "compute",
+                    CodeFileType::Compute,
+                    CodeFileType::Compute.default_source(),
+                ),
+            ],
+            Self::Atmosphere => vec![(
+                "atmosphere",
+                CodeFileType::Fragment,
+                include_str!("../assets/templates/atmosphere.frag.wgsl").to_owned(),
+            )],
+        }
+    }
+}
+
 // TODO: after deserialize (so load), also override the CodeFiles sources with what's on disk, if it can be found
 // so basically a soft load, don't care if it fails to load any files, we still got the source from the project
 // after this step, also perform a save, to again make sure the missing files on disk (but still present in the project) are pooped out to disk
@@ -535,6 +666,75 @@ pub struct Project {
     path: PathBuf,
     render_graph: RenderGraph,
     pub code_files: CodeFiles,
+    #[serde(default)]
+    pub editor_settings: EditorSettings,
+    /// Changed since the project was last saved or written to its recovery file.
+    #[serde(skip)]
+    dirty: bool,
+}
+
+/// Autosaved state of a project, see [`Project::write_recovery`].
+#[derive(Serialize, Deserialize)]
+struct Recovery<P> {
+    project: P,
+    /// Sources of code editors that weren't saved yet.
+    unsaved_sources: HashMap<Uuid, String>,
+}
+
+fn recovery_path(project_path: &Path) -> PathBuf {
+    project_path.with_extension("tarproj.recovery")
+}
+
+/// Newest modification time of the code files in `dir` and its subfolders.
+fn newest_code_file(dir: &Path) -> Option<std::time::SystemTime> {
+    let extensions: Vec<&str> = CodeFileType::iter().map(|ty| ty.file_extension()).collect();
+
+    let mut newest = None;
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let path = entry.path();
+            if metadata.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| extensions.contains(&extension))
+            {
+                newest = newest.max(metadata.modified().ok());
+            }
+        }
+    }
+    newest
+}
+
+/// Whether the autosave left changes behind that are newer than the project saved at `path`
+/// and all of its code files, restoring an older recovery file would overwrite saved work.
+pub fn has_newer_recovery(path: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    let (Some(recovery), Some(saved)) = (modified(&recovery_path(path)), modified(path)) else {
+        return false;
+    };
+    recovery > saved
+        && path
+            .parent()
+            .and_then(newest_code_file)
+            .is_none_or(|code_file| recovery > code_file)
+}
+
+/// Delete the recovery file of the project saved at `path`, if there is one.
+pub fn discard_recovery(path: &Path) -> anyhow::Result<()> {
+    match std::fs::remove_file(recovery_path(path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
 }
 
 impl Project {
@@ -547,9 +747,39 @@ impl Project {
             path,
             code_files,
             render_graph,
+            editor_settings: EditorSettings::default(),
+            dirty: false,
         }
     }
 
+    /// Create a project with the code files of `template`, with a render graph that
+    /// draws its fragment shader to the display.
+    pub fn from_template<P: Into<PathBuf>>(
+        path: P,
+        template: ProjectTemplate,
+    ) -> anyhow::Result<Self> {
+        let path = path.into();
+        let mut code_files = CodeFiles::empty(path.parent().unwrap());
+        for (relative_path, ty, source) in template.code_files() {
+            let id = code_files.create_file(relative_path, ty)?;
+            code_files.set_source(id, source)?;
+        }
+
+        let render_graph = if template == ProjectTemplate::Empty {
+            RenderGraph::empty()
+        } else {
+            RenderGraph::new(&code_files)
+        };
+
+        Ok(Self {
+            path,
+            code_files,
+            render_graph,
+            editor_settings: EditorSettings::default(),
+            dirty: false,
+        })
+    }
+
     pub fn render_graph(&self) -> &RenderGraph {
         &self.render_graph
     }
@@ -558,20 +788,163 @@ impl Project {
         &mut self.render_graph
     }
 
-    pub fn save(&self) -> anyhow::Result<()> {
+    /// Recompile the shaders of the render graph when the code files changed since the last sync.
+    /// Returns whether any shader changed.
+    pub fn sync_shaders(&mut self, device: &wgpu::Device) -> bool {
+        self.render_graph.sync_code_files(&self.code_files, device)
+    }
+
+    /// Directory the render graph bundle is written to by [`Self::export_render_graph`].
+    pub fn export_path(&self) -> PathBuf {
+        self.path.parent().unwrap().join("export")
+    }
+
+    /// Export the render graph and the shaders it uses as a portable bundle.
+    pub fn export_render_graph(&self) -> anyhow::Result<PathBuf> {
+        let export_path = self.export_path();
+        self.render_graph
+            .export_bundle(&self.code_files, &export_path)?;
+
+        Ok(export_path)
+    }
+
+    /// Replace the render graph with the bundle exported to `dir`, adding its shaders as code files.
+    /// Shaders whose path is taken by a different code file are imported under a new name.
+    /// Returns a description of everything that could not be restored.
+    pub fn import_render_graph(&mut self, dir: &Path) -> anyhow::Result<Vec<String>> {
+        let file = std::fs::File::open(dir.join(BUNDLE_GRAPH_FILE))?;
+        let reader = std::io::BufReader::new(file);
+        let bundle: GraphBundle = serde_json::from_reader(reader)?;
+
+        if bundle.version > BUNDLE_VERSION {
+            anyhow::bail!(
+                "Graph bundle version {} is newer than the supported version {}",
+                bundle.version,
+                BUNDLE_VERSION
+            );
+        }
+
+        let mut code_file_ids = HashMap::new();
+        for shader in &bundle.shaders {
+            let source = std::fs::read_to_string(dir.join(&shader.file))?;
+            let extension = shader.ty.file_extension();
+
+            let mut relative_path = shader.relative_path.with_extension(extension);
+            let mut suffix = 1;
+            let id = loop {
+                let existing = self
+                    .code_files
+                    .files_iter()
+                    .find(|(_, file)| *file.relative_path() == relative_path);
+
+                match existing {
+                    Some((id, file)) if file.ty() == shader.ty && file.source == source => {
+                        break *id;
+                    }
+                    Some(_) => {
+                        let stem = shader
+                            .relative_path
+                            .file_stem()
+                            .unwrap_or_default()
+                            .to_string_lossy();
+                        relative_path = shader
+                            .relative_path
+                            .with_file_name(format!("{}_{}", stem, suffix))
+                            .with_extension(extension);
+                        suffix += 1;
+                    }
+                    None => {
+                        let id = self.code_files.create_file(&relative_path, shader.ty)?;
+                        self.code_files.set_source(id, source)?;
+                        self.code_files.save_file(id)?;
+                        break id;
+                    }
+                }
+            };
+
+            code_file_ids.insert(shader.id, id);
+        }
+
+        let (render_graph, problems) = RenderGraph::from_bundle(&bundle, &code_file_ids);
+        self.render_graph = render_graph;
+
+        Ok(problems)
+    }
+
+    pub fn save(&mut self) -> anyhow::Result<()> {
         let file = std::fs::File::create(&self.path)?;
         let writer = std::io::BufWriter::new(file);
         serde_json::to_writer(writer, &self)?;
 
         self.code_files.save_all()?;
 
+        self.dirty = false;
+        discard_recovery(&self.path)?;
+
         Ok(())
     }
 
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Delete the recovery file, for when the unsaved changes in it were thrown away.
+    pub fn discard_recovery(&mut self) -> anyhow::Result<()> {
+        self.dirty = false;
+        discard_recovery(&self.path)
+    }
+
+    /// Write the project together with the unsaved sources of open code editors next to the
+    /// project file, so they can be restored with [`Self::restore_recovery`] after a crash.
+    pub fn write_recovery(&mut self, unsaved_sources: HashMap<Uuid, String>) -> anyhow::Result<()> {
+        let recovery_path = recovery_path(&self.path);
+
+        // A crash while writing keeps the previous recovery file intact
+        let tmp_path = recovery_path.with_extension("tmp");
+        let file = std::fs::File::create(&tmp_path)?;
+        let writer = std::io::BufWriter::new(file);
+        serde_json::to_writer(
+            writer,
+            &Recovery {
+                project: &*self,
+                unsaved_sources,
+            },
+        )?;
+        std::fs::rename(tmp_path, recovery_path)?;
+
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    /// Load the autosaved state of the project saved at `path` and save it over the project.
+    pub fn restore_recovery(path: &Path) -> anyhow::Result<Self> {
+        let recovery_path = recovery_path(path);
+        let file = std::fs::File::open(&recovery_path)?;
+        let reader = std::io::BufReader::new(file);
+        let recovery: Recovery<Project> = serde_json::from_reader(reader)
+            .with_context(|| format!("{} is not a valid recovery file", recovery_path.display()))?;
+
+        let mut project = recovery.project;
+        for (id, source) in recovery.unsaved_sources {
+            if let Err(e) = project.code_files.set_source(id, source) {
+                log::warn!("Failed to restore unsaved source: {}", e);
+            }
+        }
+        project.save()?;
+
+        Ok(project)
+    }
+
     pub fn load(path: &Path) -> anyhow::Result<Self> {
         let file = std::fs::File::open(path)?;
         let reader = std::io::BufReader::new(file);
-        let mut data: Project = serde_json::from_reader(reader)?;
+        let mut data: Project = serde_json::from_reader(reader)
+            .with_context(|| format!("{} is not a valid project file", path.display()))?;
 
         data.code_files.load_all()?;
 
@@ -593,11 +966,74 @@ impl Project {
     }
 }
 
+const RECENT_PROJECTS_FILE: &str = "recent_projects.json";
+const MAX_RECENT_PROJECTS: usize = 10;
+
+/// Project files that were recently opened or created, most recent first.
+pub fn recent_projects() -> Vec<PathBuf> {
+    app_data_path()
+        .and_then(|dir| std::fs::read_to_string(dir.join(RECENT_PROJECTS_FILE)).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Move `path` to the front of the recent projects.
+pub fn add_recent_project(path: &Path) -> anyhow::Result<()> {
+    let dir = app_data_path().context("No directory to store recent projects in")?;
+    let path = std::path::absolute(path)?;
+
+    let mut recent = recent_projects();
+    recent.retain(|recent_path| *recent_path != path);
+    recent.insert(0, path);
+    recent.truncate(MAX_RECENT_PROJECTS);
+
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(
+        dir.join(RECENT_PROJECTS_FILE),
+        serde_json::to_string_pretty(&recent)?,
+    )?;
+
+    Ok(())
+}
+
+/// Show `path` selected in the file manager of the OS, does nothing where there is none.
+#[cfg(any(target_os = "android", target_arch = "wasm32"))]
+pub fn reveal_in_file_manager(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Show `path` selected in the file manager of the OS, does nothing where there is none.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+pub fn reveal_in_file_manager(path: &Path) -> anyhow::Result<()> {
+    use std::process::Command;
+
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "windows")] {
+            let mut select = std::ffi::OsString::from("/select,");
+            select.push(path);
+            Command::new("explorer").arg(select).spawn()?;
+        } else if #[cfg(target_os = "macos")] {
+            Command::new("open").arg("-R").arg(path).spawn()?;
+        } else {
+            // There is no common way to select a file, open the folder containing it instead
+            let dir = path.parent().context("File has no containing folder")?;
+            Command::new("xdg-open").arg(dir).spawn()?;
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(target_os = "android")]
 pub fn default_project_path() -> Option<PathBuf> {
     get_external_files_dir()
 }
 
+#[cfg(target_os = "android")]
+fn app_data_path() -> Option<PathBuf> {
+    get_external_files_dir()
+}
+
 #[cfg(target_os = "android")]
 fn get_external_files_dir() -> Option<PathBuf> {
     use jni::objects::{JObject, JValue};
@@ -641,6 +1077,11 @@ fn get_external_files_dir() -> Option<PathBuf> {
     Some(PathBuf::from(path_str.into()))
 }
 
+#[cfg(not(target_os = "android"))]
+fn app_data_path() -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "tar").map(|dirs| dirs.config_dir().to_path_buf())
+}
+
 #[cfg(not(target_os = "android"))]
 pub fn default_project_path() -> Option<PathBuf> {
     directories::UserDirs::new()
@@ -0,0 +1,468 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    editor::{
+        node_graph::{InputParamKind, NodeId, NodeTemplateTrait},
+        tabs::render_graph::RgEditorState,
+    },
+    project::{CodeFileType, CodeFiles},
+    render_graph::{
+        RenderGraph, RgDataType, RgGraphState, RgNodeData, RgNodeTemplate, RgValueType,
+    },
+};
+
+/// Name of the graph description inside an exported bundle directory.
+pub const BUNDLE_GRAPH_FILE: &str = "graph.json";
+/// Graphviz description of the graph inside an exported bundle, see [`RenderGraph::to_dot`].
+pub const BUNDLE_DOT_FILE: &str = "graph.dot";
+/// Directory inside an exported bundle that holds the shader sources.
+pub const BUNDLE_SHADER_DIR: &str = "shaders";
+/// Bumped whenever the layout of [`GraphBundle`] changes incompatibly.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Portable description of a render graph, independent of the slotmap ids used by the editor.
+/// Nodes are referenced by their index in [`GraphBundle::nodes`].
+#[derive(Serialize, Deserialize)]
+pub struct GraphBundle {
+    pub version: u32,
+    pub nodes: Vec<BundleNode>,
+    pub connections: Vec<BundleConnection>,
+    pub shaders: Vec<BundleShader>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundleNode {
+    pub template: RgNodeTemplate,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub color: Option<egui::Color32>,
+    #[serde(default)]
+    pub bypass: bool,
+    pub position: [f32; 2],
+    pub inputs: Vec<BundleInput>,
+    pub outputs: Vec<BundleOutput>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundleInput {
+    pub name: String,
+    pub data_type: RgDataType,
+    pub value: RgValueType,
+    /// Whether the input consumes the connected resource, needed to recreate shader binding inputs.
+    #[serde(default)]
+    pub consumer: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundleOutput {
+    pub name: String,
+    pub data_type: RgDataType,
+}
+
+/// Connection from an output of one node to an input of another.
+#[derive(Serialize, Deserialize)]
+pub struct BundleConnection {
+    pub from_node: usize,
+    pub from_output: String,
+    pub to_node: usize,
+    pub to_input: String,
+}
+
+/// Shader source referenced by a [`RgValueType::CodeFile`] input.
+#[derive(Serialize, Deserialize)]
+pub struct BundleShader {
+    pub id: Uuid,
+    pub ty: CodeFileType,
+    /// Path of the code file relative to the project code directory.
+    pub relative_path: PathBuf,
+    /// Path of the WGSL source relative to the bundle directory.
+    pub file: PathBuf,
+}
+
+/// Name of the input or output with `id` among the ports of a node.
+fn port_name<Id: PartialEq>(ports: &[(String, Id)], id: Id) -> &str {
+    ports
+        .iter()
+        .find(|(_, port_id)| *port_id == id)
+        .map_or("", |(name, _)| name.as_str())
+}
+
+impl RenderGraph {
+    /// Describe the graph and the shaders its passes use as a [`GraphBundle`],
+    /// returned together with the WGSL source of every shader.
+    pub fn to_bundle(&self, code_files: &CodeFiles) -> (GraphBundle, Vec<(PathBuf, String)>) {
+        let graph = &self.node_graph.graph;
+
+        let node_ids: Vec<NodeId> = graph.iter_nodes().collect();
+        let node_indices: HashMap<NodeId, usize> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(i, node_id)| (*node_id, i))
+            .collect();
+
+        let mut shaders = Vec::new();
+        let mut sources = Vec::new();
+
+        let nodes = node_ids
+            .iter()
+            .map(|node_id| {
+                let node = &graph[*node_id];
+
+                let inputs = node
+                    .inputs
+                    .iter()
+                    .map(|(name, input_id)| {
+                        let input = &graph[*input_id];
+
+                        if let RgValueType::CodeFile(Some(id)) = &input.value {
+                            let already_exported =
+                                shaders.iter().any(|s: &BundleShader| s.id == *id);
+                            if let (false, Some(code_file)) =
+                                (already_exported, code_files.get_file(*id))
+                            {
+                                // Keep the code file extension so files that only differ in type stay apart
+                                let mut file = Path::new(BUNDLE_SHADER_DIR)
+                                    .join(code_file.relative_path())
+                                    .into_os_string();
+                                file.push(".wgsl");
+                                let file = PathBuf::from(file);
+
+                                sources.push((file.clone(), code_file.compile_source().to_owned()));
+                                shaders.push(BundleShader {
+                                    id: *id,
+                                    ty: code_file.ty(),
+                                    relative_path: code_file.relative_path().clone(),
+                                    file,
+                                });
+                            }
+                        }
+
+                        BundleInput {
+                            name: name.clone(),
+                            data_type: input.typ.clone(),
+                            value: input.value.clone(),
+                            consumer: input.consumer,
+                        }
+                    })
+                    .collect();
+
+                let outputs = node
+                    .outputs
+                    .iter()
+                    .map(|(name, output_id)| BundleOutput {
+                        name: name.clone(),
+                        data_type: graph[*output_id].typ.clone(),
+                    })
+                    .collect();
+
+                let position = self
+                    .node_graph
+                    .node_positions
+                    .get(*node_id)
+                    .copied()
+                    .unwrap_or_default();
+
+                BundleNode {
+                    template: node.user_data.template,
+                    label: node.user_data.label.clone(),
+                    color: node.user_data.color,
+                    bypass: node.user_data.bypass,
+                    position: [position.x, position.y],
+                    inputs,
+                    outputs,
+                }
+            })
+            .collect();
+
+        let connections = graph
+            .iter_connections()
+            .filter_map(|(input_id, output_id)| {
+                let input = graph.try_get_input(input_id)?;
+                let output = graph.try_get_output(output_id)?;
+
+                let to_input = graph[input.node]
+                    .inputs
+                    .iter()
+                    .find(|(_, id)| *id == input_id)?;
+                let from_output = graph[output.node]
+                    .outputs
+                    .iter()
+                    .find(|(_, id)| *id == output_id)?;
+
+                Some(BundleConnection {
+                    from_node: node_indices[&output.node],
+                    from_output: from_output.0.clone(),
+                    to_node: node_indices[&input.node],
+                    to_input: to_input.0.clone(),
+                })
+            })
+            .collect();
+
+        (
+            GraphBundle {
+                version: BUNDLE_VERSION,
+                nodes,
+                connections,
+                shaders,
+            },
+            sources,
+        )
+    }
+
+    /// Rebuild a graph from `bundle`. Code file references are remapped through
+    /// `code_file_ids`, the ids the bundle's shaders were given in the project.
+    /// Returns the graph together with a description of every input value and
+    /// connection that could not be restored.
+    pub fn from_bundle(
+        bundle: &GraphBundle,
+        code_file_ids: &HashMap<Uuid, Uuid>,
+    ) -> (Self, Vec<String>) {
+        let mut node_graph = RgEditorState::default();
+        let mut graph_state = RgGraphState::default();
+        let mut problems = Vec::new();
+
+        let graph = &mut node_graph.graph;
+
+        let mut node_ids = Vec::with_capacity(bundle.nodes.len());
+        for (i, bundle_node) in bundle.nodes.iter().enumerate() {
+            let template = bundle_node.template;
+            let node_id = graph.add_node(
+                template.node_graph_label(&mut graph_state),
+                RgNodeData {
+                    template,
+                    bypass: bundle_node.bypass,
+                    label: bundle_node.label.clone(),
+                    color: bundle_node.color,
+                },
+                |_, _| {},
+            );
+            template.build_node(graph, &mut graph_state, node_id);
+
+            for input in &bundle_node.inputs {
+                let value = match &input.value {
+                    RgValueType::CodeFile(Some(id)) => {
+                        RgValueType::CodeFile(code_file_ids.get(id).copied())
+                    }
+                    value => value.clone(),
+                };
+
+                match graph[node_id].get_input(&input.name) {
+                    Ok(input_id) if graph[input_id].typ == input.data_type => {
+                        graph[input_id].value = value;
+                    }
+                    Ok(input_id) => problems.push(format!(
+                        "Dropped value of input {}:{}, expected {:?} but found {:?}",
+                        i, input.name, graph[input_id].typ, input.data_type
+                    )),
+                    // Graphics pass inputs derived from shader bindings
+                    Err(_) if matches!(template, RgNodeTemplate::GraphicsPass) => {
+                        graph.add_input_param(
+                            node_id,
+                            input.name.clone(),
+                            input.data_type.clone(),
+                            value,
+                            InputParamKind::ConnectionOnly,
+                            input.consumer,
+                            true,
+                        );
+                    }
+                    Err(_) => problems.push(format!("Dropped unknown input {}:{}", i, input.name)),
+                }
+            }
+
+            node_graph.node_positions.insert(
+                node_id,
+                egui::pos2(bundle_node.position[0], bundle_node.position[1]),
+            );
+            node_graph.node_order.push(node_id);
+            node_ids.push(node_id);
+        }
+
+        for connection in &bundle.connections {
+            let name = format!(
+                "{}:{} -> {}:{}",
+                connection.from_node,
+                connection.from_output,
+                connection.to_node,
+                connection.to_input
+            );
+
+            let (Some(&from_node), Some(&to_node)) = (
+                node_ids.get(connection.from_node),
+                node_ids.get(connection.to_node),
+            ) else {
+                problems.push(format!("Dropped connection {}, unknown node", name));
+                continue;
+            };
+
+            let (Ok(output), Ok(input)) = (
+                graph[from_node].get_output(&connection.from_output),
+                graph[to_node].get_input(&connection.to_input),
+            ) else {
+                problems.push(format!("Dropped connection {}, unknown port", name));
+                continue;
+            };
+
+            let output_type = &graph[output].typ;
+            let input_type = &graph[input].typ;
+            if output_type != input_type
+                || matches!(graph[input].kind, InputParamKind::ConstantOnly)
+            {
+                problems.push(format!(
+                    "Dropped connection {}, {:?} is not compatible with {:?}",
+                    name, output_type, input_type
+                ));
+                continue;
+            }
+
+            let max_connections = graph[input]
+                .max_connections
+                .map(NonZeroU32::get)
+                .unwrap_or(u32::MAX) as usize;
+            let connection_count = graph.connections(input).len();
+            if connection_count >= max_connections || !graph.can_connect(output, input) {
+                problems.push(format!(
+                    "Dropped connection {}, the port is already connected",
+                    name
+                ));
+                continue;
+            }
+
+            graph.add_connection(output, input, connection_count);
+        }
+
+        (
+            Self {
+                node_graph,
+                graph_state,
+                synced_code_revision: None,
+            },
+            problems,
+        )
+    }
+
+    /// Graphviz DOT digraph of the nodes and their connections, from producer output to
+    /// consumer input. Texture connections are blue and buffer connections orange.
+    pub fn to_dot(&self) -> String {
+        let graph = &self.node_graph.graph;
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+
+        let node_indices: HashMap<NodeId, usize> = graph
+            .iter_nodes()
+            .enumerate()
+            .map(|(i, node_id)| (node_id, i))
+            .collect();
+
+        let mut dot =
+            String::from("digraph render_graph {\n    rankdir=LR;\n    node [shape=box];\n");
+        for node_id in graph.iter_nodes() {
+            let node = &graph[node_id];
+            let label = node.user_data.label.as_deref().unwrap_or(&node.label);
+            dot.push_str(&format!(
+                "    n{} [label=\"{}\"];\n",
+                node_indices[&node_id],
+                escape(label)
+            ));
+        }
+
+        for (input_id, output_id) in graph.iter_connections() {
+            let (Some(input), Some(output)) = (
+                graph.try_get_input(input_id),
+                graph.try_get_output(output_id),
+            ) else {
+                continue;
+            };
+            let from_output = port_name(&graph[output.node].outputs, output_id);
+            let to_input = port_name(&graph[input.node].inputs, input_id);
+
+            let color = match output.typ {
+                RgDataType::Tex2D
+                | RgDataType::HistoryTex2D
+                | RgDataType::Tex2DArray
+                | RgDataType::Tex3D
+                | RgDataType::HistoryTex3D
+                | RgDataType::DepthTex2D => "#4a90d9",
+                RgDataType::Buffer | RgDataType::HistoryBuffer => "#d9822b",
+                _ => "#808080",
+            };
+
+            dot.push_str(&format!(
+                "    n{} -> n{} [label=\"{} -> {}\", color=\"{}\"];\n",
+                node_indices[&output.node],
+                node_indices[&input.node],
+                escape(from_output),
+                escape(to_input),
+                color
+            ));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Write the graph as a portable bundle into `dir`: a [`BUNDLE_GRAPH_FILE`]
+    /// describing the nodes and connections plus the WGSL source of every pass.
+    /// A [`BUNDLE_DOT_FILE`] is written next to it for documentation.
+    pub fn export_bundle(&self, code_files: &CodeFiles, dir: &Path) -> anyhow::Result<()> {
+        let (bundle, sources) = self.to_bundle(code_files);
+
+        std::fs::create_dir_all(dir)?;
+
+        for (file, source) in sources {
+            let path = dir.join(file);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, source)?;
+        }
+
+        let file = std::fs::File::create(dir.join(BUNDLE_GRAPH_FILE))?;
+        let writer = std::io::BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, &bundle)?;
+
+        std::fs::write(dir.join(BUNDLE_DOT_FILE), self.to_dot())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_graph_dot() {
+        let render_graph = RenderGraph::new(&CodeFiles::empty("project"));
+        let dot = render_graph.to_dot();
+
+        let node = |template: RgNodeTemplate| {
+            let label = template.node_graph_label(&mut RgGraphState::default());
+            let line = dot
+                .lines()
+                .find(|line| line.ends_with(&format!("[label=\"{}\"];", label)))
+                .unwrap();
+            line.trim().split(' ').next().unwrap().to_owned()
+        };
+        let screen_tex = node(RgNodeTemplate::ScreenTex);
+        let graphics_pass = node(RgNodeTemplate::GraphicsPass);
+        let display_out = node(RgNodeTemplate::DisplayOut);
+
+        assert!(dot.starts_with("digraph render_graph {"));
+        assert!(dot.contains(&format!(
+            "{} -> {} [label=\"tex -> render target\"",
+            screen_tex, graphics_pass
+        )));
+        assert!(dot.contains(&format!(
+            "{} -> {} [label=\"render target -> in\"",
+            graphics_pass, display_out
+        )));
+    }
+}
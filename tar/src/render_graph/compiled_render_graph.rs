@@ -1,18 +1,38 @@
-use std::collections::HashMap;
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    pin::Pin,
+    rc::Rc,
+    time::SystemTime,
+};
 
 use anyhow::{anyhow, bail};
+use futures::{channel::oneshot, FutureExt as _};
 use uuid::Uuid;
 
 use crate::{
     editor::node_graph::{NodeId, OutputId},
-    render_graph::{shader::Shader, RgDataType, RgGraph, RgNodeTemplate, RgValueType},
-    wgpu_util::blit_pass,
+    render_graph::{shader::Shader, BufferInit, RgDataType, RgGraph, RgNodeTemplate, RgValueType},
+    time::Timer,
+    wgpu_util::{
+        blit_pass::{self, BlendMode},
+        context_wrapper::ContextWrapper,
+        decode_texel, empty_texture_view, noise_pass, BufferReadback,
+    },
 };
 
 #[derive(Clone, Copy)]
 struct BufferHandle(usize);
 #[derive(Clone, Copy)]
 struct TextureHandle(usize);
+#[derive(Clone, Copy)]
+struct SamplerHandle(usize);
+
+/// Bound to sampler bindings that aren't connected to a sampler node.
+const DEFAULT_SAMPLER: SamplerHandle = SamplerHandle(0);
 
 // enum InputBindingData {
 //     Buffer(BufferHandle),
@@ -27,11 +47,158 @@ struct TextureHandle(usize);
 
 struct CompiledGraphicsPass {
     pub node_id: NodeId,
-    pub shader_id: Uuid,
+    pub shader_id: Option<Uuid>,
 
-    pub pipeline: wgpu::RenderPipeline,
+    /// `None` when the pass failed to build, it then only clears its render target.
+    pub pipeline: Option<wgpu::RenderPipeline>,
     pub bind_group: Option<wgpu::BindGroup>,
+    /// Validation errors of the pipeline and bind group, the pass is skipped until it resolves.
+    pub validation: Option<PendingValidation>,
     pub render_target_texture: TextureHandle,
+    pub render_target_format: wgpu::TextureFormat,
+    /// Depth attachment, with the depth to clear it to when this is the first pass using it.
+    pub depth: Option<(TextureHandle, Option<f32>)>,
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    /// See [`pass_key`], the pipeline and bind group are rebuilt when it changes.
+    pub key: u64,
+}
+
+/// Minimum time in seconds between two readbacks of the pass timestamps.
+const TIMESTAMP_READBACK_INTERVAL: f32 = 0.25;
+
+/// Timestamps written at the start and end of every graphics pass, only created when the
+/// device supports [`wgpu::Features::TIMESTAMP_QUERY`].
+struct PassTimestamps {
+    query_set: wgpu::QuerySet,
+    /// Number of passes the query set has room for, two timestamps each.
+    capacity: usize,
+    resolve_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+    /// Number of passes that got timestamps in the last recording.
+    recorded: Cell<usize>,
+    pending: Option<(Vec<NodeId>, oneshot::Receiver<Vec<u64>>)>,
+    timer: Timer,
+    last_timings: Vec<(NodeId, f32)>,
+}
+
+impl PassTimestamps {
+    fn new(pass_count: usize, context: &ContextWrapper) -> Option<Self> {
+        if pass_count == 0 || !context.supports(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let device = &context.device;
+
+        let capacity = pass_count.min(wgpu::QUERY_SET_MAX_QUERIES as usize / 2);
+        let count = capacity as u32 * 2;
+        Some(Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("rg pass timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count,
+            }),
+            capacity,
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("rg pass timestamps resolve"),
+                size: count as u64 * wgpu::QUERY_SIZE as u64,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            period: context.queue.get_timestamp_period(),
+            recorded: Cell::new(0),
+            pending: None,
+            timer: Timer::new(),
+            last_timings: Vec::new(),
+        })
+    }
+
+    fn writes(&self, pass: usize) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        (pass < self.capacity).then(|| wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(pass as u32 * 2),
+            end_of_pass_write_index: Some(pass as u32 * 2 + 1),
+        })
+    }
+
+    fn resolve(&self, encoder: &mut wgpu::CommandEncoder, pass_count: usize) {
+        let recorded = pass_count.min(self.capacity);
+        self.recorded.set(recorded);
+        if recorded > 0 {
+            encoder.resolve_query_set(
+                &self.query_set,
+                0..recorded as u32 * 2,
+                &self.resolve_buffer,
+                0,
+            );
+        }
+    }
+}
+
+/// Milliseconds between each pair of begin and end timestamps.
+fn pass_durations(timestamps: &[u64], period: f32) -> Vec<f32> {
+    timestamps
+        .chunks_exact(2)
+        .map(|pair| pair[1].saturating_sub(pair[0]) as f32 * period / 1_000_000.0)
+        .collect()
+}
+
+/// Minimum time in seconds between two readbacks of the texels sampled by readback nodes.
+const TEXEL_READBACK_INTERVAL: f32 = 0.25;
+
+/// A texel copied back to the host by a readback node.
+#[derive(Clone, Debug)]
+pub struct TexelValue {
+    pub pixel: [u32; 2],
+    pub format: wgpu::TextureFormat,
+    /// Decoded channels, see [`decode_texel`], `None` for formats that can't be decoded.
+    pub channels: Option<Vec<f64>>,
+    pub bytes: Vec<u8>,
+}
+
+impl TexelValue {
+    /// Whether no channel is NaN or infinite.
+    pub fn is_finite(&self) -> bool {
+        self.channels
+            .iter()
+            .flatten()
+            .all(|channel| channel.is_finite())
+    }
+
+    /// The channels like `[1, 0.5, NaN, 1]`, or the raw bytes of packed formats.
+    pub fn channels_text(&self) -> String {
+        match &self.channels {
+            Some(channels) => format!(
+                "[{}]",
+                channels
+                    .iter()
+                    .map(|channel| format!("{:.4}", channel)
+                        .trim_end_matches('0')
+                        .trim_end_matches('.')
+                        .to_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            None => format!(
+                "0x{}",
+                self.bytes
+                    .iter()
+                    .rev()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<String>()
+            ),
+        }
+    }
+}
+
+/// Texel of a texture sampled by a readback node.
+struct TexelReadback {
+    node_id: NodeId,
+    texture: TextureHandle,
+    pixel: [u32; 2],
+    buffer: wgpu::Buffer,
+    pending: Option<(oneshot::Receiver<Vec<u8>>, [u32; 2])>,
+    value: Option<TexelValue>,
 }
 
 pub struct CompiledRenderGraph {
@@ -41,8 +208,79 @@ pub struct CompiledRenderGraph {
     textures: Vec<wgpu::Texture>,
     texture_views: Vec<wgpu::TextureView>,
 
+    /// Kept to rebuild passes without recreating the resources, see [`Self::update_passes`].
+    texture_view_dimensions: Vec<wgpu::TextureViewDimension>,
+    output_texture_handles: HashMap<OutputId, TextureHandle>,
+    output_buffer_handles: HashMap<OutputId, BufferHandle>,
+    /// Starts with [`DEFAULT_SAMPLER`], followed by the sampler of every sampler node.
+    samplers: Vec<wgpu::Sampler>,
+    output_sampler_handles: HashMap<OutputId, SamplerHandle>,
+    /// See [`layout_hash`].
+    layout_hash: u64,
+
     graphics_passes: Vec<CompiledGraphicsPass>,
-    display_output: TextureHandle,
+    /// Textures connected to the display output, composited in order with `display_blend`.
+    display_layers: Vec<TextureHandle>,
+    display_blend: BlendMode,
+    /// Texture shown when inspecting a node, only for textures the blit pass can sample.
+    node_textures: HashMap<NodeId, TextureHandle>,
+    /// Passes that failed to build and are skipped, with a message describing why.
+    pass_errors: Vec<(NodeId, String)>,
+    timestamps: Option<PassTimestamps>,
+    /// Whether the passes write timestamps, see [`Self::set_profiling`].
+    profiling: bool,
+    texel_readbacks: Vec<TexelReadback>,
+    texel_timer: Timer,
+}
+
+/// Memory budget for all textures and buffers of a graph, used when the graph state doesn't specify one.
+pub const DEFAULT_MEMORY_BUDGET: u64 = 2 << 30;
+
+/// Number of bytes taken up by a texture including its full mip chain.
+fn texture_memory_size(
+    size: wgpu::Extent3d,
+    dimension: wgpu::TextureDimension,
+    mip_level_count: u32,
+    format: wgpu::TextureFormat,
+) -> u64 {
+    let bytes_per_texel = format.block_copy_size(None).unwrap_or(4) as u64;
+
+    (0..mip_level_count)
+        .map(|mip| {
+            let mip_size = size.mip_level_size(mip, dimension);
+            mip_size.width as u64
+                * mip_size.height as u64
+                * mip_size.depth_or_array_layers as u64
+                * bytes_per_texel
+        })
+        .sum()
+}
+
+/// Whether the texture can be shown through the blit pass, which samples a single 2D float texture.
+fn is_previewable(texture: &wgpu::Texture, view_dimension: wgpu::TextureViewDimension) -> bool {
+    view_dimension == wgpu::TextureViewDimension::D2
+        && texture
+            .usage()
+            .contains(wgpu::TextureUsages::TEXTURE_BINDING)
+        && matches!(
+            texture.format().sample_type(None, None),
+            Some(wgpu::TextureSampleType::Float { filterable: true })
+        )
+}
+
+fn view_dimension_name(view_dimension: wgpu::TextureViewDimension) -> &'static str {
+    match view_dimension {
+        wgpu::TextureViewDimension::D1 => "1D",
+        wgpu::TextureViewDimension::D2 => "2D",
+        wgpu::TextureViewDimension::D2Array => "2D array",
+        wgpu::TextureViewDimension::Cube => "cube",
+        wgpu::TextureViewDimension::CubeArray => "cube array",
+        wgpu::TextureViewDimension::D3 => "3D",
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1 << 20) as f64)
 }
 
 /// Topologically sort the nodes in a render graph using Kahn's algorithm.
@@ -98,7 +336,424 @@ pub fn topological_sort(graph: &RgGraph) -> anyhow::Result<Vec<NodeId>> {
 
 /// Helper to read a value from an input parameter on a node.
 fn read_input_value(graph: &RgGraph, node_id: NodeId, name: &str) -> anyhow::Result<RgValueType> {
-    Ok(graph.get_input(graph[node_id].get_input(name)?).value)
+    Ok(graph
+        .get_input(graph[node_id].get_input(name)?)
+        .value
+        .clone())
+}
+
+/// Initial contents of a buffer node, buffers saved before they had the "init" and "fill"
+/// inputs are zeroed.
+fn read_buffer_init(graph: &RgGraph, node_id: NodeId) -> anyhow::Result<(BufferInit, u32)> {
+    let has_input = |name: &str| graph[node_id].get_input(name).is_ok();
+
+    let init = if has_input("init") {
+        *read_input_value(graph, node_id, "init")?.as_buffer_init()?
+    } else {
+        BufferInit::Zeroed
+    };
+    let fill = if has_input("fill") {
+        *read_input_value(graph, node_id, "fill")?.as_uint()?
+    } else {
+        0
+    };
+    Ok((init, fill))
+}
+
+thread_local! {
+    /// Decoded images by path, with the modification time of the file they were decoded from.
+    static IMAGE_CACHE: RefCell<HashMap<PathBuf, (SystemTime, Rc<image::RgbaImage>)>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Decode the image at `path`, reusing the last decode while the file is unchanged.
+fn load_image(path: &Path) -> anyhow::Result<Rc<image::RgbaImage>> {
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    if let Ok(modified) = modified {
+        let cached = IMAGE_CACHE.with(|cache| {
+            cache
+                .borrow()
+                .get(path)
+                .filter(|(cached_modified, _)| *cached_modified == modified)
+                .map(|(_, image)| image.clone())
+        });
+        if let Some(image) = cached {
+            return Ok(image);
+        }
+    }
+
+    let image = Rc::new(
+        image::open(path)
+            .map_err(|e| anyhow!("Failed to load image {}: {}", path.display(), e))?
+            .to_rgba8(),
+    );
+    if let Ok(modified) = modified {
+        IMAGE_CACHE.with(|cache| {
+            cache
+                .borrow_mut()
+                .insert(path.to_path_buf(), (modified, image.clone()))
+        });
+    }
+    Ok(image)
+}
+
+/// Depth texture connected to the depth attachment of a graphics pass, if any.
+fn depth_attachment(
+    graph: &RgGraph,
+    node_id: NodeId,
+    output_texture_handles: &HashMap<OutputId, TextureHandle>,
+) -> anyhow::Result<Option<TextureHandle>> {
+    let Some(output) = graph[node_id]
+        .get_input("depth")
+        .ok()
+        .and_then(|input_id| graph.connection(input_id))
+    else {
+        return Ok(None);
+    };
+
+    output_texture_handles
+        .get(&output)
+        .copied()
+        .map(Some)
+        .ok_or(anyhow!(
+            "GraphicsPass 'depth' not connected to a depth texture"
+        ))
+}
+
+/// Outputs of texture nodes that end up bound as a storage texture, following
+/// passes that forward the texture they render to.
+fn storage_texture_outputs(
+    graph: &RgGraph,
+    shader_cache: &HashMap<Uuid, Shader>,
+) -> HashSet<OutputId> {
+    let mut outputs = HashSet::new();
+
+    for node_id in graph.iter_nodes() {
+        if !matches!(
+            graph[node_id].user_data.template,
+            RgNodeTemplate::GraphicsPass
+        ) {
+            continue;
+        }
+
+        let Some(shader) = read_input_value(graph, node_id, "code")
+            .ok()
+            .and_then(|value| value.as_code_file().ok().copied().flatten())
+            .and_then(|id| shader_cache.get(&id))
+        else {
+            continue;
+        };
+
+        for binding in shader.get_bindings() {
+            if binding.storage_format.is_none() {
+                continue;
+            }
+
+            let mut output = graph[node_id]
+                .get_input(&binding.name)
+                .ok()
+                .and_then(|input_id| graph.connection(input_id));
+            while let Some(output_id) = output {
+                let producer = graph.get_output(output_id).node;
+                let forwarded = matches!(
+                    graph[producer].user_data.template,
+                    RgNodeTemplate::GraphicsPass
+                ) && graph[producer].get_output("render target").ok()
+                    == Some(output_id);
+                if !forwarded {
+                    break;
+                }
+
+                output = graph[producer]
+                    .get_input("render target")
+                    .ok()
+                    .and_then(|input_id| graph.connection(input_id));
+            }
+
+            outputs.extend(output);
+        }
+    }
+
+    outputs
+}
+
+/// Hash of everything the resources and connections of a compiled graph depend on. Shaders
+/// only count through the textures they use as storage textures, so graphs with the same
+/// layout hash can keep their resources and only rebuild the passes whose shader changed.
+fn layout_hash(
+    graph: &RgGraph,
+    shader_cache: &HashMap<Uuid, Shader>,
+    screen_size: [u32; 2],
+    memory_budget: u64,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    screen_size.hash(&mut hasher);
+    memory_budget.hash(&mut hasher);
+
+    for node_id in graph.iter_nodes() {
+        let node = &graph[node_id];
+        node_id.hash(&mut hasher);
+        std::mem::discriminant(&node.user_data.template).hash(&mut hasher);
+        node.user_data.bypass.hash(&mut hasher);
+
+        for (name, input_id) in &node.inputs {
+            name.hash(&mut hasher);
+            // Code files only affect their own pass, see `pass_key`
+            let value = &graph[*input_id].value;
+            if !matches!(value, RgValueType::CodeFile(_)) {
+                // Values hold floats, so hash their debug representation
+                format!("{:?}", value).hash(&mut hasher);
+            }
+            graph.connections(*input_id).hash(&mut hasher);
+        }
+        for (name, output_id) in &node.outputs {
+            name.hash(&mut hasher);
+            output_id.hash(&mut hasher);
+        }
+    }
+
+    let mut storage_outputs: Vec<OutputId> = storage_texture_outputs(graph, shader_cache)
+        .into_iter()
+        .collect();
+    storage_outputs.sort();
+    storage_outputs.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// Changes when a pass uses a different code file or its shader source was edited.
+fn pass_key(node_id: NodeId, shader_id: Option<Uuid>, shader_cache: &HashMap<Uuid, Shader>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    node_id.hash(&mut hasher);
+    shader_id.hash(&mut hasher);
+    shader_id
+        .and_then(|id| shader_cache.get(&id))
+        .map(|shader| shader.get_source())
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Resolves to the validation error of a pass, see [`with_validation`].
+type PendingValidation = Pin<Box<dyn Future<Output = Option<wgpu::Error>>>>;
+
+/// Run `build` in a validation error scope, so a pass with validation errors fails
+/// on its own instead of taking down the whole graph. The error scope resolves
+/// asynchronously, see [`CompiledRenderGraph::poll_validation`].
+fn with_validation<T>(
+    device: &wgpu::Device,
+    build: impl FnOnce() -> anyhow::Result<T>,
+) -> (anyhow::Result<T>, Option<PendingValidation>) {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let result = build();
+    let validation: PendingValidation = Box::pin(device.pop_error_scope());
+    let validation = result.is_ok().then_some(validation);
+    (result, validation)
+}
+
+/// The pipeline and bind group of a built pass, or `None` after recording why it is skipped.
+fn pass_or_skip(
+    graph: &RgGraph,
+    node_id: NodeId,
+    pass: anyhow::Result<(wgpu::RenderPipeline, Option<wgpu::BindGroup>)>,
+    pass_errors: &mut Vec<(NodeId, String)>,
+) -> (Option<wgpu::RenderPipeline>, Option<wgpu::BindGroup>) {
+    match pass {
+        Ok((pipeline, bind_group)) => (Some(pipeline), bind_group),
+        Err(e) => {
+            let message = format!("Skipped pass '{}': {}", graph[node_id].label, e);
+            log::warn!("{}", message);
+            pass_errors.push((node_id, message));
+            (None, None)
+        }
+    }
+}
+
+/// Create the pipeline and bind group of a graphics pass, fails when its shader
+/// is unusable or one of its bindings is not connected.
+#[allow(clippy::too_many_arguments)]
+fn build_graphics_pass(
+    graph: &RgGraph,
+    node_id: NodeId,
+    shader_id: Option<Uuid>,
+    shader_cache: &HashMap<Uuid, Shader>,
+    render_target: TextureHandle,
+    render_target_format: wgpu::TextureFormat,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    output_texture_handles: &HashMap<OutputId, TextureHandle>,
+    output_buffer_handles: &HashMap<OutputId, BufferHandle>,
+    output_sampler_handles: &HashMap<OutputId, SamplerHandle>,
+    textures: &[wgpu::Texture],
+    texture_views: &[wgpu::TextureView],
+    texture_view_dimensions: &[wgpu::TextureViewDimension],
+    buffers: &[wgpu::Buffer],
+    samplers: &[wgpu::Sampler],
+    device: &wgpu::Device,
+) -> anyhow::Result<(wgpu::RenderPipeline, Option<wgpu::BindGroup>)> {
+    let shader_id = shader_id.ok_or(anyhow!("Unassigned code file"))?;
+    let shader = match shader_cache.get(&shader_id) {
+        Some(s) if s.shader_module().is_some() => s,
+        _ => bail!("Invalid shader"), // Shader not compiled or has errors
+    };
+
+    // Separate the index lookups so we can borrow texture_views / buffers
+    // after the closures are no longer needed.
+    let mut tex_entries: Vec<(u32, usize)> = Vec::new(); // (binding, tex_idx)
+    let mut buf_entries: Vec<(u32, usize)> = Vec::new(); // (binding, buf_idx)
+    let mut sampler_entries: Vec<(u32, usize)> = Vec::new(); // (binding, sampler_idx)
+    let mut storage_entries: Vec<(u32, usize, wgpu::TextureViewDimension)> = Vec::new();
+
+    for binding in shader.get_bindings() {
+        let connected_output = graph[node_id]
+            .get_input(&binding.name)
+            .ok()
+            .and_then(|input_id| graph.connection(input_id));
+
+        match binding.resource_type {
+            RgDataType::Tex2D
+            | RgDataType::Tex2DArray
+            | RgDataType::Tex3D
+            | RgDataType::DepthTex2D => {
+                let tex_handle = connected_output
+                    .and_then(|output| output_texture_handles.get(&output))
+                    .ok_or(anyhow!("'{}' is not connected", binding.name))?;
+
+                let view_dimension = match binding.resource_type {
+                    RgDataType::Tex2DArray => wgpu::TextureViewDimension::D2Array,
+                    RgDataType::Tex3D => wgpu::TextureViewDimension::D3,
+                    _ => wgpu::TextureViewDimension::D2,
+                };
+                let connected_dimension = texture_view_dimensions[tex_handle.0];
+                if connected_dimension != view_dimension {
+                    bail!(
+                        "'{}' expects a {} texture, it is connected to a {} texture",
+                        binding.name,
+                        view_dimension_name(view_dimension),
+                        view_dimension_name(connected_dimension)
+                    );
+                }
+
+                if let Some(storage_format) = binding.storage_format {
+                    let texture = &textures[tex_handle.0];
+                    if tex_handle.0 == render_target.0 {
+                        bail!(
+                            "'{}' can't be a storage texture while it is also the render target",
+                            binding.name
+                        );
+                    }
+                    if texture.format() != storage_format {
+                        bail!(
+                            "'{}' expects a {:?} texture, it is connected to a {:?} texture",
+                            binding.name,
+                            storage_format,
+                            texture.format()
+                        );
+                    }
+                    if !texture
+                        .usage()
+                        .contains(wgpu::TextureUsages::STORAGE_BINDING)
+                    {
+                        bail!(
+                            "'{}' is a storage texture, {:?} textures can't be used as one",
+                            binding.name,
+                            texture.format()
+                        );
+                    }
+
+                    storage_entries.push((binding.binding, tex_handle.0, view_dimension));
+                } else {
+                    tex_entries.push((binding.binding, tex_handle.0));
+                }
+            }
+            RgDataType::Buffer => {
+                let buf_handle = connected_output
+                    .and_then(|output| output_buffer_handles.get(&output))
+                    .ok_or(anyhow!("'{}' is not connected", binding.name))?;
+                buf_entries.push((binding.binding, buf_handle.0));
+            }
+            RgDataType::Sampler => {
+                let sampler_handle = connected_output
+                    .and_then(|output| output_sampler_handles.get(&output))
+                    .copied()
+                    .unwrap_or(DEFAULT_SAMPLER);
+                sampler_entries.push((binding.binding, sampler_handle.0));
+            }
+            _ => {}
+        }
+    }
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("rg pipeline {}", shader_id)),
+        layout: None,
+        vertex: wgpu::VertexState {
+            module: shader.shader_module().as_ref().unwrap(),
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader.shader_module().as_ref().unwrap(),
+            entry_point: Some("fs_main"),
+            compilation_options: Default::default(),
+            targets: &[Some(render_target_format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    });
+
+    // Storage textures can only be bound with a single mip level
+    let storage_views: Vec<(u32, wgpu::TextureView)> = storage_entries
+        .iter()
+        .map(|(binding, idx, view_dimension)| {
+            let view = textures[*idx].create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(*view_dimension),
+                mip_level_count: Some(1),
+                ..Default::default()
+            });
+            (*binding, view)
+        })
+        .collect();
+
+    // Now build the actual wgpu entries (borrows texture_views / buffers directly).
+    let mut entries: Vec<wgpu::BindGroupEntry> = Vec::new();
+    for (binding, idx) in &tex_entries {
+        entries.push(wgpu::BindGroupEntry {
+            binding: *binding,
+            resource: wgpu::BindingResource::TextureView(&texture_views[*idx]),
+        });
+    }
+    for (binding, view) in &storage_views {
+        entries.push(wgpu::BindGroupEntry {
+            binding: *binding,
+            resource: wgpu::BindingResource::TextureView(view),
+        });
+    }
+    for (binding, idx) in &buf_entries {
+        entries.push(wgpu::BindGroupEntry {
+            binding: *binding,
+            resource: buffers[*idx].as_entire_binding(),
+        });
+    }
+    for (binding, idx) in &sampler_entries {
+        entries.push(wgpu::BindGroupEntry {
+            binding: *binding,
+            resource: wgpu::BindingResource::Sampler(&samplers[*idx]),
+        });
+    }
+
+    let bind_group = if !entries.is_empty() {
+        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("rg bind group {}", shader_id)),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        }))
+    } else {
+        None
+    };
+
+    Ok((pipeline, bind_group))
 }
 
 impl CompiledRenderGraph {
@@ -106,65 +761,150 @@ impl CompiledRenderGraph {
         graph: &RgGraph,
         shader_cache: &HashMap<Uuid, Shader>,
         screen_size: [u32; 2],
-        device: &wgpu::Device,
+        context: &ContextWrapper,
+        memory_budget: u64,
     ) -> anyhow::Result<Self> {
+        let (device, queue) = (&context.device, &context.queue);
         let mut buffers = Vec::new();
+        let mut samplers = vec![device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("rg default sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        })];
         let mut textures = Vec::new();
         let mut texture_views = Vec::new();
+        let mut texture_view_dimensions = Vec::new();
         let mut graphics_passes = Vec::new();
-        let mut display_output = None;
+        let mut display_layers = Vec::new();
+        let mut display_blend = BlendMode::default();
+        let mut node_textures = HashMap::new();
+        let mut pass_errors = Vec::new();
+        let mut texel_readbacks = Vec::new();
+        // Depth textures cleared by an earlier pass
+        let mut cleared_depth = HashSet::new();
 
         let nodes = topological_sort(graph)?;
+        let storage_outputs = storage_texture_outputs(graph, shader_cache);
+
+        // Total bytes of all textures and buffers allocated so far
+        let memory_used = Cell::new(0u64);
+        let reserve_memory = |bytes: u64| -> anyhow::Result<()> {
+            let total = memory_used.get() + bytes;
+            if total > memory_budget {
+                bail!(
+                    "Render graph needs more than its memory budget of {} ({} requested)",
+                    format_bytes(memory_budget),
+                    format_bytes(total)
+                );
+            }
+            memory_used.set(total);
+            Ok(())
+        };
 
         let mut output_buffer_handles: HashMap<OutputId, BufferHandle> = HashMap::new();
+        let mut output_sampler_handles: HashMap<OutputId, SamplerHandle> = HashMap::new();
         let mut output_texture_handles: HashMap<OutputId, TextureHandle> = HashMap::new();
 
         for &node_id in &nodes {
+            let storage_usage = if graph[node_id]
+                .output_ids()
+                .any(|output_id| storage_outputs.contains(&output_id))
+            {
+                wgpu::TextureUsages::STORAGE_BINDING
+            } else {
+                wgpu::TextureUsages::empty()
+            };
+
             let mut build_tex = |width: u32,
                                  height: u32,
                                  array_layers: u32,
-                                 dimension,
+                                 view_dimension: wgpu::TextureViewDimension,
                                  mip_level_count: u32,
-                                 format,
-                                 usage| {
+                                 format: wgpu::TextureFormat,
+                                 usage: wgpu::TextureUsages|
+             -> anyhow::Result<TextureHandle> {
                 let handle = TextureHandle(textures.len());
+                let dimension = view_dimension.compatible_texture_dimension();
+
+                let size = wgpu::Extent3d {
+                    width: width.max(1),
+                    height: height.max(1),
+                    depth_or_array_layers: array_layers.max(1),
+                };
+                // Never request more mips than the full chain for this size
+                let mip_level_count = mip_level_count.clamp(1, size.max_mips(dimension));
+
+                // Textures written by a shader can be stored to when their format allows it,
+                // readback nodes can copy texels out of any texture that can be copied
+                let allowed_usages = format
+                    .guaranteed_format_features(device.features())
+                    .allowed_usages;
+                let mut usage = usage;
+                if allowed_usages.contains(storage_usage) {
+                    usage |= storage_usage;
+                }
+                if allowed_usages.contains(wgpu::TextureUsages::COPY_SRC) {
+                    usage |= wgpu::TextureUsages::COPY_SRC;
+                }
+
+                reserve_memory(texture_memory_size(
+                    size,
+                    dimension,
+                    mip_level_count,
+                    format,
+                ))?;
 
                 let texture = device.create_texture(&wgpu::TextureDescriptor {
                     label: Some(&format!("rg texture {}", handle.0)),
-                    size: wgpu::Extent3d {
-                        width: width.max(1),
-                        height: height.max(1),
-                        depth_or_array_layers: array_layers.max(1),
-                    },
-                    mip_level_count: mip_level_count.max(1),
+                    size,
+                    mip_level_count,
                     format,
                     sample_count: 1,
                     dimension,
                     view_formats: &[],
                     usage,
                 });
-                let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                // Explicit, an array with a single layer would otherwise get a plain 2D view
+                let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(view_dimension),
+                    ..Default::default()
+                });
 
                 textures.push(texture);
                 texture_views.push(texture_view);
-                handle
+                texture_view_dimensions.push(view_dimension);
+                Ok(handle)
             };
 
-            let mut build_buffer = |size: u64| {
-                let handle = BufferHandle(buffers.len());
+            let mut build_buffer =
+                |size: u64, init: BufferInit, fill: u32| -> anyhow::Result<BufferHandle> {
+                    let handle = BufferHandle(buffers.len());
 
-                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
-                    label: Some(&format!("rg buffer {}", handle.0)),
-                    size: size.max(1),
-                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::UNIFORM,
-                    mapped_at_creation: false,
-                });
+                    // Keep the size copy aligned so the initial contents can always be uploaded
+                    let size = size.max(1).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
 
-                buffers.push(buffer);
-                handle
-            };
+                    reserve_memory(size)?;
+
+                    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(&format!("rg buffer {}", handle.0)),
+                        size,
+                        usage: wgpu::BufferUsages::STORAGE
+                            | wgpu::BufferUsages::UNIFORM
+                            | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    });
+
+                    if let Some(contents) = init.contents(size, fill) {
+                        queue.write_buffer(&buffer, 0, &contents);
+                    }
+
+                    buffers.push(buffer);
+                    Ok(handle)
+                };
 
-            let template = graph[node_id].user_data.0;
+            let template = graph[node_id].user_data.template;
 
             match template {
                 RgNodeTemplate::ScreenTex => {
@@ -182,11 +922,11 @@ impl CompiledRenderGraph {
                         width,
                         height,
                         1,
-                        wgpu::TextureDimension::D2,
+                        wgpu::TextureViewDimension::D2,
                         mip_level_count,
                         format.into(),
                         usage.into(),
-                    );
+                    )?;
 
                     if let Ok(output_id) = graph[node_id].get_output("tex") {
                         output_texture_handles.insert(output_id, handle);
@@ -207,20 +947,20 @@ impl CompiledRenderGraph {
                         width,
                         height,
                         1,
-                        wgpu::TextureDimension::D2,
+                        wgpu::TextureViewDimension::D2,
                         mip_level_count,
                         format.into(),
                         usage.into(),
-                    );
+                    )?;
                     let previous_handle = build_tex(
                         width,
                         height,
                         1,
-                        wgpu::TextureDimension::D2,
+                        wgpu::TextureViewDimension::D2,
                         mip_level_count,
                         format.into(),
                         usage.into(),
-                    );
+                    )?;
 
                     if let Ok(output_id) = graph[node_id].get_output("current tex") {
                         output_texture_handles.insert(output_id, current_handle);
@@ -242,11 +982,11 @@ impl CompiledRenderGraph {
                         width,
                         height,
                         1,
-                        wgpu::TextureDimension::D2,
+                        wgpu::TextureViewDimension::D2,
                         mip_level_count,
                         format.into(),
                         usage.into(),
-                    );
+                    )?;
 
                     if let Ok(output_id) = graph[node_id].get_output("tex") {
                         output_texture_handles.insert(output_id, handle);
@@ -264,20 +1004,20 @@ impl CompiledRenderGraph {
                         width,
                         height,
                         1,
-                        wgpu::TextureDimension::D2,
+                        wgpu::TextureViewDimension::D2,
                         mip_level_count,
                         format.into(),
                         usage.into(),
-                    );
+                    )?;
                     let previous_handle = build_tex(
                         width,
                         height,
                         1,
-                        wgpu::TextureDimension::D2,
+                        wgpu::TextureViewDimension::D2,
                         mip_level_count,
                         format.into(),
                         usage.into(),
-                    );
+                    )?;
 
                     if let Ok(output_id) = graph[node_id].get_output("current tex") {
                         output_texture_handles.insert(output_id, current_handle);
@@ -293,18 +1033,18 @@ impl CompiledRenderGraph {
                     let mip_level_count = *read_input_value(graph, node_id, "mips")?.as_uint()?;
                     let format =
                         *read_input_value(graph, node_id, "format")?.as_texture_format()?;
-                    let usage = *read_input_value(graph, node_id, "usage")?.as_texture_usage()?;
                     let _persistent = read_input_value(graph, node_id, "persistent")?.as_bool()?;
 
+                    // Arrays can't be rendered to, storage usage is added when a pass stores to it
                     let handle = build_tex(
                         width,
                         height,
                         array_count,
-                        wgpu::TextureDimension::D2,
+                        wgpu::TextureViewDimension::D2Array,
                         mip_level_count,
                         format.into(),
-                        usage.into(),
-                    );
+                        wgpu::TextureUsages::TEXTURE_BINDING,
+                    )?;
 
                     if let Ok(output_id) = graph[node_id].get_output("tex") {
                         output_texture_handles.insert(output_id, handle);
@@ -316,18 +1056,18 @@ impl CompiledRenderGraph {
                     let mip_level_count = *read_input_value(graph, node_id, "mips")?.as_uint()?;
                     let format =
                         *read_input_value(graph, node_id, "format")?.as_texture_format()?;
-                    let usage = *read_input_value(graph, node_id, "usage")?.as_texture_usage()?;
                     let _persistent = read_input_value(graph, node_id, "persistent")?.as_bool()?;
 
+                    // 3D textures can't be rendered to, storage usage is added when a pass stores to it
                     let handle = build_tex(
                         width,
                         height,
                         depth,
-                        wgpu::TextureDimension::D3,
+                        wgpu::TextureViewDimension::D3,
                         mip_level_count,
                         format.into(),
-                        usage.into(),
-                    );
+                        wgpu::TextureUsages::TEXTURE_BINDING,
+                    )?;
 
                     if let Ok(output_id) = graph[node_id].get_output("tex") {
                         output_texture_handles.insert(output_id, handle);
@@ -339,26 +1079,25 @@ impl CompiledRenderGraph {
                     let mip_level_count = *read_input_value(graph, node_id, "mips")?.as_uint()?;
                     let format =
                         *read_input_value(graph, node_id, "format")?.as_texture_format()?;
-                    let usage = *read_input_value(graph, node_id, "usage")?.as_texture_usage()?;
 
                     let current_handle = build_tex(
                         width,
                         height,
                         depth,
-                        wgpu::TextureDimension::D3,
+                        wgpu::TextureViewDimension::D3,
                         mip_level_count,
                         format.into(),
-                        usage.into(),
-                    );
+                        wgpu::TextureUsages::TEXTURE_BINDING,
+                    )?;
                     let previous_handle = build_tex(
                         width,
                         height,
                         depth,
-                        wgpu::TextureDimension::D3,
+                        wgpu::TextureViewDimension::D3,
                         mip_level_count,
                         format.into(),
-                        usage.into(),
-                    );
+                        wgpu::TextureUsages::TEXTURE_BINDING,
+                    )?;
 
                     if let Ok(output_id) = graph[node_id].get_output("current tex") {
                         output_texture_handles.insert(output_id, current_handle);
@@ -367,11 +1106,114 @@ impl CompiledRenderGraph {
                         output_texture_handles.insert(output_id, previous_handle);
                     }
                 }
+                RgNodeTemplate::ImageTexture => {
+                    let path = read_input_value(graph, node_id, "image")?
+                        .as_image_file()?
+                        .clone();
+                    let srgb = *read_input_value(graph, node_id, "srgb")?.as_bool()?;
+
+                    // Without an image the node outputs a single black texel
+                    let image = match path {
+                        Some(path) => load_image(&path)?,
+                        None => Rc::new(image::RgbaImage::from_pixel(
+                            1,
+                            1,
+                            image::Rgba([0, 0, 0, 255]),
+                        )),
+                    };
+                    let (width, height) = image.dimensions();
+
+                    let format = if srgb {
+                        wgpu::TextureFormat::Rgba8UnormSrgb
+                    } else {
+                        wgpu::TextureFormat::Rgba8Unorm
+                    };
+
+                    let handle = build_tex(
+                        width,
+                        height,
+                        1,
+                        wgpu::TextureViewDimension::D2,
+                        1,
+                        format,
+                        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    )?;
+
+                    let texture = &textures[handle.0];
+                    queue.write_texture(
+                        texture.as_image_copy(),
+                        image.as_raw(),
+                        wgpu::TexelCopyBufferLayout {
+                            offset: 0,
+                            bytes_per_row: Some(width * 4),
+                            rows_per_image: Some(height),
+                        },
+                        texture.size(),
+                    );
+
+                    if let Ok(output_id) = graph[node_id].get_output("tex") {
+                        output_texture_handles.insert(output_id, handle);
+                    }
+                }
+                RgNodeTemplate::Noise => {
+                    let [width, height] =
+                        *read_input_value(graph, node_id, "resolution")?.as_uint2()?;
+                    let format: wgpu::TextureFormat =
+                        (*read_input_value(graph, node_id, "format")?.as_texture_format()?).into();
+                    let noise_type = *read_input_value(graph, node_id, "type")?.as_noise_type()?;
+                    let seed = *read_input_value(graph, node_id, "seed")?.as_uint()?;
+                    let frequency = *read_input_value(graph, node_id, "frequency")?.as_float()?;
+
+                    // The noise shader outputs floats, which integer formats can't store
+                    if !matches!(
+                        format.sample_type(None, None),
+                        Some(wgpu::TextureSampleType::Float { .. })
+                    ) {
+                        bail!(
+                            "Noise can't be written to {:?}, use a float or normalized format",
+                            format
+                        );
+                    }
+
+                    let handle = build_tex(
+                        width,
+                        height,
+                        1,
+                        wgpu::TextureViewDimension::D2,
+                        1,
+                        format,
+                        wgpu::TextureUsages::RENDER_ATTACHMENT
+                            | wgpu::TextureUsages::TEXTURE_BINDING,
+                    )?;
+
+                    // The noise only depends on the node inputs, so it's generated once here
+                    let mut encoder =
+                        device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("rg noise cmd encoder"),
+                        });
+                    noise_pass::encode_noise(
+                        &noise_pass::NoisePassParameters {
+                            dst_view: &texture_views[handle.0],
+                            target_format: format,
+                            noise_type,
+                            seed,
+                            frequency,
+                        },
+                        device,
+                        &mut encoder,
+                    );
+                    queue.submit(Some(encoder.finish()));
+
+                    if let Ok(output_id) = graph[node_id].get_output("tex") {
+                        output_texture_handles.insert(output_id, handle);
+                    }
+                }
                 RgNodeTemplate::Buffer => {
                     let size = *read_input_value(graph, node_id, "size")?.as_uint()?;
                     let _persistent = read_input_value(graph, node_id, "persistent")?.as_bool()?;
+                    let (init, fill) = read_buffer_init(graph, node_id)?;
 
-                    let handle = build_buffer(size as u64);
+                    let handle = build_buffer(size as u64, init, fill)?;
 
                     if let Ok(output_id) = graph[node_id].get_output("buf") {
                         output_buffer_handles.insert(output_id, handle);
@@ -379,9 +1221,10 @@ impl CompiledRenderGraph {
                 }
                 RgNodeTemplate::HistoryBuffer => {
                     let size = *read_input_value(graph, node_id, "size")?.as_uint()?;
+                    let (init, fill) = read_buffer_init(graph, node_id)?;
 
-                    let current_handle = build_buffer(size as u64);
-                    let previous_handle = build_buffer(size as u64);
+                    let current_handle = build_buffer(size as u64, init, fill)?;
+                    let previous_handle = build_buffer(size as u64, init, fill)?;
 
                     if let Ok(output_id) = graph[node_id].get_output("current buf") {
                         output_buffer_handles.insert(output_id, current_handle);
@@ -391,9 +1234,77 @@ impl CompiledRenderGraph {
                     }
                 }
 
+                RgNodeTemplate::Sampler => {
+                    let filter = |name: &str| -> anyhow::Result<wgpu::FilterMode> {
+                        Ok((*read_input_value(graph, node_id, name)?.as_sampler_filter()?).into())
+                    };
+                    let address_mode = |name: &str| -> anyhow::Result<wgpu::AddressMode> {
+                        Ok(
+                            (*read_input_value(graph, node_id, name)?.as_sampler_address_mode()?)
+                                .into(),
+                        )
+                    };
+
+                    let handle = SamplerHandle(samplers.len());
+                    samplers.push(device.create_sampler(&wgpu::SamplerDescriptor {
+                        label: Some(&format!("rg sampler {}", handle.0)),
+                        address_mode_u: address_mode("address mode u")?,
+                        address_mode_v: address_mode("address mode v")?,
+                        address_mode_w: address_mode("address mode w")?,
+                        mag_filter: filter("mag filter")?,
+                        min_filter: filter("min filter")?,
+                        mipmap_filter: filter("mipmap filter")?,
+                        ..Default::default()
+                    }));
+
+                    if let Ok(output_id) = graph[node_id].get_output("sampler") {
+                        output_sampler_handles.insert(output_id, handle);
+                    }
+                }
+                RgNodeTemplate::DepthTex => {
+                    let resolution = *read_input_value(graph, node_id, "resolution")?
+                        .as_screen_tex_resolution()?;
+                    let format = *read_input_value(graph, node_id, "format")?.as_depth_format()?;
+
+                    let [width, height] = resolution.resolve(screen_size);
+
+                    let handle = build_tex(
+                        width,
+                        height,
+                        1,
+                        wgpu::TextureViewDimension::D2,
+                        1,
+                        format.into(),
+                        wgpu::TextureUsages::RENDER_ATTACHMENT
+                            | wgpu::TextureUsages::TEXTURE_BINDING,
+                    )?;
+
+                    if let Ok(output_id) = graph[node_id].get_output("depth tex") {
+                        output_texture_handles.insert(output_id, handle);
+                    }
+                }
+                RgNodeTemplate::GraphicsPass if graph[node_id].user_data.bypass => {
+                    // The pass renders in place, so leaving it out forwards the render target untouched
+                    let in_input_id = graph[node_id].get_input("render target")?;
+                    let render_target_handle = graph
+                        .connection(in_input_id)
+                        .and_then(|out| output_texture_handles.get(&out).copied())
+                        .ok_or(anyhow!(
+                            "GraphicsPass 'render target' not connected to a texture"
+                        ))?;
+
+                    if let Ok(output_id) = graph[node_id].get_output("render target") {
+                        output_texture_handles.insert(output_id, render_target_handle);
+                    }
+                    if let (Some(depth_handle), Ok(output_id)) = (
+                        depth_attachment(graph, node_id, &output_texture_handles)?,
+                        graph[node_id].get_output("depth"),
+                    ) {
+                        output_texture_handles.insert(output_id, depth_handle);
+                    }
+                }
                 RgNodeTemplate::GraphicsPass => {
                     let shader_id = *read_input_value(graph, node_id, "code")?.as_code_file()?;
-                    let shader_id = shader_id.ok_or(anyhow!("Unassigned code file"))?;
 
                     // Resolve the render target from the "in" connection.
                     let in_tex = *read_input_value(graph, node_id, "render target")?.as_tex2d()?;
@@ -405,133 +1316,176 @@ impl CompiledRenderGraph {
                             "GraphicsPass 'render target' not connected to a texture"
                         ))?;
 
-                    let shader = match shader_cache.get(&shader_id) {
-                        Some(s) if s.shader_module().is_some() => s,
-                        _ => bail!("Invalid shader"), // Shader not compiled or has errors
-                    };
-
                     let render_target_format: wgpu::TextureFormat = in_tex.format.into();
 
-                    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                        label: Some(&format!("rg pipeline {}", shader_id)),
-                        layout: None,
-                        vertex: wgpu::VertexState {
-                            module: shader.shader_module().as_ref().unwrap(),
-                            entry_point: Some("vs_main"),
-                            buffers: &[],
-                            compilation_options: Default::default(),
-                        },
-                        fragment: Some(wgpu::FragmentState {
-                            module: shader.shader_module().as_ref().unwrap(),
-                            entry_point: Some("fs_main"),
-                            compilation_options: Default::default(),
-                            targets: &[Some(render_target_format.into())],
-                        }),
-                        primitive: wgpu::PrimitiveState::default(),
-                        depth_stencil: None,
-                        multisample: wgpu::MultisampleState::default(),
-                        multiview: None,
-                        cache: None,
-                    });
-
-                    // Separate the index lookups so we can borrow texture_views / buffers
-                    // after the closures are no longer needed.
-                    let mut tex_entries: Vec<(u32, usize)> = Vec::new(); // (binding, tex_idx)
-                    let mut buf_entries: Vec<(u32, usize)> = Vec::new(); // (binding, buf_idx)
-
-                    for binding in shader.get_bindings() {
-                        match binding.resource_type {
-                            RgDataType::Tex2D | RgDataType::Tex2DArray | RgDataType::Tex3D => {
-                                if let Ok(input_id) = graph[node_id].get_input(&binding.name) {
-                                    if let Some(connected_output) = graph.connection(input_id) {
-                                        if let Some(tex_handle) =
-                                            output_texture_handles.get(&connected_output)
-                                        {
-                                            tex_entries.push((binding.binding, tex_handle.0));
-                                        }
-                                    }
-                                }
-                            }
-                            RgDataType::Buffer => {
-                                if let Ok(input_id) = graph[node_id].get_input(&binding.name) {
-                                    if let Some(connected_output) = graph.connection(input_id) {
-                                        if let Some(buf_handle) =
-                                            output_buffer_handles.get(&connected_output)
-                                        {
-                                            buf_entries.push((binding.binding, buf_handle.0));
-                                        }
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-
-                    // Now build the actual wgpu entries (borrows texture_views / buffers directly).
-                    let mut entries: Vec<wgpu::BindGroupEntry> = Vec::new();
-                    for (binding, idx) in &tex_entries {
-                        entries.push(wgpu::BindGroupEntry {
-                            binding: *binding,
-                            resource: wgpu::BindingResource::TextureView(&texture_views[*idx]),
-                        });
-                    }
-                    for (binding, idx) in &buf_entries {
-                        entries.push(wgpu::BindGroupEntry {
-                            binding: *binding,
-                            resource: buffers[*idx].as_entire_binding(),
+                    let depth_handle = depth_attachment(graph, node_id, &output_texture_handles)?;
+                    let mut depth = None;
+                    let mut depth_stencil = None;
+                    if let Some(depth_handle) = depth_handle {
+                        let compare = *read_input_value(graph, node_id, "depth compare")?
+                            .as_depth_compare()?;
+                        let write = *read_input_value(graph, node_id, "depth write")?.as_bool()?;
+
+                        // Only the first pass clears the depth, later passes test against it
+                        let clear = cleared_depth
+                            .insert(depth_handle.0)
+                            .then(|| compare.clear_value());
+                        depth = Some((depth_handle, clear));
+                        depth_stencil = Some(wgpu::DepthStencilState {
+                            format: textures[depth_handle.0].format(),
+                            depth_write_enabled: write,
+                            depth_compare: compare.into(),
+                            stencil: wgpu::StencilState::default(),
+                            bias: wgpu::DepthBiasState::default(),
                         });
                     }
 
-                    let bind_group = if !entries.is_empty() {
-                        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
-                            label: Some(&format!("rg bind group {}", shader_id)),
-                            layout: &pipeline.get_bind_group_layout(0),
-                            entries: &entries,
-                        }))
-                    } else {
-                        None
-                    };
+                    let (pass, validation) = with_validation(device, || {
+                        build_graphics_pass(
+                            graph,
+                            node_id,
+                            shader_id,
+                            shader_cache,
+                            render_target_handle,
+                            render_target_format,
+                            depth_stencil.clone(),
+                            &output_texture_handles,
+                            &output_buffer_handles,
+                            &output_sampler_handles,
+                            &textures,
+                            &texture_views,
+                            &texture_view_dimensions,
+                            &buffers,
+                            &samplers,
+                            device,
+                        )
+                    });
+                    let (pipeline, bind_group) =
+                        pass_or_skip(graph, node_id, pass, &mut pass_errors);
 
                     graphics_passes.push(CompiledGraphicsPass {
                         node_id,
                         shader_id,
                         pipeline,
                         bind_group,
+                        validation,
                         render_target_texture: render_target_handle,
+                        render_target_format,
+                        depth,
+                        depth_stencil,
+                        key: pass_key(node_id, shader_id, shader_cache),
                     });
 
                     if let Ok(output_id) = graph[node_id].get_output("render target") {
                         output_texture_handles.insert(output_id, render_target_handle);
                     }
+                    if let (Some(depth_handle), Ok(output_id)) =
+                        (depth_handle, graph[node_id].get_output("depth"))
+                    {
+                        output_texture_handles.insert(output_id, depth_handle);
+                    }
                 }
-                RgNodeTemplate::DisplayOut => {
-                    if let Ok(input_id) = graph[node_id].get_input("in") {
-                        if let Some(connected_output) = graph.connection(input_id) {
-                            if let Some(&tex_handle) = output_texture_handles.get(&connected_output)
-                            {
-                                display_output = Some(tex_handle);
-                            } else {
-                                bail!("DisplayOut in texture is invalid");
-                            }
-                        } else {
-                            bail!("DisplayOut in is not connected");
+                RgNodeTemplate::Readback => {
+                    let input_id = graph[node_id].get_input("tex")?;
+                    // An unconnected readback has nothing to read, which is fine
+                    if let Some(&texture) = graph
+                        .connection(input_id)
+                        .and_then(|output| output_texture_handles.get(&output))
+                    {
+                        let pixel = *read_input_value(graph, node_id, "pixel")?.as_uint2()?;
+                        let size = textures[texture.0]
+                            .format()
+                            .block_copy_size(None)
+                            .ok_or(anyhow!("Readback texture format can't be copied"))?;
+                        // Buffer copies move whole words, the texel is sliced out when decoding
+                        let size = (size as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+
+                        texel_readbacks.push(TexelReadback {
+                            node_id,
+                            texture,
+                            pixel,
+                            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                                label: Some("rg texel readback"),
+                                size,
+                                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                                mapped_at_creation: false,
+                            }),
+                            pending: None,
+                            value: None,
+                        });
+
+                        if is_previewable(&textures[texture.0], texture_view_dimensions[texture.0])
+                        {
+                            node_textures.insert(node_id, texture);
                         }
-                    } else {
+                    }
+                }
+                RgNodeTemplate::DisplayOut => {
+                    let Ok(input_id) = graph[node_id].get_input("in") else {
                         bail!("No input 'in' found for DisplayOut")
+                    };
+
+                    let connections = graph.connections(input_id);
+                    if connections.is_empty() {
+                        bail!("DisplayOut in is not connected");
+                    }
+                    for connected_output in connections {
+                        let Some(&tex_handle) = output_texture_handles.get(&connected_output)
+                        else {
+                            bail!("DisplayOut in texture is invalid");
+                        };
+                        display_layers.push(tex_handle);
+                    }
+
+                    let layer = display_layers[0].0;
+                    if is_previewable(&textures[layer], texture_view_dimensions[layer]) {
+                        node_textures.insert(node_id, display_layers[0]);
+                    }
+
+                    // Graphs saved before compositing existed have no blend input
+                    if let Ok(blend) = read_input_value(graph, node_id, "blend") {
+                        display_blend = *blend.as_blend_mode()?;
                     }
                 }
             }
+
+            if let Some(handle) = graph[node_id]
+                .output_ids()
+                .filter_map(|output_id| output_texture_handles.get(&output_id))
+                .find(|handle| {
+                    is_previewable(&textures[handle.0], texture_view_dimensions[handle.0])
+                })
+            {
+                node_textures.insert(node_id, *handle);
+            }
         }
 
-        let display_output = display_output.ok_or(anyhow!("No display output"))?;
+        if display_layers.is_empty() {
+            bail!("No display output");
+        }
+
+        let timestamps = PassTimestamps::new(graphics_passes.len(), context);
 
         Ok(Self {
             screen_size,
             buffers,
             textures,
             texture_views,
+            texture_view_dimensions,
+            output_texture_handles,
+            output_buffer_handles,
+            samplers,
+            output_sampler_handles,
+            layout_hash: layout_hash(graph, shader_cache, screen_size, memory_budget),
             graphics_passes,
-            display_output,
+            display_layers,
+            display_blend,
+            node_textures,
+            pass_errors,
+            timestamps,
+            profiling: false,
+            texel_readbacks,
+            texel_timer: Timer::new(),
         })
     }
 
@@ -539,19 +1493,359 @@ impl CompiledRenderGraph {
         &self.screen_size
     }
 
-    pub fn record_command_encoder(
+    pub fn pass_errors(&self) -> &[(NodeId, String)] {
+        &self.pass_errors
+    }
+
+    /// Rebuild the pipeline and bind group of only the passes whose shader changed, keeping
+    /// all textures and buffers. Returns `false` without changing anything when the graph
+    /// layout changed too, the graph then has to be compiled again with [`Self::new`].
+    pub fn update_passes(
+        &mut self,
+        graph: &RgGraph,
+        shader_cache: &HashMap<Uuid, Shader>,
+        memory_budget: u64,
+        device: &wgpu::Device,
+    ) -> bool {
+        if layout_hash(graph, shader_cache, self.screen_size, memory_budget) != self.layout_hash {
+            return false;
+        }
+
+        for i in 0..self.graphics_passes.len() {
+            let node_id = self.graphics_passes[i].node_id;
+            let Ok(shader_id) = read_input_value(graph, node_id, "code")
+                .and_then(|value| value.as_code_file().copied())
+            else {
+                return false;
+            };
+            let key = pass_key(node_id, shader_id, shader_cache);
+            if key == self.graphics_passes[i].key {
+                continue;
+            }
+
+            let pass = &self.graphics_passes[i];
+            let (built, validation) = with_validation(device, || {
+                build_graphics_pass(
+                    graph,
+                    node_id,
+                    shader_id,
+                    shader_cache,
+                    pass.render_target_texture,
+                    pass.render_target_format,
+                    pass.depth_stencil.clone(),
+                    &self.output_texture_handles,
+                    &self.output_buffer_handles,
+                    &self.output_sampler_handles,
+                    &self.textures,
+                    &self.texture_views,
+                    &self.texture_view_dimensions,
+                    &self.buffers,
+                    &self.samplers,
+                    device,
+                )
+            });
+            self.pass_errors.retain(|(id, _)| *id != node_id);
+            let (pipeline, bind_group) = pass_or_skip(graph, node_id, built, &mut self.pass_errors);
+
+            let pass = &mut self.graphics_passes[i];
+            pass.shader_id = shader_id;
+            pass.pipeline = pipeline;
+            pass.bind_group = bind_group;
+            pass.validation = validation;
+            pass.key = key;
+        }
+
+        true
+    }
+
+    /// Skip the passes whose validation error scope resolved with an error, returns
+    /// whether [`Self::pass_errors`] changed.
+    pub fn poll_validation(&mut self, graph: &RgGraph) -> bool {
+        let mut changed = false;
+        for pass in &mut self.graphics_passes {
+            let Some(error) = pass
+                .validation
+                .as_mut()
+                .and_then(|validation| validation.now_or_never())
+            else {
+                continue;
+            };
+            pass.validation = None;
+
+            if let Some(e) = error {
+                let label = graph.nodes.get(pass.node_id).map_or("", |node| &node.label);
+                let message = format!("Skipped pass '{}': {}", label, e);
+                log::warn!("{}", message);
+                self.pass_errors.push((pass.node_id, message));
+                pass.pipeline = None;
+                pass.bind_group = None;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Whether the last pass rendering into `handle` was skipped, see [`Self::output_view`].
+    fn is_skipped_output(&self, handle: TextureHandle) -> bool {
+        self.graphics_passes
+            .iter()
+            .rfind(|pass| pass.render_target_texture.0 == handle.0)
+            .is_some_and(|pass| pass.pipeline.is_none() || pass.validation.is_some())
+    }
+
+    /// View of `handle` to blit, the placeholder [`empty_texture_view`] when the pass
+    /// rendering it was skipped.
+    fn output_view(&self, handle: TextureHandle, device: &wgpu::Device) -> wgpu::TextureView {
+        if self.is_skipped_output(handle) {
+            empty_texture_view(device)
+        } else {
+            self.texture_views[handle.0].clone()
+        }
+    }
+
+    /// GPU time in milliseconds of each pass from the last timestamp readback, empty when
+    /// the device doesn't support timestamp queries.
+    pub fn last_pass_timings(&self) -> Vec<(NodeId, f32)> {
+        self.timestamps
+            .as_ref()
+            .filter(|_| self.profiling)
+            .map(|timestamps| timestamps.last_timings.clone())
+            .unwrap_or_default()
+    }
+
+    /// Time the passes with timestamp queries, read back by [`Self::read_timestamps`].
+    pub fn set_profiling(&mut self, profiling: bool) {
+        if self.profiling == profiling {
+            return;
+        }
+        self.profiling = profiling;
+
+        if let Some(timestamps) = &mut self.timestamps {
+            timestamps.recorded.set(0);
+            timestamps.pending = None;
+            timestamps.last_timings.clear();
+        }
+    }
+
+    /// Texels of the readback nodes from the last completed readback.
+    pub fn texel_values(&self) -> Vec<(NodeId, TexelValue)> {
+        self.texel_readbacks
+            .iter()
+            .filter_map(|readback| Some((readback.node_id, readback.value.clone()?)))
+            .collect()
+    }
+
+    /// Copies the texel of every readback node to the host once the recorded commands are
+    /// submitted, at most once every [`TEXEL_READBACK_INTERVAL`].
+    pub fn read_texels(
+        &mut self,
+        buffer_readback: &BufferReadback,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let due = self.texel_timer.elapsed() >= TEXEL_READBACK_INTERVAL;
+        if due {
+            self.texel_timer.reset();
+        }
+
+        // Finish the copies of earlier frames without waiting for the GPU
+        if self
+            .texel_readbacks
+            .iter()
+            .any(|readback| readback.pending.is_some())
+        {
+            let _ = device.poll(wgpu::PollType::Poll);
+        }
+
+        for readback in &mut self.texel_readbacks {
+            let texture = &self.textures[readback.texture.0];
+
+            if let Some((pending, pixel)) = &mut readback.pending {
+                match pending.try_recv() {
+                    Ok(Some(mut bytes)) => {
+                        let format = texture.format();
+                        bytes.truncate(format.block_copy_size(None).unwrap_or(0) as usize);
+                        readback.value = Some(TexelValue {
+                            pixel: *pixel,
+                            format,
+                            channels: decode_texel(format, &bytes),
+                            bytes,
+                        });
+                        readback.pending = None;
+                    }
+                    Ok(None) => continue,
+                    Err(_) => readback.pending = None,
+                }
+            }
+
+            if !due {
+                continue;
+            }
+
+            // Out of bounds pixels sample the closest edge
+            let pixel = [
+                readback.pixel[0].min(texture.width() - 1),
+                readback.pixel[1].min(texture.height() - 1),
+            ];
+
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("rg texel readback"),
+            });
+            encoder.copy_texture_to_buffer(
+                wgpu::TexelCopyTextureInfo {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: pixel[0],
+                        y: pixel[1],
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &readback.buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: None,
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+            queue.submit(Some(encoder.finish()));
+
+            let pending = buffer_readback.readback_deferred(
+                &readback.buffer,
+                readback.buffer.size(),
+                device,
+                queue,
+            );
+            readback.pending = Some((pending, pixel));
+        }
+    }
+
+    /// Reads back the pass timestamps once the recorded commands are submitted, at most once
+    /// every [`TIMESTAMP_READBACK_INTERVAL`]. Does nothing without timestamp queries.
+    pub fn read_timestamps(
+        &mut self,
+        buffer_readback: &BufferReadback,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let Some(timestamps) = self.timestamps.as_mut().filter(|_| self.profiling) else {
+            return;
+        };
+
+        if let Some((node_ids, pending)) = &mut timestamps.pending {
+            // Finish the readback of an earlier frame without waiting for the GPU
+            let _ = device.poll(wgpu::PollType::Poll);
+
+            match pending.try_recv() {
+                Ok(Some(data)) => {
+                    let durations = pass_durations(&data, timestamps.period);
+                    timestamps.last_timings = node_ids.iter().copied().zip(durations).collect();
+                    timestamps.pending = None;
+                }
+                Ok(None) => return,
+                Err(_) => timestamps.pending = None,
+            }
+        }
+
+        let recorded = timestamps.recorded.get();
+        if recorded == 0 || timestamps.timer.elapsed() < TIMESTAMP_READBACK_INTERVAL {
+            return;
+        }
+        timestamps.timer.reset();
+
+        let node_ids = self.graphics_passes[..recorded]
+            .iter()
+            .map(|pass| pass.node_id)
+            .collect();
+        let pending = buffer_readback.readback_deferred(
+            &timestamps.resolve_buffer,
+            recorded as u64 * 2 * wgpu::QUERY_SIZE as u64,
+            device,
+            queue,
+        );
+        timestamps.pending = Some((node_ids, pending));
+    }
+
+    /// Records all passes of the graph into `encoder`, followed by compositing
+    /// the display output layers into `target_view`.
+    pub fn record(
         &self,
+        encoder: &mut wgpu::CommandEncoder,
         device: &wgpu::Device,
         target_view: &wgpu::TextureView,
         target_format: wgpu::TextureFormat,
-    ) -> wgpu::CommandEncoder {
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("rg cmd encoder"),
-        });
+        aspect_mode: blit_pass::AspectMode,
+    ) {
+        self.record_passes(encoder, &self.graphics_passes);
+
+        for (i, layer) in self.display_layers.iter().enumerate() {
+            blit_pass::encode_blit(
+                &blit_pass::BlitPassParameters {
+                    src_view: &self.output_view(*layer, device),
+                    dst_view: target_view,
+                    target_format,
+                    blending: (i > 0).then_some(self.display_blend),
+                    tonemap: None,
+                    viewport: self.fit_viewport(*layer, aspect_mode),
+                },
+                device,
+                encoder,
+            );
+        }
+    }
 
-        for (i, pass) in self.graphics_passes.iter().enumerate() {
+    /// Like [`Self::record`], but returns the output of `node` instead of the display output.
+    /// Passes after `node` are left out, so the result isn't overwritten by later passes
+    /// rendering into the same texture. Returns `None` when the node has no texture to show.
+    pub fn record_inspect(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        node: NodeId,
+    ) -> Option<wgpu::TextureView> {
+        let handle = self.node_textures.get(&node)?;
+
+        let pass_count = self
+            .graphics_passes
+            .iter()
+            .position(|pass| pass.node_id == node)
+            .map_or(self.graphics_passes.len(), |i| i + 1);
+        self.record_passes(encoder, &self.graphics_passes[..pass_count]);
+
+        Some(self.output_view(*handle, device))
+    }
+
+    /// Where a texture lands in the screen sized target, see [`blit_pass::AspectMode::viewport`].
+    fn fit_viewport(
+        &self,
+        handle: TextureHandle,
+        aspect_mode: blit_pass::AspectMode,
+    ) -> Option<[f32; 4]> {
+        let size = self.textures[handle.0].size();
+        aspect_mode.viewport([size.width, size.height], self.screen_size)
+    }
+
+    fn record_passes(&self, encoder: &mut wgpu::CommandEncoder, passes: &[CompiledGraphicsPass]) {
+        for (i, pass) in passes.iter().enumerate() {
             let output_view = &self.texture_views[pass.render_target_texture.0];
 
+            // A skipped pass leaves a cleared render target behind, like the placeholder
+            // its output is shown as
+            let pipeline = pass.pipeline.as_ref().filter(|_| pass.validation.is_none());
+            let clear_color = if pipeline.is_some() {
+                wgpu::Color::GREEN
+            } else {
+                wgpu::Color::TRANSPARENT
+            };
+
             {
                 let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                     label: Some(&format!("rg render pass {}", i)),
@@ -559,15 +1853,31 @@ impl CompiledRenderGraph {
                         view: output_view,
                         resolve_target: None,
                         ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                            load: wgpu::LoadOp::Clear(clear_color),
                             store: wgpu::StoreOp::Store,
                         },
                     })],
-                    depth_stencil_attachment: None,
-                    timestamp_writes: None,
+                    depth_stencil_attachment: pass.depth.map(|(handle, clear)| {
+                        wgpu::RenderPassDepthStencilAttachment {
+                            view: &self.texture_views[handle.0],
+                            depth_ops: Some(wgpu::Operations {
+                                load: clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+                                store: wgpu::StoreOp::Store,
+                            }),
+                            stencil_ops: None,
+                        }
+                    }),
+                    timestamp_writes: self
+                        .timestamps
+                        .as_ref()
+                        .filter(|_| self.profiling)
+                        .and_then(|timestamps| timestamps.writes(i)),
                     occlusion_query_set: None,
                 });
-                rpass.set_pipeline(&pass.pipeline);
+                let Some(pipeline) = pipeline else {
+                    continue;
+                };
+                rpass.set_pipeline(pipeline);
 
                 if let Some(bind_group) = &pass.bind_group {
                     rpass.set_bind_group(0, bind_group, &[]);
@@ -577,17 +1887,49 @@ impl CompiledRenderGraph {
             }
         }
 
-        let src_view = &self.texture_views[self.display_output.0];
-        blit_pass::encode_blit(
-            &blit_pass::BlitPassParameters {
-                src_view,
-                dst_view: target_view,
+        if let Some(timestamps) = self.timestamps.as_ref().filter(|_| self.profiling) {
+            timestamps.resolve(encoder, passes.len());
+        }
+    }
+
+    /// Records the graph and blits the display output to `target_view`, or the
+    /// output of `inspect_node` when it has one.
+    pub fn record_command_encoder(
+        &self,
+        device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        target_view: &wgpu::TextureView,
+        target_format: wgpu::TextureFormat,
+        inspect_node: Option<NodeId>,
+        aspect_mode: blit_pass::AspectMode,
+    ) -> wgpu::CommandEncoder {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("rg cmd encoder"),
+        });
+
+        match inspect_node
+            .and_then(|node| Some((node, self.record_inspect(&mut encoder, device, node)?)))
+        {
+            Some((node, src_view)) => blit_pass::encode_blit(
+                &blit_pass::BlitPassParameters {
+                    src_view: &src_view,
+                    dst_view: target_view,
+                    target_format,
+                    blending: None,
+                    tonemap: None,
+                    viewport: self.fit_viewport(self.node_textures[&node], aspect_mode),
+                },
+                device,
+                &mut encoder,
+            ),
+            None => self.record(
+                &mut encoder,
+                device,
+                target_view,
                 target_format,
-                blending: None,
-            },
-            device,
-            &mut encoder,
-        );
+                aspect_mode,
+            ),
+        }
 
         encoder
     }
@@ -0,0 +1,101 @@
+use anyhow::anyhow;
+
+use crate::{
+    project::CodeFiles,
+    render_graph::RenderGraph,
+    wgpu_util::{context_wrapper::ContextWrapper, BufferReadback},
+};
+
+/// Format of the pixels returned by [`RenderGraph::render_headless`].
+pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+impl RenderGraph {
+    /// Compile the graph at a fixed `size`, run a single frame without any window
+    /// or egui involvement and read back the display output.
+    /// Returns tightly packed rows of [`HEADLESS_FORMAT`] pixels.
+    pub fn render_headless(
+        &mut self,
+        code_files: &CodeFiles,
+        size: [u32; 2],
+        context: &ContextWrapper,
+    ) -> anyhow::Result<Vec<u8>> {
+        let (device, queue) = (&context.device, &context.queue);
+        if self.sync_code_files(code_files, device) {
+            self.sync_dynamic_node_inputs();
+        }
+
+        let compiled_rg = self.compile(size, context)?;
+
+        let [width, height] = size.map(|x| x.max(1));
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("rg headless target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HEADLESS_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Rows copied out of a texture have to be aligned to COPY_BYTES_PER_ROW_ALIGNMENT
+        let bytes_per_pixel = HEADLESS_FORMAT.block_copy_size(None).unwrap();
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let padded_bytes_per_row =
+            unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback_size = padded_bytes_per_row as u64 * height as u64;
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("rg headless readback"),
+            size: readback_size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("rg headless cmd encoder"),
+        });
+
+        compiled_rg.record(
+            &mut encoder,
+            device,
+            &target_view,
+            HEADLESS_FORMAT,
+            Default::default(),
+        );
+
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            target.size(),
+        );
+
+        queue.submit(Some(encoder.finish()));
+
+        let padded = futures::executor::block_on(BufferReadback::new().readback::<u8>(
+            &readback_buffer,
+            readback_size,
+            device,
+            queue,
+        ))
+        .map_err(|_| anyhow!("Headless readback was cancelled"))?;
+
+        Ok(padded
+            .chunks_exact(padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+            .copied()
+            .collect())
+    }
+}
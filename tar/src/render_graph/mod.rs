@@ -12,15 +12,92 @@ use crate::{
         EditorDragPayload,
     },
     project::{CodeFileType, CodeFiles},
-    render_graph::{compiled_render_graph::CompiledRenderGraph, shader::Shader},
-    wgpu_util::BasicColorTextureFormat,
+    render_graph::{
+        compiled_render_graph::{
+            topological_sort, CompiledRenderGraph, TexelValue, DEFAULT_MEMORY_BUDGET,
+        },
+        shader::{BindingDefault, Shader},
+    },
+    wgpu_util::{
+        blit_pass::BlendMode, context_wrapper::ContextWrapper, noise_pass::NoiseType,
+        BasicColorTextureFormat,
+    },
 };
 
+pub mod bundle;
 pub mod compiled_render_graph;
+pub mod headless;
 pub mod shader;
 
+/// Inputs every graphics pass has, the other inputs are created from the bindings of its shader.
+pub const GRAPHICS_PASS_STATIC_INPUTS: &[&str] = &[
+    "code",
+    "render target",
+    "depth",
+    "depth compare",
+    "depth write",
+];
+
+/// Add the optional depth attachment inputs of a graphics pass that it doesn't have yet,
+/// passes saved before depth attachments existed get them when the graph is loaded.
+pub fn add_graphics_pass_depth_inputs(graph: &mut RgGraph, node_id: NodeId) {
+    let missing = |graph: &RgGraph, name: &str| graph[node_id].get_input(name).is_err();
+
+    if missing(graph, "depth") {
+        graph.add_input_param(
+            node_id,
+            "depth".to_string(),
+            RgDataType::DepthTex2D,
+            RgValueType::DepthTex2D,
+            InputParamKind::ConnectionOnly,
+            true,
+            true,
+        );
+    }
+    if missing(graph, "depth compare") {
+        graph.add_input_param(
+            node_id,
+            "depth compare".to_string(),
+            RgDataType::DepthCompare,
+            RgValueType::DepthCompare(DepthCompare::default()),
+            InputParamKind::ConstantOnly,
+            true,
+            true,
+        );
+    }
+    if missing(graph, "depth write") {
+        graph.add_input_param(
+            node_id,
+            "depth write".to_string(),
+            RgDataType::Bool,
+            RgValueType::Bool(true),
+            InputParamKind::ConstantOnly,
+            true,
+            true,
+        );
+    }
+}
+
 pub type RgGraph = Graph<RgNodeData, RgDataType, RgValueType>;
 
+/// Deleting a node with more connections than this asks for confirmation first.
+const DELETE_CONFIRM_CONNECTIONS: usize = 3;
+/// Number of graph edits that can be undone.
+const UNDO_LIMIT: usize = 32;
+
+/// Extensions of the image files that can be loaded into an [`RgNodeTemplate::ImageTexture`].
+pub const IMAGE_FILE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+pub fn is_image_file(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            IMAGE_FILE_EXTENSIONS
+                .iter()
+                .any(|image_extension| extension.eq_ignore_ascii_case(image_extension))
+        })
+}
+
 #[derive(
     Default,
     Copy,
@@ -79,6 +156,176 @@ impl From<TextureUsage> for wgpu::TextureUsages {
     }
 }
 
+/// How the contents of a buffer are seeded when the render graph is compiled.
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+pub enum BufferInit {
+    #[default]
+    Uninitialized,
+    Zeroed,
+    /// Every u32 in the buffer is set to the node's "fill" value.
+    Fill,
+}
+
+impl BufferInit {
+    /// Builds the initial contents for a buffer of `size` bytes, or `None` when
+    /// the buffer should be left untouched.
+    pub fn contents(&self, size: u64, fill: u32) -> Option<Vec<u8>> {
+        match self {
+            Self::Uninitialized => None,
+            Self::Zeroed => Some(vec![0; size as usize]),
+            Self::Fill => Some(
+                fill.to_ne_bytes()
+                    .into_iter()
+                    .cycle()
+                    .take(size as usize)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Filter of a sampler node, used for magnification, minification and between mips.
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+pub enum SamplerFilter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+impl From<SamplerFilter> for wgpu::FilterMode {
+    fn from(filter: SamplerFilter) -> Self {
+        match filter {
+            SamplerFilter::Linear => wgpu::FilterMode::Linear,
+            SamplerFilter::Nearest => wgpu::FilterMode::Nearest,
+        }
+    }
+}
+
+/// How a sampler node handles texture coordinates outside of [0, 1].
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+pub enum SamplerAddressMode {
+    #[default]
+    ClampToEdge,
+    Repeat,
+    MirrorRepeat,
+}
+
+impl From<SamplerAddressMode> for wgpu::AddressMode {
+    fn from(mode: SamplerAddressMode) -> Self {
+        match mode {
+            SamplerAddressMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            SamplerAddressMode::Repeat => wgpu::AddressMode::Repeat,
+            SamplerAddressMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+/// Format of a depth texture node.
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+pub enum DepthFormat {
+    #[default]
+    Depth32Float,
+    Depth24Plus,
+}
+
+impl From<DepthFormat> for wgpu::TextureFormat {
+    fn from(format: DepthFormat) -> Self {
+        match format {
+            DepthFormat::Depth32Float => wgpu::TextureFormat::Depth32Float,
+            DepthFormat::Depth24Plus => wgpu::TextureFormat::Depth24Plus,
+        }
+    }
+}
+
+/// Depth test of a graphics pass with a depth attachment.
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+pub enum DepthCompare {
+    #[default]
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    Always,
+    Never,
+}
+
+impl DepthCompare {
+    /// Depth the attachment is cleared to, the far plane is at 0 for reversed depth.
+    pub fn clear_value(self) -> f32 {
+        match self {
+            Self::Greater | Self::GreaterEqual => 0.0,
+            _ => 1.0,
+        }
+    }
+}
+
+impl From<DepthCompare> for wgpu::CompareFunction {
+    fn from(compare: DepthCompare) -> Self {
+        match compare {
+            DepthCompare::Less => wgpu::CompareFunction::Less,
+            DepthCompare::LessEqual => wgpu::CompareFunction::LessEqual,
+            DepthCompare::Greater => wgpu::CompareFunction::Greater,
+            DepthCompare::GreaterEqual => wgpu::CompareFunction::GreaterEqual,
+            DepthCompare::Equal => wgpu::CompareFunction::Equal,
+            DepthCompare::NotEqual => wgpu::CompareFunction::NotEqual,
+            DepthCompare::Always => wgpu::CompareFunction::Always,
+            DepthCompare::Never => wgpu::CompareFunction::Never,
+        }
+    }
+}
+
 #[derive(Default, Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct ScreenTex {
     pub resolution: ScreenTexResolution,
@@ -137,11 +384,19 @@ pub struct HistoryTex3D {
 pub struct Buffer {
     pub size: u32,
     pub persistent: bool,
+    #[serde(default)]
+    pub init: BufferInit,
+    #[serde(default)]
+    pub fill: u32,
 }
 
 #[derive(Default, Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct HistoryBuffer {
     pub size: u32,
+    #[serde(default)]
+    pub init: BufferInit,
+    #[serde(default)]
+    pub fill: u32,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -155,6 +410,13 @@ pub enum RgDataType {
     ScreenTexResolution,
     TextureFormat,
     TextureUsage,
+    BufferInit,
+    NoiseType,
+    BlendMode,
+    SamplerFilter,
+    SamplerAddressMode,
+    DepthFormat,
+    DepthCompare,
 
     Tex2D,
     HistoryTex2D,
@@ -163,11 +425,14 @@ pub enum RgDataType {
     HistoryTex3D,
     Buffer,
     HistoryBuffer,
+    Sampler,
+    DepthTex2D,
 
     CodeFile,
+    ImageFile,
 }
 
-#[derive(Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum RgValueType {
     UInt(u32),
     UInt2([u32; 2]),
@@ -178,13 +443,26 @@ pub enum RgValueType {
     ScreenTexResolution(ScreenTexResolution),
     TextureFormat(BasicColorTextureFormat),
     TextureUsage(TextureUsage),
+    BufferInit(BufferInit),
+    NoiseType(NoiseType),
+    BlendMode(BlendMode),
+    SamplerFilter(SamplerFilter),
+    SamplerAddressMode(SamplerAddressMode),
+    DepthFormat(DepthFormat),
+    DepthCompare(DepthCompare),
 
     Tex2D(Tex2D),
     Tex2DArray(Tex2DArray),
     Tex3D(Tex3D),
     Buffer(Buffer),
+    /// Sampler bindings of a graphics pass, connected to a sampler node.
+    Sampler,
+    /// Depth attachment of a graphics pass, connected to a depth texture node.
+    DepthTex2D,
 
     CodeFile(Option<Uuid>),
+    /// Path of an image on disk, loaded when the graph is compiled.
+    ImageFile(Option<PathBuf>),
 }
 
 impl Default for RgValueType {
@@ -215,6 +493,55 @@ impl RgValueType {
         }
     }
 
+    pub fn as_buffer_init(&self) -> anyhow::Result<&BufferInit> {
+        match self {
+            Self::BufferInit(result) => Ok(result),
+            _ => bail!("{:?} is not of type BufferInit", self),
+        }
+    }
+
+    pub fn as_noise_type(&self) -> anyhow::Result<&NoiseType> {
+        match self {
+            Self::NoiseType(result) => Ok(result),
+            _ => bail!("{:?} is not of type NoiseType", self),
+        }
+    }
+
+    pub fn as_blend_mode(&self) -> anyhow::Result<&BlendMode> {
+        match self {
+            Self::BlendMode(result) => Ok(result),
+            _ => bail!("{:?} is not of type BlendMode", self),
+        }
+    }
+
+    pub fn as_sampler_filter(&self) -> anyhow::Result<&SamplerFilter> {
+        match self {
+            Self::SamplerFilter(result) => Ok(result),
+            _ => bail!("{:?} is not of type SamplerFilter", self),
+        }
+    }
+
+    pub fn as_sampler_address_mode(&self) -> anyhow::Result<&SamplerAddressMode> {
+        match self {
+            Self::SamplerAddressMode(result) => Ok(result),
+            _ => bail!("{:?} is not of type SamplerAddressMode", self),
+        }
+    }
+
+    pub fn as_depth_format(&self) -> anyhow::Result<&DepthFormat> {
+        match self {
+            Self::DepthFormat(result) => Ok(result),
+            _ => bail!("{:?} is not of type DepthFormat", self),
+        }
+    }
+
+    pub fn as_depth_compare(&self) -> anyhow::Result<&DepthCompare> {
+        match self {
+            Self::DepthCompare(result) => Ok(result),
+            _ => bail!("{:?} is not of type DepthCompare", self),
+        }
+    }
+
     pub fn as_uint(&self) -> anyhow::Result<&u32> {
         match self {
             Self::UInt(result) => Ok(result),
@@ -284,6 +611,13 @@ impl RgValueType {
             _ => bail!("{:?} is not of type CodeFile", self),
         }
     }
+
+    pub fn as_image_file(&self) -> anyhow::Result<&Option<PathBuf>> {
+        match self {
+            Self::ImageFile(result) => Ok(result),
+            _ => bail!("{:?} is not of type ImageFile", self),
+        }
+    }
 }
 
 #[derive(Clone, Copy, serde::Serialize, serde::Deserialize, EnumIter)]
@@ -295,16 +629,82 @@ pub enum RgNodeTemplate {
     Tex2DArray,
     Tex3D,
     HistoryTex3D,
+    /// Texture loaded from an image file.
+    ImageTexture,
+    /// Texture filled with procedural noise.
+    Noise,
     Buffer,
     HistoryBuffer,
+    /// Sampler with configurable filtering and address modes for graphics passes.
+    Sampler,
+    /// Screen sized depth texture, used as the depth attachment of graphics passes.
+    DepthTex,
 
     GraphicsPass,
 
+    /// Copies a texel of a texture back to the host to inspect its value.
+    Readback,
+
     DisplayOut,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct RgNodeData(pub RgNodeTemplate);
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(from = "RgNodeDataRepr")]
+pub struct RgNodeData {
+    pub template: RgNodeTemplate,
+    /// Skip executing this node and forward its input resources to its outputs.
+    pub bypass: bool,
+    /// User given name shown in the title bar instead of the template name.
+    pub label: Option<String>,
+    /// User given title bar color.
+    pub color: Option<egui::Color32>,
+}
+
+impl RgNodeData {
+    pub fn new(template: RgNodeTemplate) -> Self {
+        Self {
+            template,
+            bypass: false,
+            label: None,
+            color: None,
+        }
+    }
+}
+
+/// Projects saved before nodes carried more than their template stored only the template.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum RgNodeDataRepr {
+    Template(RgNodeTemplate),
+    Data {
+        template: RgNodeTemplate,
+        #[serde(default)]
+        bypass: bool,
+        #[serde(default)]
+        label: Option<String>,
+        #[serde(default)]
+        color: Option<egui::Color32>,
+    },
+}
+
+impl From<RgNodeDataRepr> for RgNodeData {
+    fn from(repr: RgNodeDataRepr) -> Self {
+        match repr {
+            RgNodeDataRepr::Template(template) => Self::new(template),
+            RgNodeDataRepr::Data {
+                template,
+                bypass,
+                label,
+                color,
+            } => Self {
+                template,
+                bypass,
+                label,
+                color,
+            },
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct RgEditorGraphState {
@@ -320,17 +720,56 @@ pub struct RgGraphState {
     #[serde(skip)]
     pub shader_cache: HashMap<Uuid, Shader>,
 
-    // TODO: also editor-only related
+    /// Node shown in the viewport instead of the display output, saved with the graph
+    /// so a reopened project keeps showing the same pass.
+    #[serde(default)]
     pub inspect_node: Option<NodeId>,
 
+    /// Maximum number of bytes the compiled graph may allocate for its textures and buffers.
+    /// Falls back to [`DEFAULT_MEMORY_BUDGET`] when unset.
+    #[serde(default)]
+    pub memory_budget: Option<u64>,
+
+    /// Passes skipped by the last compiled graph, with a message describing why.
+    #[serde(skip)]
+    pub pass_errors: Vec<(NodeId, String)>,
+
+    /// Time the passes on the GPU, off by default as reading the timings back costs time too.
+    #[serde(default)]
+    pub profile_passes: bool,
+
+    /// GPU time in milliseconds of each pass, see [`CompiledRenderGraph::last_pass_timings`].
+    #[serde(skip)]
+    pub pass_timings: HashMap<NodeId, f32>,
+
+    /// Texels sampled by readback nodes, see [`CompiledRenderGraph::texel_values`].
+    #[serde(skip)]
+    pub texel_values: HashMap<NodeId, TexelValue>,
+
     #[serde(skip)]
     pub editor: Option<RgEditorGraphState>,
+
+    /// Nodes waiting for the user to confirm they should be deleted.
+    #[serde(skip)]
+    pub pending_delete: Vec<NodeId>,
+    /// Graph from before each edit, see [`RenderGraph::record_undo`].
+    #[serde(skip)]
+    pub undo: Vec<RgEditorState>,
+    /// Graph as of the last recorded edit, pushed onto `undo` by the next one.
+    #[serde(skip)]
+    pub undo_base: Option<RgEditorState>,
+    /// Whether the last edit happened while dragging, the rest of the drag is merged into it.
+    #[serde(skip)]
+    pub undo_dragging: bool,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct RenderGraph {
     node_graph: RgEditorState,
     graph_state: RgGraphState,
+    /// [`CodeFiles::revision`] the shaders were last synced with.
+    #[serde(skip)]
+    synced_code_revision: Option<u64>,
 }
 
 impl RenderGraph {
@@ -341,7 +780,7 @@ impl RenderGraph {
         let mut add_node = |template: RgNodeTemplate, pos| -> NodeId {
             let node_id = node_graph.graph.add_node(
                 template.node_graph_label(&mut graph_state),
-                RgNodeData(template),
+                RgNodeData::new(template),
                 |_, _| {},
             );
             template.build_node(&mut node_graph.graph, &mut graph_state, node_id);
@@ -403,7 +842,10 @@ impl RenderGraph {
             0,
         );
 
-        let code_file = code_files.files_iter().next().map(|(id, _)| *id);
+        let code_file = code_files
+            .files_iter()
+            .find(|(_, file)| file.ty() == CodeFileType::Fragment)
+            .map(|(id, _)| *id);
 
         let graphics_pass_code = node_graph
             .graph
@@ -417,6 +859,31 @@ impl RenderGraph {
         Self {
             node_graph,
             graph_state,
+            synced_code_revision: None,
+        }
+    }
+
+    /// A graph with nothing but the display output.
+    pub fn empty() -> Self {
+        let mut node_graph = RgEditorState::default();
+        let mut graph_state = RgGraphState::default();
+
+        let template = RgNodeTemplate::DisplayOut;
+        let node_id = node_graph.graph.add_node(
+            template.node_graph_label(&mut graph_state),
+            RgNodeData::new(template),
+            |_, _| {},
+        );
+        template.build_node(&mut node_graph.graph, &mut graph_state, node_id);
+        node_graph
+            .node_positions
+            .insert(node_id, egui::Pos2::new(100.0, 100.0));
+        node_graph.node_order.push(node_id);
+
+        Self {
+            node_graph,
+            graph_state,
+            synced_code_revision: None,
         }
     }
 
@@ -431,6 +898,15 @@ impl RenderGraph {
             drag_payload: std::mem::take(drag_payload),
         });
 
+        let editor_rect = ui.max_rect();
+
+        if self.graph_state.undo_base.is_none() {
+            self.graph_state.undo_base = Some(self.node_graph.clone());
+        }
+        if !ui.input(|i| i.pointer.any_down()) {
+            self.graph_state.undo_dragging = false;
+        }
+
         let graph_response = self.node_graph.draw_graph_editor(
             ui,
             AllMyNodeTemplates,
@@ -444,16 +920,75 @@ impl RenderGraph {
 
         let mut dirty = false;
 
+        // Image files dropped from the OS onto the canvas become image texture nodes
+        let drop_pos = ui
+            .input(|i| i.pointer.latest_pos())
+            .unwrap_or(editor_rect.center());
+        if editor_rect.contains(drop_pos) {
+            // Only the images are taken out, other dropped files stay for the other tabs
+            let dropped_images: Vec<PathBuf> = ui.input_mut(|i| {
+                let (images, others): (Vec<_>, Vec<_>) = std::mem::take(&mut i.raw.dropped_files)
+                    .into_iter()
+                    .partition(|file| file.path.as_deref().is_some_and(is_image_file));
+                i.raw.dropped_files = others;
+                images.into_iter().filter_map(|file| file.path).collect()
+            });
+
+            // Node positions are stored zoomed, so the spacing between the nodes scales with the zoom
+            const DROP_SPACING: egui::Vec2 = egui::Vec2::new(30.0, 30.0);
+            let zoom = self.node_graph.pan_zoom.zoom;
+            let graph_pos = drop_pos - self.node_graph.pan_zoom.pan - editor_rect.min.to_vec2();
+            for (i, path) in dropped_images.into_iter().enumerate() {
+                self.add_image_texture_node(path, graph_pos + DROP_SPACING * zoom * i as f32);
+                dirty = true;
+            }
+        }
+
+        let mut delete_requests = Vec::new();
+        let mut undone = false;
+
+        let shortcuts_enabled = graph_response.cursor_in_editor && !ui.ctx().wants_keyboard_input();
+        if shortcuts_enabled && ui.input(|i| i.key_pressed(egui::Key::Delete)) {
+            delete_requests.extend(self.node_graph.selected_nodes.iter().copied());
+        }
+        if shortcuts_enabled && ui.input(|i| i.modifiers.command && i.key_pressed(egui::Key::Z)) {
+            undone = self.undo();
+        }
+
+        let duplicate_pressed = ui.input(|i| i.modifiers.command && i.key_pressed(egui::Key::D));
+        if duplicate_pressed && graph_response.cursor_in_editor {
+            if let [node] = self.node_graph.selected_nodes[..] {
+                self.duplicate_node(node);
+                dirty = true;
+            }
+        }
+
         for node_response in graph_response.node_responses {
             match node_response {
                 NodeResponse::User(user_event) => match user_event {
                     MyResponse::SetInspectNode(node) => self.graph_state.inspect_node = Some(node),
                     MyResponse::ClearInspectNode => self.graph_state.inspect_node = None,
+                    MyResponse::SetBypass(node, bypass) => {
+                        self.node_graph.graph[node].user_data.bypass = bypass;
+                        dirty = true;
+                    }
+                    MyResponse::SetLabel(node, label) => {
+                        self.node_graph.graph[node].user_data.label = label;
+                        dirty = true;
+                    }
+                    MyResponse::SetColor(node, color) => {
+                        self.node_graph.graph[node].user_data.color = color;
+                        dirty = true;
+                    }
+                    MyResponse::DuplicateNode(node) => {
+                        self.duplicate_node(node);
+                        dirty = true;
+                    }
                     MyResponse::ValueChanged => dirty = true,
                 },
+                NodeResponse::DeleteNodeRequest(node_id) => delete_requests.push(node_id),
                 NodeResponse::ConnectEventEnded { .. }
                 | NodeResponse::CreatedNode(_)
-                | NodeResponse::DeleteNodeFull { .. }
                 | NodeResponse::DisconnectEvent { .. } => {
                     dirty = true;
                 }
@@ -461,7 +996,216 @@ impl RenderGraph {
             }
         }
 
-        dirty
+        if !delete_requests.is_empty() {
+            let needs_confirmation = delete_requests
+                .iter()
+                .any(|&node| self.connection_count(node) > DELETE_CONFIRM_CONNECTIONS);
+            if needs_confirmation {
+                self.graph_state.pending_delete = delete_requests;
+            } else {
+                self.delete_nodes(&delete_requests);
+                dirty = true;
+            }
+        }
+
+        if self.delete_confirmation_ui(ui.ctx()) {
+            dirty = true;
+        }
+
+        if dirty && !undone {
+            self.record_undo(ui.input(|i| i.pointer.any_down()));
+        }
+
+        dirty || undone
+    }
+
+    /// Connections to the inputs and from the outputs of `node`.
+    fn connection_count(&self, node: NodeId) -> usize {
+        let graph = &self.node_graph.graph;
+        graph
+            .iter_connections()
+            .filter(|&(input, output)| {
+                graph.get_input(input).node == node || graph.get_output(output).node == node
+            })
+            .count()
+    }
+
+    /// Delete `nodes` in one step that can be undone.
+    fn delete_nodes(&mut self, nodes: &[NodeId]) {
+        for &node in nodes {
+            if !self.node_graph.graph.nodes.contains_key(node) {
+                continue;
+            }
+
+            self.node_graph.delete_node(node);
+            if self.graph_state.inspect_node == Some(node) {
+                self.graph_state.inspect_node = None;
+            }
+        }
+    }
+
+    /// Push the graph from before this frame's edit onto the undo stack. Edits made while
+    /// `dragging`, like dragging a value, are undone together.
+    fn record_undo(&mut self, dragging: bool) {
+        let state = &mut self.graph_state;
+        if !state.undo_dragging {
+            if let Some(base) = state.undo_base.take() {
+                if state.undo.len() == UNDO_LIMIT {
+                    state.undo.remove(0);
+                }
+                state.undo.push(base);
+            }
+        }
+        state.undo_dragging = dragging;
+        state.undo_base = Some(self.node_graph.clone());
+    }
+
+    /// Restore the graph from before the last edit, returns whether there was one.
+    fn undo(&mut self) -> bool {
+        let Some(mut node_graph) = self.graph_state.undo.pop() else {
+            return false;
+        };
+
+        // Keep looking at the same part of the graph, moving nodes isn't an edit
+        node_graph.pan_zoom = self.node_graph.pan_zoom.clone();
+        for (node, pos) in &self.node_graph.node_positions {
+            if let Some(restored_pos) = node_graph.node_positions.get_mut(node) {
+                *restored_pos = *pos;
+            }
+        }
+        self.node_graph = node_graph;
+
+        if self
+            .graph_state
+            .inspect_node
+            .is_some_and(|node| !self.node_graph.graph.nodes.contains_key(node))
+        {
+            self.graph_state.inspect_node = None;
+        }
+        // The graph may be from before the shaders last changed
+        self.sync_dynamic_node_inputs();
+
+        self.graph_state.undo_base = Some(self.node_graph.clone());
+        self.graph_state.undo_dragging = false;
+        true
+    }
+
+    /// Ask whether the pending nodes should be deleted, returns whether they were.
+    fn delete_confirmation_ui(&mut self, ctx: &egui::Context) -> bool {
+        if self.graph_state.pending_delete.is_empty() {
+            return false;
+        }
+
+        let connections: usize = self
+            .graph_state
+            .pending_delete
+            .iter()
+            .map(|&node| self.connection_count(node))
+            .sum();
+
+        let mut confirmed = false;
+        let mut cancelled = false;
+        let modal = egui::Modal::new(egui::Id::new("rg_delete_confirmation")).show(ctx, |ui| {
+            ui.heading("Delete Nodes");
+            ui.label(format!(
+                "Delete {} node(s) with {} connection(s)? This can be undone with Ctrl+Z.",
+                self.graph_state.pending_delete.len(),
+                connections
+            ));
+
+            ui.horizontal(|ui| {
+                confirmed =
+                    ui.button("Delete").clicked() || ui.input(|i| i.key_pressed(egui::Key::Enter));
+                cancelled = ui.button("Cancel").clicked();
+            });
+        });
+
+        if confirmed {
+            let nodes = std::mem::take(&mut self.graph_state.pending_delete);
+            self.delete_nodes(&nodes);
+        } else if cancelled || modal.should_close() {
+            self.graph_state.pending_delete.clear();
+        }
+
+        confirmed
+    }
+
+    /// Add an [`RgNodeTemplate::ImageTexture`] node at `pos` that loads `path`.
+    pub fn add_image_texture_node(&mut self, path: PathBuf, pos: egui::Pos2) -> NodeId {
+        let template = RgNodeTemplate::ImageTexture;
+        let graph = &mut self.node_graph.graph;
+        let node_id = graph.add_node(
+            template.node_graph_label(&mut self.graph_state),
+            RgNodeData::new(template),
+            |_, _| {},
+        );
+        template.build_node(graph, &mut self.graph_state, node_id);
+
+        if let Ok(input) = graph[node_id].get_input("image") {
+            graph[input].value = RgValueType::ImageFile(Some(path));
+        }
+
+        self.node_graph.node_positions.insert(node_id, pos);
+        self.node_graph.node_order.push(node_id);
+
+        node_id
+    }
+
+    /// Add a copy of `node` next to it with the same input constants but without
+    /// any connections, and select the copy.
+    pub fn duplicate_node(&mut self, node: NodeId) -> NodeId {
+        const DUPLICATE_OFFSET: egui::Vec2 = egui::Vec2::new(30.0, 30.0);
+
+        let graph = &mut self.node_graph.graph;
+        let template = graph[node].user_data.template;
+        let new_node = graph.add_node(
+            template.node_graph_label(&mut self.graph_state),
+            graph[node].user_data.clone(),
+            |_, _| {},
+        );
+        template.build_node(graph, &mut self.graph_state, new_node);
+
+        let copy_values = |graph: &mut RgGraph| {
+            let values: Vec<(String, RgValueType)> = graph[node]
+                .inputs
+                .iter()
+                .map(|(name, input)| (name.clone(), graph[*input].value.clone()))
+                .collect();
+            for (name, value) in values {
+                if let Ok(input) = graph[new_node].get_input(&name) {
+                    graph[input].value = value;
+                }
+            }
+        };
+
+        // The code file decides which dynamic inputs exist, copy those values once they do
+        copy_values(graph);
+        self.sync_dynamic_node_inputs();
+        copy_values(&mut self.node_graph.graph);
+
+        let pos = self.node_graph.node_positions[node] + DUPLICATE_OFFSET;
+        self.node_graph.node_positions.insert(new_node, pos);
+        self.node_graph.node_order.push(new_node);
+        self.node_graph.selected_nodes = vec![new_node];
+
+        new_node
+    }
+
+    /// Like [`Self::sync_graphics_shaders`] with the fragment files of `code_files`,
+    /// but skipped entirely when they didn't change since the last call.
+    pub fn sync_code_files(&mut self, code_files: &CodeFiles, device: &wgpu::Device) -> bool {
+        if self.synced_code_revision == Some(code_files.revision()) {
+            return false;
+        }
+        self.synced_code_revision = Some(code_files.revision());
+
+        let code_sources: Vec<(Uuid, String)> = code_files
+            .files_iter()
+            .filter(|(_, f)| f.ty() == CodeFileType::Fragment)
+            .map(|(id, f)| (*id, f.compile_source().to_owned()))
+            .collect();
+
+        self.sync_graphics_shaders(&code_sources, device)
     }
 
     /// Synchronize the shader cache with the current code file sources.
@@ -503,13 +1247,18 @@ impl RenderGraph {
         let graph = &mut self.node_graph.graph;
         let shader_cache = &self.graph_state.shader_cache;
 
+        // Newly created ports with a default to wire up once all ports are in place
+        let mut new_defaults = Vec::new();
+
         // Collect all GraphicsPass node ids
         let graphics_pass_nodes: Vec<NodeId> = graph
             .iter_nodes()
-            .filter(|&nid| matches!(graph[nid].user_data.0, RgNodeTemplate::GraphicsPass))
+            .filter(|&nid| matches!(graph[nid].user_data.template, RgNodeTemplate::GraphicsPass))
             .collect();
 
         for node_id in graphics_pass_nodes {
+            add_graphics_pass_depth_inputs(graph, node_id);
+
             // Read the code file uuid from the "code" input
             let code_file_id = graph[node_id].get_input("code").ok().and_then(|input_id| {
                 if let RgValueType::CodeFile(Some(id)) = &graph.get_input(input_id).value {
@@ -525,14 +1274,21 @@ impl RenderGraph {
                 .map(|s| s.get_bindings().to_vec())
                 .unwrap_or_default();
 
-            // Build desired input ports from bindings (skip Samplers - auto-injected)
-            let desired: Vec<(String, RgDataType, bool)> = bindings
+            // Build desired input ports from bindings
+            let desired: Vec<(String, RgDataType, bool, Option<BindingDefault>)> = bindings
                 .iter()
-                .map(|b| (b.name.clone(), b.resource_type.clone(), b.readonly))
+                .map(|b| {
+                    (
+                        b.name.clone(),
+                        b.resource_type.clone(),
+                        b.readonly,
+                        b.default.clone(),
+                    )
+                })
                 .collect();
 
             // Names of static inputs that should never be removed
-            let static_names: &[&str] = &["code", "render target"];
+            let static_names = GRAPHICS_PASS_STATIC_INPUTS;
 
             // Current dynamic inputs
             let current_dynamic: Vec<(String, node_graph::InputId)> = graph[node_id]
@@ -544,31 +1300,37 @@ impl RenderGraph {
 
             // Remove ports not in desired set
             for (name, input_id) in &current_dynamic {
-                if !desired.iter().any(|(n, _, _)| n == name) {
+                if !desired.iter().any(|(n, _, _, _)| n == name) {
                     graph.remove_input_param(*input_id);
                 }
             }
 
             // Add ports in desired set not currently present
-            for (name, data_type, readonly) in &desired {
+            for (name, data_type, readonly, default) in &desired {
                 let exists = graph[node_id].inputs.iter().any(|(n, _)| n == name);
                 if !exists {
                     let (dt, vt) = match data_type {
                         RgDataType::Tex2D => {
                             (RgDataType::Tex2D, RgValueType::Tex2D(Tex2D::default()))
                         }
+                        RgDataType::Tex2DArray => (
+                            RgDataType::Tex2DArray,
+                            RgValueType::Tex2DArray(Tex2DArray::default()),
+                        ),
                         RgDataType::Tex3D => {
                             (RgDataType::Tex3D, RgValueType::Tex3D(Tex3D::default()))
                         }
                         RgDataType::Buffer => {
                             (RgDataType::Buffer, RgValueType::Buffer(Buffer::default()))
                         }
+                        RgDataType::Sampler => (RgDataType::Sampler, RgValueType::Sampler),
+                        RgDataType::DepthTex2D => (RgDataType::DepthTex2D, RgValueType::DepthTex2D),
                         _ => continue,
                     };
 
                     let consumer = !readonly;
 
-                    graph.add_input_param(
+                    let input_id = graph.add_input_param(
                         node_id,
                         name.clone(),
                         dt,
@@ -577,25 +1339,247 @@ impl RenderGraph {
                         consumer,
                         true,
                     );
+
+                    if let Some(default) = default {
+                        new_defaults.push((node_id, input_id, default.clone()));
+                    }
                 }
             }
         }
+
+        for (node_id, input_id, default) in new_defaults {
+            self.connect_binding_default(node_id, input_id, &default);
+        }
     }
 
-    pub fn compile(
+    /// Connect a freshly created binding port of `node_id` to what its annotation asks for.
+    /// Leaves the port unconnected when nothing suitable is found.
+    fn connect_binding_default(
+        &mut self,
+        node_id: NodeId,
+        input_id: node_graph::InputId,
+        default: &BindingDefault,
+    ) {
+        let graph = &self.node_graph.graph;
+        let input_type = graph.get_input(input_id).typ.clone();
+
+        let output_id = match default {
+            BindingDefault::Screen => {
+                if input_type != RgDataType::Tex2D {
+                    log::warn!("Only textures can default to the screen");
+                    return;
+                }
+
+                self.free_output(node_id, input_id, |node| {
+                    matches!(node.user_data.template, RgNodeTemplate::ScreenTex)
+                })
+                .unwrap_or_else(|| self.add_default_screen_tex(node_id))
+            }
+            BindingDefault::Node(label) => {
+                let Some(output_id) = self.free_output(node_id, input_id, |node| {
+                    node.label.eq_ignore_ascii_case(label)
+                }) else {
+                    log::warn!("No free output on a node labeled `{}` to connect to", label);
+                    return;
+                };
+
+                output_id
+            }
+        };
+
+        let graph = &mut self.node_graph.graph;
+        graph.add_connection(output_id, input_id, 0);
+
+        // Wiring up to a node further down the graph would create a cycle
+        if topological_sort(graph).is_err() {
+            graph.remove_connection(input_id, output_id);
+            log::warn!("Default connection would create a cycle, leaving it unconnected");
+        }
+    }
+
+    /// First output of a node matching `filter` that `input_id` of `node_id` can connect to.
+    fn free_output(
         &self,
+        node_id: NodeId,
+        input_id: node_graph::InputId,
+        filter: impl Fn(&node_graph::Node<RgNodeData>) -> bool,
+    ) -> Option<node_graph::OutputId> {
+        let graph = &self.node_graph.graph;
+        let input_type = &graph.get_input(input_id).typ;
+
+        self.node_graph
+            .node_order
+            .iter()
+            .filter(|&&other| other != node_id && filter(&graph[other]))
+            .flat_map(|&other| graph[other].outputs.iter().map(|(_, output)| *output))
+            .find(|&output| {
+                graph.get_output(output).typ == *input_type && graph.can_connect(output, input_id)
+            })
+    }
+
+    /// Add a screen texture node to the left of `node_id`, returns its texture output.
+    fn add_default_screen_tex(&mut self, node_id: NodeId) -> node_graph::OutputId {
+        let template = RgNodeTemplate::ScreenTex;
+        let graph = &mut self.node_graph.graph;
+        let screen_tex_node = graph.add_node(
+            template.node_graph_label(&mut self.graph_state),
+            RgNodeData::new(template),
+            |_, _| {},
+        );
+        template.build_node(graph, &mut self.graph_state, screen_tex_node);
+
+        let position = self
+            .node_graph
+            .node_positions
+            .get(node_id)
+            .copied()
+            .unwrap_or_default();
+        self.node_graph
+            .node_positions
+            .insert(screen_tex_node, position + egui::vec2(-350.0, 200.0));
+        self.node_graph.node_order.push(screen_tex_node);
+
+        self.node_graph.graph[screen_tex_node]
+            .get_output("tex")
+            .unwrap()
+    }
+
+    pub fn compile(
+        &mut self,
         screen_size: [u32; 2],
-        device: &wgpu::Device,
+        context: &ContextWrapper,
     ) -> anyhow::Result<CompiledRenderGraph> {
-        CompiledRenderGraph::new(
+        // Errors of an earlier compile are stale, also when this one fails
+        self.graph_state.pass_errors.clear();
+        let mut compiled_rg = CompiledRenderGraph::new(
             &self.node_graph.graph,
             &self.graph_state.shader_cache,
             screen_size,
+            context,
+            self.graph_state
+                .memory_budget
+                .unwrap_or(DEFAULT_MEMORY_BUDGET),
+        )?;
+
+        compiled_rg.poll_validation(&self.node_graph.graph);
+        self.graph_state.pass_errors = compiled_rg.pass_errors().to_vec();
+
+        Ok(compiled_rg)
+    }
+
+    /// Rebuild only the passes of `compiled_rg` whose shader changed, see
+    /// [`CompiledRenderGraph::update_passes`]. Returns `false` when it has to be compiled again.
+    pub fn update_compiled(
+        &mut self,
+        compiled_rg: &mut CompiledRenderGraph,
+        device: &wgpu::Device,
+    ) -> bool {
+        let updated = compiled_rg.update_passes(
+            &self.node_graph.graph,
+            &self.graph_state.shader_cache,
+            self.graph_state
+                .memory_budget
+                .unwrap_or(DEFAULT_MEMORY_BUDGET),
             device,
-        )
+        );
+        if updated {
+            compiled_rg.poll_validation(&self.node_graph.graph);
+            self.graph_state.pass_errors = compiled_rg.pass_errors().to_vec();
+        }
+        updated
+    }
+
+    /// Pick up the validation errors of passes that resolved since the last frame, see
+    /// [`CompiledRenderGraph::poll_validation`].
+    pub fn poll_validation(&mut self, compiled_rg: &mut CompiledRenderGraph) {
+        if compiled_rg.poll_validation(&self.node_graph.graph) {
+            self.graph_state.pass_errors = compiled_rg.pass_errors().to_vec();
+        }
+    }
+
+    pub fn pass_errors(&self) -> &[(NodeId, String)] {
+        &self.graph_state.pass_errors
+    }
+
+    pub fn set_pass_timings(&mut self, timings: Vec<(NodeId, f32)>) {
+        self.graph_state.pass_timings = timings.into_iter().collect();
+    }
+
+    pub fn set_texel_values(&mut self, values: Vec<(NodeId, TexelValue)>) {
+        self.graph_state.texel_values = values.into_iter().collect();
+    }
+
+    /// Texels sampled by readback nodes with the label of their node, in graph order.
+    pub fn texel_values(&self) -> impl Iterator<Item = (&str, &TexelValue)> {
+        self.node_graph
+            .graph
+            .nodes
+            .iter()
+            .filter_map(|(node_id, node)| {
+                let value = self.graph_state.texel_values.get(&node_id)?;
+                Some((
+                    node.user_data.label.as_deref().unwrap_or(&node.label),
+                    value,
+                ))
+            })
     }
 
     pub fn shaders_iter(&self) -> impl Iterator<Item = (&Uuid, &Shader)> {
         self.graph_state.shader_cache.iter()
     }
+
+    pub fn show_port_types(&self) -> bool {
+        self.node_graph.show_port_types
+    }
+
+    pub fn profile_passes(&self) -> bool {
+        self.graph_state.profile_passes
+    }
+
+    pub fn set_profile_passes(&mut self, profile_passes: bool) {
+        self.graph_state.profile_passes = profile_passes;
+    }
+
+    /// See [`RgGraphState::memory_budget`].
+    pub fn memory_budget(&self) -> u64 {
+        self.graph_state
+            .memory_budget
+            .unwrap_or(DEFAULT_MEMORY_BUDGET)
+    }
+
+    /// Takes effect when the graph is compiled again.
+    pub fn set_memory_budget(&mut self, memory_budget: u64) {
+        self.graph_state.memory_budget = Some(memory_budget);
+    }
+
+    /// Label the ports of all nodes with their data type.
+    pub fn set_show_port_types(&mut self, show_port_types: bool) {
+        self.node_graph.show_port_types = show_port_types;
+    }
+
+    /// The inspected node, `None` when a saved inspect node no longer exists.
+    pub fn inspect_node(&self) -> Option<NodeId> {
+        self.graph_state
+            .inspect_node
+            .filter(|&node| self.node_graph.graph.nodes.contains_key(node))
+    }
+
+    /// Whether the graph has history resources, which carry state from one frame to the next
+    /// so its output keeps changing without any edits.
+    pub fn uses_history(&self) -> bool {
+        let graph = &self.node_graph.graph;
+        graph.iter_nodes().any(|node_id| {
+            matches!(
+                graph[node_id].user_data.template,
+                RgNodeTemplate::HistoryScreenTex
+                    | RgNodeTemplate::HistoryTex2D
+                    | RgNodeTemplate::HistoryTex3D
+                    | RgNodeTemplate::HistoryBuffer
+            )
+        })
+    }
+
+    pub fn shader(&self, id: Uuid) -> Option<&Shader> {
+        self.graph_state.shader_cache.get(&id)
+    }
 }
@@ -1,10 +1,103 @@
 use wgpu::naga::{
     front::wgsl,
     valid::{Capabilities, ValidationFlags, Validator},
-    StorageAccess,
+    Span, StorageAccess, StorageFormat,
 };
 
-use crate::render_graph::RgDataType;
+use crate::render_graph::{RgDataType, GRAPHICS_PASS_STATIC_INPUTS};
+
+/// What a binding gets connected to when its port is first created,
+/// annotated with a `//@default <value>` comment above the binding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindingDefault {
+    /// `//@default screen`, a screen sized texture.
+    Screen,
+    /// `//@default <label>`, an output of the node with this label.
+    Node(String),
+}
+
+impl BindingDefault {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "" => None,
+            "screen" => Some(Self::Screen),
+            label => Some(Self::Node(label.to_owned())),
+        }
+    }
+}
+
+const DEFAULT_ANNOTATION: &str = "@default";
+
+/// Where in the source a compile message points to. The line starts at 1 like in naga,
+/// the column at 0, both the column and the length count chars rather than bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageLocation {
+    pub line: u32,
+    pub column: u32,
+    pub length: u32,
+}
+
+impl MessageLocation {
+    fn new(span: Span, src: &str) -> Self {
+        let location = span.location(src);
+        let start = location.offset as usize;
+        let line_start = start + 1 - location.line_position as usize;
+        let chars =
+            |range: std::ops::Range<usize>| src.get(range).map_or(0, |s| s.chars().count()) as u32;
+
+        Self {
+            line: location.line_number,
+            column: chars(line_start..start),
+            length: chars(start..start + location.length as usize),
+        }
+    }
+}
+
+/// Texture format of the storage texture formats WGSL supports.
+fn storage_texture_format(format: StorageFormat) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat;
+
+    Some(match format {
+        StorageFormat::Rgba8Unorm => TextureFormat::Rgba8Unorm,
+        StorageFormat::Rgba8Snorm => TextureFormat::Rgba8Snorm,
+        StorageFormat::Rgba8Uint => TextureFormat::Rgba8Uint,
+        StorageFormat::Rgba8Sint => TextureFormat::Rgba8Sint,
+        StorageFormat::Bgra8Unorm => TextureFormat::Bgra8Unorm,
+        StorageFormat::Rgba16Uint => TextureFormat::Rgba16Uint,
+        StorageFormat::Rgba16Sint => TextureFormat::Rgba16Sint,
+        StorageFormat::Rgba16Float => TextureFormat::Rgba16Float,
+        StorageFormat::R32Uint => TextureFormat::R32Uint,
+        StorageFormat::R32Sint => TextureFormat::R32Sint,
+        StorageFormat::R32Float => TextureFormat::R32Float,
+        StorageFormat::Rg32Uint => TextureFormat::Rg32Uint,
+        StorageFormat::Rg32Sint => TextureFormat::Rg32Sint,
+        StorageFormat::Rg32Float => TextureFormat::Rg32Float,
+        StorageFormat::Rgba32Uint => TextureFormat::Rgba32Uint,
+        StorageFormat::Rgba32Sint => TextureFormat::Rgba32Sint,
+        StorageFormat::Rgba32Float => TextureFormat::Rgba32Float,
+        _ => return None,
+    })
+}
+
+/// Value of the default annotation in the comment lines right above the binding declared
+/// on `line_number`, attribute lines in between are skipped over.
+fn binding_annotation(src: &str, line_number: u32) -> Option<&str> {
+    let lines_above = src.lines().take(line_number.saturating_sub(1) as usize);
+    for line in lines_above.collect::<Vec<_>>().into_iter().rev() {
+        let line = line.trim();
+        // Attributes on lines of their own, not the declaration of a binding above
+        if line.starts_with('@') && !line.contains("var") {
+            continue;
+        }
+
+        let comment = line.strip_prefix("//")?;
+        if let Some(value) = comment.trim().strip_prefix(DEFAULT_ANNOTATION) {
+            return Some(value.trim());
+        }
+    }
+
+    None
+}
 
 #[derive(Debug, Clone)]
 pub struct ShaderBinding {
@@ -13,14 +106,17 @@ pub struct ShaderBinding {
     pub name: String,
     pub resource_type: RgDataType,
     pub readonly: bool,
+    pub default: Option<BindingDefault>,
+    /// Format of a storage texture binding, the connected texture needs to match it.
+    pub storage_format: Option<wgpu::TextureFormat>,
 }
 
 pub struct Shader {
     src: String,
     shader_module: Option<wgpu::ShaderModule>,
     bindings: Vec<ShaderBinding>,
-    errors: Vec<(String, Option<u32>)>,
-    warnings: Vec<(String, Option<u32>)>,
+    errors: Vec<(String, Option<MessageLocation>)>,
+    warnings: Vec<(String, Option<MessageLocation>)>,
 }
 
 impl Shader {
@@ -45,27 +141,28 @@ impl Shader {
         let module = match wgsl::parse_str(&self.src) {
             Ok(module) => module,
             Err(parse_error) => {
-                let line = parse_error
+                let location = parse_error
                     .labels()
                     .next()
-                    .map(|(span, _)| span.location(&self.src).line_number);
+                    .map(|(span, _)| MessageLocation::new(span, &self.src));
 
-                self.errors.push((format!("{}", parse_error), line));
+                self.errors.push((format!("{}", parse_error), location));
                 return;
             }
         };
 
         // Validate the module
         let mut validator = Validator::new(ValidationFlags::all(), Capabilities::all());
-        let _module_info = match validator.validate(&module) {
+        let module_info = match validator.validate(&module) {
             Ok(info) => info,
             Err(validation_error) => {
-                let line = validation_error
+                let location = validation_error
                     .spans()
                     .next()
-                    .map(|(span, _)| span.location(&self.src).line_number);
+                    .map(|(span, _)| MessageLocation::new(*span, &self.src));
 
-                self.errors.push((format!("{}", validation_error), line));
+                self.errors
+                    .push((format!("{}", validation_error), location));
                 return;
             }
         };
@@ -73,9 +170,68 @@ impl Shader {
         let mut new_bindings = Vec::new();
 
         // Extract bindings from the validated module
-        for (_handle, global) in module.global_variables.iter() {
+        for (handle, global) in module.global_variables.iter() {
             if let Some(binding) = &global.binding {
+                let span = module.global_variables.get_span(handle);
+                let location = span
+                    .is_defined()
+                    .then(|| MessageLocation::new(span, &self.src));
+                let line = location.map(|location| location.line);
+                let mut name = global
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| "unnamed_binding".to_string());
+
+                // Ports are looked up by binding name, a name that is already taken would
+                // silently replace the other port
+                let taken = |name: &str| {
+                    GRAPHICS_PASS_STATIC_INPUTS.contains(&name)
+                        || new_bindings.iter().any(|b: &ShaderBinding| b.name == name)
+                };
+                if taken(&name) {
+                    let mut unique_name = format!("{}_group{}", name, binding.group);
+                    if taken(&unique_name) {
+                        unique_name = format!("{}_{}", unique_name, binding.binding);
+                    }
+
+                    self.warnings.push((
+                        format!(
+                            "Binding `{}` (@group({}) @binding({})) has the same name as another input, it is shown as `{}`, rename it to keep its connections stable",
+                            name, binding.group, binding.binding, unique_name
+                        ),
+                        location,
+                    ));
+                    name = unique_name;
+                }
+
+                let annotation = line.and_then(|line| binding_annotation(&self.src, line));
+                let default = annotation.and_then(BindingDefault::parse);
+                if annotation.is_some() && default.is_none() {
+                    self.warnings.push((
+                        format!(
+                            "`{}` on binding `{}` is missing a value",
+                            DEFAULT_ANNOTATION, name
+                        ),
+                        location,
+                    ));
+                }
+
+                // Naga doesn't warn about these itself, but an unused binding usually means
+                // something in the graph is wired up to nothing
+                let used = (0..module.entry_points.len())
+                    .any(|i| !module_info.get_entry_point(i)[handle].is_empty());
+                if !used {
+                    self.warnings.push((
+                        format!(
+                            "Binding `{}` (@group({}) @binding({})) is never used",
+                            name, binding.group, binding.binding
+                        ),
+                        location,
+                    ));
+                }
+
                 let mut readonly = true;
+                let mut storage_format = None;
 
                 let resource_type = match module.types[global.ty].inner {
                     wgpu::naga::TypeInner::Image {
@@ -83,13 +239,29 @@ impl Shader {
                         arrayed,
                         class,
                     } => {
-                        if let wgpu::naga::ImageClass::Storage { access, .. } = class {
-                            if access == StorageAccess::STORE {
+                        if let wgpu::naga::ImageClass::Storage { format, access } = class {
+                            if access.contains(StorageAccess::STORE) {
                                 readonly = false;
                             }
+
+                            storage_format = storage_texture_format(format);
+                            if storage_format.is_none() {
+                                self.warnings.push((
+                                    format!(
+                                        "Storage format {:?} of binding `{}` is not supported",
+                                        format, name
+                                    ),
+                                    location,
+                                ));
+                            }
                         }
 
-                        if !arrayed {
+                        if let wgpu::naga::ImageClass::Depth { multi: false } = class {
+                            match (dim, arrayed) {
+                                (wgpu::naga::ImageDimension::D2, false) => RgDataType::DepthTex2D,
+                                _ => RgDataType::UInt,
+                            }
+                        } else if !arrayed {
                             match dim {
                                 wgpu::naga::ImageDimension::D1 => RgDataType::UInt,
                                 wgpu::naga::ImageDimension::D2 => RgDataType::Tex2D,
@@ -105,12 +277,14 @@ impl Shader {
                             }
                         }
                     }
-                    wgpu::naga::TypeInner::Sampler { .. } => RgDataType::UInt,
+                    // Comparison samplers are only useful with depth textures
+                    wgpu::naga::TypeInner::Sampler { comparison: false } => RgDataType::Sampler,
+                    wgpu::naga::TypeInner::Sampler { comparison: true } => RgDataType::UInt,
                     wgpu::naga::TypeInner::Struct { .. } | wgpu::naga::TypeInner::Array { .. } => {
                         match global.space {
                             wgpu::naga::AddressSpace::Uniform => RgDataType::Buffer,
                             wgpu::naga::AddressSpace::Storage { access } => {
-                                if access == StorageAccess::STORE {
+                                if access.contains(StorageAccess::STORE) {
                                     readonly = false;
                                 }
 
@@ -125,12 +299,11 @@ impl Shader {
                 new_bindings.push(ShaderBinding {
                     set: binding.group,
                     binding: binding.binding,
-                    name: global
-                        .name
-                        .clone()
-                        .unwrap_or_else(|| "unnamed_binding".to_string()),
+                    name,
                     resource_type,
                     readonly,
+                    default,
+                    storage_format,
                 });
             }
         }
@@ -156,11 +329,11 @@ impl Shader {
         &self.bindings
     }
 
-    pub fn get_errors(&self) -> &[(String, Option<u32>)] {
+    pub fn get_errors(&self) -> &[(String, Option<MessageLocation>)] {
         &self.errors
     }
 
-    pub fn get_warnings(&self) -> &[(String, Option<u32>)] {
+    pub fn get_warnings(&self) -> &[(String, Option<MessageLocation>)] {
         &self.warnings
     }
 
@@ -178,3 +351,38 @@ impl Shader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_default_annotation() {
+        let src = "//@default screen\n@group(0)\n@binding(0)\nvar a: texture_2d<f32>;\n\n// Noise input\n// @default Noise\n@group(0) @binding(1) var b: texture_2d<f32>;\nvar c: texture_2d<f32>;\n";
+
+        assert_eq!(binding_annotation(src, 4), Some("screen"));
+        assert_eq!(binding_annotation(src, 8), Some("Noise"));
+        assert_eq!(binding_annotation(src, 9), None);
+        assert_eq!(
+            BindingDefault::parse("screen"),
+            Some(BindingDefault::Screen)
+        );
+        assert_eq!(BindingDefault::parse(""), None);
+    }
+
+    #[test]
+    fn message_location_in_chars() {
+        let src = "// é\nlet ü = föo;\n";
+        let start = src.find("föo").unwrap() as u32;
+        let span = Span::new(start, start + "föo".len() as u32);
+
+        assert_eq!(
+            MessageLocation::new(span, src),
+            MessageLocation {
+                line: 2,
+                column: 8,
+                length: 3,
+            }
+        );
+    }
+}
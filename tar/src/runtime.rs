@@ -221,13 +221,40 @@ pub trait RenderPipeline<A>: 'static + Sized {
         &mut self,
         target_view: &wgpu::TextureView,
         target_format: wgpu::TextureFormat,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
+        context: &ContextWrapper,
         egui_ctx: &mut egui::Context,
         egui_pass: &mut EguiPass,
         key_modifiers: &KeyModifiers,
         app: &mut A,
     );
+
+    /// Called when the window is asked to close, returning `false` keeps the application running,
+    /// for example to ask about unsaved changes first. Exit later through [`Self::should_exit`].
+    fn close_requested(&mut self, _app: &mut A) -> bool {
+        true
+    }
+
+    /// Called after every frame, returning `true` exits the application.
+    fn should_exit(&mut self, _app: &mut A) -> bool {
+        false
+    }
+
+    /// Called after every frame with the present modes the surface supports and the one in use,
+    /// returning a different mode reconfigures the surface with it.
+    fn present_mode(
+        &mut self,
+        _supported: &[wgpu::PresentMode],
+        current: wgpu::PresentMode,
+        _app: &mut A,
+    ) -> wgpu::PresentMode {
+        current
+    }
+
+    /// Whether the pipeline draws something that changes every frame, the window then keeps
+    /// redrawing even while egui is idle.
+    fn animating(&self, _app: &A) -> bool {
+        false
+    }
 }
 
 struct RenderPipelineState<A, R: RenderPipeline<A>> {
@@ -328,6 +355,28 @@ impl<A, R: RenderPipeline<A>> RenderPipelineState<A, R> {
     }
 }
 
+fn schedule_repaint(event_loop: &ActiveEventLoop, window: &Window, delay: std::time::Duration) {
+    if delay.is_zero() {
+        window.request_redraw();
+        return;
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            // Without web-time winit can't wait until an instant on web, fall back to redrawing every frame
+            if delay != std::time::Duration::MAX {
+                window.request_redraw();
+            }
+            event_loop.set_control_flow(ControlFlow::Wait);
+        } else {
+            let control_flow = std::time::Instant::now()
+                .checked_add(delay)
+                .map_or(ControlFlow::Wait, ControlFlow::WaitUntil);
+            event_loop.set_control_flow(control_flow);
+        }
+    }
+}
+
 pub struct ApplicationHandler<A, R: RenderPipeline<A>> {
     // Render pipeline state can be recreated or dropped altogether when in android or web. Applications out of focus lose access to the graphics device.
     rp_state: Option<RenderPipelineState<A, R>>,
@@ -372,7 +421,7 @@ impl<A, R: RenderPipeline<A>> ApplicationHandler<A, R> {
         }
 
         let event_loop = builder.build().unwrap();
-        event_loop.set_control_flow(ControlFlow::Poll);
+        event_loop.set_control_flow(ControlFlow::Wait);
         event_loop.run_app(&mut self)
     }
 
@@ -407,7 +456,15 @@ impl<A, R: RenderPipeline<A>> ApplicationHandler<A, R> {
 }
 
 impl<A, R: RenderPipeline<A>> winit::application::ApplicationHandler for ApplicationHandler<A, R> {
-    fn new_events(&mut self, _event_loop: &ActiveEventLoop, _cause: winit::event::StartCause) {}
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: winit::event::StartCause) {
+        // The repaint egui scheduled is due
+        if let winit::event::StartCause::ResumeTimeReached { .. } = cause {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            if let Some(rp_state) = &self.rp_state {
+                rp_state.window.request_redraw();
+            }
+        }
+    }
 
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let surface = if let Some(rp_state) = self.rp_state.take() {
@@ -470,14 +527,28 @@ impl<A, R: RenderPipeline<A>> winit::application::ApplicationHandler for Applica
 
         if let Some(rp_state) = &mut self.rp_state {
             rp_state.render_pipeline.window_event(event.clone());
-            rp_state
+            if rp_state
                 .egui_pass
-                .handle_window_event(&rp_state.window, &event);
+                .handle_window_event(&rp_state.window, &event)
+            {
+                rp_state.window.request_redraw();
+            }
         }
 
         match event {
             WindowEvent::CloseRequested => {
-                event_loop.exit();
+                if let Some(rp_state) = &mut self.rp_state {
+                    if rp_state
+                        .render_pipeline
+                        .close_requested(&mut self.app.user_app)
+                    {
+                        event_loop.exit();
+                    } else {
+                        rp_state.window.request_redraw();
+                    }
+                } else {
+                    event_loop.exit();
+                }
             }
             WindowEvent::RedrawRequested => {
                 if let Some(rp_state) = &mut self.rp_state {
@@ -500,8 +571,7 @@ impl<A, R: RenderPipeline<A>> winit::application::ApplicationHandler for Applica
                     rp_state.render_pipeline.render(
                         &view,
                         rp_state.color_target.format(),
-                        &rp_state.context.device,
-                        &rp_state.context.queue,
+                        &rp_state.context,
                         &mut egui_ctx,
                         &mut rp_state.egui_pass,
                         &self.app.key_modifiers,
@@ -538,6 +608,8 @@ impl<A, R: RenderPipeline<A>> winit::application::ApplicationHandler for Applica
                             dst_view: &frame_view,
                             target_format: frame_format,
                             blending: None,
+                            tonemap: None,
+                            viewport: None,
                         },
                         &rp_state.context.device,
                         &mut command_encoder,
@@ -550,7 +622,31 @@ impl<A, R: RenderPipeline<A>> winit::application::ApplicationHandler for Applica
 
                     frame.present();
 
-                    rp_state.window.request_redraw();
+                    let current_present_mode = rp_state.surface.config().present_mode;
+                    let present_mode = rp_state.render_pipeline.present_mode(
+                        rp_state.surface.supported_present_modes(),
+                        current_present_mode,
+                        &mut self.app.user_app,
+                    );
+                    if present_mode != current_present_mode {
+                        rp_state
+                            .surface
+                            .set_present_mode(&rp_state.context, present_mode);
+                    }
+
+                    if rp_state.render_pipeline.should_exit(&mut self.app.user_app) {
+                        event_loop.exit();
+                        return;
+                    }
+
+                    // Only redraw again when egui asks for it or the pipeline is animating,
+                    // so an idle UI doesn't spin
+                    let delay = if rp_state.render_pipeline.animating(&self.app.user_app) {
+                        std::time::Duration::ZERO
+                    } else {
+                        rp_state.egui_pass.repaint_delay()
+                    };
+                    schedule_repaint(event_loop, &rp_state.window, delay);
                 }
 
                 self.frame_idx += 1;
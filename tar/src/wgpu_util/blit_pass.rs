@@ -4,6 +4,8 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use wgpu::util::DeviceExt as _;
+
 const BLIT_SHADER_SRC: &str = "
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
@@ -36,21 +38,231 @@ var r_color: texture_2d<f32>;
 @binding(1)
 var r_sampler: sampler;
 
+struct Tonemap {
+    curve: u32,
+    exposure: f32,
+};
+
+@group(0)
+@binding(2)
+var<uniform> r_tonemap: Tonemap;
+
+// Narkowicz 2015, ACES filmic tone mapping curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
 @fragment
 fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(r_color, r_sampler, vertex.tex_coords);
+    let color = textureSample(r_color, r_sampler, vertex.tex_coords);
+    var rgb = color.rgb * r_tonemap.exposure;
+    switch r_tonemap.curve {
+        case 1u: {
+            rgb = rgb / (1.0 + rgb);
+        }
+        case 2u: {
+            rgb = aces(rgb);
+        }
+        default: {}
+    }
+    return vec4<f32>(rgb, color.a);
 }
 ";
 
+/// Curve used to map HDR values into the displayable range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::EnumIter, strum::Display)]
+pub enum TonemapOperator {
+    #[default]
+    None,
+    Reinhard,
+    #[strum(to_string = "ACES")]
+    Aces,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Tonemap {
+    pub operator: TonemapOperator,
+    /// Exposure in stops, the color is scaled by `2^exposure` before tonemapping.
+    pub exposure: f32,
+}
+
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct TonemapUniform {
+    curve: u32,
+    exposure: f32,
+    // Uniform buffers need to be at least 16 bytes in WebGL.
+    _padding: [u32; 2],
+}
+
+impl From<Tonemap> for TonemapUniform {
+    fn from(tonemap: Tonemap) -> Self {
+        Self {
+            curve: tonemap.operator as u32,
+            exposure: tonemap.exposure.exp2(),
+            _padding: Default::default(),
+        }
+    }
+}
+
+/// Uniform buffer holding the [`Tonemap`] a blit applies, updated in place when it changes.
+pub struct TonemapBuffer {
+    buffer: wgpu::Buffer,
+    tonemap: Tonemap,
+}
+
+impl TonemapBuffer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let tonemap = Tonemap::default();
+        Self {
+            buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("blit tonemap"),
+                contents: bytemuck::bytes_of(&TonemapUniform::from(tonemap)),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }),
+            tonemap,
+        }
+    }
+
+    /// Only writes the buffer when `tonemap` differs from what it holds.
+    pub fn write(&mut self, tonemap: Tonemap, queue: &wgpu::Queue) {
+        if self.tonemap != tonemap {
+            queue.write_buffer(
+                &self.buffer,
+                0,
+                bytemuck::bytes_of(&TonemapUniform::from(tonemap)),
+            );
+            self.tonemap = tonemap;
+        }
+    }
+}
+
+/// How a blitted layer is combined with what's already in the target.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+pub enum BlendMode {
+    /// Alpha blended on top.
+    #[default]
+    Over,
+    Add,
+    Multiply,
+}
+
+impl BlendMode {
+    fn blend_state(self) -> wgpu::BlendState {
+        match self {
+            Self::Over => wgpu::BlendState::ALPHA_BLENDING,
+            Self::Add => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            },
+            Self::Multiply => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    dst_factor: wgpu::BlendFactor::Zero,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            },
+        }
+    }
+}
+
+/// How a source with a different aspect ratio than the target is fitted into it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::EnumIter, strum::Display)]
+pub enum AspectMode {
+    /// Cover the whole target, distorting the source.
+    #[default]
+    Stretch,
+    /// Keep the aspect ratio, leaving black bars at the sides or top and bottom.
+    Fit,
+}
+
+impl AspectMode {
+    /// Region of a `dst_size` target to blit a `src_size` source into, as x, y, width and height
+    /// in pixels. `None` covers the whole target.
+    pub fn viewport(self, src_size: [u32; 2], dst_size: [u32; 2]) -> Option<[f32; 4]> {
+        if self == Self::Stretch {
+            return None;
+        }
+
+        let [src_width, src_height] = src_size.map(|x| x.max(1) as f32);
+        let [dst_width, dst_height] = dst_size.map(|x| x.max(1) as f32);
+        let scale = (dst_width / src_width).min(dst_height / src_height);
+
+        // Textures at a fraction of the screen size are rounded up, a difference of less than
+        // a source texel isn't a different aspect ratio
+        let fit = |src: f32, dst: f32| {
+            let size = src * scale;
+            if dst - size < scale {
+                dst
+            } else {
+                size
+            }
+        };
+        let width = fit(src_width, dst_width);
+        let height = fit(src_height, dst_height);
+        if width == dst_width && height == dst_height {
+            return None;
+        }
+
+        Some([
+            ((dst_width - width) * 0.5).floor(),
+            ((dst_height - height) * 0.5).floor(),
+            width,
+            height,
+        ])
+    }
+}
+
 pub struct BlitPassParameters<'a> {
     pub src_view: &'a wgpu::TextureView,
     pub dst_view: &'a wgpu::TextureView,
     pub target_format: wgpu::TextureFormat,
-    pub blending: Option<f32>,
+    /// `None` overwrites the target, otherwise the source is blended onto its current contents.
+    pub blending: Option<BlendMode>,
+    /// Tonemapping applied while blitting, `None` copies the colors unchanged.
+    pub tonemap: Option<&'a TonemapBuffer>,
+    /// Region of the target to draw into as x, y, width and height in pixels, see
+    /// [`AspectMode::viewport`]. `None` covers the whole target.
+    pub viewport: Option<[f32; 4]>,
 }
 
+/// Blit pipelines differ per target format and blend state.
+type BlitPipelineKey = (wgpu::TextureFormat, Option<BlendMode>);
+
 thread_local! {
-    static BLIT_PIPELINES: RefCell<HashMap<wgpu::TextureFormat, Arc<wgpu::RenderPipeline>>> = RefCell::new(HashMap::new());
+    static BLIT_PIPELINES: RefCell<HashMap<BlitPipelineKey, Arc<wgpu::RenderPipeline>>> = RefCell::new(HashMap::new());
+    /// Shared by all blits that don't tonemap, it's never written after creation.
+    static IDENTITY_TONEMAP: RefCell<Option<wgpu::Buffer>> = const { RefCell::new(None) };
+}
+
+fn identity_tonemap_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    IDENTITY_TONEMAP.with(|v| {
+        v.borrow_mut()
+            .get_or_insert_with(|| TonemapBuffer::new(device).buffer)
+            .clone()
+    })
 }
 
 pub fn encode_blit(
@@ -60,7 +272,7 @@ pub fn encode_blit(
 ) {
     let pipeline = BLIT_PIPELINES.with(|v| {
         let mut map = v.borrow_mut();
-        map.entry(parameters.target_format)
+        map.entry((parameters.target_format, parameters.blending))
             .or_insert_with(|| {
                 let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
                     label: Some("blit"),
@@ -81,7 +293,11 @@ pub fn encode_blit(
                             module: &module,
                             entry_point: Some("fs_main"),
                             compilation_options: Default::default(),
-                            targets: &[Some(parameters.target_format.into())],
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: parameters.target_format,
+                                blend: parameters.blending.map(BlendMode::blend_state),
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
                         }),
                         primitive: wgpu::PrimitiveState::default(),
                         depth_stencil: None,
@@ -100,6 +316,11 @@ pub fn encode_blit(
         ..Default::default()
     });
 
+    let tonemap_buffer = match parameters.tonemap {
+        Some(tonemap) => tonemap.buffer.clone(),
+        None => identity_tonemap_buffer(device),
+    };
+
     let bind_group_layout = pipeline.get_bind_group_layout(0);
     let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: None,
@@ -113,6 +334,10 @@ pub fn encode_blit(
                 binding: 1,
                 resource: wgpu::BindingResource::Sampler(&sampler),
             },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: tonemap_buffer.as_entire_binding(),
+            },
         ],
     });
 
@@ -123,7 +348,10 @@ pub fn encode_blit(
                 view: parameters.dst_view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    load: match parameters.blending {
+                        Some(_) => wgpu::LoadOp::Load,
+                        None => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    },
                     store: wgpu::StoreOp::Store,
                 },
             })],
@@ -131,8 +359,32 @@ pub fn encode_blit(
             timestamp_writes: None,
             occlusion_query_set: None,
         });
+        if let Some([x, y, width, height]) = parameters.viewport {
+            rpass.set_viewport(x, y, width, height, 0.0, 1.0);
+        }
         rpass.set_pipeline(&pipeline);
         rpass.set_bind_group(0, &bind_group, &[]);
         rpass.draw(0..3, 0..1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_viewport() {
+        assert_eq!(AspectMode::Stretch.viewport([100, 100], [200, 100]), None);
+        assert_eq!(
+            AspectMode::Fit.viewport([100, 100], [200, 100]),
+            Some([50.0, 0.0, 100.0, 100.0])
+        );
+        assert_eq!(
+            AspectMode::Fit.viewport([200, 100], [200, 200]),
+            Some([0.0, 50.0, 200.0, 100.0])
+        );
+
+        // A half resolution texture of an odd sized screen still covers it
+        assert_eq!(AspectMode::Fit.viewport([50, 51], [100, 101]), None);
+    }
+}
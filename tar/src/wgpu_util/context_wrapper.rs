@@ -91,4 +91,24 @@ impl ContextWrapper {
             queue,
         }
     }
+
+    /// Features enabled on the device, the required ones plus the optional ones the adapter supports.
+    pub fn supported_features(&self) -> wgpu::Features {
+        self.device.features()
+    }
+
+    /// Whether all of `features` are enabled on the device.
+    pub fn supports(&self, features: wgpu::Features) -> bool {
+        self.supported_features().contains(features)
+    }
+
+    /// Limits the device was created with.
+    pub fn limits(&self) -> wgpu::Limits {
+        self.device.limits()
+    }
+
+    /// Best limits the adapter could provide, useful to show what a higher `required_limits` would allow.
+    pub fn adapter_limits(&self) -> wgpu::Limits {
+        self.adapter.limits()
+    }
 }
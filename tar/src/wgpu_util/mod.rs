@@ -11,6 +11,7 @@ use futures::channel::oneshot;
 
 pub mod blit_pass;
 pub mod context_wrapper;
+pub mod noise_pass;
 pub mod surface_wrapper;
 
 pub trait ComputePipelineDescriptorExtensions<'a> {
@@ -30,6 +31,68 @@ impl<'a> ComputePipelineDescriptorExtensions<'a> for wgpu::ComputePipelineDescri
     }
 }
 
+/// Convert the bits of an IEEE 754 half precision float to `f32`.
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Channel values of a single texel of `format` as copied into a buffer, in RGBA order.
+/// Normalized channels are converted to floats, sRGB texels stay encoded. Returns `None`
+/// for packed and non-color formats.
+pub fn decode_texel(format: wgpu::TextureFormat, bytes: &[u8]) -> Option<Vec<f64>> {
+    use wgpu::TextureFormat as F;
+
+    if matches!(
+        format,
+        F::Rgb9e5Ufloat | F::Rgb10a2Uint | F::Rgb10a2Unorm | F::Rg11b10Ufloat
+    ) {
+        return None;
+    }
+
+    let size = format.block_copy_size(None)? as usize;
+    let channel_size = size / format.components() as usize;
+    let sample_type = format.sample_type(None, None)?;
+    let snorm = matches!(format, F::R8Snorm | F::Rg8Snorm | F::Rgba8Snorm);
+
+    let mut channels = bytes
+        .get(..size)?
+        .chunks_exact(channel_size)
+        .map(|channel| {
+            let bits = channel
+                .iter()
+                .rev()
+                .fold(0u64, |bits, byte| bits << 8 | *byte as u64);
+            let shift = 64 - channel_size as u32 * 8;
+
+            Some(match (sample_type, channel_size) {
+                (wgpu::TextureSampleType::Uint, _) => bits as f64,
+                (wgpu::TextureSampleType::Sint, _) => ((bits << shift) as i64 >> shift) as f64,
+                (wgpu::TextureSampleType::Float { .. }, 4) => f32::from_bits(bits as u32) as f64,
+                (wgpu::TextureSampleType::Float { .. }, 2) => f16_to_f32(bits as u16) as f64,
+                (wgpu::TextureSampleType::Float { .. }, 1) if snorm => {
+                    (bits as u8 as i8 as f64 / 127.0).max(-1.0)
+                }
+                (wgpu::TextureSampleType::Float { .. }, 1) => bits as f64 / 255.0,
+                _ => return None,
+            })
+        })
+        .collect::<Option<Vec<f64>>>()?;
+
+    if matches!(format, F::Bgra8Unorm | F::Bgra8UnormSrgb) {
+        channels.swap(0, 2);
+    }
+    Some(channels)
+}
+
 /// Read back the contents of a staging buffer asynchronously.
 async fn readback_buffer_async<T: Pod>(staging_buffer: wgpu::Buffer) -> Vec<T> {
     let buffer_slice = staging_buffer.slice(..);
@@ -71,6 +134,25 @@ impl BufferReadback {
         size: u64,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
+    ) -> oneshot::Receiver<Vec<T>> {
+        let receiver = self.readback_deferred(buffer, size, device, queue);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if device.poll(wgpu::PollType::Wait).is_err() {
+            panic!("Failed to readback buffer");
+        }
+
+        receiver
+    }
+
+    /// Like [`Self::readback`] without waiting for the GPU, the data arrives once the device
+    /// is polled again on a later frame.
+    pub fn readback_deferred<T: Pod + Send>(
+        &self,
+        buffer: &wgpu::Buffer,
+        size: u64,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
     ) -> oneshot::Receiver<Vec<T>> {
         let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
@@ -106,10 +188,6 @@ impl BufferReadback {
                     let _ = sender.send(data);
                 });
 
-                if device.poll(wgpu::PollType::Wait).is_err() {
-                    panic!("Failed to readback buffer");
-                }
-
                 receiver
             }
         }
@@ -368,3 +446,38 @@ impl From<BasicColorTextureFormat> for wgpu::TextureFormat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_texel() {
+        // 1.0, -2.0, 0.5 and 0.0 as little endian half floats
+        let bytes = [0x00, 0x3c, 0x00, 0xc0, 0x00, 0x38, 0x00, 0x00];
+        assert_eq!(
+            decode_texel(wgpu::TextureFormat::Rgba16Float, &bytes),
+            Some(vec![1.0, -2.0, 0.5, 0.0])
+        );
+
+        let nan = f32::NAN.to_le_bytes();
+        assert!(decode_texel(wgpu::TextureFormat::R32Float, &nan).unwrap()[0].is_nan());
+
+        assert_eq!(
+            decode_texel(wgpu::TextureFormat::Bgra8Unorm, &[0, 51, 255, 255]),
+            Some(vec![1.0, 0.2, 0.0, 1.0])
+        );
+        assert_eq!(
+            decode_texel(wgpu::TextureFormat::Rg16Sint, &[0xff, 0xff, 7, 0]),
+            Some(vec![-1.0, 7.0])
+        );
+        assert_eq!(
+            decode_texel(wgpu::TextureFormat::R8Snorm, &[0x81]),
+            Some(vec![-1.0])
+        );
+        assert_eq!(
+            decode_texel(wgpu::TextureFormat::Rgb10a2Unorm, &[0; 4]),
+            None
+        );
+    }
+}
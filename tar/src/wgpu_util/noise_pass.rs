@@ -0,0 +1,245 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt as _;
+
+const NOISE_SHADER_SRC: &str = "
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let x = i32(vertex_index) / 2;
+    let y = i32(vertex_index) & 1;
+    let tc = vec2<f32>(
+        f32(x) * 2.0,
+        f32(y) * 2.0
+    );
+    result.position = vec4<f32>(
+        tc.x * 2.0 - 1.0,
+        1.0 - tc.y * 2.0,
+        0.0, 1.0
+    );
+    result.tex_coords = tc;
+    return result;
+}
+
+struct Noise {
+    kind: u32,
+    seed: u32,
+    frequency: f32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> r_noise: Noise;
+
+fn hash(p: vec2<i32>) -> u32 {
+    let q = bitcast<vec2<u32>>(p);
+    var h = q.x * 1664525u + q.y * 1013904223u + r_noise.seed * 2654435769u;
+    h = h ^ (h >> 16u);
+    h = h * 2246822519u;
+    h = h ^ (h >> 13u);
+    h = h * 3266489917u;
+    h = h ^ (h >> 16u);
+    return h;
+}
+
+fn random(p: vec2<i32>) -> f32 {
+    return f32(hash(p)) / 4294967295.0;
+}
+
+fn fade(t: vec2<f32>) -> vec2<f32> {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+fn value_noise(p: vec2<f32>) -> f32 {
+    let i = vec2<i32>(floor(p));
+    let u = fade(fract(p));
+    let a = mix(random(i), random(i + vec2<i32>(1, 0)), u.x);
+    let b = mix(random(i + vec2<i32>(0, 1)), random(i + vec2<i32>(1, 1)), u.x);
+    return mix(a, b, u.y);
+}
+
+fn gradient(p: vec2<i32>, offset: vec2<f32>) -> f32 {
+    let angle = random(p) * 6.2831853;
+    return dot(vec2<f32>(cos(angle), sin(angle)), offset);
+}
+
+fn perlin_noise(p: vec2<f32>) -> f32 {
+    let i = vec2<i32>(floor(p));
+    let f = fract(p);
+    let u = fade(f);
+    let a = mix(gradient(i, f), gradient(i + vec2<i32>(1, 0), f - vec2<f32>(1.0, 0.0)), u.x);
+    let b = mix(
+        gradient(i + vec2<i32>(0, 1), f - vec2<f32>(0.0, 1.0)),
+        gradient(i + vec2<i32>(1, 1), f - vec2<f32>(1.0, 1.0)),
+        u.x
+    );
+    return clamp(mix(a, b, u.y) * 0.70710678 + 0.5, 0.0, 1.0);
+}
+
+// White noise with its low frequencies removed by subtracting the neighbourhood average,
+// a cheap approximation of blue noise
+fn blue_noise(p: vec2<i32>) -> f32 {
+    var neighbours = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            if x != 0 || y != 0 {
+                neighbours += random(p + vec2<i32>(x, y));
+            }
+        }
+    }
+    return clamp(random(p) - neighbours / 8.0 + 0.5, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let p = vertex.tex_coords * r_noise.frequency;
+    var n = 0.0;
+    switch r_noise.kind {
+        case 1u: {
+            n = perlin_noise(p);
+        }
+        case 2u: {
+            n = blue_noise(vec2<i32>(floor(vertex.position.xy)));
+        }
+        default: {
+            n = value_noise(p);
+        }
+    }
+    return vec4<f32>(n, n, n, 1.0);
+}
+";
+
+/// Kind of procedural noise written by [`encode_noise`].
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::EnumIter,
+    strum::Display,
+)]
+pub enum NoiseType {
+    #[default]
+    Value,
+    Perlin,
+    /// Per texel noise without low frequencies, ignores the frequency.
+    Blue,
+}
+
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct NoiseUniform {
+    kind: u32,
+    seed: u32,
+    frequency: f32,
+    _padding: u32,
+}
+
+pub struct NoisePassParameters<'a> {
+    pub dst_view: &'a wgpu::TextureView,
+    pub target_format: wgpu::TextureFormat,
+    pub noise_type: NoiseType,
+    pub seed: u32,
+    /// Number of noise cells across the target.
+    pub frequency: f32,
+}
+
+thread_local! {
+    static NOISE_PIPELINES: RefCell<HashMap<wgpu::TextureFormat, Arc<wgpu::RenderPipeline>>> = RefCell::new(HashMap::new());
+}
+
+/// Fill `dst_view` with noise, the same value is written to all color channels.
+pub fn encode_noise(
+    parameters: &NoisePassParameters,
+    device: &wgpu::Device,
+    command_encoder: &mut wgpu::CommandEncoder,
+) {
+    let pipeline = NOISE_PIPELINES.with(|v| {
+        let mut map = v.borrow_mut();
+        map.entry(parameters.target_format)
+            .or_insert_with(|| {
+                let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("noise"),
+                    source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(NOISE_SHADER_SRC)),
+                });
+
+                Arc::new(
+                    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some(&format!("noise {:?}", parameters.target_format)),
+                        layout: None,
+                        vertex: wgpu::VertexState {
+                            module: &module,
+                            entry_point: Some("vs_main"),
+                            buffers: &[],
+                            compilation_options: Default::default(),
+                        },
+                        fragment: Some(wgpu::FragmentState {
+                            module: &module,
+                            entry_point: Some("fs_main"),
+                            compilation_options: Default::default(),
+                            targets: &[Some(parameters.target_format.into())],
+                        }),
+                        primitive: wgpu::PrimitiveState::default(),
+                        depth_stencil: None,
+                        multisample: wgpu::MultisampleState::default(),
+                        multiview: None,
+                        cache: None,
+                    }),
+                )
+            })
+            .clone()
+    });
+
+    let noise_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("noise parameters"),
+        contents: bytemuck::bytes_of(&NoiseUniform {
+            kind: parameters.noise_type as u32,
+            seed: parameters.seed,
+            frequency: parameters.frequency,
+            _padding: Default::default(),
+        }),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+
+    let bind_group_layout = pipeline.get_bind_group_layout(0);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: noise_buffer.as_entire_binding(),
+        }],
+    });
+
+    {
+        let mut rpass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("noise"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: parameters.dst_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(&pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
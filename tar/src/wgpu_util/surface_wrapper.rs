@@ -12,6 +12,7 @@ use crate::wgpu_util::context_wrapper::ContextWrapper;
 pub struct SurfaceWrapper {
     surface: Option<wgpu::Surface<'static>>,
     config: Option<wgpu::SurfaceConfiguration>,
+    present_modes: Vec<wgpu::PresentMode>,
 }
 
 impl SurfaceWrapper {
@@ -20,6 +21,7 @@ impl SurfaceWrapper {
         Self {
             surface: None,
             config: None,
+            present_modes: Vec::new(),
         }
     }
 
@@ -76,7 +78,15 @@ impl SurfaceWrapper {
             config.format = format;
             config.view_formats.push(format);
         };
-        config.present_mode = wgpu::PresentMode::AutoVsync;
+        self.present_modes = surface.get_capabilities(&context.adapter).present_modes;
+
+        // Keep the present mode picked before a suspend
+        config.present_mode = match &self.config {
+            Some(old_config) if self.is_present_mode_supported(old_config.present_mode) => {
+                old_config.present_mode
+            }
+            _ => wgpu::PresentMode::AutoVsync,
+        };
 
         surface.configure(&context.device, &config);
         self.config = Some(config);
@@ -91,6 +101,33 @@ impl SurfaceWrapper {
         surface.configure(&context.device, config);
     }
 
+    /// Present modes supported by the surface, empty before it is created.
+    pub fn supported_present_modes(&self) -> &[wgpu::PresentMode] {
+        &self.present_modes
+    }
+
+    fn is_present_mode_supported(&self, present_mode: wgpu::PresentMode) -> bool {
+        // The automatic modes fall back to a supported one
+        matches!(
+            present_mode,
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+        ) || self.present_modes.contains(&present_mode)
+    }
+
+    /// Reconfigure the surface with `present_mode`, ignored when the surface doesn't support it.
+    pub fn set_present_mode(&mut self, context: &ContextWrapper, present_mode: wgpu::PresentMode) {
+        if !self.is_present_mode_supported(present_mode) {
+            log::warn!("Present mode {:?} is not supported", present_mode);
+            return;
+        }
+
+        let (Some(surface), Some(config)) = (&self.surface, &mut self.config) else {
+            return;
+        };
+        config.present_mode = present_mode;
+        surface.configure(&context.device, config);
+    }
+
     /// Acquire the next surface texture.
     pub fn acquire(&mut self, context: &ContextWrapper) -> Option<wgpu::SurfaceTexture> {
         let surface = self.surface.as_ref()?;